@@ -1,4 +1,4 @@
-vec4 map(vec3 p) {
+MapOpaque map(vec3 p) {
     return map_impl(Arg(p, time));
 }
 
@@ -7,14 +7,14 @@ MapTransparent map_transparent(vec3 p) {
 }
 
 vec3 normal(vec3 p, float d) {
-    float dx = map(p + vec3(d, 0, 0)).w - map(p - vec3(d, 0, 0)).w;
-    float dy = map(p + vec3(0, d, 0)).w - map(p - vec3(0, d, 0)).w;
-    float dz = map(p + vec3(0, 0, d)).w - map(p - vec3(0, 0, d)).w;
+    float dx = map(p + vec3(d, 0, 0)).d - map(p - vec3(d, 0, 0)).d;
+    float dy = map(p + vec3(0, d, 0)).d - map(p - vec3(0, d, 0)).d;
+    float dz = map(p + vec3(0, 0, d)).d - map(p - vec3(0, 0, d)).d;
 
     vec3 grad = vec3(dx, dy, dz);
-    if (length(grad) == 0)
+    if (length(grad) == 0.0)
     {
-        vec3(0);
+        return vec3(0);
     }
     else
     {
@@ -23,11 +23,12 @@ vec3 normal(vec3 p, float d) {
 }
 
 const int steps = 50;
-const float max_dist = 2000;
+const float max_dist = 2000.0;
 const vec3 sky = vec3(0.24, 0.09, 0.4);
 const vec3 ambient = sky * 0.9;
 const float delta = 0.01;
-const float shadow_coef = 64;
+const float shadow_coef = 64.0;
+const float pi = 3.14159265;
 
 struct Shadow {
     vec3 mask;
@@ -35,40 +36,64 @@ struct Shadow {
 };
 
 vec3 apply_mask(vec3 color, vec3 mask) {
-    if (length(1 - mask) > 1) {
+    if (length(1.0 - mask) > 1.0) {
         mask = normalize(mask);
     }
     return color * mask;
 }
 
 float ambient_occlusion(vec3 pos, vec3 nrm) {
-    const float max_dist = 10;
+    const float max_dist = 10.0;
     const int steps = 6;
 
-    float factor = 0;
+    float factor = 0.0;
     for (int i = 1; i < steps + 1; i++) {
         float frac = float(i) / float(steps);
         frac *= frac;
 
         float dist = max_dist * frac;
-        dist = dist + pow(sin((pos.x + pos.y + pos.z) / 3 + dist), 2.0);
+        dist = dist + pow(sin((pos.x + pos.y + pos.z) / 3.0 + dist), 2.0);
         vec3 p = pos + nrm * dist;
 
-        factor += (map(p).w) / dist / steps;
+        factor += (map(p).d) / dist / float(steps);
     }
 
     return (factor + 0.4) / 1.4;
 }
 
+// Cook-Torrance specular with the GGX distribution, Smith geometry and Schlick fresnel
+vec3 specular(Material material, vec3 nrm, vec3 view, vec3 nlight) {
+    vec3 half_dir = normalize(view + nlight);
+    float nl = max(dot(nrm, nlight), 0.0);
+    float nv = max(dot(nrm, view), 1e-4);
+    float nh = max(dot(nrm, half_dir), 0.0);
+
+    float roughness = clamp(material.roughness, 0.02, 1.0);
+    float a2 = pow(roughness, 4.0);
+    float denom = nh * nh * (a2 - 1.0) + 1.0;
+    float distribution = a2 / (pi * denom * denom);
+
+    float k = pow(roughness + 1.0, 2.0) / 8.0;
+    float geometry = nl / (nl * (1.0 - k) + k) * nv / (nv * (1.0 - k) + k);
+
+    vec3 f0 = mix(vec3(0.04), material.albedo, material.metallic);
+    vec3 fresnel = f0 + (1.0 - f0) * pow(1.0 - max(dot(half_dir, view), 0.0), 5.0);
+
+    return distribution * geometry * fresnel / (4.0 * nv * max(nl, 1e-4)) * nl;
+}
+
 vec3 color_at(vec3 pos, vec3 background, float opacity, Shadow shadow) {
     vec3 nlight = -normalize(light);
-    float dist = distance(pos, cam_pos);
+    vec3 view = normalize(cam_pos - pos);
     vec3 nrm = normal(pos, delta);
 
-    vec3 diffuse = map(pos).xyz;
-    vec3 reflected = apply_mask(diffuse, shadow.mask) * shadow.shadow;
+    Material material = map(pos).material;
+    vec3 diffuse = material.albedo * (1.0 - material.metallic);
+    vec3 lit = diffuse + specular(material, nrm, view, nlight);
+    vec3 reflected = apply_mask(lit, shadow.mask) * shadow.shadow;
 
-    return mix(background, max(ambient * diffuse * ambient_occlusion(pos, nrm), reflected), opacity);
+    vec3 surface = max(ambient * material.albedo * ambient_occlusion(pos, nrm), reflected) + material.emissive;
+    return mix(background, surface, opacity);
 }
 
 
@@ -76,23 +101,23 @@ Shadow calc_shadow(vec3 pos) {
     vec3 nlight = -normalize(light);
     vec3 nrm = normal(pos, delta);
 
-    float depth = map(pos).w;
-    if (depth < 0) {
+    float depth = map(pos).d;
+    if (depth < 0.0) {
         pos -= nrm * depth;
         nrm = normal(pos, delta);
     }
 
     //pos += normalize(screen_pos - pos) * delta * 4;
-    pos += nrm * delta * 4;
+    pos += nrm * delta * 4.0;
 
     float closest = 1.0;
 
     vec3 mask = vec3(1);
 
-    for (float t = 0; t < 100;) {
-        float od = map(pos + nlight * t).w;
+    for (float t = 0.0; t < 100.0;) {
+        float od = map(pos + nlight * t).d;
         if (od < delta) {
-            return Shadow(vec3(1), 0);
+            return Shadow(vec3(1), 0.0);
         }
         closest = min(closest, shadow_coef * od / t);
 
@@ -113,15 +138,15 @@ Shadow calc_shadow(vec3 pos) {
 
 vec3 march(vec3 pos, vec3 dir) {
     vec3 nlight = -normalize(light);
-    vec3 background = vec3(pow(max(0, dot(dir, nlight)), 10) * 5) + sky;
+    vec3 background = vec3(pow(max(0.0, dot(dir, nlight)), 10.0) * 5.0) + sky;
 
     for (int tries = 0; tries < 3; tries += 1) {
         int i = 0;
         vec3 mask = vec3(1);
         bool continue_outer = false;
-        for (float t = 0; t < max_dist;) {
-            vec4 m = map(pos + dir*t);
-            float od = m.w;
+        for (float t = 0.0; t < max_dist;) {
+            MapOpaque m = map(pos + dir*t);
+            float od = m.d;
 
             MapTransparent transparent = map_transparent(pos + dir*t);
 
@@ -134,12 +159,12 @@ vec3 march(vec3 pos, vec3 dir) {
                     break;
                 }
 
-                float itershade = smoothstep(35, 5, i) * 0.5 + 0.5;
-                float distshade = smoothstep(max_dist, 0, t);
+                float itershade = smoothstep(35.0, 5.0, float(i)) * 0.5 + 0.5;
+                float distshade = smoothstep(max_dist, 0.0, t);
                 vec3 solid_color = color_at(pos + dir*t, background, distshade * itershade, calc_shadow(pos + dir*t));
                 return apply_mask(solid_color, mask);
             } else {
-                t += max(min(od, transparent.d), delta) * (1 - 0.1 * tries);
+                t += max(min(od, transparent.d), delta) * (1.0 - 0.1 * float(tries));
             }
 
             i += 1;
@@ -151,11 +176,6 @@ vec3 march(vec3 pos, vec3 dir) {
 
         return apply_mask(background, mask);
     }
-}
-
-void main() {
-    vec3 dir = normalize(screen_pos - cam_pos);
 
-    frag_color.xyz = march(cam_pos, dir);
-    frag_color.w = 1.0;
+    return background;
 }
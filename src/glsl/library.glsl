@@ -8,6 +8,39 @@ struct MapTransparent {
     float d;
 };
 
+struct Material {
+    vec3 albedo;
+    float roughness;
+    float metallic;
+    vec3 emissive;
+};
+
+struct MapOpaque {
+    Material material;
+    float d;
+};
+
+// distance to a 2D shape in the xy plane, a float that the scene can only use where 2D shapes go
+#define float2d float
+
+// materials
+Material diffuse_material(vec3 albedo) {
+    return Material(albedo, 1.0, 0.0, vec3(0));
+}
+
+Material mix_material(Material a, Material b, float h) {
+    return Material(
+        mix(a.albedo, b.albedo, h),
+        mix(a.roughness, b.roughness, h),
+        mix(a.metallic, b.metallic, h),
+        mix(a.emissive, b.emissive, h)
+    );
+}
+
+MapOpaque map_opaque(vec3 albedo, float d) {
+    return MapOpaque(diffuse_material(albedo), d);
+}
+
 // utils
 float vmax(vec3 a) {
     return max(a.x, max(a.y, a.z));
@@ -16,11 +49,194 @@ float vmin(vec3 a) {
     return min(a.x, min(a.y, a.z));
 }
 
+// noise, the lattice cells being hashed with integers so that the CPU finds the same values
+// four random numbers from 0 to 1 for the cell of the integer lattice containing `p`, with PCG4D by Jarzynski and Olano
+vec4 hash(vec4 p) {
+    uvec4 v = uvec4(ivec4(floor(p))) * 1664525u + 1013904223u;
+    v.x += v.y * v.w; v.y += v.z * v.x; v.z += v.x * v.y; v.w += v.y * v.z;
+    v ^= v >> 16u;
+    v.x += v.y * v.w; v.y += v.z * v.x; v.z += v.x * v.y; v.w += v.y * v.z;
+    return vec4(v >> 8u) / 16777216.0;
+}
+vec4 hash(vec3 p) {
+    return hash(vec4(p, 0));
+}
+vec4 hash(vec2 p) {
+    return hash(vec4(p, 0, 0));
+}
+
+// ones for the first `n` components
+vec4 noise_dims(float n) {
+    return step(vec4(0.5, 1.5, 2.5, 3.5), vec4(n));
+}
+
+// eases the interpolation between lattice cells
+vec4 noise_fade(vec4 f) {
+    return f * f * f * (f * (f * 6.0 - 15.0) + 10.0);
+}
+
+// the `_n` versions work in the first `n` dimensions of `p`, the other components being zero
+
+// interpolated random values, from -1 to 1
+float value_noise_n(vec4 p, float n) {
+    vec4 cell = floor(p);
+    vec4 f = noise_fade(p - cell);
+    float sum = 0.0;
+    for (int i = 0; i < (1 << int(n)); i++) {
+        vec4 corner = vec4(i & 1, (i >> 1) & 1, (i >> 2) & 1, (i >> 3) & 1);
+        vec4 w = mix(1.0 - f, f, corner);
+        sum += w.x * w.y * w.z * w.w * (hash(cell + corner).x * 2.0 - 1.0);
+    }
+    return sum;
+}
+
+// interpolated random gradients, about from -1 to 1 and zero on the lattice
+float perlin_noise_n(vec4 p, float n) {
+    vec4 cell = floor(p);
+    vec4 f = p - cell;
+    vec4 u = noise_fade(f);
+    float sum = 0.0;
+    for (int i = 0; i < (1 << int(n)); i++) {
+        vec4 corner = vec4(i & 1, (i >> 1) & 1, (i >> 2) & 1, (i >> 3) & 1);
+        vec4 w = mix(1.0 - u, u, corner);
+        vec4 gradient = hash(cell + corner) * 2.0 - 1.0;
+        sum += w.x * w.y * w.z * w.w * dot(gradient, f - corner);
+    }
+    return sum;
+}
+
+// random gradients on the corners of a simplex lattice, about from -1 to 1
+float simplex_noise_n(vec4 p, float n) {
+    vec4 dims = noise_dims(n);
+    float skew = (sqrt(n + 1.0) - 1.0) / n;
+    float unskew = (1.0 - 1.0 / sqrt(n + 1.0)) / n;
+    vec4 cell = floor(p + dot(p, vec4(1)) * skew) * dims;
+    vec4 x0 = (p - cell + dot(cell, vec4(1)) * unskew) * dims;
+
+    // the corners are visited along the components of `x0` from the largest to the smallest
+    vec4 rank = vec4(0);
+    for (int i = 0; i < int(n); i++) {
+        for (int j = 0; j < i; j++) {
+            if (x0[i] > x0[j]) {
+                rank[i] += 1.0;
+            } else {
+                rank[j] += 1.0;
+            }
+        }
+    }
+
+    float r2 = n > 2.0 ? 0.6 : 0.5;
+    float sum = 0.0;
+    for (int k = 0; k <= int(n); k++) {
+        vec4 offset = step(vec4(n - float(k) - 0.5), rank) * dims;
+        vec4 x = x0 - offset + float(k) * unskew * dims;
+        float t = max(r2 - dot(x, x), 0.0);
+        vec4 gradient = (hash(cell + offset) * 2.0 - 1.0) * dims;
+        sum += t * t * t * t * dot(gradient, x);
+    }
+    return sum * (n > 3.0 ? 27.0 : n > 2.0 ? 32.0 : 70.0);
+}
+
+// distance to the closest of random points, one in each lattice cell
+float worley_noise_n(vec4 p, float n) {
+    vec4 dims = noise_dims(n);
+    vec4 cell = floor(p);
+    float closest = 1.0 / 0.0;
+    for (int i = 0; i < (n > 3.0 ? 81 : n > 2.0 ? 27 : 9); i++) {
+        vec4 offset = (mod(floor(vec4(i) / vec4(1, 3, 9, 27)), 3.0) - 1.0) * dims;
+        vec4 point = cell + offset + hash(cell + offset) * dims;
+        closest = min(closest, length(p - point));
+    }
+    return closest;
+}
+
+// `octaves` layers of Perlin noise, each twice as detailed and half as strong as the one before
+float fbm_n(vec4 p, float n, float octaves) {
+    float sum = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < int(octaves); i++) {
+        sum += amplitude * perlin_noise_n(p, n);
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return sum;
+}
+
+// like `fbm_n`, with sharp ridges where the noise crosses zero, from 0 to 1
+float ridged_n(vec4 p, float n, float octaves) {
+    float sum = 0.0;
+    float amplitude = 0.5;
+    for (int i = 0; i < int(octaves); i++) {
+        float ridge = 1.0 - min(abs(perlin_noise_n(p, n)), 1.0);
+        sum += amplitude * ridge * ridge;
+        p *= 2.0;
+        amplitude *= 0.5;
+    }
+    return sum;
+}
+
+float value_noise(vec2 p) {
+    return value_noise_n(vec4(p, 0, 0), 2.0);
+}
+float value_noise(vec3 p) {
+    return value_noise_n(vec4(p, 0), 3.0);
+}
+float value_noise(vec4 p) {
+    return value_noise_n(p, 4.0);
+}
+float perlin_noise(vec2 p) {
+    return perlin_noise_n(vec4(p, 0, 0), 2.0);
+}
+float perlin_noise(vec3 p) {
+    return perlin_noise_n(vec4(p, 0), 3.0);
+}
+float perlin_noise(vec4 p) {
+    return perlin_noise_n(p, 4.0);
+}
+float simplex_noise(vec2 p) {
+    return simplex_noise_n(vec4(p, 0, 0), 2.0);
+}
+float simplex_noise(vec3 p) {
+    return simplex_noise_n(vec4(p, 0), 3.0);
+}
+float simplex_noise(vec4 p) {
+    return simplex_noise_n(p, 4.0);
+}
+float worley_noise(vec2 p) {
+    return worley_noise_n(vec4(p, 0, 0), 2.0);
+}
+float worley_noise(vec3 p) {
+    return worley_noise_n(vec4(p, 0), 3.0);
+}
+float worley_noise(vec4 p) {
+    return worley_noise_n(p, 4.0);
+}
+float fbm(vec2 p, float octaves) {
+    return fbm_n(vec4(p, 0, 0), 2.0, octaves);
+}
+float fbm(vec3 p, float octaves) {
+    return fbm_n(vec4(p, 0), 3.0, octaves);
+}
+float fbm(vec4 p, float octaves) {
+    return fbm_n(p, 4.0, octaves);
+}
+float ridged(vec2 p, float octaves) {
+    return ridged_n(vec4(p, 0, 0), 2.0, octaves);
+}
+float ridged(vec3 p, float octaves) {
+    return ridged_n(vec4(p, 0), 3.0, octaves);
+}
+float ridged(vec4 p, float octaves) {
+    return ridged_n(p, 4.0, octaves);
+}
+
 // space transforms
+/// Moves the objects by `shift`.
 Arg vat(vec3 shift, Arg arg) {
     arg.p -= shift;
     return arg;
 }
+/// Moves the objects to `(x, y, z)`.
 Arg at(float x, float y, float z, Arg arg) {
     return vat(vec3(x,y,z), arg);
 }
@@ -38,13 +254,15 @@ Arg uscale(float sc, Arg arg) {
 }
 
 Arg vrepeat(vec3 size, Arg arg) {
-    arg.p = mod(arg.p + size/2, size) - size/2;
+    arg.p = mod(arg.p + size/2.0, size) - size/2.0;
     return arg;
 }
+/// Repeats the objects every `x`, `y` and `z` units.
 Arg repeat(float x, float y, float z, Arg arg) {
     return vrepeat(vec3(x,y,z), arg);
 }
 
+/// Rotates the objects by `angle` radians around `axis`.
 Arg rotate(vec3 axis, float angle, Arg arg) {
     axis = normalize(axis);
     float s = sin(angle);
@@ -62,28 +280,91 @@ Arg rotate(vec3 axis, float angle, Arg arg) {
     return arg;
 }
 
+/// Mirrors the objects on the side `axis` points to onto the other side of the plane normal to it.
+Arg mirror(vec3 axis, Arg arg) {
+    axis = normalize(axis);
+    arg.p -= 2.0 * min(dot(arg.p, axis), 0.0) * axis;
+    return arg;
+}
+
+/// Repeats the objects `n` times around the y axis.
+Arg repeat_polar(float n, Arg arg) {
+    float angle = 6.283185307179586 / n;
+    float a = mod(atan(arg.p.z, arg.p.x) + angle / 2.0, angle) - angle / 2.0;
+    arg.p.xz = length(arg.p.xz) * vec2(cos(a), sin(a));
+    return arg;
+}
+
+/// Repeats the objects every `spacing`, `count` times in both directions along each axis.
+Arg repeat_limited(vec3 spacing, vec3 count, Arg arg) {
+    arg.p -= spacing * clamp(round(arg.p / spacing), -count, count);
+    return arg;
+}
+
+/// Stretches the objects by `size` in both directions along each axis, from their center.
+Arg elongate(vec3 size, Arg arg) {
+    arg.p -= clamp(arg.p, -size, size);
+    return arg;
+}
+
+// into the xy plane of a 2D shape turned around the y axis, its x axis starting `offset` away from it
+Arg revolve(float offset, Arg arg) {
+    arg.p = vec3(length(arg.p.xz) - offset, arg.p.y, 0.0);
+    return arg;
+}
+
+// transforms stretching space, with a `_lipschitz` function bounding how much space is stretched within the
+// distance `d` of the point, which distances have to be divided by to never overshoot
+// largest stretch of a shear
+float shear_lipschitz(float shear) {
+    return (shear + sqrt(shear * shear + 4.0)) / 2.0;
+}
+
+Arg twist(float rate, Arg arg) {
+    float angle = rate * arg.p.y;
+    arg.p.xz = mat2(cos(angle), sin(angle), -sin(angle), cos(angle)) * arg.p.xz;
+    return arg;
+}
+float twist_lipschitz(float rate, float d, Arg arg) {
+    return shear_lipschitz(abs(rate) * (length(arg.p.xz) + abs(d)));
+}
+
+Arg bend(float rate, Arg arg) {
+    float angle = rate * arg.p.x;
+    arg.p.xy = mat2(cos(angle), sin(angle), -sin(angle), cos(angle)) * arg.p.xy;
+    return arg;
+}
+float bend_lipschitz(float rate, float d, Arg arg) {
+    return 1.0 + abs(rate) * (length(arg.p.xy) + abs(d));
+}
+
 // time transforms
 
+/// Starts the animation of the objects at the time `t`.
 Arg at_t(float t, Arg arg) {
     arg.t -= t;
     return arg;
 }
 
+/// Holds the objects at their state at `min_t` before that time.
 Arg start_at_t(float min_t, Arg arg) {
     arg.t = max(min_t, arg.t);
     return arg;
 }
 
+/// Holds the objects at their state at `max_t` after that time.
 Arg end_at_t(float max_t, Arg arg) {
     arg.t = min(max_t, arg.t);
     return arg;
 }
 
+/// Repeats the animation of the objects every `interval`.
 Arg repeat_t(float interval, Arg arg) {
     arg.t = mod(arg.t, interval);
     return arg;
 }
 
+/// Maps the time from `start_in`..`end_in` to `start_out`..`end_out` for the objects.
 Arg map_t(float start_in, float end_in, float start_out, float end_out, Arg arg) {
     arg.t = mix(start_out, end_out, (arg.t - start_in) / (end_in - start_in));
     return arg;
@@ -107,43 +388,259 @@ float sd_onionize(float thick, float a) {
     return sd_diff(a, a + thick);
 }
 
+float sd_round(float r, float a) {
+    return a - r;
+}
+
+// `n` is the noise added to the distance and `gradient` how fast it changes, which bounds how much it stretches space
+float sd_displace(float amount, float n, vec3 gradient, float a) {
+    return (a + amount * n) / (1.0 + abs(amount) * length(gradient));
+}
+
+float sd_smooth_union_factor(float a, float b, float k) {
+    return clamp( 0.5+0.5*(b-a)/k, 0.0, 1.0 );
+}
+
 float sd_smooth_union(float a, float b, float k)
 {
-    float h = clamp( 0.5+0.5*(b-a)/k, 0.0, 1.0 );
+    float h = sd_smooth_union_factor(a, b, k);
     return mix( b, a, h ) - k*h*(1.0-h);
 }
 
-vec4 csd_union(vec4 a, vec4 b) {
-    return a.w < b.w ? a : b;
+MapOpaque csd_union(MapOpaque a, MapOpaque b) {
+    return a.d < b.d ? a : b;
+}
+
+// `cutter_color` tells whether cut faces take the color of the cutting object or keep the one of the base object
+MapOpaque csd_isect(MapOpaque a, MapOpaque b, bool cutter_color) {
+    if (cutter_color) {
+        return a.d > b.d ? a : b;
+    }
+    return MapOpaque(a.material, sd_isect(a.d, b.d));
+}
+MapOpaque csd_isect(MapOpaque a, MapOpaque b) {
+    return csd_isect(a, b, true);
+}
+
+MapOpaque csd_diff(MapOpaque a, MapOpaque b, bool cutter_color) {
+    Material material = cutter_color && -b.d > a.d ? b.material : a.material;
+    return MapOpaque(material, sd_diff(a.d, b.d));
+}
+MapOpaque csd_diff(MapOpaque a, MapOpaque b) {
+    return csd_diff(a, b, true);
 }
 
-vec4 csd_isect(vec4 a, vec4 b) {
-    return a.w > b.w ? a : b;
+MapOpaque csd_smooth_union(MapOpaque a, MapOpaque b, float k) {
+    float h = sd_smooth_union_factor(a.d, b.d, k);
+    return MapOpaque(mix_material(b.material, a.material, h), sd_smooth_union(a.d, b.d, k));
 }
 
 MapTransparent tsd_union(MapTransparent a, MapTransparent b) {
     return a.d < b.d ? a : b;
 }
 
+MapTransparent tsd_isect(MapTransparent a, MapTransparent b, bool cutter_color) {
+    if (cutter_color) {
+        return a.d > b.d ? a : b;
+    }
+    return MapTransparent(a.color, sd_isect(a.d, b.d));
+}
+MapTransparent tsd_isect(MapTransparent a, MapTransparent b) {
+    return tsd_isect(a, b, true);
+}
+
+MapTransparent tsd_diff(MapTransparent a, MapTransparent b, bool cutter_color) {
+    vec4 color = cutter_color && -b.d > a.d ? b.color : a.color;
+    return MapTransparent(color, sd_diff(a.d, b.d));
+}
+MapTransparent tsd_diff(MapTransparent a, MapTransparent b) {
+    return tsd_diff(a, b, true);
+}
+
+MapTransparent tsd_smooth_union(MapTransparent a, MapTransparent b, float k) {
+    float h = sd_smooth_union_factor(a.d, b.d, k);
+    return MapTransparent(mix(b.color, a.color, h), sd_smooth_union(a.d, b.d, k));
+}
+
+// operators of hg_sdf, by Mercury
+/// Intersection blending objects, and their colors, closer than `k`.
+float sd_smooth_isect(float a, float b, float k) {
+    return -sd_smooth_union(-a, -b, k);
+}
+
+/// Difference blending objects, and their colors, closer than `k`.
+float sd_smooth_diff(float a, float b, float k) {
+    return sd_smooth_isect(a, -b, k);
+}
+
+/// Union with a 45 degree chamfer of size `r` where objects meet.
+float sd_chamfer_union(float a, float b, float r) {
+    return min(min(a, b), (a - r + b) * sqrt(0.5));
+}
+
+/// Union with a fillet of radius `r` where objects meet.
+float sd_round_union(float a, float b, float r) {
+    vec2 u = max(vec2(r - a, r - b), vec2(0));
+    return max(r, min(a, b)) - length(u);
+}
+
+/// Intersection with the edges rounded to radius `r`.
+float sd_round_isect(float a, float b, float r) {
+    vec2 u = max(vec2(r + a, r + b), vec2(0));
+    return min(-r, max(a, b)) + length(u);
+}
+
+/// Difference with the cut edges rounded to radius `r`.
+float sd_round_diff(float a, float b, float r) {
+    return sd_round_isect(a, -b, r);
+}
+
+/// Union with `n` steps of total size `r` where objects meet.
+float sd_stairs_union(float a, float b, float r, float n) {
+    float s = r / n;
+    float u = b - r;
+    return min(min(a, b), 0.5 * (u + a + abs(mod(u - a + s, 2.0 * s) - s)));
+}
+
+/// Union with `n` half columns along a joint of size `r` where objects meet.
+float sd_columns_union(float a, float b, float r, float n) {
+    if (a >= r || b >= r) {
+        return min(a, b);
+    }
+
+    float column = r * sqrt(2.0) / ((n - 1.0) * 2.0 + sqrt(2.0));
+    // turned by 45 degrees, and moved to the diagonal the columns are on
+    vec2 p = vec2(a + b, b - a) * sqrt(0.5);
+    p.x += column * sqrt(2.0) - r * sqrt(0.5);
+    if (mod(n, 2.0) == 1.0) {
+        p.y += column;
+    }
+    p.y = mod(p.y + column, 2.0 * column) - column;
+
+    return min(min(length(p) - column, p.x), min(a, b));
+}
+
+/// Pipe of radius `r` along the curve where the surfaces of the objects meet.
+float sd_pipe(float a, float b, float r) {
+    return length(vec2(a, b)) - r;
+}
+
+/// The first object with a groove of depth `d` and width `2 r` cut along the surface of the second.
+float sd_groove(float a, float b, float r, float d) {
+    return max(a, min(a + d, r - abs(b)));
+}
+
+// colored operators, each part of the surface taking the color of the object that makes it
+MapOpaque csd_smooth_isect(MapOpaque a, MapOpaque b, float k) {
+    float h = sd_smooth_union_factor(-a.d, -b.d, k);
+    return MapOpaque(mix_material(b.material, a.material, h), sd_smooth_isect(a.d, b.d, k));
+}
+
+MapOpaque csd_smooth_diff(MapOpaque a, MapOpaque b, float k) {
+    float h = sd_smooth_union_factor(-a.d, b.d, k);
+    return MapOpaque(mix_material(b.material, a.material, h), sd_smooth_diff(a.d, b.d, k));
+}
+
+MapOpaque csd_chamfer_union(MapOpaque a, MapOpaque b, float r) {
+    return MapOpaque(a.d < b.d ? a.material : b.material, sd_chamfer_union(a.d, b.d, r));
+}
+
+MapOpaque csd_round_union(MapOpaque a, MapOpaque b, float r) {
+    return MapOpaque(a.d < b.d ? a.material : b.material, sd_round_union(a.d, b.d, r));
+}
+
+MapOpaque csd_round_isect(MapOpaque a, MapOpaque b, float r) {
+    return MapOpaque(a.d > b.d ? a.material : b.material, sd_round_isect(a.d, b.d, r));
+}
+
+MapOpaque csd_round_diff(MapOpaque a, MapOpaque b, float r) {
+    return MapOpaque(a.d > -b.d ? a.material : b.material, sd_round_diff(a.d, b.d, r));
+}
+
+MapOpaque csd_stairs_union(MapOpaque a, MapOpaque b, float r, float n) {
+    return MapOpaque(a.d < b.d ? a.material : b.material, sd_stairs_union(a.d, b.d, r, n));
+}
+
+MapOpaque csd_columns_union(MapOpaque a, MapOpaque b, float r, float n) {
+    return MapOpaque(a.d < b.d ? a.material : b.material, sd_columns_union(a.d, b.d, r, n));
+}
+
+MapOpaque csd_pipe(MapOpaque a, MapOpaque b, float r) {
+    return MapOpaque(a.d < b.d ? a.material : b.material, sd_pipe(a.d, b.d, r));
+}
+
+MapOpaque csd_groove(MapOpaque a, MapOpaque b, float r, float d) {
+    bool cut = min(a.d + d, r - abs(b.d)) > a.d;
+    return MapOpaque(cut ? b.material : a.material, sd_groove(a.d, b.d, r, d));
+}
+
+MapTransparent tsd_smooth_isect(MapTransparent a, MapTransparent b, float k) {
+    float h = sd_smooth_union_factor(-a.d, -b.d, k);
+    return MapTransparent(mix(b.color, a.color, h), sd_smooth_isect(a.d, b.d, k));
+}
+
+MapTransparent tsd_smooth_diff(MapTransparent a, MapTransparent b, float k) {
+    float h = sd_smooth_union_factor(-a.d, b.d, k);
+    return MapTransparent(mix(b.color, a.color, h), sd_smooth_diff(a.d, b.d, k));
+}
+
+MapTransparent tsd_chamfer_union(MapTransparent a, MapTransparent b, float r) {
+    return MapTransparent(a.d < b.d ? a.color : b.color, sd_chamfer_union(a.d, b.d, r));
+}
+
+MapTransparent tsd_round_union(MapTransparent a, MapTransparent b, float r) {
+    return MapTransparent(a.d < b.d ? a.color : b.color, sd_round_union(a.d, b.d, r));
+}
+
+MapTransparent tsd_round_isect(MapTransparent a, MapTransparent b, float r) {
+    return MapTransparent(a.d > b.d ? a.color : b.color, sd_round_isect(a.d, b.d, r));
+}
+
+MapTransparent tsd_round_diff(MapTransparent a, MapTransparent b, float r) {
+    return MapTransparent(a.d > -b.d ? a.color : b.color, sd_round_diff(a.d, b.d, r));
+}
+
+MapTransparent tsd_stairs_union(MapTransparent a, MapTransparent b, float r, float n) {
+    return MapTransparent(a.d < b.d ? a.color : b.color, sd_stairs_union(a.d, b.d, r, n));
+}
+
+MapTransparent tsd_columns_union(MapTransparent a, MapTransparent b, float r, float n) {
+    return MapTransparent(a.d < b.d ? a.color : b.color, sd_columns_union(a.d, b.d, r, n));
+}
+
+MapTransparent tsd_pipe(MapTransparent a, MapTransparent b, float r) {
+    return MapTransparent(a.d < b.d ? a.color : b.color, sd_pipe(a.d, b.d, r));
+}
+
+MapTransparent tsd_groove(MapTransparent a, MapTransparent b, float r, float d) {
+    bool cut = min(a.d + d, r - abs(b.d)) > a.d;
+    return MapTransparent(cut ? b.color : a.color, sd_groove(a.d, b.d, r, d));
+}
+
 
-// shapes
+// shapes, the `///` comments being shown by editors
+/// Sphere of radius `r`
 float sd_sphere(float r, Arg arg) {
     return length(arg.p) - r;
 }
 
+/// Box reaching `s` from its center along each axis, the distance outside being a bound
 float sd_box(vec3 s, Arg arg)
 {
     return vmax(abs(arg.p) - s);
 }
 
+/// Space below the plane through the origin with the normal `norm`
 float sd_halfspace(vec3 norm, Arg arg) {
     return dot(arg.p, norm);
 }
 
+/// Space below the planes through the origin normal to the axes set in `axis`
 float sd_halfspace_aa(vec3 axis, Arg arg) {
     return vmax(arg.p * axis);
 }
 
+/// Endless square column along `axis`, reaching `rad` from its center
 float sd_column_aa(vec3 axis, float rad, Arg arg) {
     axis = -(axis - vec3(1));
 
@@ -151,3 +648,215 @@ float sd_column_aa(vec3 axis, float rad, Arg arg) {
             sd_halfspace_aa(axis, vat(axis * rad, arg)), 
             sd_halfspace_aa(-axis, vat(-axis * rad, arg)));
 }
+
+/// Ring of radius `R` around the y axis, with a tube of radius `r`
+float sd_torus(float R, float r, Arg arg) {
+    vec2 q = vec2(length(arg.p.xz) - R, arg.p.y);
+    return length(q) - r;
+}
+
+/// Cylinder of radius `r` along the y axis, from `-h` to `h`
+float sd_capped_cylinder(float r, float h, Arg arg) {
+    vec2 d = abs(vec2(length(arg.p.xz), arg.p.y)) - vec2(r, h);
+    return min(max(d.x, d.y), 0.0) + length(max(d, 0.0));
+}
+
+/// Segment from `a` to `b` with rounded ends, of radius `r`
+float sd_capsule(vec3 a, vec3 b, float r, Arg arg) {
+    vec3 pa = arg.p - a;
+    vec3 ba = b - a;
+    float h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0);
+    return length(pa - ba * h) - r;
+}
+
+/// Cone along the y axis from `-h`, with a radius of `r1`, to `h`, with a radius of `r2`
+float sd_capped_cone(float r1, float r2, float h, Arg arg) {
+    vec2 q = vec2(length(arg.p.xz), arg.p.y);
+    vec2 k1 = vec2(r2, h);
+    vec2 k2 = vec2(r2 - r1, 2.0 * h);
+    vec2 ca = vec2(q.x - min(q.x, q.y < 0.0 ? r1 : r2), abs(q.y) - h);
+    vec2 cb = q - k1 + k2 * clamp(dot(k1 - q, k2) / dot(k2, k2), 0.0, 1.0);
+    float s = cb.x < 0.0 && ca.y < 0.0 ? -1.0 : 1.0;
+    return s * sqrt(min(dot(ca, ca), dot(cb, cb)));
+}
+
+/// Cone standing on the xz plane, with a base of radius `r` and its tip at the height `h`
+float sd_cone(float r, float h, Arg arg) {
+    return sd_capped_cone(r, 0.0, h / 2.0, vat(vec3(0, h / 2.0, 0), arg));
+}
+
+/// Ellipsoid reaching `r` from its center along each axis, the distance being a bound
+float sd_ellipsoid(vec3 r, Arg arg) {
+    return (length(arg.p / r) - 1.0) * vmin(r);
+}
+
+/// Box reaching `s` from its center along each axis, with edges rounded with a radius of `r`
+float sd_rounded_box(vec3 s, float r, Arg arg) {
+    vec3 q = abs(arg.p) - s + r;
+    return length(max(q, 0.0)) + min(vmax(q), 0.0) - r;
+}
+
+/// Edges of a box reaching `s` from its center along each axis, made of bars `e` thick
+float sd_box_frame(vec3 s, float e, Arg arg) {
+    vec3 p = abs(arg.p) - s;
+    vec3 q = abs(p + e / 2.0) - e / 2.0;
+    return min(min(
+            length(max(vec3(p.x, q.y, q.z), 0.0)) + min(max(p.x, max(q.y, q.z)), 0.0),
+            length(max(vec3(q.x, p.y, q.z), 0.0)) + min(max(q.x, max(p.y, q.z)), 0.0)),
+            length(max(vec3(q.x, q.y, p.z), 0.0)) + min(max(q.x, max(q.y, p.z)), 0.0));
+}
+
+/// Prism along the y axis from `-h` to `h`, with a hexagonal section whose sides are `r` from its center
+float sd_hex_prism(float r, float h, Arg arg) {
+    vec3 k = vec3(-0.8660254, 0.5, 0.57735);
+    vec3 p = abs(arg.p.xzy);
+    p.xy -= 2.0 * min(dot(k.xy, p.xy), 0.0) * k.xy;
+    vec2 d = vec2(length(p.xy - vec2(clamp(p.x, -k.z * r, k.z * r), r)) * sign(p.y - r), p.z - h);
+    return min(max(d.x, d.y), 0.0) + length(max(d, 0.0));
+}
+
+/// Prism along the y axis from `-h` to `h`, with a triangular section whose sides are `r` from its center and
+/// a corner towards +z, the distance being a bound
+float sd_tri_prism(float r, float h, Arg arg) {
+    vec3 q = abs(arg.p);
+    return max(q.y - h, max(q.x * 0.866025 + arg.p.z * 0.5, -arg.p.z) - r);
+}
+
+/// Octahedron with its corners `s` from its center along each axis
+float sd_octahedron(float s, Arg arg) {
+    vec3 p = abs(arg.p);
+    float m = p.x + p.y + p.z - s;
+    vec3 q;
+    if (3.0 * p.x < m) {
+        q = p.xyz;
+    } else if (3.0 * p.y < m) {
+        q = p.yzx;
+    } else if (3.0 * p.z < m) {
+        q = p.zxy;
+    } else {
+        return m * 0.57735027;
+    }
+    float k = clamp(0.5 * (q.z - q.y + s), 0.0, s);
+    return length(vec3(q.x, q.y - s + k, q.z - k));
+}
+
+/// Chain link in the xy plane, two half rings of radius `R` joined by straight parts from `-l` to `l` along y, with
+/// a tube of radius `r`
+float sd_link(float l, float R, float r, Arg arg) {
+    vec3 q = vec3(arg.p.x, max(abs(arg.p.y) - l, 0.0), arg.p.z);
+    return length(vec2(length(q.xy) - R, q.z)) - r;
+}
+
+/// Pyramid standing on the xz plane, with a square base reaching `s` from its center and its tip at the height `h`
+float sd_pyramid(float s, float h, Arg arg) {
+    // the pyramid with a base of size 1, scaled
+    vec3 p = arg.p / (2.0 * s);
+    h /= 2.0 * s;
+    float m2 = h * h + 0.25;
+    p.xz = abs(p.xz);
+    p.xz = p.z > p.x ? p.zx : p.xz;
+    p.xz -= 0.5;
+    vec3 q = vec3(p.z, h * p.y - 0.5 * p.x, h * p.x + 0.5 * p.y);
+    float e = max(-q.x, 0.0);
+    float t = clamp((q.y - 0.5 * p.z) / (m2 + 0.25), 0.0, 1.0);
+    float a = m2 * (q.x + e) * (q.x + e) + q.y * q.y;
+    float b = m2 * (q.x + 0.5 * t) * (q.x + 0.5 * t) + (q.y - m2 * t) * (q.y - m2 * t);
+    float d2 = min(q.y, -q.x * m2 - q.y * 0.5) > 0.0 ? 0.0 : min(a, b);
+    float base = length(vec3(max(p.x, 0.0), p.y, max(p.z, 0.0)));
+    return 2.0 * s * min(sqrt((d2 + q.z * q.z) / m2), base) * sign(max(q.z, -p.y));
+}
+
+/// Tube of radius `r` along the quadratic Bezier curve from `a` to `c` pulled towards `b`
+float sd_bezier(vec3 a, vec3 b, vec3 c, float r, Arg arg) {
+    vec3 ab = b - a;
+    vec3 bend = a - 2.0 * b + c;
+    if (dot(bend, bend) < 1e-8) {
+        return sd_capsule(a, c, r, arg);
+    }
+
+    // the closest point of the curve is at a root of a cubic
+    vec3 d = a - arg.p;
+    float kk = 1.0 / dot(bend, bend);
+    float kx = kk * dot(ab, bend);
+    float ky = kk * (2.0 * dot(ab, ab) + dot(d, bend)) / 3.0;
+    float kz = kk * dot(d, ab);
+    float p = ky - kx * kx;
+    float q = kx * (2.0 * kx * kx - 3.0 * ky) + kz;
+    float h = q * q + 4.0 * p * p * p;
+    float res;
+    if (h >= 0.0) {
+        h = sqrt(h);
+        vec2 x = (vec2(h, -h) - q) / 2.0;
+        vec2 uv = sign(x) * pow(abs(x), vec2(1.0 / 3.0));
+        float t = clamp(uv.x + uv.y - kx, 0.0, 1.0);
+        vec3 e = d + (2.0 * ab + bend * t) * t;
+        res = dot(e, e);
+    } else {
+        // of the three roots, the one left out is never the closest
+        float z = sqrt(-p);
+        float v = acos(clamp(q / (p * z * 2.0), -1.0, 1.0)) / 3.0;
+        float m = cos(v);
+        float n = sin(v) * 1.732050808;
+        vec2 t = clamp(vec2(m + m, -n - m) * z - kx, 0.0, 1.0);
+        vec3 e = d + (2.0 * ab + bend * t.x) * t.x;
+        vec3 f = d + (2.0 * ab + bend * t.y) * t.y;
+        res = min(dot(e, e), dot(f, f));
+    }
+    return sqrt(res) - r;
+}
+
+// 2D shapes, in the xy plane
+/// Circle of radius `r`
+float2d sd2_circle(float r, Arg arg) {
+    return length(arg.p.xy) - r;
+}
+
+/// Rectangle reaching `s` from its center along each axis
+float2d sd2_rect(vec2 s, Arg arg) {
+    vec2 d = abs(arg.p.xy) - s;
+    return min(max(d.x, d.y), 0.0) + length(max(d, 0.0));
+}
+
+/// Rectangle reaching `s` from its center along each axis, with corners rounded to a radius of `r`
+float2d sd2_rounded_rect(vec2 s, float r, Arg arg) {
+    vec2 d = abs(arg.p.xy) - s + r;
+    return min(max(d.x, d.y), 0.0) + length(max(d, 0.0)) - r;
+}
+
+/// Arc of radius `R` going `angle` radians both ways from the y axis, with a thickness of `r` on each side
+float2d sd2_arc(float angle, float R, float r, Arg arg) {
+    vec2 sc = vec2(sin(angle), cos(angle));
+    vec2 p = vec2(abs(arg.p.x), arg.p.y);
+    float d = sc.y * p.x > sc.x * p.y ? length(p - sc * R) : abs(length(p) - R);
+    return d - r;
+}
+
+/// Segment from `a` to `b` with rounded ends, of radius `r`
+float2d sd2_segment(vec2 a, vec2 b, float r, Arg arg) {
+    vec2 pa = arg.p.xy - a;
+    vec2 ba = b - a;
+    float h = clamp(dot(pa, ba) / dot(ba, ba), 0.0, 1.0);
+    return length(pa - ba * h) - r;
+}
+
+// `sd2_polygon` goes through its edges with `polygon_edge`, which keeps the squared distance to the closest edge so
+// far and a sign that flips each time the edge crosses the line from the point along x, starting from
+// `vec2(1.0/0.0, 1.0)`
+vec2 polygon_edge(vec2 a, vec2 b, vec2 acc, Arg arg) {
+    vec2 e = b - a;
+    vec2 w = arg.p.xy - a;
+    vec2 d = w - e * clamp(dot(w, e) / dot(e, e), 0.0, 1.0);
+    bvec3 crossing = bvec3(arg.p.y >= a.y, arg.p.y < b.y, e.x * w.y > e.y * w.x);
+    float s = all(crossing) || all(not(crossing)) ? -acc.y : acc.y;
+    return vec2(min(acc.x, dot(d, d)), s);
+}
+
+float2d polygon_distance(vec2 acc) {
+    return acc.y * sqrt(acc.x);
+}
+
+// the 2D shape with the distance `d` made into geometry going from `-depth` to `depth` along z
+float sd_extrude(float depth, float2d d, Arg arg) {
+    vec2 w = vec2(d, abs(arg.p.z) - depth);
+    return min(max(w.x, w.y), 0.0) + length(max(w, 0.0));
+}
@@ -0,0 +1,6 @@
+void main() {
+    vec3 dir = normalize(screen_pos - cam_pos);
+
+    frag_color.xyz = march(cam_pos, dir);
+    frag_color.w = 1.0;
+}
@@ -0,0 +1,51 @@
+// uniforms of the renderer, set by `mainImage`
+vec3 cam_pos;
+float time;
+const vec3 light = vec3(1.0, -1.0, 1.0);
+
+// quaternions are `vec4(i, j, k, w)`, converted the same way as in nalgebra,
+// so the camera moves like it does in the renderer
+
+vec4 quat_from_mat3(mat3 m) {
+    float tr = m[0][0] + m[1][1] + m[2][2];
+
+    if (tr > 0.0) {
+        float denom = sqrt(tr + 1.0) * 2.0;
+        return vec4((m[1][2] - m[2][1]) / denom, (m[2][0] - m[0][2]) / denom, (m[0][1] - m[1][0]) / denom, 0.25 * denom);
+    } else if (m[0][0] > m[1][1] && m[0][0] > m[2][2]) {
+        float denom = sqrt(1.0 + m[0][0] - m[1][1] - m[2][2]) * 2.0;
+        return vec4(0.25 * denom, (m[1][0] + m[0][1]) / denom, (m[2][0] + m[0][2]) / denom, (m[1][2] - m[2][1]) / denom);
+    } else if (m[1][1] > m[2][2]) {
+        float denom = sqrt(1.0 + m[1][1] - m[0][0] - m[2][2]) * 2.0;
+        return vec4((m[1][0] + m[0][1]) / denom, 0.25 * denom, (m[2][1] + m[1][2]) / denom, (m[2][0] - m[0][2]) / denom);
+    } else {
+        float denom = sqrt(1.0 + m[2][2] - m[0][0] - m[1][1]) * 2.0;
+        return vec4((m[2][0] + m[0][2]) / denom, (m[2][1] + m[1][2]) / denom, 0.25 * denom, (m[0][1] - m[1][0]) / denom);
+    }
+}
+
+mat3 quat_to_mat3(vec4 q) {
+    vec3 sq = q.xyz * q.xyz;
+    float ww = q.w * q.w;
+
+    return transpose(mat3(
+        ww + sq.x - sq.y - sq.z, 2.0 * (q.x * q.y - q.w * q.z), 2.0 * (q.w * q.y + q.x * q.z),
+        2.0 * (q.w * q.z + q.x * q.y), ww - sq.x + sq.y - sq.z, 2.0 * (q.y * q.z - q.w * q.x),
+        2.0 * (q.x * q.z - q.w * q.y), 2.0 * (q.w * q.x + q.y * q.z), ww - sq.x - sq.y + sq.z
+    ));
+}
+
+// left-handed look-at rotation with the y axis up
+vec4 quat_look_at(vec3 dir) {
+    vec3 z = normalize(dir);
+    vec3 x = normalize(cross(vec3(0.0, 1.0, 0.0), z));
+    vec3 y = normalize(cross(z, x));
+
+    vec4 q = quat_from_mat3(mat3(x, y, z));
+    return vec4(-q.xyz, q.w);
+}
+
+vec4 quat_mix(vec4 a, vec4 b, float t) {
+    return normalize(mix(a, b, t));
+}
+
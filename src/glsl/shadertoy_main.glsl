@@ -0,0 +1,15 @@
+void mainImage(out vec4 frag_color, in vec2 frag_coord) {
+    time = iTime;
+
+    vec4 rot;
+    camera_at(time, cam_pos, rot);
+
+    float aspect = iResolution.x / iResolution.y;
+    float fov = pi / 2.0;
+    float dist = 1.0 / tan(fov / 2.0);
+
+    vec2 screen = frag_coord / iResolution.xy * 2.0 - 1.0;
+    vec3 dir = normalize(transpose(quat_to_mat3(rot)) * vec3(screen * vec2(aspect, 1.0), dist));
+
+    frag_color = vec4(march(cam_pos, dir), 1.0);
+}
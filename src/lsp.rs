@@ -0,0 +1,436 @@
+//! Language server for scene files, speaking the Language Server Protocol over stdio
+
+use std::collections::HashMap;
+use std::io::{self, BufRead, Write};
+use std::path::{Path, PathBuf};
+
+use anyhow::Context as _;
+use serde_json::{json, Value};
+
+use crate::shaders::{Analysis, CompletionKind, SourceFile, Span};
+
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+pub fn run() -> anyhow::Result<()> {
+    let stdin = io::stdin();
+    let stdout = io::stdout();
+    let mut input = stdin.lock();
+    let mut output = stdout.lock();
+
+    let mut server = Server::default();
+    while let Some(message) = read_message(&mut input)? {
+        for reply in server.handle(&message) {
+            write_message(&mut output, &reply)?;
+        }
+
+        if server.exit {
+            break;
+        }
+    }
+
+    Ok(())
+}
+
+/// Reads a message framed by a `Content-Length` header, `None` at the end of the input
+fn read_message(input: &mut impl BufRead) -> anyhow::Result<Option<Value>> {
+    let mut length = None;
+
+    loop {
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            return Ok(None);
+        }
+
+        let line = line.trim_end();
+        if line.is_empty() {
+            break;
+        }
+
+        if let Some(value) = line.strip_prefix("Content-Length:") {
+            length = Some(value.trim().parse::<usize>().context("Invalid Content-Length")?);
+        }
+    }
+
+    let mut body = vec![0; length.context("Missing Content-Length")?];
+    input.read_exact(&mut body)?;
+
+    Ok(Some(serde_json::from_slice(&body)?))
+}
+
+fn write_message(output: &mut impl Write, message: &Value) -> io::Result<()> {
+    let body = message.to_string();
+    write!(output, "Content-Length: {}\r\n\r\n{}", body.len(), body)?;
+    output.flush()
+}
+
+struct Document {
+    path: PathBuf,
+    text: String,
+    analysis: Analysis,
+}
+
+#[derive(Default)]
+struct Server {
+    documents: HashMap<String, Document>,
+    exit: bool,
+}
+
+impl Server {
+    /// Handles a request or a notification, returning the response and the notifications to send
+    fn handle(&mut self, message: &Value) -> Vec<Value> {
+        let method = match message["method"].as_str() {
+            Some(method) => method,
+            // responses to requests we never send
+            None => return Vec::new(),
+        };
+        let params = &message["params"];
+        let id = message.get("id").cloned();
+
+        let result = match method {
+            "initialize" => Ok(json!({
+                "capabilities": {
+                    "textDocumentSync": 1,
+                    "completionProvider": {},
+                    "hoverProvider": true,
+                    "definitionProvider": true,
+                },
+                "serverInfo": { "name": "sdf-walker" },
+            })),
+            "shutdown" => Ok(Value::Null),
+            "exit" => {
+                self.exit = true;
+                return Vec::new();
+            }
+
+            "textDocument/didOpen" => {
+                let document = &params["textDocument"];
+                return self.update(&document["uri"], document["text"].as_str());
+            }
+            "textDocument/didChange" => {
+                // full document sync, so the last change has the whole text
+                let text = params["contentChanges"].as_array().and_then(|changes| changes.last());
+                return self.update(&params["textDocument"]["uri"], text.and_then(|change| change["text"].as_str()));
+            }
+            "textDocument/didSave" => return self.reanalyze(),
+            "textDocument/didClose" => {
+                let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+                self.documents.remove(uri);
+                return vec![publish_diagnostics(uri, Vec::new())];
+            }
+
+            "textDocument/completion" => self.completion(params),
+            "textDocument/hover" => self.hover(params),
+            "textDocument/definition" => self.definition(params),
+
+            _ => Err((METHOD_NOT_FOUND, format!("Unknown method `{}`", method))),
+        };
+
+        // notifications don't get a response
+        let id = match id {
+            Some(id) => id,
+            None => return Vec::new(),
+        };
+
+        let response = match result {
+            Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+            Err((code, message)) => json!({
+                "jsonrpc": "2.0",
+                "id": id,
+                "error": { "code": code, "message": message },
+            }),
+        };
+
+        vec![response]
+    }
+
+    fn update(&mut self, uri: &Value, text: Option<&str>) -> Vec<Value> {
+        let (uri, text) = match (uri.as_str(), text) {
+            (Some(uri), Some(text)) => (uri, text),
+            _ => return Vec::new(),
+        };
+
+        let path = uri_to_path(uri);
+        let analysis = Analysis::new(&path, text);
+        let document = Document { path, text: text.to_owned(), analysis };
+        let diagnostics = diagnostics(&document);
+
+        self.documents.insert(uri.to_owned(), document);
+        vec![publish_diagnostics(uri, diagnostics)]
+    }
+
+    /// Included files might have changed, so every open document is analyzed again
+    fn reanalyze(&mut self) -> Vec<Value> {
+        self.documents
+            .iter_mut()
+            .map(|(uri, document)| {
+                document.analysis = Analysis::new(&document.path, &document.text);
+                publish_diagnostics(uri, diagnostics(document))
+            })
+            .collect()
+    }
+
+    /// The document and the offset a request points to
+    fn locate(&self, params: &Value) -> Result<(&Document, usize), (i64, String)> {
+        let uri = params["textDocument"]["uri"].as_str().unwrap_or_default();
+        let document = self
+            .documents
+            .get(uri)
+            .ok_or_else(|| (INVALID_PARAMS, format!("`{}` is not open", uri)))?;
+
+        let position = &params["position"];
+        let line = position["line"].as_u64().unwrap_or_default() as usize;
+        let character = position["character"].as_u64().unwrap_or_default() as usize;
+
+        Ok((document, offset(&document.text, line, character)))
+    }
+
+    fn completion(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, _) = self.locate(params)?;
+
+        let items = document
+            .analysis
+            .completions()
+            .into_iter()
+            .map(|completion| {
+                // values of `CompletionItemKind`
+                let kind = match completion.kind {
+                    CompletionKind::Keyword => 14,
+                    CompletionKind::Function => 3,
+                    CompletionKind::Object => 7,
+                };
+
+                json!({ "label": completion.label, "detail": completion.detail, "kind": kind })
+            })
+            .collect::<Vec<_>>();
+
+        Ok(Value::Array(items))
+    }
+
+    fn hover(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, offset) = self.locate(params)?;
+
+        Ok(match document.analysis.hover(offset) {
+            Some((span, contents)) => json!({
+                "contents": { "kind": "markdown", "value": contents },
+                "range": range(&document.text, span),
+            }),
+            None => Value::Null,
+        })
+    }
+
+    fn definition(&self, params: &Value) -> Result<Value, (i64, String)> {
+        let (document, offset) = self.locate(params)?;
+
+        Ok(match document.analysis.definition(offset) {
+            Some((file, span)) => location(file, span),
+            None => Value::Null,
+        })
+    }
+}
+
+fn diagnostics(document: &Document) -> Vec<Value> {
+    document
+        .analysis
+        .diagnostics
+        .iter()
+        .map(|(span, message)| {
+            json!({
+                "range": range(&document.text, *span),
+                "severity": 1,
+                "source": "sdf-walker",
+                "message": message,
+            })
+        })
+        .collect()
+}
+
+fn publish_diagnostics(uri: &str, diagnostics: Vec<Value>) -> Value {
+    json!({
+        "jsonrpc": "2.0",
+        "method": "textDocument/publishDiagnostics",
+        "params": { "uri": uri, "diagnostics": diagnostics },
+    })
+}
+
+fn location(file: &SourceFile, span: Span) -> Value {
+    json!({ "uri": path_to_uri(&file.path), "range": range(&file.text, span) })
+}
+
+fn range(text: &str, span: Span) -> Value {
+    json!({ "start": position(text, span.start), "end": position(text, span.end) })
+}
+
+/// LSP positions count UTF-16 code units from the start of the line
+fn position(text: &str, offset: usize) -> Value {
+    let offset = offset.min(text.len());
+    let line_start = text[..offset].rfind('\n').map(|x| x + 1).unwrap_or(0);
+    let line = text[..line_start].matches('\n').count();
+    let character = text
+        .get(line_start..offset)
+        .unwrap_or_default()
+        .encode_utf16()
+        .count();
+
+    json!({ "line": line, "character": character })
+}
+
+fn offset(text: &str, line: usize, character: usize) -> usize {
+    let line_start = match line {
+        0 => 0,
+        _ => match text.match_indices('\n').nth(line - 1) {
+            Some((idx, _)) => idx + 1,
+            None => return text.len(),
+        },
+    };
+
+    let mut units = 0;
+    for (idx, c) in text[line_start..].char_indices() {
+        if units >= character || c == '\n' {
+            return line_start + idx;
+        }
+        units += c.len_utf16();
+    }
+
+    text.len()
+}
+
+fn uri_to_path(uri: &str) -> PathBuf {
+    let path = uri.strip_prefix("file://").unwrap_or(uri);
+
+    let mut bytes = Vec::new();
+    let mut rest = path.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        let escaped = match tail {
+            [hi, lo, ..] if b == b'%' => std::str::from_utf8(&[*hi, *lo])
+                .ok()
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok()),
+            _ => None,
+        };
+
+        match escaped {
+            Some(decoded) => {
+                bytes.push(decoded);
+                rest = &tail[2..];
+            }
+            None => {
+                bytes.push(b);
+                rest = tail;
+            }
+        }
+    }
+
+    PathBuf::from(String::from_utf8_lossy(&bytes).into_owned())
+}
+
+fn path_to_uri(path: &Path) -> String {
+    let path = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+
+    let mut uri = String::from("file://");
+    for &b in path.to_string_lossy().as_bytes() {
+        if b.is_ascii_alphanumeric() || b"/-_.~".contains(&b) {
+            uri.push(b as char);
+        } else {
+            uri.push_str(&format!("%{:02X}", b));
+        }
+    }
+
+    uri
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn request(id: u64, method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params })
+    }
+
+    fn notification(method: &str, params: Value) -> Value {
+        json!({ "jsonrpc": "2.0", "method": method, "params": params })
+    }
+
+    #[test]
+    fn test_framing() {
+        let mut output = Vec::new();
+        write_message(&mut output, &json!({ "id": 1 })).unwrap();
+        write_message(&mut output, &json!({ "id": "ü" })).unwrap();
+
+        let mut input = io::Cursor::new(output);
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "id": 1 })));
+        assert_eq!(read_message(&mut input).unwrap(), Some(json!({ "id": "ü" })));
+        assert_eq!(read_message(&mut input).unwrap(), None);
+    }
+
+    #[test]
+    fn test_positions() {
+        let text = "ab\nüc𝄞d\n";
+        assert_eq!(position(text, 0), json!({ "line": 0, "character": 0 }));
+        assert_eq!(position(text, 3), json!({ "line": 1, "character": 0 }));
+        assert_eq!(position(text, 10), json!({ "line": 1, "character": 4 }));
+
+        assert_eq!(offset(text, 1, 4), 10);
+        assert_eq!(offset(text, 0, 10), 2);
+        assert_eq!(offset(text, 5, 0), text.len());
+
+        assert_eq!(uri_to_path("file:///tmp/my%20scene.scene"), Path::new("/tmp/my scene.scene"));
+        assert_eq!(path_to_uri(Path::new("/nonexistent/my scene.scene")), "file:///nonexistent/my%20scene.scene");
+    }
+
+    #[test]
+    fn test_session() {
+        let mut server = Server::default();
+        let uri = "file:///nonexistent/test.scene";
+
+        let reply = server.handle(&request(1, "initialize", json!({})));
+        assert_eq!(reply[0]["result"]["capabilities"]["hoverProvider"], true);
+        assert!(server.handle(&notification("initialized", json!({})) ).is_empty());
+
+        let text = "define_geometry(ball) sd_sphere(1);\nopaque(1, 1, 1) ball(2);";
+        let reply = server.handle(&notification(
+            "textDocument/didOpen",
+            json!({ "textDocument": { "uri": uri, "languageId": "scene", "version": 1, "text": text } }),
+        ));
+        let diagnostics = &reply[0]["params"]["diagnostics"];
+        assert_eq!(reply[0]["method"], "textDocument/publishDiagnostics");
+        assert_eq!(diagnostics[0]["message"], "`ball` expects no arguments, got 1 (in `opaque`)");
+        assert_eq!(diagnostics[0]["range"]["start"], json!({ "line": 1, "character": 16 }));
+
+        let reply = server.handle(&notification(
+            "textDocument/didChange",
+            json!({
+                "textDocument": { "uri": uri, "version": 2 },
+                "contentChanges": [{ "text": "define_geometry(ball) sd_sphere(1);\nopaque(1, 1, 1) ball();" }],
+            }),
+        ));
+        assert_eq!(reply[0]["params"]["diagnostics"], json!([]));
+
+        let at = |line: u64, character: u64| {
+            json!({ "textDocument": { "uri": uri }, "position": { "line": line, "character": character } })
+        };
+
+        let reply = server.handle(&request(2, "textDocument/completion", at(1, 0)));
+        let items = reply[0]["result"].as_array().unwrap();
+        assert!(items.iter().any(|item| item["label"] == "smooth_union"));
+        assert!(items.iter().any(|item| item["label"] == "ball" && item["detail"] == "float ball(Arg arg)"));
+
+        let reply = server.handle(&request(3, "textDocument/hover", at(0, 25)));
+        assert_eq!(reply[0]["result"]["contents"]["value"], "```glsl\nfloat sd_sphere(float r, Arg arg)\n```\nSphere of radius `r`");
+
+        let reply = server.handle(&request(4, "textDocument/definition", at(1, 17)));
+        assert_eq!(
+            reply[0]["result"],
+            json!({
+                "uri": uri,
+                "range": { "start": { "line": 0, "character": 16 }, "end": { "line": 0, "character": 20 } },
+            })
+        );
+
+        let reply = server.handle(&request(5, "textDocument/formatting", json!({})));
+        assert_eq!(reply[0]["error"]["code"], METHOD_NOT_FOUND);
+
+        assert_eq!(server.handle(&request(6, "shutdown", Value::Null))[0]["result"], Value::Null);
+        server.handle(&notification("exit", Value::Null));
+        assert!(server.exit);
+    }
+}
@@ -1,10 +1,12 @@
 extern crate nalgebra_glm as glm;
 #[macro_use] extern crate lazy_static;
 
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
 use structopt::StructOpt;
 
+mod lsp;
+mod mesh;
 mod shaders;
 mod rendering;
 
@@ -13,7 +15,8 @@ use rendering::{onscreen::new_app, offscreen::new_app_offscreen};
 
 #[derive(StructOpt)]
 struct Opt { 
-    source: PathBuf,
+    /// Scene file, not used by `lsp`
+    source: Option<PathBuf>,
 
     #[structopt(subcommand)]
     command: Command,
@@ -33,24 +36,163 @@ enum Command {
     Interactive {
         #[structopt(long, short)]
         camera: bool,
-    }
+    },
+
+    /// Prints the scene file in the canonical style
+    Fmt {
+        /// Overwrite the file instead of printing it
+        #[structopt(long, short)]
+        write: bool,
+        /// Fail if the file is not formatted, without changing it
+        #[structopt(long, conflicts_with = "write")]
+        check: bool,
+    },
+
+    /// Runs a language server for scene files over stdio
+    Lsp,
+
+    /// Converts the scene for use outside of the renderer
+    Export {
+        #[structopt(subcommand)]
+        format: ExportFormat,
+    },
+}
+
+#[derive(StructOpt)]
+enum ExportFormat {
+    /// Single `mainImage` shader that can be pasted into Shadertoy
+    Shadertoy {
+        /// Write to a file instead of printing it
+        #[structopt(long, short)]
+        output: Option<PathBuf>,
+    },
+
+    /// Triangle mesh of the opaque objects, for 3D printing or other tools
+    Mesh {
+        /// File to write, whose extension picks the format: `obj`, `stl` or `ply`
+        #[structopt(long, short)]
+        output: PathBuf,
+        /// Time at which the scene is sampled
+        #[structopt(long, short, default_value = "0")]
+        time: f64,
+        /// Lowest corner of the sampled box, which otherwise goes around the objects
+        #[structopt(long, number_of_values = 3, allow_hyphen_values = true, requires = "max")]
+        min: Option<Vec<f64>>,
+        /// Highest corner of the sampled box
+        #[structopt(long, number_of_values = 3, allow_hyphen_values = true, requires = "min")]
+        max: Option<Vec<f64>>,
+        /// Number of cells along the longest side of the box
+        #[structopt(long, short, default_value = "128")]
+        resolution: u32,
+        /// Sample every cell, for scenes whose distances can be too large to skip empty space
+        #[structopt(long)]
+        dense: bool,
+    },
 }
 
 fn main() {
     let opt = Opt::from_args();
 
-    let mut loader = SceneDescLoader::new(opt.source);
+    let source = || match &opt.source {
+        Some(source) => source.clone(),
+        None => structopt::clap::Error::with_description(
+            "The <source> argument is required by this command",
+            structopt::clap::ErrorKind::MissingRequiredArgument,
+        )
+        .exit(),
+    };
 
     match opt.command {
-        Command::Render { width, height, fps } => render(loader, [width, height], fps),
+        Command::Render { width, height, fps } => {
+            render(SceneDescLoader::new(source()), [width, height], fps)
+        }
         Command::Interactive { camera } => {
+            let mut loader = SceneDescLoader::new(source());
             loader.switch_camera(camera);
             let (app, el) = new_app([800, 600], loader);
             app.run(el);
         }
+        Command::Fmt { write, check } => {
+            if let Err(e) = format(&source(), write, check) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Lsp => {
+            if let Err(e) = lsp::run() {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
+        Command::Export { ref format } => {
+            if let Err(e) = export(&source(), format) {
+                eprintln!("{}", e);
+                std::process::exit(1);
+            }
+        }
     }
 }
 
+fn format(source: &Path, write: bool, check: bool) -> anyhow::Result<()> {
+    let formatted = format_scene_file(source)?;
+
+    if check {
+        if std::fs::read_to_string(source)? != formatted {
+            anyhow::bail!("`{}` is not formatted", source.display());
+        }
+    } else if write {
+        std::fs::write(source, formatted)?;
+    } else {
+        print!("{}", formatted);
+    }
+
+    Ok(())
+}
+
+fn export(source: &Path, format: &ExportFormat) -> anyhow::Result<()> {
+    let scene = SceneDescLoader::new(source).load()?;
+
+    match format {
+        ExportFormat::Shadertoy { output } => write_output(output.as_deref(), &scene.shadertoy()),
+        ExportFormat::Mesh { output, time, min, max, resolution, dense } => {
+            let format = mesh::MeshFormat::from_path(output)
+                .ok_or_else(|| anyhow::anyhow!("Unknown mesh format for `{}`, expected `.obj`, `.stl` or `.ply`", output.display()))?;
+
+            let grid = match (min, max) {
+                (Some(min), Some(max)) => mesh::Grid {
+                    min: [min[0], min[1], min[2]],
+                    max: [max[0], max[1], max[2]],
+                    resolution: *resolution,
+                },
+                _ => {
+                    let bound = scene.cpu.opaque_bound().ok_or_else(|| {
+                        anyhow::anyhow!("The extent of the scene isn't known, it has to be given with --min and --max")
+                    })?;
+                    mesh::Grid::around(bound, *resolution)
+                }
+            };
+
+            let mesh = mesh::Mesh::extract(&scene.cpu, &grid, *time, *dense)?;
+
+            let mut file = std::io::BufWriter::new(std::fs::File::create(output)?);
+            mesh.write(format, &mut file)?;
+            std::io::Write::flush(&mut file)?;
+
+            Ok(())
+        }
+    }
+}
+
+/// Writes to the file if there is one, and prints otherwise
+fn write_output(output: Option<&Path>, contents: &str) -> anyhow::Result<()> {
+    match output {
+        Some(path) => std::fs::write(path, contents)?,
+        None => print!("{}", contents),
+    }
+
+    Ok(())
+}
+
 fn render(mut loader: SceneDescLoader, size: [u32; 2], fps: f32) {
     let scene = loader.load().unwrap();
 
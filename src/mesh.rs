@@ -0,0 +1,339 @@
+//! Triangle meshes of the opaque objects, for tools that don't understand distance fields
+
+use std::collections::{HashMap, HashSet};
+
+use crate::shaders::{Bound, CpuScene, EvalError};
+
+pub mod format;
+
+pub use format::MeshFormat;
+
+/// Box of space that is sampled, split into cubic cells
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Grid {
+    pub min: [f64; 3],
+    pub max: [f64; 3],
+    /// Number of cells along the longest side of the box
+    pub resolution: u32,
+}
+
+impl Grid {
+    /// Box around a bound, with a margin of a couple of cells so the surface doesn't touch its sides
+    pub fn around(bound: Bound, resolution: u32) -> Self {
+        let radius = bound.radius * (1.0 + 4.0 / resolution.max(1) as f64);
+
+        Grid {
+            min: [0, 1, 2].map(|i| bound.center[i] - radius),
+            max: [0, 1, 2].map(|i| bound.center[i] + radius),
+            resolution,
+        }
+    }
+
+    fn cell_size(&self) -> f64 {
+        let longest = (0..3).map(|i| self.max[i] - self.min[i]).fold(0.0, f64::max);
+        longest / self.resolution.max(1) as f64
+    }
+
+    /// Number of cells along each axis
+    fn cells(&self) -> [i64; 3] {
+        let size = self.cell_size();
+        [0, 1, 2].map(|i| (((self.max[i] - self.min[i]) / size).ceil() as i64).max(1))
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Mesh {
+    pub positions: Vec<[f64; 3]>,
+    /// Albedo of the material at each position
+    pub colors: Vec<[f64; 3]>,
+    /// Indices of the positions, counter-clockwise when seen from outside
+    pub triangles: Vec<[usize; 3]>,
+}
+
+/// Corners of a cell, as offsets from its lowest corner
+const CORNERS: [[i64; 3]; 8] = [
+    [0, 0, 0],
+    [1, 0, 0],
+    [0, 1, 0],
+    [1, 1, 0],
+    [0, 0, 1],
+    [1, 0, 1],
+    [0, 1, 1],
+    [1, 1, 1],
+];
+
+/// Edges of a cell, as its first corner and the axis it goes along
+const EDGES: [([i64; 3], usize); 12] = [
+    ([0, 0, 0], 0),
+    ([0, 1, 0], 0),
+    ([0, 0, 1], 0),
+    ([0, 1, 1], 0),
+    ([0, 0, 0], 1),
+    ([1, 0, 0], 1),
+    ([0, 0, 1], 1),
+    ([1, 0, 1], 1),
+    ([0, 0, 0], 2),
+    ([1, 0, 0], 2),
+    ([0, 1, 0], 2),
+    ([1, 1, 0], 2),
+];
+
+fn add(a: [i64; 3], b: [i64; 3]) -> [i64; 3] {
+    [a[0] + b[0], a[1] + b[1], a[2] + b[2]]
+}
+
+fn step(axis: usize, by: i64) -> [i64; 3] {
+    let mut offset = [0; 3];
+    offset[axis] = by;
+    offset
+}
+
+/// Samples the opaque objects of a scene at the corners of the cells of a grid
+struct Sampler<'a> {
+    scene: &'a CpuScene,
+    time: f64,
+    origin: [f64; 3],
+    cell_size: f64,
+    cells: [i64; 3],
+    /// Distances at the corners that have been sampled so far
+    corners: HashMap<[i64; 3], f64>,
+}
+
+impl Sampler<'_> {
+    fn position(&self, corner: [f64; 3]) -> [f64; 3] {
+        [0, 1, 2].map(|i| self.origin[i] + corner[i] * self.cell_size)
+    }
+
+    fn distance(&self, p: [f64; 3]) -> Result<f64, EvalError> {
+        self.scene.opaque(p, self.time)?.distance()
+    }
+
+    fn corner_distance(&mut self, corner: [i64; 3]) -> Result<f64, EvalError> {
+        if let Some(d) = self.corners.get(&corner) {
+            return Ok(*d);
+        }
+
+        let d = self.distance(self.position(corner.map(|x| x as f64)))?;
+        self.corners.insert(corner, d);
+        Ok(d)
+    }
+
+    /// Cells that the surface may go through, found by splitting the grid like an octree and
+    /// leaving out the nodes that the distance at their center says are empty
+    fn surface_cells(&mut self, corner: [i64; 3], size: i64, dense: bool, out: &mut Vec<[i64; 3]>) -> Result<(), EvalError> {
+        if (0..3).any(|i| corner[i] >= self.cells[i]) {
+            return Ok(());
+        }
+
+        if !dense {
+            let center = self.position(corner.map(|x| x as f64 + size as f64 / 2.0));
+            let reach = 3f64.sqrt() / 2.0 * size as f64 * self.cell_size;
+            if self.distance(center)?.abs() > reach {
+                return Ok(());
+            }
+        }
+
+        if size == 1 {
+            out.push(corner);
+            return Ok(());
+        }
+
+        let half = size / 2;
+        for offset in &CORNERS {
+            self.surface_cells(add(corner, offset.map(|x| x * half)), half, dense, out)?;
+        }
+
+        Ok(())
+    }
+
+    /// Where the surface crosses an edge, if it does
+    fn crossing(&mut self, start: [i64; 3], axis: usize) -> Result<Option<[f64; 3]>, EvalError> {
+        let d0 = self.corner_distance(start)?;
+        let d1 = self.corner_distance(add(start, step(axis, 1)))?;
+
+        if (d0 < 0.0) == (d1 < 0.0) {
+            return Ok(None);
+        }
+
+        let mut corner = start.map(|x| x as f64);
+        corner[axis] += d0 / (d0 - d1);
+        Ok(Some(self.position(corner)))
+    }
+}
+
+impl Mesh {
+    /// Surface of the opaque objects inside the grid at the given time, built like dual contouring
+    /// without the normals (surface nets). Only the cells close to the surface are sampled unless
+    /// `dense` is set, as skipping the others assumes the distances aren't overestimated
+    pub fn extract(scene: &CpuScene, grid: &Grid, time: f64, dense: bool) -> Result<Self, EvalError> {
+        let mut sampler = Sampler {
+            scene,
+            time,
+            origin: grid.min,
+            cell_size: grid.cell_size(),
+            cells: grid.cells(),
+            corners: HashMap::new(),
+        };
+
+        let root = sampler.cells.iter().copied().max().unwrap_or(1).max(1) as u64;
+        let mut cells = Vec::new();
+        sampler.surface_cells([0; 3], root.next_power_of_two() as i64, dense, &mut cells)?;
+
+        let mut mesh = Mesh::default();
+
+        // one vertex per cell the surface goes through, at the average of its crossings
+        let mut vertices = HashMap::new();
+        let mut edges = Vec::new();
+        let mut seen = HashSet::new();
+
+        for &cell in &cells {
+            let mut sum = [0.0; 3];
+            let mut count = 0;
+
+            for &(offset, axis) in &EDGES {
+                let start = add(cell, offset);
+                if let Some(p) = sampler.crossing(start, axis)? {
+                    (0..3).for_each(|i| sum[i] += p[i]);
+                    count += 1;
+
+                    if seen.insert((start, axis)) {
+                        edges.push((start, axis));
+                    }
+                }
+            }
+
+            if count > 0 {
+                let p = sum.map(|x| x / count as f64);
+                let material = scene.opaque(p, time)?.field("material")?;
+
+                vertices.insert(cell, mesh.positions.len());
+                mesh.positions.push(p);
+                mesh.colors.push(material.field("albedo")?.as_vec3()?);
+            }
+        }
+
+        // each crossed edge joins the vertices of the four cells around it
+        for (start, axis) in edges {
+            let (b, c) = ((axis + 1) % 3, (axis + 2) % 3);
+            let around = [
+                add(start, add(step(b, -1), step(c, -1))),
+                add(start, step(c, -1)),
+                start,
+                add(start, step(b, -1)),
+            ];
+
+            let quad = around.iter().map(|cell| vertices.get(cell).copied()).collect::<Option<Vec<_>>>();
+            let mut quad = match quad {
+                Some(quad) => quad,
+                // the surface leaves the grid
+                None => continue,
+            };
+
+            // the quad faces along the axis when the edge goes from inside to outside
+            if sampler.corner_distance(start)? >= 0.0 {
+                quad.reverse();
+            }
+
+            mesh.triangles.push([quad[0], quad[1], quad[2]]);
+            mesh.triangles.push([quad[0], quad[2], quad[3]]);
+        }
+
+        Ok(mesh)
+    }
+
+    /// Normal of a triangle, following the order of its vertices
+    pub fn normal(&self, triangle: [usize; 3]) -> [f64; 3] {
+        let [a, b, c] = triangle.map(|idx| self.positions[idx]);
+        let (u, v) = ([0, 1, 2].map(|i| b[i] - a[i]), [0, 1, 2].map(|i| c[i] - a[i]));
+        let n = [u[1] * v[2] - u[2] * v[1], u[2] * v[0] - u[0] * v[2], u[0] * v[1] - u[1] * v[0]];
+
+        let length = (n[0] * n[0] + n[1] * n[1] + n[2] * n[2]).sqrt();
+        if length > 0.0 {
+            n.map(|x| x / length)
+        } else {
+            n
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::parse_scene;
+
+    fn extract(source: &str, resolution: u32, dense: bool) -> Mesh {
+        let desc = parse_scene(source.as_bytes()).unwrap();
+        let grid = Grid::around(desc.cpu.opaque_bound().unwrap(), resolution);
+        Mesh::extract(&desc.cpu, &grid, 0.0, dense).unwrap()
+    }
+
+    /// Volume enclosed by the mesh, from the divergence theorem
+    fn volume(mesh: &Mesh) -> f64 {
+        mesh.triangles
+            .iter()
+            .map(|t| {
+                let [a, b, c] = t.map(|idx| mesh.positions[idx]);
+                (a[0] * (b[1] * c[2] - b[2] * c[1]) - a[1] * (b[0] * c[2] - b[2] * c[0]) + a[2] * (b[0] * c[1] - b[1] * c[0])) / 6.0
+            })
+            .sum()
+    }
+
+    #[test]
+    fn test_sphere() {
+        let mesh = extract("opaque(1, 0, 0) at(1, 2, 3) sd_sphere(2);", 32, false);
+        assert!(!mesh.triangles.is_empty());
+
+        let cell = 2.0 * 2.0 * (1.0 + 4.0 / 32.0) / 32.0;
+        for p in &mesh.positions {
+            let r = ((p[0] - 1.0).powi(2) + (p[1] - 2.0).powi(2) + (p[2] - 3.0).powi(2)).sqrt();
+            assert!((r - 2.0).abs() < cell, "{:?}", p);
+        }
+        assert!(mesh.colors.iter().all(|c| *c == [1.0, 0.0, 0.0]));
+
+        // closed, with every edge shared by two triangles going opposite ways
+        let mut edges = HashSet::new();
+        for t in &mesh.triangles {
+            for i in 0..3 {
+                assert!(edges.insert((t[i], t[(i + 1) % 3])));
+            }
+        }
+        assert!(edges.iter().all(|&(a, b)| edges.contains(&(b, a))));
+
+        let expected = 4.0 / 3.0 * std::f64::consts::PI * 8.0;
+        assert!((volume(&mesh) - expected).abs() < expected * 0.02, "{}", volume(&mesh));
+    }
+
+    #[test]
+    fn test_octree() {
+        let source = "opaque(1, 1, 1) { sd_box(vec3(1, 2, 1)); at(0, 4, 0) sd_sphere(1); }";
+        let sparse = extract(source, 24, false);
+        let dense = extract(source, 24, true);
+
+        assert_eq!(sparse.triangles.len(), dense.triangles.len());
+        assert!((volume(&sparse) - volume(&dense)).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_colors() {
+        let mesh = extract("opaque(1, 0, 0) at(-2, 0, 0) sd_sphere(1);\nopaque(0, 0, 1) at(2, 0, 0) sd_sphere(1);", 32, false);
+
+        for (p, color) in mesh.positions.iter().zip(&mesh.colors) {
+            let expected = if p[0] < 0.0 { [1.0, 0.0, 0.0] } else { [0.0, 0.0, 1.0] };
+            assert_eq!(*color, expected);
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let grid = Grid {
+            min: [0.0, -1.0, 0.0],
+            max: [4.0, 1.0, 0.5],
+            resolution: 8,
+        };
+        assert_eq!(grid.cells(), [8, 4, 1]);
+
+        let desc = parse_scene(b"opaque(1, 1, 1) sd_sphere(1);").unwrap();
+        let mesh = Mesh::extract(&desc.cpu, &Grid { min: [2.0; 3], max: [3.0; 3], resolution: 8 }, 0.0, false).unwrap();
+        assert_eq!(mesh, Mesh::default());
+    }
+}
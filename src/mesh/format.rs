@@ -0,0 +1,174 @@
+//! Writers for the file formats meshes can be exported to
+
+use std::io::{self, Write};
+use std::path::Path;
+
+use super::Mesh;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MeshFormat {
+    /// Wavefront OBJ, with the colors after the positions like most tools accept
+    Obj,
+    /// Binary STL, which has no colors
+    Stl,
+    /// Binary PLY, with 8-bit colors
+    Ply,
+}
+
+impl MeshFormat {
+    /// Format matching the extension of a file
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let extension = path.extension()?.to_str()?.to_ascii_lowercase();
+
+        match extension.as_str() {
+            "obj" => Some(MeshFormat::Obj),
+            "stl" => Some(MeshFormat::Stl),
+            "ply" => Some(MeshFormat::Ply),
+            _ => None,
+        }
+    }
+}
+
+fn color_byte(c: f64) -> u8 {
+    (c.clamp(0.0, 1.0) * 255.0).round() as u8
+}
+
+impl Mesh {
+    pub fn write(&self, format: MeshFormat, out: &mut impl Write) -> io::Result<()> {
+        match format {
+            MeshFormat::Obj => self.write_obj(out),
+            MeshFormat::Stl => self.write_stl(out),
+            MeshFormat::Ply => self.write_ply(out),
+        }
+    }
+
+    fn write_obj(&self, out: &mut impl Write) -> io::Result<()> {
+        for (p, c) in self.positions.iter().zip(&self.colors) {
+            let c = c.map(|x| x.clamp(0.0, 1.0));
+            writeln!(out, "v {} {} {} {} {} {}", p[0], p[1], p[2], c[0], c[1], c[2])?;
+        }
+
+        for t in &self.triangles {
+            writeln!(out, "f {} {} {}", t[0] + 1, t[1] + 1, t[2] + 1)?;
+        }
+
+        Ok(())
+    }
+
+    fn write_stl(&self, out: &mut impl Write) -> io::Result<()> {
+        let mut header = [0u8; 80];
+        let title = b"sdf-walker";
+        header[..title.len()].copy_from_slice(title);
+
+        out.write_all(&header)?;
+        out.write_all(&(self.triangles.len() as u32).to_le_bytes())?;
+
+        for &t in &self.triangles {
+            let points = std::iter::once(self.normal(t)).chain(t.iter().map(|&idx| self.positions[idx]));
+            for x in points.flatten() {
+                out.write_all(&(x as f32).to_le_bytes())?;
+            }
+
+            // attribute byte count
+            out.write_all(&[0, 0])?;
+        }
+
+        Ok(())
+    }
+
+    fn write_ply(&self, out: &mut impl Write) -> io::Result<()> {
+        write!(
+            out,
+            "ply\nformat binary_little_endian 1.0\n\
+             element vertex {}\n\
+             property float x\nproperty float y\nproperty float z\n\
+             property uchar red\nproperty uchar green\nproperty uchar blue\n\
+             element face {}\n\
+             property list uchar int vertex_indices\n\
+             end_header\n",
+            self.positions.len(),
+            self.triangles.len()
+        )?;
+
+        for (p, c) in self.positions.iter().zip(&self.colors) {
+            for x in p {
+                out.write_all(&(*x as f32).to_le_bytes())?;
+            }
+            out.write_all(&c.map(color_byte))?;
+        }
+
+        for t in &self.triangles {
+            out.write_all(&[3])?;
+            for idx in t {
+                out.write_all(&(*idx as i32).to_le_bytes())?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn triangle() -> Mesh {
+        Mesh {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            colors: vec![[1.0, 0.0, 0.0], [0.0, 1.5, 0.0], [0.0, 0.0, 0.5]],
+            triangles: vec![[0, 1, 2]],
+        }
+    }
+
+    fn written(format: MeshFormat) -> Vec<u8> {
+        let mut out = Vec::new();
+        triangle().write(format, &mut out).unwrap();
+        out
+    }
+
+    #[test]
+    fn test_from_path() {
+        assert_eq!(MeshFormat::from_path(Path::new("out/scene.OBJ")), Some(MeshFormat::Obj));
+        assert_eq!(MeshFormat::from_path(Path::new("scene.stl")), Some(MeshFormat::Stl));
+        assert_eq!(MeshFormat::from_path(Path::new("scene.ply")), Some(MeshFormat::Ply));
+        assert_eq!(MeshFormat::from_path(Path::new("scene.scene")), None);
+        assert_eq!(MeshFormat::from_path(Path::new("scene")), None);
+    }
+
+    #[test]
+    fn test_obj() {
+        assert_eq!(
+            String::from_utf8(written(MeshFormat::Obj)).unwrap(),
+            "v 0 0 0 1 0 0\nv 1 0 0 0 1 0\nv 0 1 0 0 0 0.5\nf 1 2 3\n"
+        );
+    }
+
+    #[test]
+    fn test_stl() {
+        let stl = written(MeshFormat::Stl);
+        assert_eq!(stl.len(), 80 + 4 + 50);
+        assert_eq!(&stl[80..84], &1u32.to_le_bytes());
+
+        let float = |idx: usize| f32::from_le_bytes([stl[84 + idx * 4], stl[85 + idx * 4], stl[86 + idx * 4], stl[87 + idx * 4]]);
+        assert_eq!((0..3).map(float).collect::<Vec<_>>(), vec![0.0, 0.0, 1.0]);
+        assert_eq!((3..12).map(float).collect::<Vec<_>>(), vec![0.0, 0.0, 0.0, 1.0, 0.0, 0.0, 0.0, 1.0, 0.0]);
+    }
+
+    #[test]
+    fn test_ply() {
+        let ply = written(MeshFormat::Ply);
+        let header_end = b"end_header\n";
+        let start = ply.windows(header_end.len()).position(|w| w == header_end).unwrap() + header_end.len();
+
+        let header = std::str::from_utf8(&ply[..start]).unwrap();
+        assert!(header.contains("element vertex 3\n"));
+        assert!(header.contains("element face 1\n"));
+
+        let body = &ply[start..];
+        assert_eq!(body.len(), 3 * (12 + 3) + 1 + 12);
+        assert_eq!(&body[12..15], &[255, 0, 0]);
+        assert_eq!(&body[27..30], &[0, 255, 0]);
+        assert_eq!(&body[42..45], &[0, 0, 128]);
+        assert_eq!(&body[45..], &[3, 0, 0, 0, 0, 1, 0, 0, 0, 2, 0, 0, 0]);
+    }
+}
@@ -33,6 +33,7 @@ pub enum VertexSemantics {
 #[derive(Clone, Copy, Vertex)]
 #[vertex(sem = "VertexSemantics")]
 pub struct Vertex {
+    #[allow(dead_code)] // only read by the vertex shader
     pub idx: VertexIndex,
 }
 
@@ -20,6 +20,7 @@ where
     Ctx::Backend: backend::shader::Shader,
     Ctx::Backend: backend::texture::Texture<Dim2, pixel::NormRGBA8UI>,
 {
+    #[allow(clippy::wrong_self_convention)]
     pub fn to_image(&mut self) -> image::RgbImage {
         let color = self.bb.color_slot().get_raw_texels().unwrap();
         let image = image::RgbaImage::from_raw(self.size[0], self.size[1], color).unwrap();
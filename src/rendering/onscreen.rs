@@ -1,5 +1,5 @@
 use super::*;
-use std::time::{Instant, Duration};
+use std::time::Instant;
 
 impl CtxDetails for GlutinSurface {
     type FbCol = ();
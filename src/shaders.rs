@@ -1,18 +1,38 @@
+use std::fmt;
+
 use luminance::backend::shader::Shader;
 use luminance::context::GraphicsContext;
-use luminance::shader::{Program, UniformInterface};
+use luminance::shader::{Program, ProgramError, StageError, StageType, UniformInterface};
 
 mod generated;
 
 pub use generated::*;
 
-#[derive(Debug, thiserror::Error)]
-#[error("{}", .0)]
-pub struct GetProgramError(#[from] luminance::shader::ProgramError);
+#[derive(thiserror::Error)]
+pub enum GetProgramError {
+    #[error("{}", .0)]
+    Program(#[from] ProgramError),
+    /// Compile log of the fragment shader, rewritten to point at the sources it was generated from
+    #[error("fragment shader compilation error:\n{}", .0)]
+    Fragment(String),
+}
+
+// the compile log is only readable with its line breaks
+impl fmt::Debug for GetProgramError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
 
 pub trait ShaderProvider {
     fn get_sources(&self) -> [String; 2];
 
+    /// Makes the compile log of the fragment shader refer to the sources it was generated from,
+    /// `None` when it can't be improved
+    fn explain_fragment_log(&self, _log: &str) -> Option<String> {
+        None
+    }
+
     fn get_program<C, S, Out, Uni>(&self, ctx: &mut C) -> Result<Program<C::Backend, S, Out, Uni>, GetProgramError>
     where
         C: GraphicsContext,
@@ -23,7 +43,13 @@ pub trait ShaderProvider {
         let [vertex, fragment] = self.get_sources();
 
         let program = ctx.new_shader_program()
-            .from_strings(&vertex, None, None, &fragment)?
+            .from_strings(&vertex, None, None, &fragment)
+            .map_err(|e| match &e {
+                ProgramError::StageError(StageError::CompilationFailed(StageType::FragmentShader, log)) => self
+                    .explain_fragment_log(log)
+                    .map_or_else(|| e.into(), GetProgramError::Fragment),
+                _ => e.into(),
+            })?
             .ignore_warnings();
 
         Ok(program)
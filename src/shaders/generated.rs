@@ -1,26 +1,71 @@
-use std::path::PathBuf;
-
+mod analysis;
+mod check;
 mod codegen;
+mod compile_log;
 mod desc;
+mod eval;
+mod expr;
+mod format;
+#[cfg(test)]
+mod interpret;
 mod parser;
+mod source;
 mod typed;
+#[cfg(test)]
+mod validate;
 
 pub use desc::{SceneDesc, loader::SceneDescLoader};
+pub use eval::{CpuScene, EvalError};
+pub use typed::Bound;
+pub use analysis::{Analysis, CompletionKind};
+pub use format::format_scene_file;
+pub use source::{SourceFile, Span};
+#[cfg(test)]
+pub(crate) use desc::test::parse_scene;
 
-pub struct GeneratedScene {
-    pub source: PathBuf,
-}
+pub struct GeneratedScene;
 
 impl GeneratedScene {
     pub fn get_vertex() -> String {
         include_str!("../glsl/vertex.glsl").to_string()
     }
 
+    /// The generated code is numbered as its own source string, so that compile errors in it
+    /// can be traced back to the scene whatever the driver puts before the shader
     pub fn compile_fragment(main: &str) -> String {
         let header = include_str!("../glsl/header.glsl");
         let library = include_str!("../glsl/library.glsl");
         let footer = include_str!("../glsl/footer.glsl");
+        let entry = include_str!("../glsl/main.glsl");
+
+        format!(
+            "{}{}#line 1 {}\n{}\n#line 1 {}\n{}{}",
+            header,
+            library,
+            compile_log::GENERATED_SOURCE,
+            main,
+            compile_log::GENERATED_SOURCE + 1,
+            footer,
+            entry
+        )
+    }
+
+    /// Single shader with Shadertoy's `mainImage` entry point, which sets the renderer's uniforms from
+    /// `iTime`, `iResolution` and the baked `camera_at` function
+    pub fn compile_shadertoy(main: &str, camera: &str) -> String {
+        let header = include_str!("../glsl/shadertoy_header.glsl");
+        let library = include_str!("../glsl/library.glsl");
+        let footer = include_str!("../glsl/footer.glsl");
+        let entry = include_str!("../glsl/shadertoy_main.glsl");
 
-        format!("{}{}{}{}", header, library, main, footer)
+        format!(
+            "{}{}{}\n{}\n{}\n{}",
+            header,
+            library,
+            main,
+            footer,
+            camera,
+            entry
+        )
     }
 }
@@ -0,0 +1,455 @@
+use std::path::Path;
+
+use super::check::{self, Signature};
+use super::desc::loader::resolve_includes;
+use super::desc::{SceneDesc, SceneDescError, Statement, SIMPLE_FUNCTIONS};
+use super::expr::Expr;
+use super::source::{SourceFile, SourceMap, Span};
+use super::typed::{Geometry2dMarker, GeometryMarker, OpaqueMarker, TransparentMarker, TypeMarker, OPERATORS};
+
+/// Statements the scene language handles itself, with their usage and a short description, the ones calling
+/// `library.glsl` functions are documented there
+const BUILTINS: &[(&str, &str, &str)] = &[
+    ("union", "union { ... }", "Union of the nested objects."),
+    (
+        "intersection",
+        "intersection(bool cutter_color) { ... }",
+        "Intersection of the nested objects. Cut faces take the color of the cutting object unless `cutter_color` is false.",
+    ),
+    (
+        "difference",
+        "difference(bool cutter_color) { ... }",
+        "The first nested object with the others cut out of it. Cut faces take the color of the cutting object unless `cutter_color` is false.",
+    ),
+    ("smooth_union", "smooth_union(float k) { ... }", "Union blending objects, and their colors, closer than `k`."),
+    (
+        "advanced_repeat",
+        "advanced_repeat(float x, float y, float z) { ... }",
+        "Repeats the nested objects every `x`, `y` and `z` units, also evaluating the neighbouring cells so objects can cross cell borders.",
+    ),
+    ("onionize", "onionize(float thickness) { ... }", "Turns the nested objects into shells of the given thickness."),
+    ("scale", "scale(float factor) { ... }", "Scales the nested objects."),
+    ("round", "round(float r) { ... }", "Rounds the nested objects by moving their surface out by `r`."),
+    (
+        "twist",
+        "twist(float rate) { ... }",
+        "Twists the nested objects around the y axis, by `rate` radians per unit of height.",
+    ),
+    (
+        "bend",
+        "bend(float rate) { ... }",
+        "Bends the nested objects in the xy plane, by `rate` radians per unit along x.",
+    ),
+    (
+        "displace",
+        "displace(float amount, float noise) { ... }",
+        "Moves the surface of the nested objects out by `amount` times `noise`, an expression like `fbm($p, 4)`.",
+    ),
+    (
+        "extrude",
+        "extrude(float depth) { ... }",
+        "Makes geometry of the nested 2D shapes by stretching them along z, from `-depth` to `depth`.",
+    ),
+    (
+        "revolve",
+        "revolve(float offset) { ... }",
+        "Makes geometry of the nested 2D shapes by turning them around the y axis, `offset` away from it.",
+    ),
+    ("sd2_polygon", "sd2_polygon(vec2 a, vec2 b, vec2 c, ...)", "2D polygon through the given vertices, in order."),
+    ("cond", "cond(bool condition) { ... }", "Only shows the nested objects while the condition holds."),
+    (
+        "cond_else",
+        "cond_else(bool condition) { then; otherwise; }",
+        "Shows the first nested object while the condition holds, and the second one otherwise.",
+    ),
+    (
+        "switch",
+        "switch(float end...) { object; ...; last; }",
+        "Shows each nested object until its end time, in increasing order, and the last one after that.",
+    ),
+    ("let", "let(type, name, value) { ... }", "Names a value of the given type for the nested object, shadowing any outer binding of that name."),
+    ("raw", "raw(expr)", "Uses a GLSL expression as the object."),
+    ("opaque", "opaque(r, g, b) { ... }", "Colors the nested geometry, `opaque(name)` uses a material instead."),
+    ("transparent", "transparent(r, g, b, a) { ... }", "Colors the nested geometry with a transparent color."),
+    ("define_geometry", "define_geometry(name, <type> <param>...) { ... }", "Defines a reusable geometry object."),
+    (
+        "define_geometry_2d",
+        "define_geometry_2d(name, <type> <param>...) { ... }",
+        "Defines a reusable 2D shape, for `extrude` and `revolve`.",
+    ),
+    ("define_opaque", "define_opaque(name, <type> <param>...) { ... }", "Defines a reusable opaque object."),
+    ("define_transparent", "define_transparent(name, <type> <param>...) { ... }", "Defines a reusable transparent object."),
+    (
+        "material",
+        "material(name) { albedo(r, g, b); roughness(x); metallic(x); emissive(r, g, b); }",
+        "Defines a material that `opaque(name)` can use instead of a color.",
+    ),
+    ("albedo", "albedo(r, g, b)", "Base color of a material, white by default."),
+    ("roughness", "roughness(x)", "Roughness of a material, from 0 for polished to 1 for matte, the default."),
+    ("metallic", "metallic(x)", "How metallic a material is, from 0, the default, to 1."),
+    ("emissive", "emissive(r, g, b)", "Light a material gives off, none by default."),
+    ("include", "include(\"path\")", "Includes the statements of another scene file, relative to this one."),
+    ("camera", "camera { keyframe(t) { pos(x, y, z); look_at(x, y, z); } }", "Animated camera of the scene."),
+];
+
+lazy_static! {
+    /// Every statement, as name, usage and description
+    static ref STATEMENTS: Vec<(String, String, String)> = {
+        let builtins = BUILTINS
+            .iter()
+            .map(|(name, usage, doc)| ((*name).to_owned(), (*usage).to_owned(), (*doc).to_owned()));
+
+        // operators pass the distances of two objects first
+        let operators = OPERATORS.iter().map(|op| {
+            let signature = &check::library()[op.functions[0]][0];
+            let params = &params(&signature.declaration)[2..];
+            nested(op.statement, params, signature)
+        });
+
+        let mut simple = SIMPLE_FUNCTIONS.keys().collect::<Vec<_>>();
+        simple.sort();
+        // transforms get the `Arg` last
+        let transforms = simple.into_iter().map(|name| {
+            let signature = &check::library()[*name][0];
+            let params = params(&signature.declaration);
+            nested(name, &params[..params.len() - 1], signature)
+        });
+
+        builtins.chain(operators).chain(transforms).collect()
+    };
+}
+
+/// Parameters of a `library.glsl` declaration, as written
+fn params(declaration: &str) -> Vec<&str> {
+    let start = declaration.find('(').map_or(0, |open| open + 1);
+    let end = declaration.rfind(')').unwrap_or(declaration.len());
+    declaration[start..end].split(',').map(str::trim).collect()
+}
+
+/// Entry of a statement taking a body, documented by the function it calls
+fn nested(name: &str, params: &[&str], signature: &Signature) -> (String, String, String) {
+    let usage = format!("{}({}) {{ ... }}", name, params.join(", "));
+    (name.to_owned(), usage, signature.doc.clone())
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompletionKind {
+    Keyword,
+    Function,
+    Object,
+}
+
+#[derive(Debug, Clone)]
+pub struct Completion {
+    pub label: String,
+    pub detail: String,
+    pub kind: CompletionKind,
+}
+
+/// An object defined with a `define_*` statement
+struct Definition {
+    name: String,
+    /// Span of the name in the `define_*` statement
+    span: Span,
+    signature: Signature,
+}
+
+/// What an editor needs to know about an open scene file
+pub struct Analysis {
+    sources: SourceMap,
+    statements: Vec<Statement>,
+    definitions: Vec<Definition>,
+    /// Errors, located in the scene file itself
+    pub diagnostics: Vec<(Span, String)>,
+}
+
+impl Analysis {
+    /// Analyzes the text of a scene file, reading the files it includes from the disk
+    pub fn new(path: &Path, text: &str) -> Self {
+        let resolved = resolve_includes(path, text.as_bytes());
+
+        let mut analysis = Analysis {
+            sources: resolved.sources,
+            statements: Vec::new(),
+            definitions: Vec::new(),
+            diagnostics: Vec::new(),
+        };
+
+        let errors = match resolved.statements {
+            Ok(statements) => {
+                analysis.statements = statements.clone();
+                scene_errors(statements)
+            }
+            Err(e) => vec![e],
+        };
+
+        analysis.definitions = analysis.statements
+            .iter()
+            .filter(|stmt| !stmt.args.is_empty())
+            .filter_map(|stmt| {
+                let signature = match stmt.name.as_str() {
+                    "material" => Signature::of_material(stmt),
+                    name => Signature::of_definition(stmt, definition_marker(name)?),
+                };
+
+                Some(Definition {
+                    name: stmt.args[0].to_string(),
+                    span: stmt.args[0].span,
+                    signature,
+                })
+            })
+            .collect();
+
+        analysis.diagnostics = errors
+            .iter()
+            .map(|e| analysis.locate(e.span(), e.to_string()))
+            .collect();
+        analysis.diagnostics.sort_by_key(|(span, _)| span.start);
+
+        analysis
+    }
+
+    /// Moves errors in included files to the `include` statement in the scene file
+    fn locate(&self, span: Span, message: String) -> (Span, String) {
+        match self.sources.include_chain(span.file).first() {
+            None => (span, message),
+            Some(include) => {
+                let file = self.sources.get(span.file);
+                let (line, col) = file.line_col(span.start);
+                (*include, format!("{}:{}:{}: {}", file.path.display(), line, col, message))
+            }
+        }
+    }
+
+    pub fn completions(&self) -> Vec<Completion> {
+        let statements = STATEMENTS.iter().map(|(name, usage, _)| Completion {
+            label: name.clone(),
+            detail: usage.clone(),
+            kind: CompletionKind::Keyword,
+        });
+
+        let mut library = check::library()
+            .iter()
+            .filter(|(name, _)| !SIMPLE_FUNCTIONS.contains_key(name.as_str()))
+            .map(|(name, signatures)| Completion {
+                label: name.clone(),
+                detail: signatures[0].declaration.clone(),
+                kind: CompletionKind::Function,
+            })
+            .collect::<Vec<_>>();
+        library.sort_by(|a, b| a.label.cmp(&b.label));
+
+        let defined = self.definitions.iter().map(|def| Completion {
+            label: def.name.clone(),
+            detail: def.signature.declaration.clone(),
+            kind: CompletionKind::Object,
+        });
+
+        statements.chain(library).chain(defined).collect()
+    }
+
+    /// Signature of the statement or function whose name is at the offset, in Markdown
+    pub fn hover(&self, offset: usize) -> Option<(Span, String)> {
+        let (name, span) = self.name_at(offset)?;
+
+        if let Some(def) = self.definitions.iter().find(|def| def.name == name) {
+            return Some((span, format!("```glsl\n{}\n```", def.signature.declaration)));
+        }
+
+        if let Some(signatures) = check::library().get(&name) {
+            let declarations = signatures.iter().map(|s| s.declaration.as_str()).collect::<Vec<_>>();
+            let mut hover = format!("```glsl\n{}\n```", declarations.join("\n"));
+            if let Some(signature) = signatures.iter().find(|s| !s.doc.is_empty()) {
+                hover.push('\n');
+                hover.push_str(&signature.doc);
+            }
+            return Some((span, hover));
+        }
+
+        STATEMENTS
+            .iter()
+            .find(|(statement, ..)| *statement == name)
+            .map(|(_, usage, doc)| (span, format!("```\n{}\n```\n{}", usage, doc)))
+    }
+
+    /// Where the object whose name is at the offset is defined
+    pub fn definition(&self, offset: usize) -> Option<(&SourceFile, Span)> {
+        let (name, _) = self.name_at(offset)?;
+        let def = self.definitions.iter().find(|def| def.name == name)?;
+
+        Some((self.sources.get(def.span.file), def.span))
+    }
+
+    /// Name of a statement, a function call or a definition in the scene file
+    fn name_at(&self, offset: usize) -> Option<(String, Span)> {
+        fn contains(span: Span, offset: usize) -> bool {
+            span.file == 0 && span.start <= offset && offset <= span.end
+        }
+
+        fn in_expr(expr: &Expr, span: Span, offset: usize) -> Option<(String, Span)> {
+            match expr {
+                Expr::Call(name, _) | Expr::Ident(name) => {
+                    let name_span = Span { end: span.start + name.len(), ..span };
+                    if contains(name_span, offset) {
+                        return Some((name.clone(), name_span));
+                    }
+                }
+                _ => {}
+            }
+
+            match expr {
+                Expr::Unary(_, operand) => in_expr(operand, operand.span, offset),
+                Expr::Binary(_, lhs, rhs) => {
+                    in_expr(lhs, lhs.span, offset).or_else(|| in_expr(rhs, rhs.span, offset))
+                }
+                Expr::Ternary(cond, then, otherwise) => in_expr(cond, cond.span, offset)
+                    .or_else(|| in_expr(then, then.span, offset))
+                    .or_else(|| in_expr(otherwise, otherwise.span, offset)),
+                Expr::Call(_, args) => args.iter().find_map(|arg| in_expr(arg, arg.span, offset)),
+                Expr::Field(base, _) => in_expr(base, base.span, offset),
+                _ => None,
+            }
+        }
+
+        fn in_statement(stmt: &Statement, offset: usize) -> Option<(String, Span)> {
+            if contains(stmt.name_span(), offset) {
+                return Some((stmt.name.clone(), stmt.name_span()));
+            }
+
+            stmt.args
+                .iter()
+                .find_map(|arg| in_expr(arg, arg.span, offset))
+                .or_else(|| stmt.body.iter().find_map(|child| in_statement(child, offset)))
+        }
+
+        self.statements.iter().find_map(|stmt| in_statement(stmt, offset))
+    }
+}
+
+/// Every error of a scene: checking stops at the first one, so the top level statement it is in is left out and the
+/// rest checked again
+fn scene_errors(mut statements: Vec<Statement>) -> Vec<SceneDescError> {
+    let mut errors = Vec::new();
+    // definitions left without their body, so that using them doesn't repeat their error
+    let mut stubs = Vec::new();
+
+    while let Err(e) = SceneDesc::from_statements(statements.clone()) {
+        let span = e.span();
+        let idx = statements.iter().position(|stmt| {
+            stmt.span.file == span.file && stmt.span.start <= span.start && span.end <= stmt.span.end
+        });
+        let idx = match idx {
+            Some(idx) => idx,
+            None => {
+                errors.push(e);
+                break;
+            }
+        };
+
+        let mut stmt = statements.remove(idx);
+        if stubs.contains(&stmt.span) {
+            continue;
+        }
+        errors.push(e);
+
+        if definition_marker(&stmt.name).is_some() && !stmt.args.is_empty() {
+            stmt.body.clear();
+            stubs.push(stmt.span);
+            statements.insert(idx, stmt);
+        }
+    }
+
+    errors
+}
+
+fn definition_marker(statement: &str) -> Option<TypeMarker> {
+    match statement {
+        "define_geometry" => Some(GeometryMarker.into()),
+        "define_geometry_2d" => Some(Geometry2dMarker.into()),
+        "define_opaque" => Some(OpaqueMarker.into()),
+        "define_transparent" => Some(TransparentMarker.into()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    fn analyze(text: &str) -> Analysis {
+        Analysis::new(Path::new("test.scene"), text)
+    }
+
+    #[test]
+    fn test_diagnostics() {
+        assert!(analyze("opaque(1, 1, 1) sd_sphere(1);").diagnostics.is_empty());
+
+        let analysis = analyze("opaque(1, 1, 1) {\n    sd_box(1, 2);\n}");
+        assert_eq!(
+            analysis.diagnostics,
+            [(Span::new(22, 28), String::from("`sd_box` expects 1 argument, got 2 (in `opaque`)"))]
+        );
+
+        let analysis = analyze("at(1, 2");
+        assert_eq!(analysis.diagnostics[0].1, "expected `)`");
+
+        let analysis = analyze(
+            "define_geometry(ball, float r) sd_sphere(vec2(r));\nopaque(1, 1, 1) ball(1);\nopaque(1, 1, 1) sd_box(1, 2);\ncamera {}\ncamera {}",
+        );
+        let messages = analysis.diagnostics.iter().map(|(_, message)| message.as_str()).collect::<Vec<_>>();
+        assert_eq!(
+            messages,
+            [
+                "Argument 1 of `sd_sphere` should be `float`, found `vec2` (in `define_geometry`)",
+                "`sd_box` expects 1 argument, got 2 (in `opaque`)",
+                "Duplicate camera",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_completions() {
+        let analysis = analyze("define_geometry(pillar, float h) sd_box(vec3(1, h, 1));");
+        let completions = analysis.completions();
+
+        let find = |label: &str| completions.iter().find(|c| c.label == label).unwrap();
+        assert_eq!(find("smooth_union").kind, CompletionKind::Keyword);
+        assert_eq!(find("advanced_repeat").kind, CompletionKind::Keyword);
+        assert_eq!(find("round_union").detail, "round_union(float r) { ... }");
+        assert_eq!(find("rotate").detail, "rotate(vec3 axis, float angle) { ... }");
+        assert_eq!(find("rotate").kind, CompletionKind::Keyword);
+        for (name, _, doc) in STATEMENTS.iter() {
+            assert!(!doc.is_empty(), "`{}` is not documented", name);
+        }
+        assert_eq!(find("sd_sphere").detail, "float sd_sphere(float r, Arg arg)");
+        assert_eq!(find("pillar").detail, "float pillar(float h, Arg arg)");
+        assert_eq!(find("pillar").kind, CompletionKind::Object);
+    }
+
+    #[test]
+    fn test_hover_and_definition() {
+        let text = "define_opaque(red_ball, float r) opaque(1, 0, 0) sd_sphere(r);\nat(0, 0, 5) red_ball(length(vec3(1)));";
+        let analysis = analyze(text);
+        assert!(analysis.diagnostics.is_empty(), "{:?}", analysis.diagnostics);
+
+        let usage = text.rfind("red_ball").unwrap();
+        let (span, hover) = analysis.hover(usage + 2).unwrap();
+        assert_eq!(span, Span::new(usage, usage + 8));
+        assert_eq!(hover, "```glsl\nMapOpaque red_ball(float r, Arg arg)\n```");
+
+        let (_, hover) = analysis.hover(text.find("sd_sphere").unwrap()).unwrap();
+        assert!(hover.contains("float sd_sphere(float r, Arg arg)"), "{}", hover);
+        assert!(hover.ends_with("\nSphere of radius `r`"), "{}", hover);
+        let (_, hover) = analysis.hover(text.find("at(").unwrap()).unwrap();
+        assert!(hover.contains("Arg at(float x, float y, float z, Arg arg)"), "{}", hover);
+        assert!(analysis.hover(text.find("1, 0, 0").unwrap()).is_none());
+
+        let (_, hover) = analyze("round_union(0.5) { a(); b(); }").hover(0).unwrap();
+        assert_eq!(hover, "```\nround_union(float r) { ... }\n```\nUnion with a fillet of radius `r` where objects meet.");
+
+        let (file, span) = analysis.definition(usage).unwrap();
+        assert_eq!(file.path, Path::new("test.scene"));
+        assert_eq!(span, Span::new(14, 22));
+        assert!(analysis.definition(text.find("sd_sphere").unwrap()).is_none());
+    }
+}
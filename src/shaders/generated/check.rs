@@ -0,0 +1,1046 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+use super::desc::material::property_components;
+use super::desc::{Arity, Statement, StatementError, StatementErrorKind};
+use super::expr::{BinaryOp, Expr, UnaryOp};
+use super::source::{Span, Spanned};
+use super::typed::{Geometry2dMarker, GeometryMarker, Operator, TypeMarker};
+
+lazy_static! {
+    /// Functions defined in `library.glsl`
+    static ref LIBRARY: HashMap<String, Vec<Signature>> =
+        parse_signatures(include_str!("../../glsl/library.glsl"));
+
+    /// Uniforms from `header.glsl`, plus the `arg` every generated function gets
+    static ref GLOBALS: HashMap<String, Type> = {
+        let mut globals = include_str!("../../glsl/header.glsl")
+            .lines()
+            .filter_map(|line| line.trim().strip_prefix("uniform "))
+            .filter_map(|decl| match decl.trim_end_matches(';').split_whitespace().collect::<Vec<_>>()[..] {
+                [typ, name] => Some((name.to_owned(), Type::parse(typ))),
+                _ => None,
+            })
+            .collect::<HashMap<_, _>>();
+
+        globals.insert(String::from("arg"), Type::Arg);
+        globals
+    };
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Type {
+    Float,
+    /// Distance to a 2D shape, a `float` to GLSL
+    Float2d,
+    Vec2,
+    Vec3,
+    Vec4,
+    Bool,
+    Arg,
+    Material,
+    MapOpaque,
+    MapTransparent,
+    /// Anything the checker doesn't reason about, compatible with every other type
+    Other(String),
+}
+
+impl Type {
+    pub fn parse(name: &str) -> Self {
+        match name {
+            "float" => Type::Float,
+            "float2d" => Type::Float2d,
+            "vec2" => Type::Vec2,
+            "vec3" => Type::Vec3,
+            "vec4" => Type::Vec4,
+            "bool" => Type::Bool,
+            "Arg" => Type::Arg,
+            "Material" => Type::Material,
+            "MapOpaque" => Type::MapOpaque,
+            "MapTransparent" => Type::MapTransparent,
+            _ => Type::Other(name.to_owned()),
+        }
+    }
+
+    fn vector(components: usize) -> Self {
+        match components {
+            1 => Type::Float,
+            2 => Type::Vec2,
+            3 => Type::Vec3,
+            4 => Type::Vec4,
+            _ => Type::Other(format!("vec{}", components)),
+        }
+    }
+
+    /// Number of components of `float` and vector types
+    fn components(&self) -> Option<usize> {
+        match self {
+            Type::Float | Type::Float2d => Some(1),
+            Type::Vec2 => Some(2),
+            Type::Vec3 => Some(3),
+            Type::Vec4 => Some(4),
+            _ => None,
+        }
+    }
+
+    pub fn is_other(&self) -> bool {
+        matches!(self, Type::Other(_))
+    }
+
+    /// Whether a value of type `other` can be used as this type, a `float` working as a 2D distance
+    fn accepts(&self, other: &Type) -> bool {
+        self == other || self.is_other() || other.is_other() || (*self == Type::Float2d && *other == Type::Float)
+    }
+
+    fn field(&self, field: &str) -> Option<Type> {
+        match self {
+            Type::Arg => match field {
+                "p" => Some(Type::Vec3),
+                "t" => Some(Type::Float),
+                _ => None,
+            },
+            Type::Material => match field {
+                "albedo" | "emissive" => Some(Type::Vec3),
+                "roughness" | "metallic" => Some(Type::Float),
+                _ => None,
+            },
+            Type::MapOpaque => match field {
+                "material" => Some(Type::Material),
+                "d" => Some(Type::Float),
+                _ => None,
+            },
+            Type::MapTransparent => match field {
+                "color" => Some(Type::Vec4),
+                "d" => Some(Type::Float),
+                _ => None,
+            },
+            Type::Other(_) => Some(self.clone()),
+            _ => {
+                let components = self.components()?;
+                let swizzle_sets = ["xyzw", "rgba", "stpq"];
+
+                let valid = field.len() <= 4
+                    && swizzle_sets.iter().any(|set| {
+                        field
+                            .chars()
+                            .all(|c| set[..components].contains(c))
+                    });
+
+                if valid {
+                    Some(Type::vector(field.len()))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+}
+
+impl fmt::Display for Type {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let name = match self {
+            Type::Float => "float",
+            Type::Float2d => "float2d",
+            Type::Vec2 => "vec2",
+            Type::Vec3 => "vec3",
+            Type::Vec4 => "vec4",
+            Type::Bool => "bool",
+            Type::Arg => "Arg",
+            Type::Material => "Material",
+            Type::MapOpaque => "MapOpaque",
+            Type::MapTransparent => "MapTransparent",
+            Type::Other(name) => name,
+        };
+
+        write!(f, "{}", name)
+    }
+}
+
+impl From<TypeMarker> for Type {
+    fn from(marker: TypeMarker) -> Self {
+        Type::parse(marker.typ())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Signature {
+    pub ret: Type,
+    pub params: Vec<Type>,
+    /// The GLSL declaration, like `float sd_sphere(float r, Arg arg)`
+    pub declaration: String,
+    /// What the `///` comments before a `library.glsl` function say about it
+    pub doc: String,
+}
+
+impl Signature {
+    /// Signature of the function generated for a `define_*` statement
+    pub fn of_definition(stmt: &Statement, marker: TypeMarker) -> Self {
+        let params = stmt.args[1..]
+            .iter()
+            .filter_map(|arg| match &arg.node {
+                Expr::Declaration(typ, name) => Some((typ.as_str(), name.as_str())),
+                _ => None,
+            })
+            .chain(std::iter::once(("Arg", "arg")))
+            .collect::<Vec<_>>();
+
+        let declaration = format!(
+            "{} {}({})",
+            marker.typ(),
+            stmt.args[0],
+            params.iter().map(|(typ, name)| format!("{} {}", typ, name)).collect::<Vec<_>>().join(", ")
+        );
+
+        Signature {
+            ret: marker.into(),
+            params: params.into_iter().map(|(typ, _)| Type::parse(typ)).collect(),
+            declaration,
+            doc: String::new(),
+        }
+    }
+
+    /// Signature of the function generated for a `material` statement
+    pub fn of_material(stmt: &Statement) -> Self {
+        Signature {
+            ret: Type::Material,
+            params: vec![Type::Arg],
+            declaration: format!("Material {}(Arg arg)", stmt.args[0]),
+            doc: String::new(),
+        }
+    }
+
+    /// Objects and transforms get the `Arg` as their last parameter, which the scene doesn't pass
+    fn scene_params(&self) -> Option<&[Type]> {
+        match self.params.split_last() {
+            Some((Type::Arg, params)) => Some(params),
+            _ => None,
+        }
+    }
+}
+
+/// Finds top level function definitions, which always start at the beginning of a line
+fn parse_signatures(source: &str) -> HashMap<String, Vec<Signature>> {
+    let mut functions = HashMap::<_, Vec<_>>::new();
+    let mut doc = Vec::new();
+
+    for line in source.lines() {
+        if let Some(text) = line.strip_prefix("///") {
+            doc.push(text.trim());
+            continue;
+        }
+        let doc = std::mem::take(&mut doc).join(" ");
+
+        if line.starts_with(char::is_whitespace) || line.starts_with("struct") {
+            continue;
+        }
+
+        let (head, params) = match (line.find('('), line.find(')')) {
+            (Some(open), Some(close)) if open < close => (&line[..open], &line[open + 1..close]),
+            _ => continue,
+        };
+
+        if let [ret, name] = head.split_whitespace().collect::<Vec<_>>()[..] {
+            let declaration = format!("{} {}({})", ret, name, params.trim());
+            let params = params
+                .split(',')
+                .filter_map(|param| param.split_whitespace().rev().nth(1))
+                .map(Type::parse)
+                .collect();
+
+            functions.entry(name.to_owned()).or_default().push(Signature {
+                ret: Type::parse(ret),
+                params,
+                declaration,
+                doc,
+            });
+        }
+    }
+
+    functions
+}
+
+/// Functions of `library.glsl`, by name
+pub fn library() -> &'static HashMap<String, Vec<Signature>> {
+    &LIBRARY
+}
+
+/// Functions and constructors that GLSL provides
+pub const BUILTINS: &[&str] = &[
+    // constructors
+    "bool", "int", "uint", "float", "vec2", "vec3", "vec4", "bvec2", "bvec3", "bvec4", "ivec2", "ivec3", "ivec4",
+    "uvec2", "uvec3", "uvec4", "mat2", "mat3", "mat4", "mat2x3", "mat2x4", "mat3x2", "mat3x4", "mat4x2", "mat4x3",
+    // angles and trigonometry
+    "radians", "degrees", "sin", "cos", "tan", "asin", "acos", "atan", "sinh", "cosh", "tanh", "asinh", "acosh",
+    "atanh",
+    // exponentials
+    "pow", "exp", "log", "exp2", "log2", "sqrt", "inversesqrt",
+    // common
+    "abs", "sign", "floor", "trunc", "round", "roundEven", "ceil", "fract", "mod", "modf", "min", "max", "clamp",
+    "mix", "step", "smoothstep", "isnan", "isinf", "fma",
+    // geometry
+    "length", "distance", "dot", "cross", "normalize", "faceforward", "reflect", "refract",
+    // matrices
+    "matrixCompMult", "outerProduct", "transpose", "determinant", "inverse",
+    // vector relations
+    "lessThan", "lessThanEqual", "greaterThan", "greaterThanEqual", "equal", "notEqual", "any", "all", "not",
+    // textures and derivatives
+    "texture", "textureLod", "texelFetch", "textureSize", "dFdx", "dFdy", "fwidth",
+];
+
+#[derive(Debug, Clone, Copy)]
+enum Builtin {
+    /// Works on each component, with `float` arguments broadcast to the vector size
+    ComponentWise,
+    /// Takes vectors and returns a `float`
+    Reduce,
+    Cross,
+}
+
+fn builtin(name: &str) -> Option<(Builtin, Arity)> {
+    let builtin = match name {
+        "radians" | "degrees" | "sin" | "cos" | "tan" | "asin" | "acos" | "exp" | "log" | "exp2"
+        | "log2" | "sqrt" | "inversesqrt" | "abs" | "sign" | "floor" | "ceil" | "trunc" | "round"
+        | "fract" | "normalize" => (Builtin::ComponentWise, Arity::Exactly(1)),
+        "atan" => (Builtin::ComponentWise, Arity::Between(1, 2)),
+        "pow" | "mod" | "min" | "max" | "step" | "reflect" => (Builtin::ComponentWise, Arity::Exactly(2)),
+        "clamp" | "mix" | "smoothstep" => (Builtin::ComponentWise, Arity::Exactly(3)),
+        "length" => (Builtin::Reduce, Arity::Exactly(1)),
+        "distance" | "dot" => (Builtin::Reduce, Arity::Exactly(2)),
+        "cross" => (Builtin::Cross, Arity::Exactly(2)),
+        _ => return None,
+    };
+
+    Some(builtin)
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+pub enum TypeError {
+    #[error("Unknown function `{}`", .0)]
+    UnknownFunction(String),
+    #[error("Unknown name `{}`", .0)]
+    UnknownName(String),
+    #[error("`{}` has no field `{}`", .typ, .field)]
+    UnknownField { typ: Type, field: String },
+    #[error("Argument {} of `{}` should be `{}`, found `{}`", .index + 1, .function, .expected, .found)]
+    ArgumentType {
+        function: String,
+        index: usize,
+        expected: Type,
+        found: Type,
+    },
+    #[error("Expected `{}`, found `{}`", .expected, .found)]
+    Mismatch { expected: Type, found: Type },
+    #[error("Cannot apply `{}` to `{}` and `{}`", .op, .lhs, .rhs)]
+    Operands {
+        op: &'static str,
+        lhs: Type,
+        rhs: Type,
+    },
+    #[error("`{}` needs {} components, got {}", .function, .expected, .found)]
+    Components {
+        function: String,
+        expected: usize,
+        found: usize,
+    },
+    #[error("`{}` returns `{}`, but {} objects need `{}`", .name, .found, .context, .expected)]
+    ObjectType {
+        name: String,
+        found: Type,
+        context: &'static str,
+        expected: Type,
+    },
+    #[error("`{}` is not an object or a transform", .0)]
+    NotAnObject(String),
+}
+
+fn error(kind: impl Into<StatementErrorKind>, span: Span) -> StatementError {
+    StatementError::new(kind.into(), span)
+}
+
+/// Checks statements against the signatures of library functions and already defined objects
+pub struct Checker {
+    defined: HashMap<String, Vec<Signature>>,
+    /// Names of the `material` statements
+    materials: HashSet<String>,
+    /// Parameters of the object being defined and names bound by `let` so far, latest last
+    scope: Vec<(String, Type)>,
+}
+
+impl Checker {
+    pub fn new() -> Self {
+        Checker {
+            defined: HashMap::new(),
+            materials: HashSet::new(),
+            scope: Vec::new(),
+        }
+    }
+
+    /// Checks the properties of a `material` statement and makes it available to `opaque`
+    pub fn define_material(&mut self, stmt: &Statement) -> Result<(), StatementError> {
+        let name = stmt.args[0].to_string();
+        if !self.materials.insert(name.clone()) {
+            return Err(error(StatementErrorKind::DuplicateMaterial(name), stmt.args[0].span));
+        }
+
+        self.scope.clear();
+        stmt.body
+            .iter()
+            .try_for_each(|property| {
+                let expected = property_components(&property.name).unwrap_or_default();
+                self.expect_components(property, expected)
+            })
+            .map_err(|e| e.within(&stmt.name))
+    }
+
+    /// Checks the body of a `define_*` statement and makes the object available to later statements
+    pub fn define(&mut self, stmt: &Statement, marker: TypeMarker) -> Result<(), StatementError> {
+        self.scope = stmt.args[1..]
+            .iter()
+            .filter_map(|arg| match &arg.node {
+                Expr::Declaration(typ, name) => Some((name.clone(), Type::parse(typ))),
+                _ => None,
+            })
+            .collect();
+        self.check_body(stmt, marker)?;
+
+        let signature = Signature::of_definition(stmt, marker);
+        self.defined
+            .entry(stmt.args[0].to_string())
+            .or_default()
+            .push(signature);
+
+        Ok(())
+    }
+
+    /// Type of the object a statement makes, when the statement alone tells it
+    pub fn object_type(&self, stmt: &Statement) -> Option<Type> {
+        let signature = LIBRARY
+            .get(&stmt.name)
+            .into_iter()
+            .chain(self.defined.get(&stmt.name))
+            .flatten()
+            .next();
+
+        match (stmt.name.as_str(), signature) {
+            ("opaque", _) => Some(Type::MapOpaque),
+            ("transparent", _) => Some(Type::MapTransparent),
+            ("raw", _) => None,
+            (_, Some(signature)) if signature.ret != Type::Arg => Some(signature.ret.clone()),
+            // transforms and folds make whatever their body makes
+            _ => stmt.body.iter().find_map(|child| self.object_type(child)),
+        }
+    }
+
+    /// Checks the statements that end up in one `map` function
+    pub fn check_all(&mut self, stmts: &[Statement], marker: TypeMarker) -> Result<(), StatementError> {
+        self.scope.clear();
+        stmts.iter().try_for_each(|stmt| self.check(stmt, marker))
+    }
+
+    fn check(&mut self, stmt: &Statement, marker: TypeMarker) -> Result<(), StatementError> {
+        match stmt.name.as_str() {
+            "raw" => self.expect(&stmt.args[0], &marker.into())?,
+
+            "union" => {}
+
+            "intersection" | "difference" => {
+                if let Some(arg) = stmt.args.first() {
+                    self.expect(arg, &Type::Bool)?;
+                }
+            }
+
+            "smooth_union" | "onionize" | "scale" | "round" | "twist" | "bend" => {
+                self.expect(&stmt.args[0], &Type::Float)?
+            }
+
+            "displace" => {
+                for arg in &stmt.args {
+                    self.expect(arg, &Type::Float)?;
+                }
+            }
+
+            name if Operator::find(name).is_some() => {
+                for arg in &stmt.args {
+                    self.expect(arg, &Type::Float)?;
+                }
+            }
+
+            "advanced_repeat" => {
+                for arg in &stmt.args {
+                    self.expect(arg, &Type::Float)?;
+                }
+            }
+
+            "cond" | "cond_else" => self.expect(&stmt.args[0], &Type::Bool)?,
+
+            "switch" => {
+                for arg in &stmt.args {
+                    self.expect(arg, &Type::Float)?;
+                }
+            }
+
+            "opaque" | "transparent" => {
+                let material = match &stmt.args[..] {
+                    [arg] if stmt.name == "opaque" => match &arg.node {
+                        Expr::Ident(name) => self.materials.contains(name),
+                        _ => false,
+                    },
+                    _ => false,
+                };
+
+                if !material {
+                    let expected = if stmt.name == "opaque" { 3 } else { 4 };
+                    self.expect_components(stmt, expected)?;
+                }
+
+                return self.check_body(stmt, TypeMarker::Geometry(GeometryMarker));
+            }
+
+            "extrude" | "revolve" => {
+                self.expect(&stmt.args[0], &Type::Float)?;
+                return self.check_body(stmt, TypeMarker::Geometry2d(Geometry2dMarker));
+            }
+
+            "sd2_polygon" => {
+                for arg in &stmt.args {
+                    self.expect(arg, &Type::Vec2)?;
+                }
+            }
+
+            "let" => {
+                let typ = Type::parse(&stmt.args[0].to_string());
+                self.expect(&stmt.args[2], &typ)?;
+
+                // the binding is only visible in the body, where it shadows any outer one
+                self.scope.push((stmt.args[1].to_string(), typ));
+                let result = self.check_body(stmt, marker);
+                self.scope.pop();
+
+                return result;
+            }
+
+            _ => {
+                let signature = self.call(&stmt.name, stmt.name_span(), &stmt.args, true)?;
+                let expected = Type::from(marker);
+
+                // unlike in expressions, a `float` object is 3D geometry rather than a 2D shape
+                let fits = expected.accepts(&signature.ret) && !(expected == Type::Float2d && signature.ret == Type::Float);
+                if signature.ret != Type::Arg && !fits {
+                    return Err(error(
+                        TypeError::ObjectType {
+                            name: stmt.name.clone(),
+                            found: signature.ret,
+                            context: marker.name(),
+                            expected,
+                        },
+                        stmt.name_span(),
+                    ));
+                }
+            }
+        }
+
+        self.check_body(stmt, marker)
+    }
+
+    fn check_body(&mut self, stmt: &Statement, marker: TypeMarker) -> Result<(), StatementError> {
+        stmt.body
+            .iter()
+            .try_for_each(|child| self.check(child, marker))
+            .map_err(|e| e.within(&stmt.name))
+    }
+
+    fn expect(&self, expr: &Spanned<Expr>, expected: &Type) -> Result<(), StatementError> {
+        let found = self.type_of(expr)?;
+
+        if expected.accepts(&found) {
+            Ok(())
+        } else {
+            Err(error(
+                TypeError::Mismatch {
+                    expected: expected.clone(),
+                    found,
+                },
+                expr.span,
+            ))
+        }
+    }
+
+    /// Checks that the arguments of a statement add up to a vector of the given size
+    fn expect_components(&self, stmt: &Statement, expected: usize) -> Result<(), StatementError> {
+        match self.components(&stmt.args)? {
+            Some(found) if found != expected => Err(error(
+                TypeError::Components {
+                    function: stmt.name.clone(),
+                    expected,
+                    found,
+                },
+                stmt.name_span(),
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    /// Total number of components, or `None` if some of the types are unknown
+    fn components(&self, args: &[Spanned<Expr>]) -> Result<Option<usize>, StatementError> {
+        let mut total = 0;
+
+        for arg in args {
+            match self.type_of(arg)? {
+                Type::Other(_) => return Ok(None),
+                typ => match typ.components() {
+                    Some(n) => total += n,
+                    None => {
+                        return Err(error(
+                            TypeError::Mismatch {
+                                expected: Type::Float,
+                                found: typ,
+                            },
+                            arg.span,
+                        ))
+                    }
+                },
+            }
+        }
+
+        Ok(Some(total))
+    }
+
+    /// Finds the signature matching a call, `scene` calls don't pass the trailing `Arg`
+    fn call(
+        &self,
+        name: &str,
+        span: Span,
+        args: &[Spanned<Expr>],
+        scene: bool,
+    ) -> Result<Signature, StatementError> {
+        let candidates = LIBRARY
+            .get(name)
+            .into_iter()
+            .chain(self.defined.get(name))
+            .flatten()
+            .filter_map(|signature| {
+                let params = if scene { signature.scene_params()? } else { &signature.params[..] };
+                Some((signature, params))
+            })
+            .collect::<Vec<_>>();
+
+        if candidates.is_empty() {
+            let kind = if scene && (LIBRARY.contains_key(name) || self.defined.contains_key(name)) {
+                TypeError::NotAnObject(name.to_owned())
+            } else {
+                TypeError::UnknownFunction(name.to_owned())
+            };
+
+            return Err(error(kind, span));
+        }
+
+        let types = args
+            .iter()
+            .map(|arg| self.type_of(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut first_error = None;
+        for (signature, params) in candidates {
+            if params.len() != args.len() {
+                first_error.get_or_insert_with(|| {
+                    error(
+                        StatementErrorKind::WrongArgumentCount {
+                            statement: name.to_owned(),
+                            expected: Arity::Exactly(params.len()),
+                            actual: args.len(),
+                        },
+                        span,
+                    )
+                });
+                continue;
+            }
+
+            let mismatch = params
+                .iter()
+                .zip(&types)
+                .position(|(param, typ)| !param.accepts(typ));
+
+            match mismatch {
+                None => return Ok(signature.clone()),
+                Some(index) => {
+                    first_error.get_or_insert_with(|| {
+                        error(
+                            TypeError::ArgumentType {
+                                function: name.to_owned(),
+                                index,
+                                expected: params[index].clone(),
+                                found: types[index].clone(),
+                            },
+                            args[index].span,
+                        )
+                    });
+                }
+            }
+        }
+
+        Err(first_error.unwrap())
+    }
+
+    pub fn type_of(&self, expr: &Spanned<Expr>) -> Result<Type, StatementError> {
+        let typ = match &expr.node {
+            Expr::Number(_) => Type::Float,
+            Expr::String(_) => Type::Other(String::from("string")),
+            Expr::Declaration(..) | Expr::Symbol(_) => Type::Other(expr.to_string()),
+
+            Expr::Ident(name) => match name.as_str() {
+                "true" | "false" => Type::Bool,
+                _ => self
+                    .scope
+                    .iter()
+                    .rev()
+                    .find(|(bound, _)| bound == name)
+                    .map(|(_, typ)| typ)
+                    .or_else(|| GLOBALS.get(name))
+                    .cloned()
+                    .ok_or_else(|| error(TypeError::UnknownName(name.clone()), expr.span))?,
+            },
+
+            Expr::Arg(field) => Type::Arg
+                .field(field)
+                .ok_or_else(|| error(TypeError::UnknownField { typ: Type::Arg, field: field.clone() }, expr.span))?,
+
+            Expr::Field(base, field) => {
+                let typ = self.type_of(base)?;
+                typ.field(field).ok_or_else(|| {
+                    error(TypeError::UnknownField { typ, field: field.clone() }, expr.span)
+                })?
+            }
+
+            Expr::Unary(op, operand) => {
+                let typ = self.type_of(operand)?;
+                match op {
+                    UnaryOp::Not => {
+                        self.expect(operand, &Type::Bool)?;
+                        Type::Bool
+                    }
+                    UnaryOp::Neg | UnaryOp::Plus | UnaryOp::BitNot => typ,
+                }
+            }
+
+            Expr::Binary(op, lhs, rhs) => {
+                let lhs_type = self.type_of(lhs)?;
+                let rhs_type = self.type_of(rhs)?;
+                self.binary(*op, lhs_type, rhs_type)
+                    .map_err(|e| error(e, expr.span))?
+            }
+
+            Expr::Ternary(cond, then, otherwise) => {
+                self.expect(cond, &Type::Bool)?;
+                let typ = self.type_of(then)?;
+                self.expect(otherwise, &typ)?;
+                typ
+            }
+
+            Expr::Call(name, args) => self.call_expr(name, expr.span, args)?,
+        };
+
+        Ok(typ)
+    }
+
+    fn binary(&self, op: BinaryOp, lhs: Type, rhs: Type) -> Result<Type, TypeError> {
+        if lhs.is_other() || rhs.is_other() {
+            return Ok(if lhs.is_other() { lhs } else { rhs });
+        }
+
+        let mismatch = |lhs: Type, rhs: Type| TypeError::Operands { op: op.symbol(), lhs, rhs };
+
+        match op {
+            BinaryOp::Add | BinaryOp::Sub | BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => {
+                match (lhs.components(), rhs.components()) {
+                    (Some(1), Some(_)) => Ok(rhs),
+                    (Some(_), Some(1)) => Ok(lhs),
+                    (Some(a), Some(b)) if a == b => Ok(lhs),
+                    _ => Err(mismatch(lhs, rhs)),
+                }
+            }
+
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => {
+                if lhs == Type::Float && rhs == Type::Float {
+                    Ok(Type::Bool)
+                } else {
+                    Err(mismatch(lhs, rhs))
+                }
+            }
+
+            BinaryOp::Eq | BinaryOp::Ne => {
+                if lhs == rhs {
+                    Ok(Type::Bool)
+                } else {
+                    Err(mismatch(lhs, rhs))
+                }
+            }
+
+            BinaryOp::And | BinaryOp::Or | BinaryOp::Xor => {
+                if lhs == Type::Bool && rhs == Type::Bool {
+                    Ok(Type::Bool)
+                } else {
+                    Err(mismatch(lhs, rhs))
+                }
+            }
+
+            // integer operations, which scenes have no use for
+            BinaryOp::BitOr | BinaryOp::BitXor | BinaryOp::BitAnd | BinaryOp::Shl | BinaryOp::Shr => {
+                Ok(Type::Other(String::from("int")))
+            }
+        }
+    }
+
+    fn call_expr(&self, name: &str, span: Span, args: &[Spanned<Expr>]) -> Result<Type, StatementError> {
+        if let Some(components) = name.strip_prefix("vec").and_then(|n| n.parse().ok()) {
+            let found = self.components(args)?;
+
+            return match found {
+                // a single `float` fills every component
+                Some(found) if found == components || (found == 1 && args.len() == 1) => {
+                    Ok(Type::vector(components))
+                }
+                None => Ok(Type::vector(components)),
+                Some(found) => Err(error(
+                    TypeError::Components {
+                        function: name.to_owned(),
+                        expected: components,
+                        found,
+                    },
+                    span,
+                )),
+            };
+        }
+
+        let (builtin, arity) = match builtin(name) {
+            Some(builtin) => builtin,
+            None if BUILTINS.contains(&name) && !LIBRARY.contains_key(name) && !self.defined.contains_key(name) => {
+                for arg in args {
+                    self.type_of(arg)?;
+                }
+
+                // constructors are named after their type, other results go unchecked
+                return Ok(Type::parse(name));
+            }
+            None => return self.call(name, span, args, false).map(|signature| signature.ret),
+        };
+
+        if !arity.accepts(args.len()) {
+            return Err(error(
+                StatementErrorKind::WrongArgumentCount {
+                    statement: name.to_owned(),
+                    expected: arity,
+                    actual: args.len(),
+                },
+                span,
+            ));
+        }
+
+        let types = args
+            .iter()
+            .map(|arg| self.type_of(arg))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        // the widest argument decides the type, the rest has to match it or be a `float`
+        let mut result = Type::Float;
+        for (index, typ) in types.iter().enumerate() {
+            let fits = match (typ.components(), result.components()) {
+                _ if typ.is_other() => true,
+                (Some(1), _) => true,
+                (Some(_), Some(1)) => {
+                    result = typ.clone();
+                    true
+                }
+                (Some(a), Some(b)) => a == b,
+                (None, _) | (_, None) => false,
+            };
+
+            if !fits {
+                return Err(error(
+                    TypeError::ArgumentType {
+                        function: name.to_owned(),
+                        index,
+                        expected: result,
+                        found: typ.clone(),
+                    },
+                    args[index].span,
+                ));
+            }
+        }
+
+        Ok(match builtin {
+            Builtin::ComponentWise => result,
+            Builtin::Reduce => Type::Float,
+            Builtin::Cross => Type::Vec3,
+        })
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::desc::{SceneDescError, test::parse_scene};
+
+    fn type_error(source: &str) -> (String, Span) {
+        match parse_scene(source.as_bytes()) {
+            Err(SceneDescError::StatementError(e)) => (e.to_string(), e.span),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("no error in `{}`", source),
+        }
+    }
+
+    #[test]
+    fn test_library_signatures() {
+        let rotate = &LIBRARY["rotate"][0];
+        assert_eq!(rotate.ret, Type::Arg);
+        assert_eq!(rotate.params, [Type::Vec3, Type::Float, Type::Arg]);
+        assert_eq!(LIBRARY["sd_box"][0].scene_params().unwrap(), [Type::Vec3]);
+        assert_eq!(GLOBALS["time"], Type::Float);
+    }
+
+    #[test]
+    fn test_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let source = std::fs::read(&path).unwrap();
+
+            if let Err(e) = parse_scene(&source) {
+                panic!("{}: {}", path.display(), e);
+            }
+        }
+    }
+
+    #[test]
+    fn test_unknown_names() {
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_spehre(1);"),
+            (String::from("Unknown function `sd_spehre` (in `opaque`)"), Span::new(16, 25))
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_sphere(radius);").0,
+            "Unknown name `radius` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_sphere($q.x);").0,
+            "`Arg` has no field `q` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_sphere($p.w);").0,
+            "`vec3` has no field `w` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) vmax(vec3(1));").0,
+            "`vmax` is not an object or a transform (in `opaque`)"
+        );
+    }
+
+    #[test]
+    fn test_glsl_builtins() {
+        assert!(parse_scene(b"opaque(1, 1, 1) raw(length($p) - float(1));").is_ok());
+        assert!(parse_scene(b"opaque(1, 1, 1) let(mat2, m, mat2(1, 0, 0, 1)) sd_sphere(1);").is_ok());
+        assert!(parse_scene(b"opaque(1, 1, 1) sd_sphere(tanh($t) + 1);").is_ok());
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_sphere(tanh(radius));").0,
+            "Unknown name `radius` (in `opaque`)"
+        );
+    }
+
+    #[test]
+    fn test_arguments() {
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_box(1, 2);").0,
+            "`sd_box` expects 1 argument, got 2 (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_sphere(vec3(1));"),
+            (
+                String::from("Argument 1 of `sd_sphere` should be `float`, found `vec3` (in `opaque`)"),
+                Span::new(26, 33)
+            )
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) rotate(vec2(1, 0), 1) sd_sphere(1);").0,
+            "Argument 1 of `rotate` should be `vec3`, found `vec2` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_box(vec3(1, 2));").0,
+            "`vec3` needs 3 components, got 2 (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_box(vec3(1) + vec2(1));").0,
+            "Cannot apply `+` to `vec3` and `vec2` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd_sphere(length(vec3(1), 2));").0,
+            "`length` expects 1 argument, got 2 (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) cond($t) sd_sphere(1);").0,
+            "Expected `bool`, found `float` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1) sd_sphere(1);").0,
+            "`opaque` needs 3 components, got 2"
+        );
+    }
+
+    #[test]
+    fn test_2d_shapes() {
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd2_circle(1);"),
+            (
+                String::from("`sd2_circle` returns `float2d`, but geometry objects need `float` (in `opaque`)"),
+                Span::new(16, 26)
+            )
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) extrude(1) sd_sphere(1);").0,
+            "`sd_sphere` returns `float`, but 2D geometry objects need `float2d` (in `opaque` > `extrude`)"
+        );
+        assert_eq!(
+            type_error("define_geometry_2d(ring) sd2_circle(1); opaque(1, 1, 1) ring();").0,
+            "`ring` returns `float2d`, but geometry objects need `float` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) sd2_polygon(vec2(0), vec2(1, 0), vec2(0, 1));").0,
+            "`sd2_polygon` cannot be used inside geometry objects (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) revolve(1) extrude(1) sd2_circle(1);").0,
+            "`extrude` cannot be used inside 2D geometry objects (in `opaque` > `revolve`)"
+        );
+        assert_eq!(
+            type_error("opaque(1, 1, 1) extrude(1) sd2_polygon(vec2(0), vec2(1, 0), vec3(0, 1, 0));").0,
+            "Expected `vec2`, found `vec3` (in `opaque` > `extrude`)"
+        );
+        assert!(parse_scene(b"opaque(1, 1, 1) extrude(1) raw(length($p.xy) - 1);").is_ok());
+    }
+
+    #[test]
+    fn test_defined_objects() {
+        assert_eq!(
+            type_error("define_geometry(ring, float r, vec2 size) sd_sphere(size);").0,
+            "Argument 1 of `sd_sphere` should be `float`, found `vec2` (in `define_geometry`)"
+        );
+        assert_eq!(
+            type_error("define_geometry(ball, float r) sd_sphere(r); opaque(1, 1, 1) ball(vec2(1));").0,
+            "Argument 1 of `ball` should be `float`, found `vec2` (in `opaque`)"
+        );
+        assert_eq!(
+            type_error("define_geometry(ball, float r) sd_sphere(r); define_opaque(red_ball) ball(1);").0,
+            "`ball` returns `float`, but opaque objects need `MapOpaque` (in `define_opaque`)"
+        );
+        assert_eq!(
+            type_error("define_geometry(ball) let(float, r, 1) sd_sphere(r); define_geometry(other) sd_sphere(r);").0,
+            "Unknown name `r` (in `define_geometry`)"
+        );
+        assert_eq!(
+            type_error("define_geometry(pair) union { let(float, r, 1) sd_sphere(r); sd_sphere(r); }").0,
+            "Unknown name `r` (in `define_geometry` > `union`)"
+        );
+        assert_eq!(type_error("define_geometry(ball) let(float, r, vec2(1)) sd_sphere(r);").0, "Expected `float`, found `vec2` (in `define_geometry`)");
+        assert_eq!(
+            type_error("define_geometry(ball) let(float, r, 1) let(vec2, r, vec2(r)) sd_sphere(r);").0,
+            "Argument 1 of `sd_sphere` should be `float`, found `vec2` (in `define_geometry` > `let` > `let`)"
+        );
+    }
+}
@@ -1,6 +1,16 @@
+use std::collections::HashMap;
+use std::fmt;
+
+use super::expr;
+use super::source::Span;
+
 pub struct Glsl {
     uniq: usize,
     functions: Vec<String>,
+    /// Statement each line of the output comes from, when it's known
+    lines: Vec<Option<Span>>,
+    /// Whether functions reuse identical definitions
+    reuse_definitions: bool,
 }
 
 impl Glsl {
@@ -8,6 +18,17 @@ impl Glsl {
         Glsl {
             uniq: 0,
             functions: Vec::new(),
+            lines: Vec::new(),
+            reuse_definitions: true,
+        }
+    }
+
+    /// Emits every definition, even when an identical one already exists
+    #[cfg(test)]
+    pub fn without_reuse() -> Self {
+        Glsl {
+            reuse_definitions: false,
+            ..Glsl::new()
         }
     }
 
@@ -24,17 +45,26 @@ impl Glsl {
             ret: typ.to_string(),
             name: name.to_string(),
             args: args
-                .into_iter()
+                .iter()
                 .map(|(a, b)| (a.to_string(), b.to_string()))
                 .collect(),
             definitions: Vec::new(),
+            known: HashMap::new(),
+            reuse_definitions: self.reuse_definitions,
+            statements: Vec::new(),
+            top_level: Vec::new(),
         }
     }
+
+    /// Statement each line of the output comes from, one entry per line
+    pub fn lines(&self) -> &[Option<Span>] {
+        &self.lines
+    }
 }
 
-impl ToString for Glsl {
-    fn to_string(&self) -> String {
-        self.functions.join("\n")
+impl fmt::Display for Glsl {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.functions.join("\n"))
     }
 }
 
@@ -44,23 +74,85 @@ pub struct Function {
     ret: String,
     name: String,
     args: Vec<(String, String)>,
-    definitions: Vec<String>,
+    /// Lines of the body, with the statement being generated when they were added
+    definitions: Vec<(String, Option<Span>)>,
+    /// Generated definitions by their type and expression, so identical ones are only computed once
+    known: HashMap<(String, String), String>,
+    reuse_definitions: bool,
+    /// Statements currently being generated, innermost last
+    statements: Vec<Span>,
+    /// Statements entered while no other one was, the whole function comes from them when there's only one
+    top_level: Vec<Span>,
 }
 
 impl Function {
+    /// Attributes the lines added until the matching `leave` to a statement
+    pub fn enter(&mut self, span: Span) {
+        if self.statements.is_empty() {
+            self.top_level.push(span);
+        }
+        self.statements.push(span);
+    }
+
+    pub fn leave(&mut self) {
+        self.statements.pop();
+    }
+
+    fn push_line(&mut self, line: String) {
+        let span = self.statements.last().copied();
+        self.definitions.push((line, span));
+    }
+
+    /// Stores the expression in a new variable, or in the one that already holds it.
+    ///
+    /// Generated expressions have no side effects and variables are never assigned to again,
+    /// so an identical expression always has the same value.
     pub fn gen_definition(&mut self, typ: impl ToString, expr: impl ToString) -> String {
+        let key = (typ.to_string(), expr.to_string());
+        if let Some(ident) = self.known.get(&key).filter(|_| self.reuse_definitions) {
+            return ident.clone();
+        }
+
         self.uniq += 1;
         let ident = format!("def_{}_{}", self.uniq_above, self.uniq);
 
-        let def = format!("{} {} = {};", typ.to_string(), ident, expr.to_string());
-        self.definitions.push(def);
+        let def = format!("{} {} = {};", key.0, ident, key.1);
+        self.push_line(def);
+        self.known.insert(key, ident.clone());
 
         ident
     }
 
-    pub fn add_definition(&mut self, typ: impl ToString, name: impl ToString, expr: impl ToString) {
-        let def = format!("{} {} = {};", typ.to_string(), name.to_string(), expr.to_string());
-        self.definitions.push(def);
+    /// Stores `far` in a new variable when the condition holds, and otherwise the expression
+    /// `inside` builds, with the definitions it needs only computed in that case
+    pub fn gen_conditional(
+        &mut self,
+        typ: impl ToString,
+        condition: impl ToString,
+        far: impl ToString,
+        inside: impl FnOnce(&mut Function) -> Expr,
+    ) -> String {
+        self.uniq += 1;
+        let ident = format!("def_{}_{}", self.uniq_above, self.uniq);
+        self.push_line(format!("{} {};", typ.to_string(), ident));
+
+        // definitions in the block aren't visible after it
+        let outer_definitions = std::mem::take(&mut self.definitions);
+        let outer_known = self.known.clone();
+        let expr = inside(self);
+        let block = std::mem::replace(&mut self.definitions, outer_definitions);
+        self.known = outer_known;
+
+        self.push_line(format!("if ({}) {{", condition.to_string()));
+        self.push_line(format!("    {} = {};", ident, far.to_string()));
+        self.push_line(String::from("} else {"));
+        for (def, span) in block {
+            self.definitions.push((format!("    {}", def), span));
+        }
+        self.push_line(format!("    {} = {};", ident, expr));
+        self.push_line(String::from("}"));
+
+        ident
     }
 
     pub fn ret(self, glsl: &mut Glsl, expr: impl ToString) {
@@ -72,10 +164,22 @@ impl Function {
 
         let fst = format!("{} {}({}) {{", self.ret, self.name, args.join(", "));
 
-        let definitions = self.definitions.join("\n");
+        let definitions = self.definitions.iter().map(|(def, _)| def.as_str()).collect::<Vec<_>>().join("\n");
 
         let ret = format!("return {};", expr.to_string());
 
+        // the signature, the return and an empty body belong to the function as a whole
+        let whole = match self.top_level[..] {
+            [span] => Some(span),
+            _ => None,
+        };
+        glsl.lines.push(whole);
+        if self.definitions.is_empty() {
+            glsl.lines.push(whole);
+        }
+        glsl.lines.extend(self.definitions.iter().map(|(_, span)| span.or(whole)));
+        glsl.lines.extend(&[whole, whole]);
+
         glsl.functions
             .push(format!("{}\n{}\n{}\n}}", fst, definitions, ret));
     }
@@ -101,16 +205,21 @@ impl RawString {
     }
 }
 
-pub struct ArgString(String);
+/// Scene expression with `$name` resolved against the given `Arg` variable
+pub struct ArgExpr(String);
+
+impl ArgExpr {
+    pub fn new(expr: &expr::Expr, arg: &str) -> Self {
+        ArgExpr(expr.glsl(arg).to_string())
+    }
 
-impl ArgString {
-    pub fn new(s: impl Into<String>, arg: impl Into<String>) -> Self {
-        let string = s.into().replace("$", &(arg.into() + "."));
-        ArgString(string)
+    /// Expression with the names bound in `names` replaced by their variable
+    pub fn scoped(expr: &expr::Expr, arg: &str, names: &[(String, String)], float_literals: bool) -> Self {
+        ArgExpr(expr.glsl_scoped(arg, names, float_literals).to_string())
     }
 }
 
-impl AsRef<str> for ArgString {
+impl AsRef<str> for ArgExpr {
     fn as_ref(&self) -> &str {
         &self.0
     }
@@ -123,14 +232,14 @@ impl From<RawString> for Expr {
     }
 }
 
-impl From<ArgString> for Expr {
-    fn from(s: ArgString) -> Self {
+impl From<ArgExpr> for Expr {
+    fn from(s: ArgExpr) -> Self {
         Expr::String(s.0)
     }
 }
 
-impl ToString for Expr {
-    fn to_string(&self) -> String {
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         match self {
             Expr::FunctionCall(FunctionCall { name, args }) => {
                 let args = args
@@ -139,10 +248,10 @@ impl ToString for Expr {
                     .collect::<Vec<_>>()
                     .join(", ");
 
-                format!("{}({})", name, args)
+                write!(f, "{}({})", name, args)
             }
 
-            Expr::String(s) => s.clone(),
+            Expr::String(s) => write!(f, "{}", s),
         }
     }
 }
@@ -166,8 +275,87 @@ impl FunctionCall {
     }
 }
 
-impl ToString for FunctionCall {
-    fn to_string(&self) -> String {
-        Expr::from(self.clone()).to_string()
+impl fmt::Display for FunctionCall {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Expr::from(self.clone()).fmt(f)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_reuse_definitions() {
+        let mut glsl = Glsl::new();
+        let mut func = glsl.add_function("float", "f", &[("Arg", "arg")]);
+
+        let a = func.gen_definition("Arg", "rotate(vec3(0, 1, 0), arg.t, arg)");
+        let b = func.gen_definition("Arg", "rotate(vec3(0, 1, 0), arg.t, arg)");
+        let c = func.gen_definition("Arg", format!("at(1, 2, 3, {})", a));
+        let d = func.gen_definition("float", "rotate(vec3(0, 1, 0), arg.t, arg)");
+        assert_eq!(a, b);
+        assert_ne!(a, c);
+        assert_ne!(a, d);
+
+        func.ret(&mut glsl, format!("sd_sphere(1, {})", c));
+        assert_eq!(glsl.to_string().matches("rotate(").count(), 2);
+
+        let mut glsl = Glsl::without_reuse();
+        let mut func = glsl.add_function("float", "f", &[("Arg", "arg")]);
+        let a = func.gen_definition("Arg", "rotate(vec3(0, 1, 0), arg.t, arg)");
+        let b = func.gen_definition("Arg", "rotate(vec3(0, 1, 0), arg.t, arg)");
+        assert_ne!(a, b);
+    }
+
+    #[test]
+    fn test_conditional() {
+        let mut glsl = Glsl::new();
+        let mut func = glsl.add_function("float", "f", &[("Arg", "arg")]);
+
+        let outer = func.gen_definition("Arg", "at(1, 2, 3, arg)");
+        let result = func.gen_conditional("float", "arg.t > 1.0", "1.0", |func| {
+            assert_eq!(func.gen_definition("Arg", "at(1, 2, 3, arg)"), outer);
+            let inner = func.gen_definition("Arg", "uscale(2, arg)");
+            RawString::new(format!("sd_sphere(1, {})", inner)).into()
+        });
+
+        // the definition in the block can't be used after it
+        let after = func.gen_definition("Arg", "uscale(2, arg)");
+        func.ret(&mut glsl, format!("min({}, sd_sphere(1, {}))", result, after));
+
+        assert_eq!(
+            glsl.to_string(),
+            "float f(Arg arg) {\nArg def_1_1 = at(1, 2, 3, arg);\nfloat def_1_2;\nif (arg.t > 1.0) {\n    def_1_2 = 1.0;\n} else {\n    Arg def_1_3 = uscale(2, arg);\n    def_1_2 = sd_sphere(1, def_1_3);\n}\nArg def_1_4 = uscale(2, arg);\nreturn min(def_1_2, sd_sphere(1, def_1_4));\n}"
+        );
+    }
+
+    #[test]
+    fn test_lines() {
+        let (outer, inner) = (Span::new(0, 10), Span::new(5, 10));
+
+        let mut glsl = Glsl::new();
+        let mut func = glsl.add_function("float", "f", &[("Arg", "arg")]);
+        func.enter(outer);
+        let a = func.gen_definition("Arg", "at(1, 2, 3, arg)");
+        func.enter(inner);
+        let b = func.gen_definition("Arg", format!("uscale(2, {})", a));
+        func.leave();
+        func.leave();
+        func.ret(&mut glsl, format!("sd_sphere(1, {})", b));
+
+        // several statements at the top level, the function as a whole comes from none of them
+        let mut func = glsl.add_function("float", "g", &[("Arg", "arg")]);
+        func.enter(outer);
+        func.leave();
+        func.enter(inner);
+        func.leave();
+        func.ret(&mut glsl, "0.0");
+
+        assert_eq!(glsl.to_string().lines().count(), glsl.lines().len());
+        assert_eq!(
+            glsl.lines(),
+            [Some(outer), Some(outer), Some(inner), Some(outer), Some(outer), None, None, None, None]
+        );
     }
 }
@@ -0,0 +1,178 @@
+use std::sync::Arc;
+
+use super::codegen::Glsl;
+use super::source::{SourceMap, Span};
+
+/// Source string number of the generated code in the fragment shader, see `GeneratedScene::compile_fragment`
+pub const GENERATED_SOURCE: u32 = 1;
+
+/// Generated GLSL lines with the scene statement each one comes from,
+/// used to point errors of the GLSL compiler at the scene
+#[derive(Debug, Clone, Default)]
+pub struct LineMap {
+    lines: Vec<(String, Option<Span>)>,
+    sources: Option<Arc<SourceMap>>,
+}
+
+impl LineMap {
+    pub fn new(glsl: &Glsl) -> Self {
+        let text = glsl.to_string();
+        assert_eq!(text.lines().count(), glsl.lines().len());
+
+        LineMap {
+            lines: text.lines().map(String::from).zip(glsl.lines().iter().copied()).collect(),
+            sources: None,
+        }
+    }
+
+    /// Files the spans refer to, without them logs can't be rewritten
+    pub fn attach_sources(&mut self, sources: Arc<SourceMap>) {
+        self.sources = Some(sources);
+    }
+
+    /// Rewrites the errors of a compile log that are in generated code coming from a known statement,
+    /// `None` when there are none
+    pub fn explain(&self, log: &str) -> Option<String> {
+        let sources = self.sources.as_ref()?;
+        let mut explained = false;
+
+        let lines = log
+            .lines()
+            .map(|line| {
+                let located = parse_error(line)
+                    .filter(|error| error.source == GENERATED_SOURCE)
+                    .and_then(|error| {
+                        let (text, span) = self.lines.get(error.line.checked_sub(1)?)?;
+                        Some((error, text, (*span)?))
+                    });
+
+                match located {
+                    Some((error, text, span)) => {
+                        explained = true;
+                        let mut diagnostic = sources.diagnostic(span, error.message);
+                        diagnostic.notes.push(format!("in generated GLSL: {}", text.trim()));
+                        diagnostic.to_string()
+                    }
+                    None => line.to_owned(),
+                }
+            })
+            .collect::<Vec<_>>();
+
+        if explained {
+            Some(lines.join("\n"))
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+struct LogError<'a> {
+    source: u32,
+    /// One-based line in the source string
+    line: usize,
+    message: &'a str,
+}
+
+/// Reads an error line of a GLSL compile log, in one of the formats drivers use:
+///
+/// - `0:12(5): error: message`
+/// - `0(12) : error C1008: message`
+/// - `ERROR: 0:12: message`
+fn parse_error(line: &str) -> Option<LogError<'_>> {
+    let (rest, known_error) = match line.strip_prefix("ERROR:") {
+        Some(rest) => (rest.trim_start(), true),
+        None => (line, false),
+    };
+
+    let (source, rest) = number(rest)?;
+    let (line, rest) = if let Some(rest) = rest.strip_prefix(':') {
+        let (line, rest) = number(rest)?;
+        // the column isn't precise enough to be worth keeping
+        let rest = match rest.strip_prefix('(') {
+            Some(rest) => &rest[rest.find(')')? + 1..],
+            None => rest,
+        };
+        (line, rest)
+    } else {
+        let (line, rest) = number(rest.strip_prefix('(')?)?;
+        (line, rest.strip_prefix(')')?)
+    };
+
+    let message = rest.trim_start().strip_prefix(':')?.trim_start();
+    let message = if known_error {
+        message
+    } else if message.starts_with("error") {
+        // `error:` or `error C1008:`
+        message[message.find(':')? + 1..].trim_start()
+    } else {
+        return None;
+    };
+
+    Some(LogError {
+        source: source as u32,
+        line,
+        message,
+    })
+}
+
+fn number(s: &str) -> Option<(usize, &str)> {
+    let end = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    Some((s[..end].parse().ok()?, &s[end..]))
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::desc::test::parse_scene;
+    use crate::shaders::generated::source::SourceFile;
+
+    #[test]
+    fn test_parse_error() {
+        let error = |source, line, message| Some(LogError { source, line, message });
+
+        assert_eq!(parse_error("1:12(5): error: `foo' undeclared"), error(1, 12, "`foo' undeclared"));
+        assert_eq!(
+            parse_error("1(3) : error C1008: undefined variable \"foo\""),
+            error(1, 3, "undefined variable \"foo\"")
+        );
+        assert_eq!(parse_error("ERROR: 2:40: 'foo' : undeclared identifier"), error(2, 40, "'foo' : undeclared identifier"));
+
+        assert_eq!(parse_error("1:12(5): warning: unused variable"), None);
+        assert_eq!(parse_error("Compilation failed"), None);
+        assert_eq!(parse_error(""), None);
+    }
+
+    #[test]
+    fn test_explain() {
+        let source = "define_geometry(blob) raw(length($p.xy) - 1.0);\n\nopaque(1, 0, 0) at(0, 1, 0) rotate(vec3(0, 1, 0), $t) blob();";
+        let mut desc = parse_scene(source.as_bytes()).unwrap();
+
+        let mut sources = SourceMap::default();
+        sources.add(SourceFile::new("test.scene", source.as_bytes()), None);
+        desc.line_map.attach_sources(Arc::new(sources));
+
+        // the driver only knows about the fragment shader
+        let generated = desc.fragment.split("#line 1 1\n").nth(1).unwrap();
+        let line_of = |text| generated.lines().position(|line| line.contains(text)).unwrap() + 1;
+
+        let log = format!(
+            "{}:{}(24): error: no function with name 'rotate'\n0:3(1): error: something else",
+            GENERATED_SOURCE,
+            line_of("rotate(")
+        );
+        assert_eq!(
+            desc.line_map.explain(&log).unwrap(),
+            "error: no function with name 'rotate'\n --> test.scene:3:29\n  |\n3 | opaque(1, 0, 0) at(0, 1, 0) rotate(vec3(0, 1, 0), $t) blob();\n  |                             ^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^^\n  = note: in generated GLSL: Arg def_2_2 = rotate(vec3(0.0, 1.0, 0.0), def_2_1.t, def_2_1);\n0:3(1): error: something else"
+        );
+
+        // lines that no statement inside claims belong to the whole definition
+        let log = format!("ERROR: {}:{}: 'length' : no matching overloaded function found", GENERATED_SOURCE, line_of("length("));
+        assert!(desc.line_map.explain(&log).unwrap().starts_with(
+            "error: 'length' : no matching overloaded function found\n --> test.scene:1:1\n"
+        ));
+
+        // errors outside the generated code are left alone
+        assert_eq!(desc.line_map.explain("0:3(1): error: something else"), None);
+    }
+}
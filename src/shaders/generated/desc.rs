@@ -1,85 +1,163 @@
 use crate::shaders::{GeneratedScene, ShaderProvider};
 
-use std::collections::HashSet;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::sync::Arc;
 
+use super::check::{Checker, Type, TypeError};
 use super::codegen::Glsl;
-use super::parser;
+use super::compile_log::LineMap;
+use super::eval::{CpuScene, Eval, Functions};
+use super::expr::{BinaryOp, Expr};
+use super::parser::ParseError;
+use super::source::{Span, Spanned};
 use super::typed::*;
 
 pub mod camera;
+pub mod deps;
 pub mod loader;
+pub mod material;
 
 use camera::{CameraDesc, CameraDescError};
+use deps::Definitions;
+use material::MaterialDesc;
 
 #[derive(Debug, thiserror::Error)]
 pub enum SceneDescError {
-    #[error("Parse error")]
-    ParseError,
+    #[error("{}", .0)]
+    ParseError(#[from] ParseError),
     #[error("{}", .0)]
     StatementError(#[from] StatementError),
     #[error("Duplicate camera")]
-    DuplicateCamera,
+    DuplicateCamera(Span),
     #[error("{}", .0)]
     CameraError(#[from] CameraDescError),
+    #[error("{}", .1)]
+    IncludeError(Span, loader::IncludeError),
+}
+
+impl SceneDescError {
+    pub fn span(&self) -> Span {
+        match self {
+            SceneDescError::ParseError(e) => e.span,
+            SceneDescError::StatementError(e) => e.span,
+            SceneDescError::DuplicateCamera(span) => *span,
+            SceneDescError::CameraError(e) => e.span,
+            SceneDescError::IncludeError(span, _) => *span,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct SceneDesc {
     pub vertex: String,
     pub fragment: String,
+    /// GLSL generated from the scene, without the code shared by every scene
+    pub generated: String,
     pub camera: Option<CameraDesc>,
+    pub line_map: LineMap,
+    /// The same objects, evaluated on the CPU
+    pub cpu: Arc<CpuScene>,
 }
 
 impl SceneDesc {
-    pub fn parse(source: &[u8]) -> Result<Self, SceneDescError> {
-        let statements = parser::scene(source)
-            .map_err(|_| SceneDescError::ParseError)?.1;
-        Self::from_statements(statements)
-    }
-
     pub fn from_statements(statements: Vec<Statement>) -> Result<Self, SceneDescError> {
-        let mut glsl = Glsl::new();
+        Self::generate(statements, Glsl::new())
+    }
 
-        let mut fold_opaque = Statement {
-            name: String::from("union"),
-            args: Vec::new(),
-            body: Vec::new(),
-        };
+    fn generate(statements: Vec<Statement>, mut glsl: Glsl) -> Result<Self, SceneDescError> {
+        let mut checker = Checker::new();
 
-        let mut fold_transparent = Statement {
-            name: String::from("union"),
-            args: Vec::new(),
-            body: Vec::new()
-        };
+        let mut definitions = Vec::new();
+        let mut materials = Vec::new();
+        let mut objects = Vec::new();
 
         let mut camera = None;
 
         for stmt in statements {
             match stmt.name.as_str() {
-                "define_geometry" => define_object(&mut glsl, stmt, GeometryVisitor)?,
-                "define_opaque" => define_object(&mut glsl, stmt, OpaqueVisitor)?,
-                "define_transparent" => define_object(&mut glsl, stmt, TransparentVisitor)?,
+                "define_geometry" | "define_geometry_2d" | "define_opaque" | "define_transparent" => {
+                    stmt.expect_args(Arity::AtLeast(1))?;
+                    definitions.push(stmt);
+                }
+                "material" => {
+                    let material = MaterialDesc::new(&stmt)?;
+                    checker.define_material(&stmt)?;
+                    materials.push(material);
+                }
+                "include" => {
+                    return Err(StatementError::new(StatementErrorKind::UnresolvedInclude, stmt.span).into())
+                }
                 "camera" => {
                     if camera.is_some() {
-                        return Err(SceneDescError::DuplicateCamera)
+                        return Err(SceneDescError::DuplicateCamera(stmt.span))
                     } else {
                         camera = Some(CameraDesc::new(stmt)?)
                     }
                 }
+                _ => objects.push(stmt),
+            }
+        }
+
+        // materials don't use anything, so they can all go first
+        for material in &materials {
+            material.emit(&mut glsl);
+        }
+
+        let opaque_visitor = OpaqueVisitor {
+            materials: materials.iter().map(|material| material.name.clone()).collect(),
+        };
+
+        let mut functions = Functions::default();
+        for material in materials {
+            functions.define(material::function_name(&material.name), Vec::new(), Box::new(material));
+        }
+
+        // definitions can be used before they appear in the file, but GLSL needs them first,
+        // and only the ones the scene actually uses are emitted
+        let deps = Definitions::new(&definitions);
+        let used = deps.reachable(&deps.used_by(&objects));
+
+        for idx in deps.sorted()? {
+            let stmt = &definitions[idx];
+            let glsl = if used[idx] { Some(&mut glsl) } else { None };
+
+            let (params, object) = match stmt.name.as_str() {
+                "define_geometry" => define_object(glsl, &mut checker, stmt, &GeometryVisitor)?,
+                "define_geometry_2d" => define_object(glsl, &mut checker, stmt, &Geometry2dVisitor)?,
+                "define_opaque" => define_object(glsl, &mut checker, stmt, &opaque_visitor)?,
+                _ => define_object(glsl, &mut checker, stmt, &TransparentVisitor)?,
+            };
+            functions.define(stmt.args[0].to_string(), params, object);
+        }
+
+        let mut opaque = Vec::new();
+        let mut transparent = Vec::new();
+
+        for stmt in objects {
+            match checker.object_type(&stmt) {
+                Some(Type::MapOpaque) => opaque.push(stmt),
+                Some(Type::MapTransparent) => transparent.push(stmt),
                 _ => {
                     if stmt.apply(&TransparentVisitor).is_ok() {
-                        fold_transparent.body.push(stmt);
+                        transparent.push(stmt);
                     } else {
-                        fold_opaque.body.push(stmt);
+                        opaque.push(stmt);
                     }
                 }
             }
         }
 
-        let opaque = fold_opaque.apply(&OpaqueVisitor)?;
-        let transparent = fold_transparent.apply(&TransparentVisitor)?;
+        let opaque_objects = opaque_visitor.visit_statements(&opaque)?;
+        let transparent_objects = TransparentVisitor.visit_statements(&transparent)?;
 
-        let mut map = glsl.add_function("vec4", "map_impl", &[("Arg", "arg")]);
+        checker.check_all(&opaque, opaque_visitor.get_type_marker())?;
+        checker.check_all(&transparent, TransparentVisitor.get_type_marker())?;
+
+        let opaque = opaque_visitor.construct_fold(Union, opaque_objects);
+        let transparent = TransparentVisitor.construct_fold(Union, transparent_objects);
+
+        let mut map = glsl.add_function("MapOpaque", "map_impl", &[("Arg", "arg")]);
         let expr = opaque.make_expr(&Context::new(), &mut map);
         map.ret(&mut glsl, expr);
 
@@ -87,139 +165,428 @@ impl SceneDesc {
         let expr = transparent.make_expr(&Context::new(), &mut map_transparent);
         map_transparent.ret(&mut glsl, expr);
 
+        let generated = glsl.to_string();
+
+        let cpu = CpuScene {
+            functions,
+            opaque: Some(opaque),
+            #[cfg(test)]
+            transparent: Some(transparent),
+        };
+
         Ok(
             SceneDesc {
                 vertex: GeneratedScene::get_vertex(),
-                fragment: GeneratedScene::compile_fragment(&glsl.to_string()),
+                fragment: GeneratedScene::compile_fragment(&generated),
+                generated,
                 camera,
+                line_map: LineMap::new(&glsl),
+                cpu: Arc::new(cpu),
             }
         )
     }
 }
 
+impl SceneDesc {
+    /// Self-contained shader for Shadertoy, with the camera keyframes baked in
+    pub fn shadertoy(&self) -> String {
+        let camera = self.camera.clone().unwrap_or_default();
+        GeneratedScene::compile_shadertoy(&self.generated, &camera.glsl_function())
+    }
+}
+
 impl ShaderProvider for SceneDesc {
     fn get_sources(&self) -> [String; 2] {
         [self.vertex.clone(), self.fragment.clone()]
     }
-}
 
-fn define_object(
-    glsl: &mut Glsl,
-    stmt: Statement,
-    visitor: impl StatementVisitor,
-) -> Result<(), StatementError> {
-    if stmt.args.is_empty() {
-        return Err(StatementError(format!("{} requires at least one argument", stmt.name)));
-    }
-
-    let fold = Statement {
-        name: String::from("union"),
-        args: Vec::new(),
-        body: stmt.body,
-    };
+    fn explain_fragment_log(&self, log: &str) -> Option<String> {
+        self.line_map.explain(log)
+    }
+}
 
-    let object = fold.apply(&visitor)?;
+/// Types and names of the parameters of a definition, and its object
+type CpuDefinition = (Vec<(String, String)>, Box<dyn Eval>);
 
-    let name = &stmt.args[0];
+/// Checks a `define_*` statement, and emits its function if there's somewhere to emit it.
+/// The definition is returned for the CPU either way
+fn define_object(
+    glsl: Option<&mut Glsl>,
+    checker: &mut Checker,
+    stmt: &Statement,
+    visitor: &impl StatementVisitor,
+) -> Result<CpuDefinition, StatementError> {
+    let object = visitor.construct_fold(Union, visitor.visit_body(stmt)?);
+
+    let name = stmt.args[0].to_string();
     let args = stmt.args
         .iter()
         .skip(1)
-        .map(|arg| {
-            let parts = arg.split_whitespace().collect::<Vec<_>>();
-
-            (parts[0], parts[1])
+        .map(|arg| match &arg.node {
+            Expr::Declaration(typ, name) => Ok((typ.as_str(), name.as_str())),
+            _ => Err(StatementError::new(
+                StatementErrorKind::BadParameter(arg.to_string()),
+                arg.span,
+            )),
         })
-        .chain(std::iter::once(("Arg", "arg")))
-        .collect::<Vec<_>>();
+        .chain(std::iter::once(Ok(("Arg", "arg"))))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    checker.define(stmt, visitor.get_type_marker())?;
+
+    if let Some(glsl) = glsl {
+        let type_name = visitor.get_type_marker().typ();
+        let mut func = glsl.add_function(type_name, name, &args);
+        func.enter(stmt.span);
+        let expr = object.make_expr(&Context::definition(), &mut func);
+        func.leave();
+        func.ret(glsl, expr);
+    }
 
-    let type_name = visitor.get_type_marker().typ();
-    let mut func = glsl.add_function(type_name, name, &args);
-    let expr = object.make_expr(&Context::new(), &mut func);
-    func.ret(glsl, expr);
+    let params = args[..args.len() - 1]
+        .iter()
+        .map(|(typ, name)| ((*typ).to_owned(), (*name).to_owned()))
+        .collect();
 
-    Ok(())
+    Ok((params, Box::new(object)))
 }
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone)]
 pub struct Statement {
     pub name: String,
-    pub args: Vec<String>,
+    pub args: Vec<Spanned<Expr>>,
     pub body: Vec<Statement>,
+    pub span: Span,
+
+    /// Comments on the lines right before the statement
+    pub comments: Vec<String>,
+    /// Comment on the same line after the statement
+    pub trailing_comment: Option<String>,
+    /// Comments after the last statement of the body, before the closing `}`
+    pub closing_comments: Vec<String>,
 }
 
-impl ToString for Statement {
-    fn to_string(&self) -> String {
-        let body = self.body.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+impl Statement {
+    pub fn arg_exprs(&self) -> Vec<Expr> {
+        self.args.iter().map(|arg| arg.node.clone()).collect()
+    }
+
+    pub fn expect_args(&self, expected: Arity) -> Result<(), StatementError> {
+        if expected.accepts(self.args.len()) {
+            Ok(())
+        } else {
+            Err(StatementError::new(
+                StatementErrorKind::WrongArgumentCount {
+                    statement: self.name.clone(),
+                    expected,
+                    actual: self.args.len(),
+                },
+                self.span,
+            ))
+        }
+    }
 
-        format!(
-            "{}({}){{{}}}",
-            self.name,
-            self.args.join(", "),
-            body.join("; ")
+    fn not_allowed_in<V: StatementVisitor + ?Sized>(&self, vis: &V) -> StatementError {
+        StatementError::new(
+            StatementErrorKind::NotAllowedHere {
+                statement: self.name.clone(),
+                context: vis.get_type_marker().name(),
+            },
+            self.span,
         )
     }
+
+    /// Span of the statement name, which every statement starts with
+    pub fn name_span(&self) -> Span {
+        Span {
+            end: self.span.start + self.name.len(),
+            ..self.span
+        }
+    }
+
+    pub fn expect_body_len(&self, expected: usize) -> Result<(), StatementError> {
+        if self.body.len() == expected {
+            Ok(())
+        } else {
+            Err(StatementError::new(
+                StatementErrorKind::WrongBodyLength {
+                    statement: self.name.clone(),
+                    expected,
+                    actual: self.body.len(),
+                },
+                self.span,
+            ))
+        }
+    }
+
+    pub fn expect_no_body(&self) -> Result<(), StatementError> {
+        if self.body.is_empty() {
+            Ok(())
+        } else {
+            Err(StatementError::new(
+                StatementErrorKind::UnexpectedBody(self.name.clone()),
+                self.span,
+            ))
+        }
+    }
+}
+
+/// Spans are ignored, so statements only compare by their contents
+impl PartialEq for Statement {
+    fn eq(&self, other: &Self) -> bool {
+        self.name == other.name
+            && self.args == other.args
+            && self.body == other.body
+            && self.comments == other.comments
+            && self.trailing_comment == other.trailing_comment
+            && self.closing_comments == other.closing_comments
+    }
+}
+
+/// Writes statements along with their comments in a way that parses back into the same statements
+fn write_statements(f: &mut fmt::Formatter, stmts: &[Statement], separator: &str) -> fmt::Result {
+    for (idx, stmt) in stmts.iter().enumerate() {
+        if idx > 0 && stmts[idx - 1].trailing_comment.is_none() {
+            f.write_str(separator)?;
+        }
+
+        // comments always start on a new line, so that they don't become trailing comments
+        for comment in &stmt.comments {
+            write!(f, "\n{}\n", comment)?;
+        }
+
+        write!(f, "{}", stmt)?;
+
+        if let Some(comment) = &stmt.trailing_comment {
+            writeln!(f, " {}", comment)?;
+        }
+    }
+
+    Ok(())
+}
+
+impl fmt::Display for Statement {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let args = self.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+
+        write!(f, "{}({}){{", self.name, args.join(", "))?;
+        write_statements(f, &self.body, "; ")?;
+
+        for comment in &self.closing_comments {
+            write!(f, "\n{}\n", comment)?;
+        }
+
+        write!(f, "}}")
+    }
+}
+
+/// Statements of a whole file, with the comments after the last one
+#[derive(Debug, Clone, PartialEq)]
+pub struct SceneFile {
+    pub statements: Vec<Statement>,
+    pub comments: Vec<String>,
+}
+
+impl fmt::Display for SceneFile {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write_statements(f, &self.statements, "\n")?;
+
+        for comment in &self.comments {
+            write!(f, "\n{}\n", comment)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Number of arguments a statement accepts
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Arity {
+    Exactly(usize),
+    AtLeast(usize),
+    Between(usize, usize),
+}
+
+impl Arity {
+    pub fn accepts(self, n: usize) -> bool {
+        match self {
+            Arity::Exactly(x) => n == x,
+            Arity::AtLeast(x) => n >= x,
+            Arity::Between(min, max) => n >= min && n <= max,
+        }
+    }
+}
+
+impl fmt::Display for Arity {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let plural = |n: usize| if n == 1 { "argument" } else { "arguments" };
+
+        match *self {
+            Arity::Exactly(0) => write!(f, "no arguments"),
+            Arity::Exactly(n) => write!(f, "{} {}", n, plural(n)),
+            Arity::AtLeast(n) => write!(f, "at least {} {}", n, plural(n)),
+            Arity::Between(min, max) => write!(f, "{} to {} arguments", min, max),
+        }
+    }
+}
+
+/// Formats the names of the statements enclosing an error, outermost first
+pub(crate) struct StatementPath<'a>(pub &'a [String]);
+
+impl fmt::Display for StatementPath<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if !self.0.is_empty() {
+            let path = self.0.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>();
+            write!(f, " (in {})", path.join(" > "))?;
+        }
+
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{}{}", .kind, StatementPath(.path))]
+pub struct StatementError {
+    pub kind: StatementErrorKind,
+    pub span: Span,
+    /// Names of the enclosing statements, outermost first
+    pub path: Vec<String>,
+}
+
+impl StatementError {
+    pub fn new(kind: StatementErrorKind, span: Span) -> Self {
+        StatementError {
+            kind,
+            span,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn within(mut self, parent: &str) -> Self {
+        self.path.insert(0, parent.to_owned());
+        self
+    }
 }
 
 #[derive(Debug, Clone, thiserror::Error)]
-#[error("Statement error: {}", .0)]
-pub struct StatementError(String);
+pub enum StatementErrorKind {
+    #[error("`{}` expects {}, got {}", .statement, .expected, .actual)]
+    WrongArgumentCount {
+        statement: String,
+        expected: Arity,
+        actual: usize,
+    },
+    #[error("`{}` does not take a body", .0)]
+    UnexpectedBody(String),
+    #[error("`{}` expects {} statements in its body, got {}", .statement, .expected, .actual)]
+    WrongBodyLength {
+        statement: String,
+        expected: usize,
+        actual: usize,
+    },
+    #[error("`{}` cannot be used inside {} objects", .statement, .context)]
+    NotAllowedHere {
+        statement: String,
+        context: &'static str,
+    },
+    #[error("Parameter `{}` must be in the form `<type> <name>`", .0)]
+    BadParameter(String),
+    #[error("`{}` can only be used at the top level of a file", .0)]
+    NotTopLevel(String),
+    #[error("`include` can only be resolved in scenes loaded from a file")]
+    UnresolvedInclude,
+    #[error("`{}` is not a material property", .0)]
+    UnknownProperty(String),
+    #[error("`{}` is set more than once", .0)]
+    DuplicateProperty(String),
+    #[error("Material `{}` is defined more than once", .0)]
+    DuplicateMaterial(String),
+    #[error("Recursive definition: {}", .0.iter().map(|name| format!("`{}`", name)).collect::<Vec<_>>().join(" -> "))]
+    RecursiveDefinition(Vec<String>),
+    #[error("{}", .0)]
+    Type(Box<TypeError>),
+}
+
+impl From<TypeError> for StatementErrorKind {
+    fn from(e: TypeError) -> Self {
+        StatementErrorKind::Type(Box::new(e))
+    }
+}
+
+lazy_static! {
+    /// Transforms calling the `library.glsl` function of the same name, with their number of arguments
+    pub static ref SIMPLE_FUNCTIONS: HashMap<&'static str, usize> = {
+        let simple_functions = [
+            ("at", 3),
+            ("vat", 1),
+            ("rotate", 2),
+            ("repeat", 3),
+            ("mirror", 1),
+            ("repeat_polar", 1),
+            ("repeat_limited", 2),
+            ("elongate", 1),
+            ("at_t", 1),
+            ("start_at_t", 1),
+            ("end_at_t", 1),
+            ("repeat_t", 1),
+            ("map_t", 4),
+        ];
+
+        simple_functions.iter()
+            .cloned()
+            .collect()
+    };
+}
 
 impl Statement {
     pub fn apply<V: StatementVisitor + ?Sized>(
         &self,
         vis: &V,
     ) -> Result<V::Output, StatementError> {
-        lazy_static! {
-            static ref SIMPLE_FUNCTIONS: HashSet<&'static str> = {
-                let simple_functions = [
-                    "at",
-                    "vat",
-                    "rotate",
-                    "repeat",
-                    "at_t",
-                    "start_at_t",
-                    "end_at_t",
-                    "repeat_t",
-                    "map_t",
-                ];
-
-                simple_functions.iter()
-                    .cloned()
-                    .collect()
-            };
+        if let Some(op) = Operator::find(&self.name) {
+            self.expect_args(Arity::Exactly(op.arity))?;
+            return Ok(vis.construct_fold(OperatorFunc { op, args: self.arg_exprs() }, vis.visit_body(self)?));
         }
 
         let x = match self.name.as_str() {
             "raw" => {
-                assert_eq!(self.args.len(), 1);
-                vis.construct_raw(self.args[0].clone())
+                self.expect_args(Arity::Exactly(1))?;
+                self.expect_no_body()?;
+                vis.construct_raw(self.args[0].node.clone())
+            }
+
+            "include" => {
+                return Err(StatementError::new(
+                    StatementErrorKind::NotTopLevel(self.name.clone()),
+                    self.span,
+                ))
             }
 
             "union" => {
-                assert!(self.args.is_empty());
+                self.expect_args(Arity::Exactly(0))?;
                 vis.construct_fold(Union, vis.visit_body(self)?)
             }
 
             "intersection" => {
-                assert!(self.args.is_empty());
-                vis.construct_fold(Isect, vis.visit_body(self)?)
+                self.expect_args(Arity::Between(0, 1))?;
+                vis.construct_fold(Isect { args: self.arg_exprs() }, vis.visit_body(self)?)
             }
 
             "difference" => {
-                assert!(self.args.is_empty());
-                vis.construct_fold(Diff, vis.visit_body(self)?)
+                self.expect_args(Arity::Between(0, 1))?;
+                vis.construct_fold(Diff { args: self.arg_exprs() }, vis.visit_body(self)?)
             }
 
             "smooth_union" => {
-                assert_eq!(self.args.len(), 1);
-                vis.construct_fold(SmoothUnion{ args: self.args.clone() }, vis.visit_body(self)?)
+                self.expect_args(Arity::Exactly(1))?;
+                vis.construct_fold(SmoothUnion{ args: self.arg_exprs() }, vis.visit_body(self)?)
             }
 
             "advanced_repeat" => {
-                assert_eq!(self.args.len(), 3);
+                self.expect_args(Arity::Exactly(3))?;
                 vis.construct_transform(
                     AdvancedRepeat {
-                        args: self.args.clone()
+                        args: self.arg_exprs()
                     },
                     vis.construct_fold(Union, vis.visit_body(self)?)
                 )
@@ -229,73 +596,155 @@ impl Statement {
                 let geom = GeometryVisitor;
 
                 vis.construct_opaque(
-                    self.args.clone(),
+                    self.arg_exprs(),
                     geom.construct_fold(Union, geom.visit_body(self)?),
-                )?
+                )
+                .ok_or_else(|| self.not_allowed_in(vis))?
             }
 
             "transparent" => {
                 let geom = GeometryVisitor;
 
                 vis.construct_transparent(
-                    self.args.clone(),
+                    self.arg_exprs(),
                     geom.construct_fold(Union, geom.visit_body(self)?),
-                )?
+                )
+                .ok_or_else(|| self.not_allowed_in(vis))?
+            }
+
+            "extrude" => {
+                self.expect_args(Arity::Exactly(1))?;
+                let profile = Geometry2dVisitor;
+
+                vis.construct_lifted(Extrude {
+                    args: self.arg_exprs(),
+                    profile: profile.construct_fold(Union, profile.visit_body(self)?),
+                })
+                .ok_or_else(|| self.not_allowed_in(vis))?
+            }
+
+            "revolve" => {
+                self.expect_args(Arity::Exactly(1))?;
+                let profile = Geometry2dVisitor;
+
+                vis.construct_lifted(Revolve {
+                    args: self.arg_exprs(),
+                    profile: profile.construct_fold(Union, profile.visit_body(self)?),
+                })
+                .ok_or_else(|| self.not_allowed_in(vis))?
+            }
+
+            "sd2_polygon" => {
+                self.expect_args(Arity::AtLeast(3))?;
+                self.expect_no_body()?;
+                vis.construct_polygon(self.arg_exprs())
+                    .ok_or_else(|| self.not_allowed_in(vis))?
             }
 
             "onionize" => {
-                assert!(self.args.len() == 1);
+                self.expect_args(Arity::Exactly(1))?;
                 vis.construct_transform(
                     Onionize {
-                        args: self.args.clone(),
+                        args: self.arg_exprs(),
                     },
                     vis.construct_fold(Union, vis.visit_body(self)?),
                 )
             }
 
             "scale" => {
-                assert!(self.args.len() == 1);
+                self.expect_args(Arity::Exactly(1))?;
                 vis.construct_transform(
                     Scale {
-                        args: self.args.clone(),
+                        args: self.arg_exprs(),
+                    },
+                    vis.construct_fold(Union, vis.visit_body(self)?),
+                )
+            }
+
+            "round" => {
+                self.expect_args(Arity::Exactly(1))?;
+                vis.construct_transform(
+                    Round {
+                        args: self.arg_exprs(),
+                    },
+                    vis.construct_fold(Union, vis.visit_body(self)?),
+                )
+            }
+
+            "displace" => {
+                self.expect_args(Arity::Exactly(2))?;
+                vis.construct_transform(
+                    Displace {
+                        args: self.arg_exprs(),
+                    },
+                    vis.construct_fold(Union, vis.visit_body(self)?),
+                )
+            }
+
+            "twist" | "bend" => {
+                self.expect_args(Arity::Exactly(1))?;
+                vis.construct_transform(
+                    Distortion {
+                        func: self.name.clone(),
+                        args: self.arg_exprs(),
                     },
                     vis.construct_fold(Union, vis.visit_body(self)?),
                 )
             }
 
             "cond" => {
-                assert!(self.args.len() == 1);
+                self.expect_args(Arity::Exactly(1))?;
                 vis.construct_transform(
                     Cond {
-                        args: self.args.clone(),
+                        args: self.arg_exprs(),
                     },
                     vis.construct_fold(Union, vis.visit_body(self)?),
                 )
             }
 
+            "cond_else" => {
+                self.expect_args(Arity::Exactly(1))?;
+                self.expect_body_len(2)?;
+                vis.construct_branch(self.arg_exprs(), vis.visit_body(self)?)
+            }
+
+            "switch" => {
+                self.expect_args(Arity::AtLeast(1))?;
+                self.expect_body_len(self.args.len() + 1)?;
+
+                // each object is shown until its time is up
+                let conditions = self
+                    .arg_exprs()
+                    .into_iter()
+                    .map(|end| Expr::binary(BinaryOp::Lt, Expr::Arg(String::from("t")), end))
+                    .collect();
+                vis.construct_branch(conditions, vis.visit_body(self)?)
+            }
+
             "let" => {
-                assert_eq!(self.args.len(), 3);
+                self.expect_args(Arity::Exactly(3))?;
                 vis.construct_transform(
                     Let {
-                        args: self.args.clone(),
+                        args: self.arg_exprs(),
                     },
                     vis.construct_fold(Union, vis.visit_body(self)?),
                 )
             }
 
-            x if SIMPLE_FUNCTIONS.contains(x) => {
+            x if SIMPLE_FUNCTIONS.contains_key(x) => {
+                self.expect_args(Arity::Exactly(SIMPLE_FUNCTIONS[x]))?;
                 vis.construct_transform(
                     FunctionTf {
                         func: String::from(x),
-                        args: self.args.clone(),
+                        args: self.arg_exprs(),
                     },
                     vis.construct_fold(Union, vis.visit_body(self)?),
                 )
             }
 
             _ => {
-                assert!(self.body.is_empty());
-                vis.construct_named(self.name.clone(), self.args.clone())
+                self.expect_no_body()?;
+                vis.construct_named(self.name.clone(), self.arg_exprs())
             }
         };
 
@@ -304,37 +753,44 @@ impl Statement {
 }
 
 pub trait StatementVisitor {
-    type Output: MakeExpr;
+    type Output: MakeExpr + Eval + 'static;
 
     fn get_type_marker(&self) -> TypeMarker;
 
-    fn construct_named(&self, name: String, args: Vec<String>) -> Self::Output;
-    fn construct_raw(&self, expr: String) -> Self::Output;
+    fn construct_named(&self, name: String, args: Vec<Expr>) -> Self::Output;
+    fn construct_raw(&self, expr: Expr) -> Self::Output;
     fn construct_fold(&self, func: impl IFunc, items: Vec<Self::Output>) -> Self::Output;
+    fn construct_branch(&self, conditions: Vec<Expr>, items: Vec<Self::Output>) -> Self::Output;
     fn construct_transform(&self, tf: impl ITransform, item: Self::Output) -> Self::Output;
+    fn construct_located(&self, span: Span, item: Self::Output) -> Self::Output;
 
-    fn construct_opaque(
-        &self,
-        _color: Vec<String>,
-        _geometry: impl IGeometry,
-    ) -> Result<Self::Output, StatementError> {
-        Err(StatementError(String::from(
-            "cannot construct opaque shape",
-        )))
+    fn construct_opaque(&self, _color: Vec<Expr>, _geometry: impl IGeometry) -> Option<Self::Output> {
+        None
     }
 
-    fn construct_transparent(
-        &self,
-        _color: Vec<String>,
-        _geometry: impl IGeometry,
-    ) -> Result<Self::Output, StatementError> {
-        Err(StatementError(String::from(
-            "cannot construct transparent shape",
-        )))
+    fn construct_transparent(&self, _color: Vec<Expr>, _geometry: impl IGeometry) -> Option<Self::Output> {
+        None
+    }
+
+    /// Geometry made from a 2D shape by `extrude` or `revolve`
+    fn construct_lifted(&self, _geometry: impl IGeometry) -> Option<Self::Output> {
+        None
+    }
+
+    fn construct_polygon(&self, _vertices: Vec<Expr>) -> Option<Self::Output> {
+        None
     }
 
     fn visit_body(&self, stmt: &Statement) -> Result<Vec<Self::Output>, StatementError> {
-        stmt.body.iter().map(|stmt| stmt.apply(self)).collect()
+        self.visit_statements(&stmt.body)
+            .map_err(|e| e.within(&stmt.name))
+    }
+
+    fn visit_statements(&self, stmts: &[Statement]) -> Result<Vec<Self::Output>, StatementError> {
+        stmts
+            .iter()
+            .map(|stmt| Ok(self.construct_located(stmt.span, stmt.apply(self)?)))
+            .collect()
     }
 }
 
@@ -346,11 +802,11 @@ impl StatementVisitor for GeometryVisitor {
         TypeMarker::Geometry(GeometryMarker)
     }
 
-    fn construct_named(&self, name: String, args: Vec<String>) -> Self::Output {
+    fn construct_named(&self, name: String, args: Vec<Expr>) -> Self::Output {
         Box::new(NamedGeometry { name, args })
     }
 
-    fn construct_raw(&self, expr: String) -> Self::Output {
+    fn construct_raw(&self, expr: Expr) -> Self::Output {
         Box::new(RawGeometry { expr })
     }
 
@@ -362,6 +818,14 @@ impl StatementVisitor for GeometryVisitor {
         })
     }
 
+    fn construct_branch(&self, conditions: Vec<Expr>, items: Vec<Self::Output>) -> Self::Output {
+        Box::new(Branch {
+            conditions,
+            items,
+            marker: GeometryMarker,
+        })
+    }
+
     fn construct_transform(&self, tf: impl ITransform, item: Self::Output) -> Self::Output {
         Box::new(Transform {
             tf,
@@ -369,9 +833,71 @@ impl StatementVisitor for GeometryVisitor {
             marker: GeometryMarker,
         })
     }
+
+    fn construct_located(&self, span: Span, item: Self::Output) -> Self::Output {
+        Box::new(Located { span, item })
+    }
+
+    fn construct_lifted(&self, geometry: impl IGeometry) -> Option<Self::Output> {
+        Some(Box::new(geometry))
+    }
+}
+
+pub struct Geometry2dVisitor;
+impl StatementVisitor for Geometry2dVisitor {
+    type Output = Box<dyn IGeometry2d>;
+
+    fn get_type_marker(&self) -> TypeMarker {
+        TypeMarker::Geometry2d(Geometry2dMarker)
+    }
+
+    fn construct_named(&self, name: String, args: Vec<Expr>) -> Self::Output {
+        Box::new(NamedGeometry2d { name, args })
+    }
+
+    fn construct_raw(&self, expr: Expr) -> Self::Output {
+        Box::new(RawGeometry2d { expr })
+    }
+
+    fn construct_fold(&self, func: impl IFunc, items: Vec<Self::Output>) -> Self::Output {
+        Box::new(Fold {
+            func,
+            items,
+            marker: Geometry2dMarker,
+        })
+    }
+
+    fn construct_branch(&self, conditions: Vec<Expr>, items: Vec<Self::Output>) -> Self::Output {
+        Box::new(Branch {
+            conditions,
+            items,
+            marker: Geometry2dMarker,
+        })
+    }
+
+    fn construct_transform(&self, tf: impl ITransform, item: Self::Output) -> Self::Output {
+        Box::new(Transform {
+            tf,
+            item,
+            marker: Geometry2dMarker,
+        })
+    }
+
+    fn construct_located(&self, span: Span, item: Self::Output) -> Self::Output {
+        Box::new(Located { span, item })
+    }
+
+    fn construct_polygon(&self, vertices: Vec<Expr>) -> Option<Self::Output> {
+        Some(Box::new(Polygon { vertices }))
+    }
+}
+
+#[derive(Default)]
+pub struct OpaqueVisitor {
+    /// Names of the `material` statements, which `opaque` can use instead of a color
+    pub materials: HashSet<String>,
 }
 
-pub struct OpaqueVisitor;
 impl StatementVisitor for OpaqueVisitor {
     type Output = Box<dyn IOpaqueShape>;
 
@@ -379,11 +905,11 @@ impl StatementVisitor for OpaqueVisitor {
         TypeMarker::Opaque(OpaqueMarker)
     }
 
-    fn construct_named(&self, name: String, args: Vec<String>) -> Self::Output {
+    fn construct_named(&self, name: String, args: Vec<Expr>) -> Self::Output {
         Box::new(NamedOpaqueShape { name, args })
     }
 
-    fn construct_raw(&self, expr: String) -> Self::Output {
+    fn construct_raw(&self, expr: Expr) -> Self::Output {
         Box::new(RawOpaque { expr })
     }
 
@@ -395,6 +921,14 @@ impl StatementVisitor for OpaqueVisitor {
         })
     }
 
+    fn construct_branch(&self, conditions: Vec<Expr>, items: Vec<Self::Output>) -> Self::Output {
+        Box::new(Branch {
+            conditions,
+            items,
+            marker: OpaqueMarker,
+        })
+    }
+
     fn construct_transform(&self, tf: impl ITransform, item: Self::Output) -> Self::Output {
         Box::new(Transform {
             tf,
@@ -403,12 +937,17 @@ impl StatementVisitor for OpaqueVisitor {
         })
     }
 
-    fn construct_opaque(
-        &self,
-        color: Vec<String>,
-        geometry: impl IGeometry,
-    ) -> Result<Self::Output, StatementError> {
-        Ok(Box::new(OpaqueShape { color, geometry }))
+    fn construct_located(&self, span: Span, item: Self::Output) -> Self::Output {
+        Box::new(Located { span, item })
+    }
+
+    fn construct_opaque(&self, color: Vec<Expr>, geometry: impl IGeometry) -> Option<Self::Output> {
+        let paint = match &color[..] {
+            [Expr::Ident(name)] if self.materials.contains(name) => Paint::Material(material::function_name(name)),
+            _ => Paint::Color(color),
+        };
+
+        Some(Box::new(OpaqueShape { paint, geometry }))
     }
 }
 
@@ -420,11 +959,11 @@ impl StatementVisitor for TransparentVisitor {
         TypeMarker::Transparent(TransparentMarker)
     }
 
-    fn construct_named(&self, name: String, args: Vec<String>) -> Self::Output {
+    fn construct_named(&self, name: String, args: Vec<Expr>) -> Self::Output {
         Box::new(NamedTransparentShape { name, args })
     }
 
-    fn construct_raw(&self, expr: String) -> Self::Output {
+    fn construct_raw(&self, expr: Expr) -> Self::Output {
         Box::new(RawTransparent { expr })
     }
 
@@ -436,6 +975,14 @@ impl StatementVisitor for TransparentVisitor {
         })
     }
 
+    fn construct_branch(&self, conditions: Vec<Expr>, items: Vec<Self::Output>) -> Self::Output {
+        Box::new(Branch {
+            conditions,
+            items,
+            marker: TransparentMarker,
+        })
+    }
+
     fn construct_transform(&self, tf: impl ITransform, item: Self::Output) -> Self::Output {
         Box::new(Transform {
             tf,
@@ -444,7 +991,143 @@ impl StatementVisitor for TransparentVisitor {
         })
     }
 
-    fn construct_transparent(&self, color: Vec<String>, geometry: impl IGeometry) -> Result<Self::Output, StatementError> {
-        Ok(Box::new(TransparentShape { color, geometry }))
+    fn construct_located(&self, span: Span, item: Self::Output) -> Self::Output {
+        Box::new(Located { span, item })
+    }
+
+    fn construct_transparent(&self, color: Vec<Expr>, geometry: impl IGeometry) -> Option<Self::Output> {
+        Some(Box::new(TransparentShape { color, geometry }))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod test {
+    use super::*;
+    use crate::shaders::generated::parser::test::scene;
+
+    /// Scene from a source that has no includes
+    pub(crate) fn parse_scene(source: &[u8]) -> Result<SceneDesc, SceneDescError> {
+        SceneDesc::from_statements(scene(source)?)
+    }
+
+    fn scene_error(source: &str) -> SceneDescError {
+        parse_scene(source.as_bytes()).unwrap_err()
+    }
+
+    #[test]
+    fn test_wrong_argument_count() {
+        let err = scene_error("opaque(1,1,1) at(0,0,0) smooth_union { sd_sphere(1); }");
+        match err {
+            SceneDescError::StatementError(ref e) => {
+                assert_eq!(e.path, ["opaque", "at"]);
+                assert_eq!(e.span, Span::new(24, 54));
+            }
+            ref e => panic!("unexpected error: {}", e),
+        }
+        assert_eq!(
+            err.to_string(),
+            "`smooth_union` expects 1 argument, got 0 (in `opaque` > `at`)"
+        );
+
+        assert_eq!(
+            scene_error("opaque(1,1,1) raw(1, 2);").to_string(),
+            "`raw` expects 1 argument, got 2 (in `opaque`)"
+        );
+        assert_eq!(
+            scene_error("opaque(1,1,1) at(1, 2) sd_sphere(1);").to_string(),
+            "`at` expects 3 arguments, got 2 (in `opaque`)"
+        );
+        assert_eq!(
+            scene_error("define_geometry() sd_sphere(1);").to_string(),
+            "`define_geometry` expects at least 1 argument, got 0"
+        );
+    }
+
+    #[test]
+    fn test_body_shape() {
+        assert_eq!(
+            scene_error("opaque(1,1,1) sd_sphere(1) { sd_box(vec3(1)); }").to_string(),
+            "`sd_sphere` does not take a body (in `opaque`)"
+        );
+        assert_eq!(
+            scene_error("opaque(1,1,1) opaque(1,1,1) sd_sphere(1);").to_string(),
+            "`opaque` cannot be used inside geometry objects (in `opaque`)"
+        );
+        assert_eq!(
+            scene_error("opaque(1,1,1) cond_else(true) sd_sphere(1);").to_string(),
+            "`cond_else` expects 2 statements in its body, got 1 (in `opaque`)"
+        );
+        assert_eq!(
+            scene_error("opaque(1,1,1) switch(1, 2) { sd_sphere(1); sd_sphere(2); }").to_string(),
+            "`switch` expects 3 statements in its body, got 2 (in `opaque`)"
+        );
+        assert_eq!(
+            scene_error("define_geometry(thing, float) sd_sphere(1);").to_string(),
+            "Parameter `float` must be in the form `<type> <name>`"
+        );
+    }
+
+    #[test]
+    fn test_reused_definitions() {
+        let mut total = (0, 0);
+
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let statements = scene(&std::fs::read(entry.unwrap().path()).unwrap()).unwrap();
+
+            let reused = SceneDesc::generate(statements.clone(), Glsl::new()).unwrap().fragment;
+            let repeated = SceneDesc::generate(statements, Glsl::without_reuse()).unwrap().fragment;
+            assert!(reused.len() <= repeated.len());
+
+            total.0 += reused.len();
+            total.1 += repeated.len();
+        }
+
+        assert!(total.0 < total.1, "{:?}", total);
+    }
+
+    #[test]
+    fn test_camera_errors() {
+        assert_eq!(
+            scene_error("camera(1) { keyframe(0); }").to_string(),
+            "`camera` expects no arguments, got 1"
+        );
+        assert_eq!(
+            scene_error("camera { keyframe(0) { pos(1, 2); } }").to_string(),
+            "`pos` expects 3 arguments, got 2 (in `camera` > `keyframe`)"
+        );
+        assert_eq!(
+            scene_error("camera { keyframe(0) { pos(1, 2, 3) { look_at(0, 0, 0); } } }").to_string(),
+            "`pos` does not take a body (in `camera` > `keyframe`)"
+        );
+    }
+
+    #[test]
+    fn test_shadertoy() {
+        let desc = parse_scene(
+            b"opaque(1, 0, 0) at(0, 1, 0) sd_sphere(1);\ncamera { keyframe(0) { pos(0, 0, -10); look_at(0, 0, 0); } keyframe(2) { pos(10, 0, -10); } keyframe(1, +); }",
+        )
+        .unwrap();
+        let shader = desc.shadertoy();
+
+        assert!(shader.contains("void mainImage(out vec4 frag_color, in vec2 frag_coord)"));
+        assert!(shader.contains("map_opaque(vec3(1.0, 0.0, 0.0), sd_sphere(1.0, def_"), "{}", shader);
+        assert!(!shader.contains("uniform "));
+        assert!(!shader.contains("#line"));
+        assert!(!shader.contains("void main()"));
+
+        assert!(shader.contains(
+            "void camera_at(float t, out vec3 pos, out vec4 rot) {\n    if (t <= 0.0) {\n        pos = vec3(0.0, 0.0, -10.0);\n        rot = quat_look_at(vec3(0.0, 0.0, 0.0) - pos);\n        return;\n    }\n    if (t <= 2.0) {\n        pos = mix(vec3(0.0, 0.0, -10.0), vec3(10.0, 0.0, -10.0), (t - 0.0) / 2.0);\n        rot = quat_look_at(vec3(0.0, 0.0, 0.0) - pos);\n        return;\n    }\n    if (t <= 3.0) {\n        pos = vec3(10.0, 0.0, -10.0);\n"
+        ), "{}", shader);
+    }
+
+    #[test]
+    fn test_shadertoy_literals() {
+        let desc = parse_scene(b"opaque(1, 1, 1) let(int, n, 3) let(float, r, 2) at(2, 0, 0) sd_sphere(r);").unwrap();
+        let shader = desc.shadertoy();
+
+        // only the numbers the checker sees as `float` become `float` literals
+        assert!(shader.contains("int def_1_1 = 3;"), "{}", shader);
+        assert!(shader.contains("float def_1_2 = 2.0;"), "{}", shader);
+        assert!(shader.contains("at(2.0, 0.0, 0.0, "), "{}", shader);
     }
 }
@@ -1,4 +1,5 @@
-use super::Statement;
+use super::{Arity, Statement, StatementError, StatementErrorKind, StatementPath};
+use crate::shaders::generated::source::Span;
 
 use std::collections::HashMap;
 use std::num::ParseFloatError;
@@ -7,7 +8,6 @@ mod keyframe;
 mod marker;
 
 use keyframe::{Keyframe, KeyframeError};
-use marker::MarkerError;
 
 #[derive(Debug, Clone, Copy)]
 pub enum Param<T: Clone + Copy> {
@@ -48,6 +48,23 @@ impl Rotation {
     }
 }
 
+impl Rotation {
+    /// GLSL quaternion of the rotation for a camera at the `pos` variable
+    fn glsl(self) -> String {
+        match self {
+            Rotation::Absolute(x) => {
+                let [i, j, k, w]: [f32; 4] = x.coords.into();
+                format!("vec4({:?}, {:?}, {:?}, {:?})", i, j, k, w)
+            }
+            Rotation::LookAt(x) => format!("quat_look_at({} - pos)", glsl_vec3(x)),
+        }
+    }
+}
+
+fn glsl_vec3(x: glm::Vec3) -> String {
+    format!("vec3({:?}, {:?}, {:?})", x.x, x.y, x.z)
+}
+
 impl Default for Rotation {
     fn default() -> Self {
         Rotation::Absolute(glm::Quat::default())
@@ -55,32 +72,27 @@ impl Default for Rotation {
 }
 
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct CameraDesc {
     timeline: Vec<Keyframe>,
     markers: HashMap<String, glm::Vec3>,
 }
 
-impl Default for CameraDesc {
-    fn default() -> Self {
-        CameraDesc {
-            timeline: Vec::new(),
-            markers: HashMap::new(),
-        }
-    }
-}
-
 impl CameraDesc {
     pub fn new(stmt: Statement) -> Result<CameraDesc, CameraDescError> {
         assert_eq!(stmt.name, "camera");
-        assert!(stmt.args.is_empty(), "Camera does not take arguments");
+        stmt.expect_args(Arity::Exactly(0))?;
+
+        Self::parse_body(stmt.body).map_err(|e| e.within("camera"))
+    }
 
+    fn parse_body(body: Vec<Statement>) -> Result<CameraDesc, CameraDescError> {
         let mut timeline = Vec::new();
         let mut markers = HashMap::new();
 
         let mut prev_t = 0.0;
         let mut keyframe_offset = 0.0;
-        for stmt in stmt.body {
+        for stmt in body {
             match stmt.name.as_str() {
                 "keyframe" => {
                     let mut kf = Keyframe::new(stmt, prev_t)?;
@@ -89,18 +101,20 @@ impl CameraDesc {
                     timeline.push(kf);
                 },
                 "offset" => {
-                    if stmt.args.is_empty() {
-                        return Err(CameraDescError::NoArgs);
-                    }
+                    stmt.expect_args(Arity::Exactly(1))?;
+                    stmt.expect_no_body()?;
 
-                    keyframe_offset += stmt.args[0].parse::<f32>()?;
+                    keyframe_offset += parse_number(&stmt, 0)?;
                 },
                 "marker" => {
                     let (k, v) = marker::parse_marker(stmt)?;
                     markers.insert(k, v);
                 }
 
-                x => return Err(CameraDescError::UnknownStatement(x.into()))
+                x => return Err(CameraDescError::new(
+                    CameraDescErrorKind::UnknownStatement(x.into()),
+                    stmt.span,
+                ))
             }
         }
 
@@ -151,7 +165,7 @@ impl CameraDesc {
             .unwrap_or_else(|closest_idx| closest_idx);
 
         if frame_idx == 0 {
-            let kf = self.timeline.get(0).cloned().unwrap_or_default();
+            let kf = self.timeline.first().cloned().unwrap_or_default();
 
             let pos = kf.pos.get();
             (pos, kf.rot.get().to_quat(pos))
@@ -178,18 +192,106 @@ impl CameraDesc {
             (pos, rot)
         }
     }
+
+    /// GLSL `camera_at(t, out pos, out rot)`, the same transform as `get_transform_at` with the keyframes baked in
+    pub fn glsl_function(&self) -> String {
+        let mut body = String::new();
+
+        let segment = |body: &mut String, condition: Option<String>, pos: String, rot: String| match condition {
+            Some(condition) => body.push_str(&format!(
+                "    if ({}) {{\n        pos = {};\n        rot = {};\n        return;\n    }}\n",
+                condition, pos, rot
+            )),
+            None => body.push_str(&format!("    pos = {};\n    rot = {};\n", pos, rot)),
+        };
+
+        if let Some(first) = self.timeline.first() {
+            segment(
+                &mut body,
+                Some(format!("t <= {:?}", first.t)),
+                glsl_vec3(first.pos.get()),
+                first.rot.get().glsl(),
+            );
+        }
+
+        for (idx, kf) in self.timeline.iter().enumerate().skip(1) {
+            let t1 = self.timeline[idx - 1].t;
+            let a = format!("(t - {:?}) / {:?}", t1, kf.t - t1);
+
+            let pos1 = glsl_vec3(self.get_pos_at_frame(idx - 1));
+            let pos = match kf.pos {
+                Param::Reuse => pos1,
+                Param::Override(pos2) => format!("mix({}, {}, {})", pos1, glsl_vec3(pos2), a),
+            };
+
+            let rot1 = self.get_rot_at_frame(idx - 1).glsl();
+            let rot = match kf.rot {
+                Param::Reuse => rot1,
+                Param::Override(rot2) => format!("quat_mix({}, {}, {})", rot1, rot2.glsl(), a),
+            };
+
+            segment(&mut body, Some(format!("t <= {:?}", kf.t)), pos, rot);
+        }
+
+        let (pos, rot) = match self.timeline.len() {
+            0 => (Default::default(), Default::default()),
+            len => (self.get_pos_at_frame(len - 1), self.get_rot_at_frame(len - 1)),
+        };
+        segment(&mut body, None, glsl_vec3(pos), rot.glsl());
+
+        format!("void camera_at(float t, out vec3 pos, out vec4 rot) {{\n{}}}\n", body)
+    }
+}
+
+fn parse_number(stmt: &Statement, idx: usize) -> Result<f32, CameraDescError> {
+    stmt.args[idx]
+        .to_string()
+        .parse()
+        .map_err(|e| CameraDescError::new(e, stmt.args[idx].span))
+}
+
+#[derive(Debug, thiserror::Error)]
+#[error("{}{}", .kind, StatementPath(.path))]
+pub struct CameraDescError {
+    pub kind: CameraDescErrorKind,
+    pub span: Span,
+    /// Names of the enclosing statements, outermost first
+    pub path: Vec<String>,
+}
+
+impl CameraDescError {
+    pub fn new(kind: impl Into<CameraDescErrorKind>, span: Span) -> Self {
+        CameraDescError {
+            kind: kind.into(),
+            span,
+            path: Vec::new(),
+        }
+    }
+
+    pub fn within(mut self, parent: &str) -> Self {
+        self.path.insert(0, parent.to_owned());
+        self
+    }
+}
+
+impl From<StatementError> for CameraDescError {
+    fn from(e: StatementError) -> Self {
+        CameraDescError {
+            kind: e.kind.into(),
+            span: e.span,
+            path: e.path,
+        }
+    }
 }
 
 #[derive(Debug, thiserror::Error)]
-pub enum CameraDescError {
+pub enum CameraDescErrorKind {
     #[error("Unknown statement: '{}'", .0)]
     UnknownStatement(String),
     #[error("{}", .0)]
-    Keyframe(#[from] KeyframeError),
+    Statement(#[from] StatementErrorKind),
     #[error("{}", .0)]
-    Marker(#[from] MarkerError),
-    #[error("Offset must have at least one argument")]
-    NoArgs,
+    Keyframe(#[from] KeyframeError),
     #[error("Failed to parse a number: {}", .0)]
     NumberParseError(#[from] ParseFloatError)
 }
@@ -1,7 +1,7 @@
-use super::{Rotation, Param};
+use super::{parse_number, Arity, CameraDescError, Rotation, Param};
 use crate::shaders::generated::desc::Statement;
+use crate::shaders::generated::expr::Expr;
 
-use std::num::ParseFloatError;
 use std::collections::HashMap;
 
 #[derive(Debug, Clone)]
@@ -13,7 +13,7 @@ pub struct Keyframe {
 }
 
 impl Keyframe {
-    pub fn new(stmt: Statement, prev_t: f32) -> Result<Keyframe, KeyframeError> {
+    pub fn new(stmt: Statement, prev_t: f32) -> Result<Keyframe, CameraDescError> {
         parse_keyframe(stmt, prev_t)
     }
 
@@ -57,13 +57,11 @@ impl Default for Keyframe {
     }
 }
 
-pub fn parse_keyframe(stmt: Statement, prev_t: f32) -> Result<Keyframe, KeyframeError> {
+pub fn parse_keyframe(stmt: Statement, prev_t: f32) -> Result<Keyframe, CameraDescError> {
     assert_eq!(stmt.name, "keyframe");
-    if stmt.args.is_empty() {
-        return Err(KeyframeError::NoArgs);
-    }
+    stmt.expect_args(Arity::Between(1, 2))?;
 
-    let mut t = stmt.args[0].parse()?;
+    let mut t = parse_number(&stmt, 0)?;
     if stmt.args.len() > 1 && stmt.args[1] == "+" {
         t += prev_t;
     }
@@ -73,15 +71,16 @@ pub fn parse_keyframe(stmt: Statement, prev_t: f32) -> Result<Keyframe, Keyframe
     let mut rot = None;
 
     for mut stmt in stmt.body {
-        if stmt.args.len() >= 1 && stmt.args[0].starts_with('$') {
-            let marker_name = stmt.args.remove(0);
-            marker = Some(marker_name[1..].to_owned());
+        if let Some(Expr::Arg(marker_name)) = stmt.args.first().map(|arg| &arg.node) {
+            marker = Some(marker_name.clone());
+            stmt.args.remove(0);
         }
 
-        match parse_keyframe_arg(stmt)? {
+        let span = stmt.span;
+        match parse_keyframe_arg(stmt).map_err(|e| e.within("keyframe"))? {
             KeyframeArg::Position(x) => {
                 if pos.is_some() {
-                    return Err(KeyframeError::Duplicate("position"))
+                    return Err(CameraDescError::new(KeyframeError::Duplicate("position"), span).within("keyframe"))
                 } else {
                     pos = Some(x);
                 }
@@ -89,7 +88,7 @@ pub fn parse_keyframe(stmt: Statement, prev_t: f32) -> Result<Keyframe, Keyframe
 
             KeyframeArg::Rotation(x) => {
                 if rot.is_some() {
-                    return Err(KeyframeError::Duplicate("rotation"))
+                    return Err(CameraDescError::new(KeyframeError::Duplicate("rotation"), span).within("keyframe"))
                 } else {
                     rot = Some(x);
                 }
@@ -112,50 +111,53 @@ enum KeyframeArg {
     Rotation(Rotation),
 }
 
-fn parse_keyframe_arg(stmt: Statement) -> Result<KeyframeArg, KeyframeError> {
-    assert!(stmt.body.is_empty());
+fn parse_keyframe_arg(stmt: Statement) -> Result<KeyframeArg, CameraDescError> {
+    stmt.expect_no_body()?;
 
     let arg = match stmt.name.as_str() {
         "pos" => {
-            assert_eq!(stmt.args.len(), 3);
+            stmt.expect_args(Arity::Exactly(3))?;
 
             let pos = glm::Vec3::new(
-                stmt.args[0].parse()?,
-                stmt.args[1].parse()?,
-                stmt.args[2].parse()?,
+                parse_number(&stmt, 0)?,
+                parse_number(&stmt, 1)?,
+                parse_number(&stmt, 2)?,
             );
 
             KeyframeArg::Position(pos)
         }
 
         "look_at" => {
-            assert_eq!(stmt.args.len(), 3);
+            stmt.expect_args(Arity::Exactly(3))?;
 
             let pos = glm::Vec3::new(
-                stmt.args[0].parse()?,
-                stmt.args[1].parse()?,
-                stmt.args[2].parse()?,
+                parse_number(&stmt, 0)?,
+                parse_number(&stmt, 1)?,
+                parse_number(&stmt, 2)?,
             );
 
             KeyframeArg::Rotation(Rotation::LookAt(pos))
         }
 
         "euler" => {
-            assert!(stmt.args.len() >= 3);
+            stmt.expect_args(Arity::Between(3, 4))?;
 
             let unit: fn(f32) -> f32 = if let Some(unit) = stmt.args.get(3) {
-                match unit.as_str() {
+                match unit.to_string().as_str() {
                     "degrees" => |x: f32| x.to_radians(),
                     "radians" => |x: f32| x,
-                    unit => return Err(KeyframeError::UnknownUnit(unit.into())),
+                    _ => return Err(CameraDescError::new(
+                        KeyframeError::UnknownUnit(unit.to_string()),
+                        unit.span,
+                    )),
                 }
             } else {
                 |x: f32| x.to_radians()
             };
 
-            let pitch = stmt.args[0].parse()?;
-            let yaw = stmt.args[1].parse()?;
-            let roll = stmt.args[2].parse()?;
+            let pitch = parse_number(&stmt, 0)?;
+            let yaw = parse_number(&stmt, 1)?;
+            let roll = parse_number(&stmt, 2)?;
 
             let mut quat = glm::Quat::new(1.0, 0.0, 0.0, 0.0);
             quat = glm::quat_rotate(&quat, unit(pitch), &glm::Vec3::x());
@@ -166,19 +168,19 @@ fn parse_keyframe_arg(stmt: Statement) -> Result<KeyframeArg, KeyframeError> {
         }
 
         "quat" | "quaternion" => {
-            assert_eq!(stmt.args.len(), 4);
+            stmt.expect_args(Arity::Exactly(4))?;
 
             let quat = glm::Quat::new(
-                stmt.args[0].parse()?,
-                stmt.args[1].parse()?,
-                stmt.args[2].parse()?,
-                stmt.args[3].parse()?,
+                parse_number(&stmt, 0)?,
+                parse_number(&stmt, 1)?,
+                parse_number(&stmt, 2)?,
+                parse_number(&stmt, 3)?,
             );
 
             KeyframeArg::Rotation(Rotation::Absolute(quat))
         }
 
-        _ => return Err(KeyframeError::UnknownArgument(stmt.name)),
+        _ => return Err(CameraDescError::new(KeyframeError::UnknownArgument(stmt.name), stmt.span)),
     };
 
     Ok(arg)
@@ -186,14 +188,10 @@ fn parse_keyframe_arg(stmt: Statement) -> Result<KeyframeArg, KeyframeError> {
 
 #[derive(Debug, thiserror::Error)]
 pub enum KeyframeError {
-    #[error("Keyframe must have at least one argument")]
-    NoArgs,
     #[error("Duplicate {} inside keyframe", .0)]
     Duplicate(&'static str),
     #[error("Unknown unit: {}", .0)]
     UnknownUnit(String),
     #[error("Unknown keyframe argument: {}", .0)]
     UnknownArgument(String),
-    #[error("Failed to parse a number: {}", .0)]
-    NumberParseError(#[from] ParseFloatError)
 }
@@ -1,25 +1,14 @@
-use super::Statement;
+use super::{parse_number, Arity, CameraDescError, Statement};
 
-use std::num::ParseFloatError;
+pub fn parse_marker(stmt: Statement) -> Result<(String, glm::Vec3), CameraDescError> {
+    stmt.expect_args(Arity::Exactly(4))?;
+    stmt.expect_no_body()?;
 
-pub fn parse_marker(stmt: Statement) -> Result<(String, glm::Vec3), MarkerError> {
-    if stmt.args.len() != 4 {
-        return Err(MarkerError::WrongNumberOfArguments);
-    }
+    let name = stmt.args[0].to_string();
 
-    let name = stmt.args[0].clone();
-
-    let x = stmt.args[1].parse()?;
-    let y = stmt.args[2].parse()?;
-    let z = stmt.args[3].parse()?;
+    let x = parse_number(&stmt, 1)?;
+    let y = parse_number(&stmt, 2)?;
+    let z = parse_number(&stmt, 3)?;
 
     Ok((name, glm::Vec3::new(x, y, z)))
 }
-
-#[derive(Debug, thiserror::Error)]
-pub enum MarkerError {
-    #[error("Marker expects four arguments")]
-    WrongNumberOfArguments,
-    #[error("Failed to parse a number: {}", .0)]
-    NumberParseError(#[from] ParseFloatError),
-}
@@ -0,0 +1,216 @@
+use std::collections::HashMap;
+
+use super::{Statement, StatementError, StatementErrorKind};
+use crate::shaders::generated::expr::Expr;
+
+/// Dependencies between `define_*` statements, which can use each other in any order
+pub struct Definitions<'a> {
+    definitions: &'a [Statement],
+    /// Indices of the definitions with each name, there can be several with different parameters
+    by_name: HashMap<String, Vec<usize>>,
+    /// Indices of the definitions each definition uses, in the order of their first use
+    uses: Vec<Vec<usize>>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Visit {
+    New,
+    Active,
+    Done,
+}
+
+impl<'a> Definitions<'a> {
+    /// Every definition must have its name as the first argument
+    pub fn new(definitions: &'a [Statement]) -> Self {
+        let mut by_name = HashMap::<_, Vec<_>>::new();
+        for (idx, def) in definitions.iter().enumerate() {
+            by_name.entry(def.args[0].to_string()).or_default().push(idx);
+        }
+
+        let mut deps = Definitions {
+            definitions,
+            by_name,
+            uses: Vec::new(),
+        };
+        deps.uses = definitions.iter().map(|def| deps.used_by(&def.body)).collect();
+
+        deps
+    }
+
+    /// Indices of the definitions the statements refer to, in the order of their first use
+    pub fn used_by(&self, stmts: &[Statement]) -> Vec<usize> {
+        let mut names = Vec::new();
+        for stmt in stmts {
+            references(stmt, &mut names);
+        }
+
+        let mut used = Vec::new();
+        for name in names {
+            for &idx in self.by_name.get(&name).into_iter().flatten() {
+                if !used.contains(&idx) {
+                    used.push(idx);
+                }
+            }
+        }
+
+        used
+    }
+
+    /// Orders the definitions so that each one comes after the definitions it uses,
+    /// otherwise keeping the order of the file
+    pub fn sorted(&self) -> Result<Vec<usize>, StatementError> {
+        let mut state = vec![Visit::New; self.definitions.len()];
+        let mut order = Vec::new();
+        let mut path = Vec::new();
+
+        for idx in 0..self.definitions.len() {
+            self.visit(idx, &mut state, &mut path, &mut order)?;
+        }
+
+        Ok(order)
+    }
+
+    fn visit(
+        &self,
+        idx: usize,
+        state: &mut [Visit],
+        path: &mut Vec<usize>,
+        order: &mut Vec<usize>,
+    ) -> Result<(), StatementError> {
+        match state[idx] {
+            Visit::Done => return Ok(()),
+            Visit::Active => {
+                let start = path.iter().position(|&x| x == idx).unwrap();
+                let cycle = path[start..]
+                    .iter()
+                    .chain(std::iter::once(&idx))
+                    .map(|&x| self.definitions[x].args[0].to_string())
+                    .collect();
+
+                let def = &self.definitions[idx];
+                return Err(StatementError::new(
+                    StatementErrorKind::RecursiveDefinition(cycle),
+                    def.args[0].span,
+                )
+                .within(&def.name));
+            }
+            Visit::New => {}
+        }
+
+        state[idx] = Visit::Active;
+        path.push(idx);
+
+        for &used in &self.uses[idx] {
+            self.visit(used, state, path, order)?;
+        }
+
+        path.pop();
+        state[idx] = Visit::Done;
+        order.push(idx);
+
+        Ok(())
+    }
+
+    /// Marks the definitions the given ones use, directly or through other definitions
+    pub fn reachable(&self, roots: &[usize]) -> Vec<bool> {
+        let mut reached = vec![false; self.definitions.len()];
+        let mut stack = roots.to_vec();
+
+        while let Some(idx) = stack.pop() {
+            if !reached[idx] {
+                reached[idx] = true;
+                stack.extend(&self.uses[idx]);
+            }
+        }
+
+        reached
+    }
+}
+
+/// Names of the statements and functions a statement uses
+fn references(stmt: &Statement, names: &mut Vec<String>) {
+    fn expr_references(expr: &Expr, names: &mut Vec<String>) {
+        match expr {
+            Expr::Call(name, args) => {
+                names.push(name.clone());
+                for arg in args {
+                    expr_references(arg, names);
+                }
+            }
+            Expr::Unary(_, operand) => expr_references(operand, names),
+            Expr::Binary(_, lhs, rhs) => {
+                expr_references(lhs, names);
+                expr_references(rhs, names);
+            }
+            Expr::Ternary(cond, then, otherwise) => {
+                expr_references(cond, names);
+                expr_references(then, names);
+                expr_references(otherwise, names);
+            }
+            Expr::Field(base, _) => expr_references(base, names),
+            _ => {}
+        }
+    }
+
+    names.push(stmt.name.clone());
+    for arg in &stmt.args {
+        expr_references(arg, names);
+    }
+    for child in &stmt.body {
+        references(child, names);
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::desc::{SceneDescError, test::parse_scene};
+    use crate::shaders::generated::parser::test::scene;
+    use crate::shaders::generated::source::Span;
+
+    fn sorted(source: &str) -> Result<Vec<String>, StatementError> {
+        let defs = scene(source.as_bytes()).unwrap();
+        let order = Definitions::new(&defs).sorted()?;
+        Ok(order.into_iter().map(|idx| defs[idx].args[0].to_string()).collect())
+    }
+
+    #[test]
+    fn test_sorted() {
+        assert_eq!(
+            sorted("define_geometry(a) b(); define_geometry(b) raw(c(arg) + 1.0); define_geometry(c) sd_sphere(1);").unwrap(),
+            ["c", "b", "a"]
+        );
+        assert_eq!(
+            sorted("define_geometry(a) sd_sphere(1); define_geometry(b) a(); define_geometry(c) a();").unwrap(),
+            ["a", "b", "c"]
+        );
+
+        let err = sorted("define_geometry(a) b(); define_geometry(b) union { sd_sphere(1); a(); }").unwrap_err();
+        assert_eq!(err.to_string(), "Recursive definition: `a` -> `b` -> `a` (in `define_geometry`)");
+        assert_eq!(err.span, Span::new(16, 17));
+
+        let err = sorted("define_geometry(a, float r) a(r);").unwrap_err();
+        assert_eq!(err.to_string(), "Recursive definition: `a` -> `a` (in `define_geometry`)");
+    }
+
+    #[test]
+    fn test_scene() {
+        let desc = parse_scene(
+            b"opaque(1, 1, 1) pillar();\ndefine_geometry(pillar) base();\ndefine_geometry(base) sd_box(vec3(1));\ndefine_geometry(unused) sd_sphere(1);",
+        )
+        .unwrap();
+
+        let base = desc.fragment.find("float base(Arg arg)").unwrap();
+        let pillar = desc.fragment.find("float pillar(Arg arg)").unwrap();
+        assert!(base < pillar);
+        assert!(!desc.fragment.contains("unused"));
+
+        // unused definitions are still checked
+        match parse_scene(b"define_geometry(unused) sd_sphere(vec3(1));") {
+            Err(SceneDescError::StatementError(e)) => {
+                assert_eq!(e.to_string(), "Argument 1 of `sd_sphere` should be `float`, found `vec3` (in `define_geometry`)")
+            }
+            _ => panic!("expected a type error"),
+        }
+    }
+}
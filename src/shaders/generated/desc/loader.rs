@@ -1,18 +1,40 @@
-use std::path::PathBuf;
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::SystemTime;
 
-use super::SceneDesc;
+use anyhow::Context as _;
+
+use super::{Arity, SceneDesc, SceneDescError, Statement};
+use crate::shaders::generated::expr::Expr;
+use crate::shaders::generated::parser;
+use crate::shaders::generated::source::{SourceFile, SourceMap, Span};
+
+#[derive(Debug, thiserror::Error)]
+pub enum IncludeError {
+    #[error("`include` expects a quoted path, got `{}`", .0)]
+    NotAPath(String),
+    #[error("Could not read `{}`: {}", .0.display(), .1)]
+    Io(PathBuf, std::io::Error),
+    #[error("Include cycle: {}", .0.iter().map(|p| format!("`{}`", p.display())).collect::<Vec<_>>().join(" -> "))]
+    Cycle(Vec<PathBuf>),
+}
 
 pub struct SceneDescLoader {
     file: PathBuf,
     use_camera: bool,
     last_update: SystemTime,
+    /// Every file of the include graph seen by the last `load`
+    watched: Vec<PathBuf>,
 }
 
 impl SceneDescLoader {
     pub fn new(path: impl Into<PathBuf>) -> Self {
+        let file = path.into();
+
         SceneDescLoader {
-            file: path.into(),
+            watched: vec![file.clone()],
+            file,
             use_camera: true,
             last_update: SystemTime::now(),
         }
@@ -23,33 +45,37 @@ impl SceneDescLoader {
     }
 
     pub fn load(&mut self) -> anyhow::Result<SceneDesc> {
-        let source = std::fs::read(&self.file)?;
-        let mut desc = SceneDesc::parse(&source)?;
+        let source = std::fs::read(&self.file);
+        self.last_update = SystemTime::now();
+
+        let source = match source {
+            Ok(source) => source,
+            Err(e) => {
+                self.watched = vec![self.file.clone()];
+                return Err(e).with_context(|| format!("Could not read `{}`", self.file.display()));
+            }
+        };
+
+        let resolved = resolve_includes(&self.file, &source);
+
+        // files that were found before an error still need to be watched, so that fixing them triggers a reload
+        self.watched = resolved.files;
+        let sources = Arc::new(resolved.sources);
+
+        let mut desc = resolved.statements
+            .and_then(SceneDesc::from_statements)
+            .map_err(|e| sources.diagnostic(e.span(), &e))?;
         if !self.use_camera {
             desc.camera = None;
         }
+        desc.line_map.attach_sources(sources);
 
-        self.last_update = SystemTime::now();
         Ok(desc)
     }
 
     pub fn load_if_updated(&mut self) -> Option<anyhow::Result<SceneDesc>> {
-        // sometimes fails, so repeat three times
-        let metadata = std::fs::metadata(&self.file)
-            .or_else(|_| {
-                std::thread::sleep(std::time::Duration::from_millis(10));
-                std::fs::metadata(&self.file)
-            })
-            .or_else(|_| {
-                std::thread::sleep(std::time::Duration::from_millis(50));
-                std::fs::metadata(&self.file)
-            });
-
-        let modified = metadata
-            .expect("Could not read file metadata")
-            .modified()
-            .expect("Modified time not supported");
-            
+        let modified = self.watched.iter().filter_map(|file| modified_time(file)).max()?;
+
         if modified > self.last_update {
             let ret = self.load();
             self.last_update = modified;
@@ -59,3 +85,199 @@ impl SceneDescLoader {
         }
     }
 }
+
+fn modified_time(file: &Path) -> Option<SystemTime> {
+    // sometimes fails, so repeat three times
+    let metadata = std::fs::metadata(file)
+        .or_else(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            std::fs::metadata(file)
+        })
+        .or_else(|_| {
+            std::thread::sleep(std::time::Duration::from_millis(50));
+            std::fs::metadata(file)
+        });
+
+    metadata
+        .ok()?
+        .modified()
+        .ok()
+}
+
+/// A scene file along with everything it includes
+pub struct Resolved {
+    /// The scene file itself is always the first file
+    pub sources: SourceMap,
+    /// Every file of the include graph read so far
+    pub files: Vec<PathBuf>,
+    pub statements: Result<Vec<Statement>, SceneDescError>,
+}
+
+/// Resolves the `include` statements of a scene whose own source is already loaded,
+/// splicing the included statements in their place.
+pub fn resolve_includes(path: &Path, source: &[u8]) -> Resolved {
+    let mut includes = Includes::default();
+    let statements = includes.parse(path, source, None);
+
+    Resolved {
+        sources: includes.sources,
+        files: includes.files,
+        statements,
+    }
+}
+
+/// Every file is included at most once, so libraries can be included from several places.
+#[derive(Default)]
+struct Includes {
+    sources: SourceMap,
+    files: Vec<PathBuf>,
+    /// Canonical paths of the files being read, used to detect cycles
+    stack: Vec<PathBuf>,
+    done: HashSet<PathBuf>,
+}
+
+impl Includes {
+    fn read(&mut self, path: &Path, included_from: Span) -> Result<Vec<Statement>, SceneDescError> {
+        match std::fs::read(path) {
+            Ok(source) => self.parse(path, &source, Some(included_from)),
+            Err(e) => {
+                self.files.push(path.to_owned());
+                Err(SceneDescError::IncludeError(included_from, IncludeError::Io(path.to_owned(), e)))
+            }
+        }
+    }
+
+    fn parse(&mut self, path: &Path, source: &[u8], included_from: Option<Span>) -> Result<Vec<Statement>, SceneDescError> {
+        self.files.push(path.to_owned());
+
+        // a scene that only exists in an editor has no canonical path yet
+        let canonical = path.canonicalize().unwrap_or_else(|_| path.to_owned());
+        let file = self.sources.add(SourceFile::new(path, source), included_from);
+        let statements = parser::scene_file_in(source, file)?.statements;
+
+        self.stack.push(canonical);
+
+        let mut result = Vec::new();
+        for stmt in statements {
+            if stmt.name == "include" {
+                let included = self.include(path, &stmt)?;
+                result.extend(included);
+            } else {
+                result.push(stmt);
+            }
+        }
+
+        let canonical = self.stack.pop().unwrap();
+        self.done.insert(canonical);
+
+        Ok(result)
+    }
+
+    fn include(&mut self, from: &Path, stmt: &Statement) -> Result<Vec<Statement>, SceneDescError> {
+        stmt.expect_args(Arity::Exactly(1))?;
+        stmt.expect_no_body()?;
+
+        let arg = &stmt.args[0];
+        let relative = match &arg.node {
+            Expr::String(path) => path,
+            _ => return Err(SceneDescError::IncludeError(arg.span, IncludeError::NotAPath(arg.to_string()))),
+        };
+
+        let path = from.parent().unwrap_or_else(|| Path::new("")).join(relative);
+        let canonical = match path.canonicalize() {
+            Ok(canonical) => canonical,
+            // let `read` report the error
+            Err(_) => return self.read(&path, arg.span),
+        };
+
+        if let Some(start) = self.stack.iter().position(|p| *p == canonical) {
+            let mut cycle = self.stack[start..].to_vec();
+            cycle.push(canonical);
+            return Err(SceneDescError::IncludeError(arg.span, IncludeError::Cycle(cycle)));
+        }
+
+        if self.done.contains(&canonical) {
+            return Ok(Vec::new());
+        }
+
+        self.read(&path, arg.span)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    /// Writes the files into a fresh directory and loads the first one
+    fn load(name: &str, files: &[(&str, &str)]) -> (PathBuf, anyhow::Result<SceneDesc>) {
+        let dir = std::env::temp_dir().join(format!("sdf-walker-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+
+        for (path, text) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, text).unwrap();
+        }
+
+        let mut loader = SceneDescLoader::new(dir.join(files[0].0));
+        let result = loader.load();
+        (dir, result)
+    }
+
+    #[test]
+    fn test_include() {
+        let (dir, result) = load(
+            "include",
+            &[
+                ("main.scene", "include(\"lib/shapes.scene\");\ninclude(\"lib/pillar.scene\");\nat(0, 0, 5) opaque(1, 1, 1) pillar();\n"),
+                ("lib/shapes.scene", "define_geometry(pillar_base) { sd_box(vec3(1)) }\n"),
+                ("lib/pillar.scene", "include(\"shapes.scene\");\ndefine_geometry(pillar) { pillar_base() }\n"),
+            ],
+        );
+
+        let desc = result.unwrap();
+        assert_eq!(desc.fragment.matches("pillar_base(Arg arg)").count(), 1);
+        assert!(desc.fragment.contains("pillar(Arg arg)"));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[test]
+    fn test_include_errors() {
+        let (dir, result) = load(
+            "cycle",
+            &[
+                ("a.scene", "include(\"b.scene\")"),
+                ("b.scene", "\ninclude(\"a.scene\")"),
+            ],
+        );
+
+        let message = result.unwrap_err().to_string();
+        assert!(message.starts_with("error: Include cycle: "), "{}", message);
+        assert!(message.contains("b.scene:2:9"), "{}", message);
+        let note = format!("= note: included from {}:1:9", dir.join("a.scene").display());
+        assert!(message.ends_with(&note), "{}", message);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let (dir, result) = load(
+            "missing",
+            &[("a.scene", "include(\"missing.scene\")")],
+        );
+        let message = result.unwrap_err().to_string();
+        assert!(message.starts_with("error: Could not read `"), "{}", message);
+        assert!(message.contains("a.scene:1:9"), "{}", message);
+        std::fs::remove_dir_all(dir).unwrap();
+
+        let (dir, result) = load(
+            "nested",
+            &[
+                ("a.scene", "include(\"b.scene\")"),
+                ("b.scene", "union { include(\"c.scene\") }"),
+            ],
+        );
+        let message = result.unwrap_err().to_string();
+        assert!(message.starts_with("error: `include` can only be used at the top level of a file (in `union`)"), "{}", message);
+        assert!(message.contains("b.scene:1:9"), "{}", message);
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,177 @@
+use super::{Arity, Statement, StatementError, StatementErrorKind};
+use crate::shaders::generated::codegen::{self as glsl, ArgExpr, Glsl};
+use crate::shaders::generated::eval::{Env, Eval, EvalError, Value};
+use crate::shaders::generated::expr::Expr;
+use crate::shaders::generated::source::Span;
+
+/// Statements a `material` body can have, with the number of components and the default value,
+/// in the order of the fields of the GLSL `Material`
+pub const PROPERTIES: [(&str, usize, f64); 4] = [
+    ("albedo", 3, 1.0),
+    ("roughness", 1, 1.0),
+    ("metallic", 1, 0.0),
+    ("emissive", 3, 0.0),
+];
+
+/// Number of components a material property needs
+pub fn property_components(name: &str) -> Option<usize> {
+    PROPERTIES
+        .iter()
+        .find(|(property, ..)| *property == name)
+        .map(|(_, components, _)| *components)
+}
+
+/// Function a `material` is emitted as, with a prefix so that it can't clash with a `define_*`, a library function or
+/// a GLSL built-in
+pub fn function_name(material: &str) -> String {
+    format!("material_{}", material)
+}
+
+/// Surface properties shared by the opaque objects that use a `material`
+#[derive(Debug)]
+pub struct MaterialDesc {
+    pub name: String,
+    span: Span,
+    /// Arguments of each property, `None` for the ones left at their default
+    properties: [Option<Vec<Expr>>; 4],
+}
+
+impl MaterialDesc {
+    pub fn new(stmt: &Statement) -> Result<Self, StatementError> {
+        assert_eq!(stmt.name, "material");
+        stmt.expect_args(Arity::Exactly(1))?;
+
+        let mut properties = [None, None, None, None];
+        for property in &stmt.body {
+            let idx = PROPERTIES
+                .iter()
+                .position(|(name, ..)| *name == property.name)
+                .ok_or_else(|| {
+                    StatementError::new(StatementErrorKind::UnknownProperty(property.name.clone()), property.span)
+                })
+                .map_err(|e| e.within(&stmt.name))?;
+
+            property.expect_args(Arity::AtLeast(1)).map_err(|e| e.within(&stmt.name))?;
+            property.expect_no_body().map_err(|e| e.within(&stmt.name))?;
+
+            if properties[idx].is_some() {
+                return Err(StatementError::new(
+                    StatementErrorKind::DuplicateProperty(property.name.clone()),
+                    property.span,
+                )
+                .within(&stmt.name));
+            }
+
+            properties[idx] = Some(property.arg_exprs());
+        }
+
+        Ok(MaterialDesc {
+            name: stmt.args[0].to_string(),
+            span: stmt.span,
+            properties,
+        })
+    }
+
+    /// Emits `Material material_<name>(Arg arg)`, so properties can depend on the position and time
+    pub fn emit(&self, glsl: &mut Glsl) {
+        let mut func = glsl.add_function("Material", function_name(&self.name), &[("Arg", "arg")]);
+        func.enter(self.span);
+
+        let mut material = glsl::FunctionCall::new("Material");
+        for (args, (_, components, default)) in self.properties.iter().zip(&PROPERTIES) {
+            match args {
+                None if *components == 1 => material.push_arg(glsl::RawString::new(format!("{:?}", default))),
+                None => material.push_arg(glsl::RawString::new(format!("vec{}({})", components, default))),
+                Some(args) if *components == 1 => material.push_arg(ArgExpr::new(&args[0], "arg")),
+                Some(args) => {
+                    let mut vec = glsl::FunctionCall::new(format!("vec{}", components));
+                    for arg in args {
+                        vec.push_arg(ArgExpr::new(arg, "arg"));
+                    }
+                    material.push_arg(vec);
+                }
+            }
+        }
+
+        func.leave();
+        func.ret(glsl, material);
+    }
+}
+
+impl Eval for MaterialDesc {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut fields = Vec::new();
+        for (args, (_, components, default)) in self.properties.iter().zip(&PROPERTIES) {
+            let field = match args {
+                None if *components == 1 => Value::Float(*default),
+                None => Value::Vec(*components, [*default, *default, *default, 0.0]),
+                Some(args) if *components == 1 => env.expr(&args[0])?,
+                Some(args) => {
+                    let args = args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+                    env.call(&format!("vec{}", components), args)?
+                }
+            };
+            fields.push(field);
+        }
+
+        Value::new_struct("Material", fields)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use crate::shaders::generated::desc::{SceneDescError, test::parse_scene};
+    use crate::shaders::generated::validate::validate;
+
+    fn error(source: &str) -> String {
+        match parse_scene(source.as_bytes()) {
+            Err(SceneDescError::StatementError(e)) => e.to_string(),
+            Err(e) => panic!("unexpected error: {}", e),
+            Ok(_) => panic!("no error in `{}`", source),
+        }
+    }
+
+    #[test]
+    fn test_material() {
+        let desc = parse_scene(
+            b"opaque(gold) sd_sphere(1);\ndefine_opaque(ball, vec3 color) opaque(color) sd_sphere(1);\nat(3, 0, 0) ball(vec3(1, 0, 0));\nmaterial(gold) { albedo(1, 0.8, 0.2); metallic(1); roughness(0.3 + 0.1 * sin($t)); }",
+        )
+        .unwrap();
+
+        assert!(desc.fragment.contains(
+            "Material material_gold(Arg arg) {\n\nreturn Material(vec3(1.0, 0.8, 0.2), 0.3 + 0.1 * sin(arg.t), 1.0, vec3(0));\n}"
+        ));
+        assert!(desc.fragment.contains("MapOpaque(material_gold(arg), sd_sphere(1.0, arg))"));
+        assert!(desc.fragment.contains("map_opaque(vec3(color), sd_sphere(1.0, arg))"));
+    }
+
+    #[test]
+    fn test_material_names() {
+        let desc = parse_scene(
+            b"material(ball) emissive(1, 0, 0);
+material(sd_sphere) albedo(0, 1, 0);
+material(length) albedo(0, 0, 1);
+define_geometry(ball) sd_sphere(1);
+opaque(ball) ball;
+opaque(sd_sphere) at(3, 0, 0) sd_sphere(length($p));",
+        )
+        .unwrap();
+
+        assert_eq!(validate(&desc.fragment), Ok(()));
+        assert!(desc.fragment.contains("float ball(Arg arg)"));
+        assert!(desc.fragment.contains("MapOpaque(material_ball(arg), ball(arg))"));
+
+        let opaque = desc.cpu.opaque([0.0, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(opaque.to_string(), "MapOpaque(Material(vec3(1, 1, 1), 1, 0, vec3(1, 0, 0)), -1)");
+    }
+
+    #[test]
+    fn test_material_errors() {
+        assert_eq!(error("material(a) { shininess(1); }"), "`shininess` is not a material property (in `material`)");
+        assert_eq!(error("material(a) { metallic(1); metallic(0); }"), "`metallic` is set more than once (in `material`)");
+        assert_eq!(error("material(a) { albedo(1, 0); }"), "`albedo` needs 3 components, got 2 (in `material`)");
+        assert_eq!(error("material(a) albedo(1, 1, 1); material(a);"), "Material `a` is defined more than once");
+        assert_eq!(error("material(a) albedo(1, 1, 1); opaque(b) sd_sphere(1);"), "Unknown name `b`");
+        assert_eq!(error("material();"), "`material` expects 1 argument, got 0");
+    }
+}
@@ -0,0 +1,965 @@
+//! CPU evaluation of scenes, the counterpart of the GLSL generated by `MakeExpr`
+
+use std::collections::HashMap;
+use std::fmt::{self, Debug};
+
+use super::expr::{BinaryOp, Expr, UnaryOp};
+use super::typed::{Bound, IOpaqueShape, MakeExpr, OpaqueMarker, TypeMarker};
+#[cfg(test)]
+use super::typed::{ITransparentShape, TransparentMarker};
+
+mod builtins;
+pub mod library;
+pub mod noise;
+pub mod shapes;
+
+lazy_static! {
+    /// Structs of `library.glsl` with the names of their fields, in order
+    static ref STRUCTS: Vec<(String, Vec<String>)> = parse_structs(include_str!("../../glsl/library.glsl"));
+}
+
+/// Value of a GLSL expression
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Float(f64),
+    /// Number of components and their values, the unused ones are zero
+    Vec(usize, [f64; 4]),
+    /// Name of a `library.glsl` struct and its fields
+    Struct(&'static str, Vec<Value>),
+}
+
+impl Value {
+    pub fn vec3([x, y, z]: [f64; 3]) -> Self {
+        Value::Vec(3, [x, y, z, 0.0])
+    }
+
+    pub fn arg(p: [f64; 3], t: f64) -> Self {
+        Value::new_struct("Arg", vec![Value::vec3(p), Value::Float(t)]).unwrap()
+    }
+
+    /// Instance of a `library.glsl` struct, checking the number of fields
+    pub fn new_struct(name: &str, fields: Vec<Value>) -> Result<Self, EvalError> {
+        let (name, names) = STRUCTS
+            .iter()
+            .find(|(struct_name, _)| struct_name == name)
+            .ok_or_else(|| EvalError::UnknownFunction(name.to_owned()))?;
+
+        if names.len() == fields.len() {
+            Ok(Value::Struct(name.as_str(), fields))
+        } else {
+            Err(EvalError::Arguments(name.clone()))
+        }
+    }
+
+    pub fn type_name(&self) -> String {
+        match self {
+            Value::Bool(_) => String::from("bool"),
+            Value::Float(_) => String::from("float"),
+            Value::Vec(n, _) => format!("vec{}", n),
+            Value::Struct(name, _) => (*name).to_owned(),
+        }
+    }
+
+    pub fn as_float(&self) -> Result<f64, EvalError> {
+        match self {
+            Value::Float(x) => Ok(*x),
+            _ => Err(self.mismatch("float")),
+        }
+    }
+
+    pub fn as_bool(&self) -> Result<bool, EvalError> {
+        match self {
+            Value::Bool(x) => Ok(*x),
+            _ => Err(self.mismatch("bool")),
+        }
+    }
+
+    pub fn as_vec2(&self) -> Result<[f64; 2], EvalError> {
+        match self {
+            Value::Vec(2, [x, y, _, _]) => Ok([*x, *y]),
+            _ => Err(self.mismatch("vec2")),
+        }
+    }
+
+    pub fn as_vec3(&self) -> Result<[f64; 3], EvalError> {
+        match self {
+            Value::Vec(3, [x, y, z, _]) => Ok([*x, *y, *z]),
+            _ => Err(self.mismatch("vec3")),
+        }
+    }
+
+    /// Distance of a `float`, `MapOpaque` or `MapTransparent`
+    pub fn distance(&self) -> Result<f64, EvalError> {
+        match self {
+            Value::Struct(_, _) => self.field("d")?.as_float(),
+            _ => self.as_float(),
+        }
+    }
+
+    /// Same object with the distance changed
+    pub fn map_distance(mut self, f: impl FnOnce(f64) -> f64) -> Result<Self, EvalError> {
+        match &mut self {
+            Value::Float(d) => *d = f(*d),
+            Value::Struct(_, _) => {
+                let d = self.field("d")?.as_float()?;
+                self.set_field("d", Value::Float(f(d)))?;
+            }
+            _ => return Err(self.mismatch("float")),
+        }
+
+        Ok(self)
+    }
+
+    /// Struct field or swizzle
+    pub fn field(&self, name: &str) -> Result<Value, EvalError> {
+        match self {
+            Value::Struct(struct_name, fields) => Ok(fields[field_index(struct_name, name)?].clone()),
+            Value::Vec(n, c) => {
+                let mut out = [0.0; 4];
+                for (idx, letter) in name.chars().enumerate() {
+                    let component = ["xyzw", "rgba", "stpq"]
+                        .iter()
+                        .find_map(|set| set.find(letter))
+                        .filter(|&component| component < *n && idx < 4)
+                        .ok_or_else(|| EvalError::UnknownField(self.type_name(), name.to_owned()))?;
+                    out[idx] = c[component];
+                }
+
+                Ok(match name.len() {
+                    1 => Value::Float(out[0]),
+                    len => Value::Vec(len, out),
+                })
+            }
+            _ => Err(EvalError::UnknownField(self.type_name(), name.to_owned())),
+        }
+    }
+
+    pub fn set_field(&mut self, name: &str, value: Value) -> Result<(), EvalError> {
+        match self {
+            Value::Struct(struct_name, fields) => {
+                fields[field_index(struct_name, name)?] = value;
+                Ok(())
+            }
+            _ => Err(EvalError::UnknownField(self.type_name(), name.to_owned())),
+        }
+    }
+
+    fn mismatch(&self, expected: &str) -> EvalError {
+        EvalError::Type(expected.to_owned(), self.type_name())
+    }
+}
+
+fn field_index(struct_name: &str, name: &str) -> Result<usize, EvalError> {
+    STRUCTS
+        .iter()
+        .find(|(other, _)| other == struct_name)
+        .and_then(|(_, fields)| fields.iter().position(|field| field == name))
+        .ok_or_else(|| EvalError::UnknownField(struct_name.to_owned(), name.to_owned()))
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Value::Bool(x) => write!(f, "{}", x),
+            Value::Float(x) => write!(f, "{}", x),
+            Value::Vec(n, c) => {
+                let components = c[..*n].iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                write!(f, "vec{}({})", n, components.join(", "))
+            }
+            Value::Struct(name, fields) => {
+                let fields = fields.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+                write!(f, "{}({})", name, fields.join(", "))
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum EvalError {
+    #[error("`{}` has no CPU implementation", .0)]
+    UnknownFunction(String),
+    #[error("Unknown name `{}`", .0)]
+    UnknownName(String),
+    #[error("Wrong arguments for `{}`", .0)]
+    Arguments(String),
+    #[error("`{}` has no field `{}`", .0, .1)]
+    UnknownField(String, String),
+    #[error("Expected `{}`, found `{}`", .0, .1)]
+    Type(String, String),
+    #[error("`{}` can't be evaluated on the CPU", .0)]
+    Unsupported(String),
+}
+
+/// Object that can be evaluated on the CPU, the counterpart of `MakeExpr`
+pub trait Eval: Debug + Send + Sync {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError>;
+}
+
+impl<T: Eval + ?Sized> Eval for &T {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        (**self).eval(env)
+    }
+}
+
+impl<T: Eval + ?Sized> Eval for Box<T> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        (**self).eval(env)
+    }
+}
+
+/// Function defined by the scene, a `define_*` or a `material`
+#[derive(Debug)]
+struct Definition {
+    /// Types and names of the parameters before the `Arg`
+    params: Vec<(String, String)>,
+    body: Box<dyn Eval>,
+}
+
+/// Functions defined by the scene, which can have several definitions with different parameters
+#[derive(Debug, Default)]
+pub struct Functions {
+    definitions: HashMap<String, Vec<Definition>>,
+}
+
+impl Functions {
+    pub fn define(&mut self, name: impl Into<String>, params: Vec<(String, String)>, body: Box<dyn Eval>) {
+        self.definitions
+            .entry(name.into())
+            .or_default()
+            .push(Definition { params, body });
+    }
+
+    /// Definition whose parameters take the arguments, the last argument being the `Arg`
+    fn find(&self, name: &str, args: &[Value]) -> Option<&Definition> {
+        self.definitions.get(name)?.iter().find(|def| {
+            def.params.len() + 1 == args.len()
+                && def.params.iter().zip(args).all(|((typ, _), arg)| *typ == arg.type_name())
+        })
+    }
+}
+
+/// State of an evaluation, like the variables of a generated GLSL function
+pub struct Env<'a> {
+    functions: &'a Functions,
+    /// `Arg` that `$name` refers to
+    arg: Value,
+    /// Variables in scope, innermost last
+    variables: Vec<(String, Value)>,
+    /// Value of the `time` uniform
+    time: f64,
+}
+
+impl<'a> Env<'a> {
+    /// Environment of a call to `map_impl`, with `arg` as its argument
+    pub fn new(functions: &'a Functions, p: [f64; 3], time: f64) -> Self {
+        let arg = Value::arg(p, time);
+        Env {
+            functions,
+            variables: vec![(String::from("arg"), arg.clone())],
+            arg,
+            time,
+        }
+    }
+
+    pub fn arg(&self) -> &Value {
+        &self.arg
+    }
+
+    /// Evaluates with `$name` referring to another `Arg`
+    pub fn with_arg<R>(&mut self, arg: Value, f: impl FnOnce(&mut Self) -> R) -> R {
+        let outer = std::mem::replace(&mut self.arg, arg);
+        let result = f(self);
+        self.arg = outer;
+
+        result
+    }
+
+    /// Evaluates with a variable in scope, shadowing any other of the same name
+    pub fn with_variable<R>(&mut self, name: &str, value: Value, f: impl FnOnce(&mut Self) -> R) -> R {
+        self.variables.push((name.to_owned(), value));
+        let result = f(self);
+        self.variables.pop();
+
+        result
+    }
+
+    /// Calls a scene definition, a `library.glsl` function, a GLSL built-in or a struct constructor
+    pub fn call(&mut self, name: &str, args: Vec<Value>) -> Result<Value, EvalError> {
+        if let Some(def) = self.functions.find(name, &args) {
+            let mut args = args;
+            let arg = args.pop().unwrap();
+
+            let mut variables = def
+                .params
+                .iter()
+                .map(|(_, param)| param.clone())
+                .zip(args)
+                .collect::<Vec<_>>();
+            variables.push((String::from("arg"), arg.clone()));
+
+            // like a GLSL function, the body only sees its parameters and the uniforms
+            let mut env = Env {
+                functions: self.functions,
+                arg,
+                variables,
+                time: self.time,
+            };
+            return def.body.eval(&mut env);
+        }
+
+        if let Some(result) = library::call(name, &args) {
+            return result;
+        }
+
+        if let Some(result) = noise::call(name, &args) {
+            return result;
+        }
+
+        if let Some(result) = shapes::call(name, &args) {
+            return result;
+        }
+
+        if let Some(result) = builtins::call(name, &args) {
+            return result;
+        }
+
+        Value::new_struct(name, args)
+    }
+
+    pub fn expr(&mut self, expr: &Expr) -> Result<Value, EvalError> {
+        match expr {
+            Expr::Number(x) => x
+                .parse()
+                .map(Value::Float)
+                .map_err(|_| EvalError::Unsupported(x.clone())),
+            Expr::Ident(name) => self.variable(name),
+            Expr::Arg(name) => self.arg.field(name),
+            Expr::Unary(op, operand) => {
+                let operand = self.expr(operand)?;
+                match op {
+                    UnaryOp::Neg => builtins::componentwise("-", &[operand], |x| -x[0]),
+                    UnaryOp::Plus => Ok(operand),
+                    UnaryOp::Not => Ok(Value::Bool(!operand.as_bool()?)),
+                    UnaryOp::BitNot => Err(EvalError::Unsupported(String::from("~"))),
+                }
+            }
+            Expr::Binary(op, lhs, rhs) => match op {
+                // the right side isn't evaluated when the left one decides
+                BinaryOp::And => Ok(Value::Bool(self.expr(lhs)?.as_bool()? && self.expr(rhs)?.as_bool()?)),
+                BinaryOp::Or => Ok(Value::Bool(self.expr(lhs)?.as_bool()? || self.expr(rhs)?.as_bool()?)),
+                _ => {
+                    let (lhs, rhs) = (self.expr(lhs)?, self.expr(rhs)?);
+                    builtins::binary(*op, &lhs, &rhs)
+                }
+            },
+            Expr::Ternary(cond, then, otherwise) => {
+                if self.expr(cond)?.as_bool()? {
+                    self.expr(then)
+                } else {
+                    self.expr(otherwise)
+                }
+            }
+            Expr::Call(name, args) => {
+                let args = args.iter().map(|arg| self.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+                self.call(name, args)
+            }
+            Expr::Field(base, field) => self.expr(base)?.field(field),
+            Expr::String(_) | Expr::Declaration(..) | Expr::Symbol(_) => Err(EvalError::Unsupported(expr.to_string())),
+        }
+    }
+
+    fn variable(&self, name: &str) -> Result<Value, EvalError> {
+        match self.variables.iter().rev().find(|(var, _)| var == name) {
+            Some((_, value)) => Ok(value.clone()),
+            None if name == "time" => Ok(Value::Float(self.time)),
+            None if name == "true" || name == "false" => Ok(Value::Bool(name == "true")),
+            None => Err(EvalError::UnknownName(name.to_owned())),
+        }
+    }
+}
+
+/// Scene that can be evaluated on the CPU, like the `map` functions of the shader
+#[derive(Debug, Default)]
+pub struct CpuScene {
+    pub functions: Functions,
+    pub opaque: Option<Box<dyn IOpaqueShape>>,
+    /// Meshes only have opaque objects, so transparent ones are only evaluated by the tests so far
+    #[cfg(test)]
+    pub(crate) transparent: Option<Box<dyn ITransparentShape>>,
+}
+
+impl CpuScene {
+    /// `MapOpaque` of the closest opaque object
+    pub fn opaque(&self, p: [f64; 3], t: f64) -> Result<Value, EvalError> {
+        match &self.opaque {
+            Some(opaque) => opaque.eval(&mut Env::new(&self.functions, p, t)),
+            None => Ok(TypeMarker::from(OpaqueMarker).value_with_distance(f64::INFINITY)),
+        }
+    }
+
+    /// Bound of the opaque objects, `None` when it can't be known
+    pub fn opaque_bound(&self) -> Option<Bound> {
+        self.opaque.as_ref()?.bound()
+    }
+}
+
+#[cfg(test)]
+impl CpuScene {
+    /// `MapTransparent` of the closest transparent object
+    pub(crate) fn transparent(&self, p: [f64; 3], t: f64) -> Result<Value, EvalError> {
+        match &self.transparent {
+            Some(transparent) => transparent.eval(&mut Env::new(&self.functions, p, t)),
+            None => Ok(TypeMarker::from(TransparentMarker).value_with_distance(f64::INFINITY)),
+        }
+    }
+
+    /// Distance to the closest surface of any kind
+    pub(crate) fn distance(&self, p: [f64; 3], t: f64) -> Result<f64, EvalError> {
+        Ok(self.opaque(p, t)?.distance()?.min(self.transparent(p, t)?.distance()?))
+    }
+}
+
+fn parse_structs(source: &str) -> Vec<(String, Vec<String>)> {
+    let mut structs = Vec::new();
+    let mut lines = source.lines();
+
+    while let Some(line) = lines.next() {
+        if let Some(name) = line.strip_prefix("struct ") {
+            let fields = lines
+                .by_ref()
+                .take_while(|line| !line.starts_with('}'))
+                .filter_map(|line| line.trim().trim_end_matches(';').split_whitespace().nth(1))
+                .map(String::from)
+                .collect();
+
+            structs.push((name.trim_end_matches('{').trim().to_owned(), fields));
+        }
+    }
+
+    structs
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::desc::{SceneDesc, test::parse_scene};
+    use crate::shaders::generated::interpret::Library;
+    use crate::shaders::generated::source::{Span, Spanned};
+
+    fn scene(source: &str) -> SceneDesc {
+        parse_scene(source.as_bytes()).unwrap()
+    }
+
+    fn distance(source: &str, p: [f64; 3], t: f64) -> f64 {
+        scene(source).cpu.distance(p, t).unwrap()
+    }
+
+    /// Distance to geometry statements, which are only objects once given a color
+    fn geometry(source: &str, p: [f64; 3], t: f64) -> f64 {
+        distance(&format!("opaque(1, 1, 1) {{ {} }}", source), p, t)
+    }
+
+    fn assert_near(actual: f64, expected: f64) {
+        assert!((actual - expected).abs() < 1e-9, "{} != {}", actual, expected);
+    }
+
+    /// Distances to the shapes of `library.glsl`, which its port has to give as well
+    fn shape_cases() -> Vec<(&'static str, [f64; 3], f64)> {
+        vec![
+            ("sd_sphere(1)", [2.0, 0.0, 0.0], 1.0),
+            ("sd_sphere(2)", [0.0, 0.0, 0.0], -2.0),
+            ("sd_box(vec3(1, 2, 3))", [0.0, 5.0, 0.0], 3.0),
+            ("sd_halfspace(vec3(0, 1, 0))", [3.0, 2.0, 1.0], 2.0),
+            ("sd_halfspace_aa(vec3(0, 0, 1))", [3.0, 2.0, -1.0], 0.0),
+            ("sd_column_aa(vec3(0, 1, 0), 1)", [3.0, 100.0, 0.0], 2.0),
+            ("sd_torus(2, 0.5)", [2.0, 0.0, 0.0], -0.5),
+            ("sd_torus(2, 0.5)", [0.0, 0.0, 0.0], 1.5),
+            ("sd_torus(2, 0.5)", [0.0, 1.0, 2.0], 0.5),
+            ("sd_capped_cylinder(1, 2)", [0.0, 3.0, 0.0], 1.0),
+            ("sd_capped_cylinder(1, 2)", [2.0, 3.0, 0.0], 2f64.sqrt()),
+            ("sd_capped_cylinder(1, 2)", [0.0, 0.0, 0.0], -1.0),
+            ("sd_capsule(vec3(0), vec3(0, 2, 0), 0.5)", [0.0, 3.0, 0.0], 0.5),
+            ("sd_capsule(vec3(0), vec3(0, 2, 0), 0.5)", [1.0, 1.0, 0.0], 0.5),
+            ("sd_capped_cone(1, 0.5, 1)", [0.0, 2.0, 0.0], 1.0),
+            ("sd_capped_cone(1, 0.5, 1)", [0.0, -2.0, 0.0], 1.0),
+            ("sd_capped_cone(1, 0.5, 1)", [2.0, -1.0, 0.0], 1.0),
+            ("sd_cone(1, 2)", [0.0, 3.0, 0.0], 1.0),
+            ("sd_cone(1, 2)", [0.0, -1.0, 0.0], 1.0),
+            ("sd_cone(1, 2)", [0.0, 0.0, 0.0], 0.0),
+            ("sd_ellipsoid(vec3(1, 2, 3))", [2.0, 0.0, 0.0], 1.0),
+            ("sd_ellipsoid(vec3(1, 2, 3))", [0.0, 2.0, 0.0], 0.0),
+            ("sd_rounded_box(vec3(1), 0.2)", [2.0, 0.5, 0.0], 1.0),
+            ("sd_rounded_box(vec3(1), 0.2)", [2.0, 2.0, 2.0], 1.2 * 3f64.sqrt() - 0.2),
+            ("sd_box_frame(vec3(1), 0.2)", [0.0, 0.0, 0.0], 0.8 * 2f64.sqrt()),
+            ("sd_box_frame(vec3(1), 0.2)", [0.0, 1.5, 1.0], 0.5),
+            ("sd_hex_prism(1, 2)", [0.0, 0.0, 3.0], 2.0),
+            ("sd_hex_prism(1, 2)", [3.0, 0.0, 0.0], 3.0 - 2.0 / 3f64.sqrt()),
+            ("sd_hex_prism(1, 2)", [0.0, 3.0, 0.0], 1.0),
+            ("sd_tri_prism(1, 2)", [0.0, 0.0, -3.0], 2.0),
+            ("sd_tri_prism(1, 2)", [0.0, 3.0, 0.0], 1.0),
+            ("sd_octahedron(1)", [2.0, 0.0, 0.0], 1.0),
+            ("sd_octahedron(1)", [1.0, 1.0, 1.0], 2.0 / 3f64.sqrt()),
+            ("sd_link(1, 1, 0.25)", [0.0, 0.0, 0.0], 0.75),
+            ("sd_link(1, 1, 0.25)", [1.0, 0.5, 0.0], -0.25),
+            ("sd_link(1, 1, 0.25)", [0.0, 3.0, 0.0], 0.75),
+            ("sd_pyramid(1, 2)", [0.0, 3.0, 0.0], 1.0),
+            ("sd_pyramid(1, 2)", [0.0, -1.0, 0.0], 1.0),
+            ("sd_pyramid(1, 2)", [2.0, 2.0, 0.0], 4.0 / 5f64.sqrt()),
+            ("sd_bezier(vec3(0), vec3(1, 2, 0), vec3(2, 0, 0), 0.1)", [1.0, 3.0, 0.0], 1.9),
+            ("sd_bezier(vec3(0), vec3(1, 2, 0), vec3(2, 0, 0), 0.1)", [-1.0, 0.0, 0.0], 0.9),
+            ("sd_bezier(vec3(0), vec3(1, 0, 0), vec3(2, 0, 0), 0.5)", [1.0, 1.0, 0.0], 0.5),
+        ]
+    }
+
+    #[test]
+    fn test_primitives() {
+        for (shape, p, expected) in shape_cases() {
+            let d = geometry(&format!("{};", shape), p, 0.0);
+            assert!((d - expected).abs() < 1e-6, "{} at {:?}: {} != {}", shape, p, d, expected);
+        }
+
+        assert_near(geometry("raw(length($p) - 1);", [0.0, 3.0, 0.0], 0.0), 2.0);
+    }
+
+    /// The same distances from the GLSL of the shapes, run by an interpreter
+    #[test]
+    fn test_library_shapes() {
+        let library = Library::parse(include_str!("../../glsl/library.glsl"));
+
+        for (shape, p, expected) in shape_cases() {
+            // the shape with the `Arg` of the point as its last argument
+            let call = format!("{}, Arg(vec3({:?}, {:?}, {:?}), 0.0))", &shape[..shape.len() - 1], p[0], p[1], p[2]);
+            let d = library.eval(&call).float();
+            assert!((d - expected).abs() < 1e-6, "{} at {:?}: {} != {}", shape, p, d, expected);
+        }
+    }
+
+    /// Distances of shapes never change faster than the points, and for the exact ones, moving by the distance
+    /// against the direction it grows in lands on the surface
+    #[test]
+    fn test_shape_distances() {
+        let shapes = [
+            ("sd_torus(2, 0.5)", true),
+            ("sd_capped_cylinder(1, 2)", true),
+            ("sd_capsule(vec3(0), vec3(1, 2, 0), 0.5)", true),
+            ("sd_capped_cone(1, 0.5, 1)", true),
+            ("sd_cone(1, 2)", true),
+            ("sd_ellipsoid(vec3(1, 2, 3))", false),
+            ("sd_rounded_box(vec3(1, 2, 3), 0.2)", true),
+            ("sd_box_frame(vec3(1, 2, 3), 0.2)", true),
+            ("sd_hex_prism(1, 2)", true),
+            ("sd_tri_prism(1, 2)", false),
+            ("sd_octahedron(2)", true),
+            ("sd_link(1, 1, 0.25)", true),
+            ("sd_pyramid(1, 2)", true),
+            ("sd_bezier(vec3(0), vec3(1, 2, 0), vec3(2, 0, 1), 0.1)", true),
+            ("extrude(1) sd2_rounded_rect(vec2(1, 2), 0.3)", true),
+            ("extrude(2) sd2_polygon(vec2(-1, 0), vec2(2, -1), vec2(0, 1), vec2(-1, 2))", true),
+            ("revolve(2) sd2_arc(1, 1, 0.2)", true),
+            ("revolve(3) sd2_segment(vec2(-1, 0), vec2(1, 2), 0.5)", true),
+        ];
+        let step = 1e-6;
+
+        for &(shape, exact) in &shapes {
+            let desc = scene(&format!("opaque(1, 1, 1) {};", shape));
+            let d = |p: [f64; 3]| desc.cpu.distance(p, 0.0).unwrap();
+            let mut landed = 0;
+
+            for p in sample_points() {
+                let d0 = d(p);
+                let diagonal = step / 3f64.sqrt();
+                let moved = d([p[0] + diagonal, p[1] + diagonal, p[2] + diagonal]);
+                assert!((moved - d0).abs() < step * (1.0 + 1e-4), "{} at {:?}", shape, p);
+
+                let gradient = [0, 1, 2].map(|axis| {
+                    let mut q = p;
+                    q[axis] += step;
+                    (d(q) - d0) / step
+                });
+                assert!(gradient.iter().all(|x| x.abs() < 1.0 + 1e-4), "{} at {:?}: {:?}", shape, p, gradient);
+                let slope = gradient.iter().map(|x| x * x).sum::<f64>().sqrt();
+
+                if exact && (slope - 1.0).abs() < 1e-4 {
+                    let surface = [0, 1, 2].map(|axis| p[axis] - gradient[axis] / slope * d0);
+                    assert!(d(surface).abs() < 1e-4, "{} from {:?}: {}", shape, p, d(surface));
+                    landed += 1;
+                }
+            }
+
+            // the distance can only be ambiguous where several parts of the surface are as close
+            assert!(!exact || landed > 190, "{}: {}", shape, landed);
+        }
+    }
+
+    #[test]
+    fn test_2d_shapes() {
+        let cases: &[(&str, [f64; 3], f64)] = &[
+            ("extrude(1) sd2_circle(1)", [2.0, 0.0, 0.0], 1.0),
+            ("extrude(1) sd2_circle(1)", [0.0, 0.0, 3.0], 2.0),
+            ("extrude(1) sd2_circle(1)", [2.0, 0.0, 2.0], 2f64.sqrt()),
+            ("extrude(2) sd2_circle(1)", [0.0, 0.0, 0.0], -1.0),
+            ("extrude(1) sd2_rect(vec2(1, 2))", [0.0, 3.0, 0.0], 1.0),
+            ("extrude(10) sd2_rect(vec2(1, 2))", [2.0, 3.0, 0.0], 2f64.sqrt()),
+            ("extrude(10) sd2_rounded_rect(vec2(1), 0.5)", [2.0, 2.0, 0.0], 1.5 * 2f64.sqrt() - 0.5),
+            ("extrude(10) sd2_rounded_rect(vec2(1), 0.5)", [0.0, 2.0, 0.0], 1.0),
+            ("extrude(10) sd2_arc(radians(90), 2, 0.1)", [0.0, 3.0, 0.0], 0.9),
+            ("extrude(10) sd2_arc(radians(90), 2, 0.1)", [0.0, -1.0, 0.0], 5f64.sqrt() - 0.1),
+            ("extrude(10) sd2_segment(vec2(0), vec2(2, 0), 0.5)", [1.0, 2.0, 0.0], 1.5),
+            ("extrude(10) sd2_segment(vec2(0), vec2(2, 0), 0.5)", [3.0, 0.0, 0.0], 0.5),
+            ("extrude(10) sd2_polygon(vec2(0, 0), vec2(2, 0), vec2(0, 2))", [0.5, 0.25, 0.0], -0.25),
+            ("extrude(10) sd2_polygon(vec2(0, 0), vec2(2, 0), vec2(0, 2))", [-1.0, 0.0, 0.0], 1.0),
+            ("extrude(10) sd2_polygon(vec2(0, 0), vec2(2, 0), vec2(0, 2))", [2.0, 2.0, 0.0], 2f64.sqrt()),
+            ("extrude(10) difference { sd2_rect(vec2(2)); sd2_circle(1); }", [0.0, 0.0, 0.0], 1.0),
+            ("extrude(10) at(1, 0, 0) sd2_circle(1)", [3.0, 0.0, 0.0], 1.0),
+            ("revolve(3) sd2_circle(1)", [3.0, 0.0, 0.0], -1.0),
+            ("revolve(3) sd2_circle(1)", [0.0, 0.0, 0.0], 2.0),
+            ("revolve(3) sd2_circle(1)", [0.0, 2.0, -3.0], 1.0),
+        ];
+        for (shape, p, expected) in cases {
+            let d = geometry(&format!("{};", shape), *p, 0.0);
+            assert!((d - expected).abs() < 1e-9, "{} at {:?}: {} != {}", shape, p, d, expected);
+        }
+
+        let defined = "define_geometry_2d(ring, float r) onionize(0.1) sd2_circle(r);\nopaque(1, 1, 1) extrude(1) ring(2);";
+        assert_near(distance(defined, [1.95, 0.0, 0.0], 0.0), -0.05);
+        assert_near(distance(defined, [0.0, 0.0, 0.0], 0.0), 1.9);
+    }
+
+    #[test]
+    fn test_transforms() {
+        assert_near(geometry("at(1, 0, 0) sd_sphere(1);", [3.0, 0.0, 0.0], 0.0), 1.0);
+        assert_near(geometry("vat(vec3(0, 2, 0)) sd_sphere(1);", [0.0, -1.0, 0.0], 0.0), 2.0);
+        assert_near(geometry("rotate(vec3(0, 0, 1), radians(90)) at(2, 0, 0) sd_sphere(1);", [0.0, 3.0, 0.0], 0.0), 0.0);
+        assert_near(geometry("rotate(vec3(0, 0, 1), radians(90)) at(2, 0, 0) sd_sphere(1);", [3.0, 0.0, 0.0], 0.0), 13f64.sqrt() - 1.0);
+        assert_near(geometry("repeat(4, 0, 0) sd_sphere(1);", [9.0, 0.0, 0.0], 0.0), 0.0);
+        assert_near(geometry("repeat(4, 0, 0) sd_sphere(1);", [-8.0, 2.0, 0.0], 0.0), 1.0);
+        assert_near(geometry("advanced_repeat(4, 0, 0) at(1.5, 0, 0) sd_sphere(1);", [-2.0, 0.0, 0.0], 0.0), -0.5);
+        assert_near(geometry("scale(2) sd_sphere(1);", [3.0, 0.0, 0.0], 0.0), 1.0);
+        assert_near(geometry("onionize(0.1) sd_sphere(1);", [0.0, 0.0, 0.0], 0.0), 0.9);
+        assert_near(geometry("onionize(0.1) sd_sphere(1);", [0.95, 0.0, 0.0], 0.0), -0.05);
+        assert_near(geometry("cond($p.x > 0) sd_sphere(1);", [2.0, 0.0, 0.0], 0.0), 1.0);
+        assert_eq!(geometry("cond($p.x > 0) sd_sphere(1);", [-2.0, 0.0, 0.0], 0.0), f64::INFINITY);
+        assert_near(geometry("cond_else($p.x > 0) { sd_sphere(1); sd_box(vec3(2)); }", [3.0, 0.0, 0.0], 0.0), 2.0);
+        assert_near(geometry("cond_else($p.x > 0) { sd_sphere(1); sd_box(vec3(2)); }", [-3.0, 0.0, 0.0], 0.0), 1.0);
+
+        let switch = "switch(2, 4) { sd_sphere(1); sd_sphere(2); sd_sphere(3); }";
+        assert_near(geometry(switch, [5.0, 0.0, 0.0], 1.0), 4.0);
+        assert_near(geometry(switch, [5.0, 0.0, 0.0], 2.0), 3.0);
+        assert_near(geometry(switch, [5.0, 0.0, 0.0], 10.0), 2.0);
+        assert_near(geometry(&format!("at_t(3) {}", switch), [5.0, 0.0, 0.0], 4.0), 4.0);
+    }
+
+    #[test]
+    fn test_domain_transforms() {
+        let mirrored = "mirror(vec3(1, 0, 0)) at(2, 0, 0) sd_sphere(1);";
+        assert_near(geometry(mirrored, [-3.0, 0.0, 0.0], 0.0), 0.0);
+        assert_near(geometry(mirrored, [0.0, 0.0, 0.0], 0.0), 1.0);
+
+        let polar = "repeat_polar(4) at(2, 0, 0) sd_sphere(0.5);";
+        assert_near(geometry(polar, [0.0, 0.0, 2.5], 0.0), 0.0);
+        assert_near(geometry(polar, [-2.5, 0.0, 0.0], 0.0), 0.0);
+        assert_near(geometry(polar, [0.0, 0.0, 0.0], 0.0), 1.5);
+
+        let limited = "repeat_limited(vec3(3, 1, 1), vec3(1, 0, 0)) sd_sphere(1);";
+        assert_near(geometry(limited, [-4.0, 0.0, 0.0], 0.0), 0.0);
+        assert_near(geometry(limited, [6.0, 0.0, 0.0], 0.0), 2.0);
+        assert_near(geometry(limited, [0.0, 3.0, 0.0], 0.0), 2.0);
+
+        assert_near(geometry("elongate(vec3(1, 0, 0)) sd_sphere(1);", [2.0, 0.0, 0.0], 0.0), 0.0);
+        assert_near(geometry("elongate(vec3(1, 0, 0)) sd_sphere(1);", [0.5, 2.0, 0.0], 0.0), 1.0);
+        assert_near(geometry("round(0.5) sd_box(vec3(1));", [2.0, 0.0, 0.0], 0.0), 0.5);
+
+        // the stretch is bounded within the distance of the point, up to 3 units from the axis
+        assert_near(geometry("twist(1) sd_box(vec3(1));", [2.0, 0.0, 0.0], 0.0), 2.0 / (3.0 + 13f64.sqrt()));
+        assert_near(geometry("bend(0.5) sd_sphere(1);", [0.0, 2.0, 0.0], 0.0), 0.4);
+        assert_near(geometry("twist(0) sd_box(vec3(1));", [2.0, 0.0, 0.0], 0.0), 1.0);
+    }
+
+    /// Marching by the distance of the stretching transforms never goes through the surface
+    #[test]
+    fn test_distortions_dont_overshoot() {
+        for source in &["twist(2) sd_box(vec3(0.5, 2, 0.5));", "bend(1) sd_box(vec3(2, 0.2, 0.5));"] {
+            let desc = scene(&format!("opaque(1, 1, 1) {{ {} }}", source));
+
+            for idx in 0..32 {
+                let angle = idx as f64 * 0.3;
+                let mut p = [3.0 * angle.cos(), (idx as f64 - 16.0) / 6.0, 3.0 * angle.sin()];
+                let dir = [-p[0] / 3.0, 0.0, -p[2] / 3.0];
+
+                for _ in 0..100 {
+                    let d = desc.cpu.distance(p, 0.0).unwrap();
+                    assert!(d > -1e-9, "{} at {:?}: {}", source, p, d);
+                    p = [p[0] + dir[0] * d, p[1] + dir[1] * d, p[2] + dir[2] * d];
+                }
+            }
+        }
+    }
+
+    /// Points spread around the origin in a reproducible way
+    fn sample_points() -> impl Iterator<Item = [f64; 3]> {
+        (0..200).map(|i| {
+            let i = f64::from(i);
+            [(i * 1.37).sin() * 7.0, (i * 2.11).cos() * 7.0, (i * 0.73).sin() * 7.0]
+        })
+    }
+
+    #[test]
+    fn test_noise() {
+        let at = |expr: &str, p: [f64; 3]| geometry(&format!("raw({});", expr), p, 0.5);
+
+        assert_eq!(at("hash($p).x", [0.2, 0.3, 0.4]), at("hash($p).x", [0.8, 0.9, 0.1]));
+        assert_ne!(at("hash($p).x", [0.2, 0.3, 0.4]), at("hash($p).x", [1.2, 0.3, 0.4]));
+        assert_near(at("value_noise($p) - (hash($p).x * 2 - 1)", [2.0, -3.0, 5.0]), 0.0);
+        assert_near(at("perlin_noise(vec4($p, 4))", [1.0, 2.0, -3.0]), 0.0);
+
+        for p in sample_points() {
+            let lower = at("perlin_noise(vec3($p.xy, 0))", p);
+            assert_near(at("perlin_noise($p.xy)", p), lower);
+            assert_near(at("fbm($p.xy, 1)", p), lower / 2.0);
+
+            for (expr, min, max) in &[
+                ("hash($p).w", 0.0, 1.0),
+                ("value_noise($p)", -1.0, 1.0),
+                ("value_noise(vec4($p, $t))", -1.0, 1.0),
+                ("perlin_noise($p)", -1.5, 1.5),
+                ("simplex_noise($p.xz)", -1.5, 1.5),
+                ("simplex_noise($p)", -1.5, 1.5),
+                ("simplex_noise(vec4($p, $t))", -1.5, 1.5),
+                ("worley_noise($p.xz)", 0.0, 2f64.sqrt()),
+                ("worley_noise($p)", 0.0, 3f64.sqrt()),
+                ("worley_noise(vec4($p, $t))", 0.0, 2.0),
+                ("fbm($p, 4)", -1.5, 1.5),
+                ("ridged(vec4($p, $t), 4)", 0.0, 1.0),
+            ] {
+                let value = at(expr, p);
+                assert!(*min <= value && value <= *max, "{} at {:?}: {}", expr, p, value);
+            }
+
+            // the noise is continuous, even where it moves to another cell or simplex
+            for expr in &[
+                "value_noise($p)",
+                "perlin_noise(vec4($p, $t))",
+                "simplex_noise($p.xy)",
+                "simplex_noise($p)",
+                "simplex_noise(vec4($p, $t))",
+                "worley_noise($p)",
+                "ridged($p, 3)",
+            ] {
+                let moved = [p[0] + 1e-7, p[1] - 1e-7, p[2] + 1e-7];
+                assert!((at(expr, p) - at(expr, moved)).abs() < 1e-5, "{} at {:?}", expr, p);
+            }
+        }
+    }
+
+    #[test]
+    fn test_displace() {
+        assert_near(geometry("displace(0, perlin_noise($p)) sd_sphere(1);", [3.0, 0.5, 0.0], 0.0), 9.25f64.sqrt() - 1.0);
+        assert_near(geometry("displace(0.5, 1) sd_sphere(1);", [3.0, 0.0, 0.0], 0.0), 2.5);
+        assert_near(geometry("displace(2, $p.x) sd_sphere(1);", [3.0, 0.0, 0.0], 0.0), 8.0 / 3.0);
+
+        // the slope of the noise is only estimated at each point, so marching by the distance can go a little through
+        // the surface, but it stops on it
+        let desc = scene("opaque(1, 1, 1) displace(0.3, fbm($p * 2, 3)) sd_sphere(1.5);");
+        for p in sample_points() {
+            let length = p.iter().map(|x| x * x).sum::<f64>().sqrt();
+            let dir = [-p[0] / length, -p[1] / length, -p[2] / length];
+            let mut p = [dir[0] * -5.0, dir[1] * -5.0, dir[2] * -5.0];
+            for _ in 0..100 {
+                let d = desc.cpu.distance(p, 0.0).unwrap();
+                assert!(d > -0.02, "{:?}: {}", p, d);
+                p = [p[0] + dir[0] * d, p[1] + dir[1] * d, p[2] + dir[2] * d];
+            }
+            assert!(desc.cpu.distance(p, 0.0).unwrap().abs() < 1e-6, "{:?}", p);
+        }
+    }
+
+    #[test]
+    fn test_time_transforms() {
+        let grow = "raw(length($p) - $t);";
+        assert_near(geometry(grow, [5.0, 0.0, 0.0], 3.0), 2.0);
+        assert_near(geometry(&format!("at_t(1) {}", grow), [5.0, 0.0, 0.0], 3.0), 3.0);
+        assert_near(geometry(&format!("start_at_t(4) {}", grow), [5.0, 0.0, 0.0], 3.0), 1.0);
+        assert_near(geometry(&format!("end_at_t(2) {}", grow), [5.0, 0.0, 0.0], 3.0), 3.0);
+        assert_near(geometry(&format!("repeat_t(2) {}", grow), [5.0, 0.0, 0.0], 3.0), 4.0);
+        assert_near(geometry(&format!("map_t(0, 1, 0, 2) {}", grow), [5.0, 0.0, 0.0], 1.5), 2.0);
+        assert_near(geometry("raw(length($p) - time);", [5.0, 0.0, 0.0], 1.0), 4.0);
+    }
+
+    #[test]
+    fn test_folds() {
+        let two = "{ sd_sphere(2); at(1, 0, 0) sd_sphere(2); }";
+        assert_near(geometry(&format!("union {}", two), [4.0, 0.0, 0.0], 0.0), 1.0);
+        assert_near(geometry(&format!("intersection {}", two), [0.0, 0.0, 0.0], 0.0), -1.0);
+        assert_near(geometry(&format!("difference {}", two), [0.5, 0.0, 0.0], 0.0), 1.5);
+        assert_near(geometry("smooth_union(0.5) { sd_sphere(1); sd_sphere(1); }", [2.0, 0.0, 0.0], 0.0), 0.875);
+        assert_near(geometry("intersection {}", [2.0, 0.0, 0.0], 0.0), 0.0);
+        assert_eq!(distance("", [2.0, 0.0, 0.0], 0.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn test_definitions() {
+        assert_near(geometry("let(float, r, 1 + 1) sd_sphere(r);", [5.0, 0.0, 0.0], 0.0), 3.0);
+        assert_near(geometry("let(vec3, q, $p * 2.0) raw(q.x);", [5.0, 0.0, 0.0], 0.0), 10.0);
+        assert_near(
+            geometry("union { let(float, r, 1) sd_sphere(r); let(float, r, 3) at(10, 0, 0) sd_sphere(r); }", [5.0, 0.0, 0.0], 0.0),
+            2.0,
+        );
+        assert_near(
+            geometry("let(float, r, 1) union { at(10, 0, 0) let(float, r, r + 1) sd_sphere(r); sd_sphere(r); }", [6.0, 0.0, 0.0], 0.0),
+            2.0,
+        );
+        assert_near(geometry("let(float, length, 2) sd_sphere(length);", [5.0, 0.0, 0.0], 0.0), 3.0);
+        assert_near(
+            distance("define_geometry(ball, float r) sd_sphere(r);\nopaque(1, 1, 1) at(1, 0, 0) ball(2);", [5.0, 0.0, 0.0], 0.0),
+            2.0,
+        );
+        assert_near(
+            distance(
+                "define_geometry(pair, float gap) { at(gap, 0, 0) blob; at(-gap, 0, 0) blob; }\ndefine_geometry(blob) sd_sphere(1);\nopaque(1, 1, 1) pair(3);",
+                [0.0, 0.0, 0.0],
+                0.0,
+            ),
+            2.0,
+        );
+    }
+
+    #[test]
+    fn test_colors() {
+        let desc = scene("opaque(1, 0, 0) sd_sphere(1);\ntransparent(0, 0, 1, 0.5) at(4, 0, 0) sd_sphere(1);");
+
+        let opaque = desc.cpu.opaque([2.0, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(opaque.to_string(), "MapOpaque(Material(vec3(1, 0, 0), 1, 0, vec3(0, 0, 0)), 1)");
+        let transparent = desc.cpu.transparent([2.0, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(transparent.to_string(), "MapTransparent(vec4(0, 0, 1, 0.5), 1)");
+        assert_near(desc.cpu.distance([3.5, 0.0, 0.0], 0.0).unwrap(), -0.5);
+
+        let desc = scene("material(gold) { albedo(1, 0.8, 0.2); roughness(0.25 * $t); }\nopaque(gold) sd_sphere(1);");
+        let opaque = desc.cpu.opaque([0.0, 0.0, 0.0], 2.0).unwrap();
+        assert_eq!(opaque.to_string(), "MapOpaque(Material(vec3(1, 0.8, 0.2), 0.5, 0, vec3(0, 0, 0)), -1)");
+
+        let desc = scene("cond($t > 1) opaque(1, 1, 1) sd_sphere(1);");
+        assert_eq!(desc.cpu.opaque([0.0; 3], 0.0).unwrap().distance().unwrap(), f64::INFINITY);
+
+        let desc = scene("switch(1) { transparent(1, 0, 0, 0.5) sd_sphere(1); transparent(0, 0, 1, 0.5) sd_sphere(2); }");
+        let transparent = desc.cpu.transparent([3.0, 0.0, 0.0], 2.0).unwrap();
+        assert_eq!(transparent.to_string(), "MapTransparent(vec4(0, 0, 1, 0.5), 1)");
+    }
+
+    #[test]
+    fn test_colored_folds() {
+        let albedo = |source: &str, p: [f64; 3]| {
+            let opaque = scene(source).cpu.opaque(p, 0.0).unwrap();
+            opaque.field("material").unwrap().field("albedo").unwrap().to_string()
+        };
+        let objects = "opaque(1, 0, 0) sd_box(vec3(1)); opaque(0, 0, 1) at(1, 0, 0) sd_sphere(0.5);";
+
+        // the face cut by the sphere, and the rest of the box
+        let source = format!("difference {{ {} }}", objects);
+        assert_eq!(albedo(&source, [0.45, 0.0, 0.0]), "vec3(0, 0, 1)");
+        assert_eq!(albedo(&source, [-0.95, 0.0, 0.0]), "vec3(1, 0, 0)");
+        let source = format!("difference(false) {{ {} }}", objects);
+        assert_eq!(albedo(&source, [0.45, 0.0, 0.0]), "vec3(1, 0, 0)");
+        assert_near(scene(&source).cpu.opaque([0.45, 0.0, 0.0], 0.0).unwrap().distance().unwrap(), -0.05);
+
+        let source = format!("intersection {{ {} }}", objects);
+        assert_eq!(albedo(&source, [0.55, 0.0, 0.0]), "vec3(0, 0, 1)");
+        assert_eq!(albedo(&source, [0.95, 0.0, 0.0]), "vec3(1, 0, 0)");
+        let source = format!("intersection(false) {{ {} }}", objects);
+        assert_eq!(albedo(&source, [0.55, 0.0, 0.0]), "vec3(1, 0, 0)");
+
+        // halfway between the spheres both colors count the same
+        let source = "smooth_union(1) { opaque(1, 0, 0) sd_sphere(1); opaque(0, 0, 1) at(3, 0, 0) sd_sphere(1); }";
+        assert_eq!(albedo(source, [1.5, 0.0, 0.0]), "vec3(0.5, 0, 0.5)");
+        assert_eq!(albedo(source, [-1.0, 0.0, 0.0]), "vec3(1, 0, 0)");
+
+        let desc = scene("difference(false) { transparent(1, 0, 0, 0.5) sd_sphere(1); transparent(0, 0, 1, 1) sd_sphere(0.5); }");
+        let transparent = desc.cpu.transparent([0.75, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(transparent.to_string(), "MapTransparent(vec4(1, 0, 0, 0.5), -0.25)");
+        let desc = scene("smooth_union(1) { transparent(1, 0, 0, 0) sd_sphere(1); transparent(0, 0, 1, 1) at(3, 0, 0) sd_sphere(1); }");
+        let transparent = desc.cpu.transparent([1.5, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(transparent.field("color").unwrap().to_string(), "vec4(0.5, 0, 0.5, 0.5)");
+    }
+
+    #[test]
+    fn test_operators() {
+        let call = |name: &str, args: &[f64]| {
+            let args = args.iter().map(|x| Value::Float(*x)).collect::<Vec<_>>();
+            library::call(name, &args).unwrap().unwrap().as_float().unwrap()
+        };
+
+        assert_near(call("sd_smooth_isect", &[0.0, 0.0, 1.0]), 0.25);
+        assert_near(call("sd_smooth_diff", &[0.0, 0.0, 1.0]), 0.25);
+        assert_near(call("sd_chamfer_union", &[1.0, 1.0, 1.0]), 0.5f64.sqrt());
+        assert_near(call("sd_round_union", &[0.5, 0.5, 1.0]), 1.0 - 0.5f64.sqrt());
+        assert_near(call("sd_round_isect", &[-0.5, -0.5, 1.0]), 0.5f64.sqrt() - 1.0);
+        assert_near(call("sd_round_diff", &[-0.5, 0.5, 1.0]), 0.5f64.sqrt() - 1.0);
+        assert_near(call("sd_stairs_union", &[2.0, 2.0, 1.0, 2.0]), 1.5);
+        assert_near(call("sd_columns_union", &[2.0, 0.5, 1.0, 3.0]), 0.5);
+        assert_near(call("sd_pipe", &[0.3, 0.4, 0.1]), 0.4);
+        assert_near(call("sd_groove", &[0.0, 0.0, 0.5, 0.2]), 0.2);
+        assert_near(call("sd_groove", &[0.0, 1.0, 0.5, 0.2]), 0.0);
+
+        // the rounded edge of the cut takes the color of the sphere
+        let desc = scene(
+            "round_difference(0.1) { opaque(1, 0, 0) sd_box(vec3(1)); opaque(0, 0, 1) at(1, 0, 0) sd_sphere(0.5); }",
+        );
+        let opaque = desc.cpu.opaque([0.45, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(opaque.field("material").unwrap().field("albedo").unwrap().to_string(), "vec3(0, 0, 1)");
+        assert_near(opaque.distance().unwrap(), -0.05);
+
+        let desc = scene("groove(0.1, 0.2) { transparent(1, 0, 0, 1) sd_box(vec3(1)); transparent(0, 0, 1, 1) sd_halfspace(vec3(0, 1, 0)); }");
+        let transparent = desc.cpu.transparent([1.0, 0.0, 0.0], 0.0).unwrap();
+        assert_eq!(transparent.to_string(), "MapTransparent(vec4(0, 0, 1, 1), 0.1)");
+        assert_eq!(desc.cpu.transparent([1.0, 0.5, 0.0], 0.0).unwrap().field("color").unwrap().to_string(), "vec4(1, 0, 0, 1)");
+
+        // empty intersections are everywhere, like `intersection`
+        let desc = scene("opaque(1, 1, 1) { round_intersection(1) {} }");
+        assert_eq!(desc.cpu.opaque([5.0, 0.0, 0.0], 0.0).unwrap().distance().unwrap(), 0.0);
+    }
+
+    #[test]
+    fn test_errors() {
+        let functions = Functions::default();
+        let mut env = Env::new(&functions, [0.0; 3], 0.0);
+
+        let call = Expr::Call(String::from("dFdx"), vec![Spanned::new(Expr::number(1), Span::point(0))]);
+        assert_eq!(env.expr(&call), Err(EvalError::UnknownFunction(String::from("dFdx"))));
+        let call = Expr::Call(String::from("sd_sphere"), vec![Spanned::new(Expr::number(1), Span::point(0))]);
+        assert_eq!(env.expr(&call), Err(EvalError::Arguments(String::from("sd_sphere"))));
+        let field = Expr::Arg(String::from("q"));
+        assert_eq!(env.expr(&field), Err(EvalError::UnknownField(String::from("Arg"), String::from("q"))));
+    }
+
+    #[test]
+    fn test_library_coverage() {
+        let mut functions = parse_structs(include_str!("../../glsl/library.glsl"))
+            .into_iter()
+            .map(|(name, _)| name)
+            .collect::<Vec<_>>();
+        functions.extend(library::NAMES.iter().chain(noise::NAMES).chain(shapes::NAMES).map(|name| (*name).to_owned()));
+
+        for line in include_str!("../../glsl/library.glsl").lines() {
+            let mut words = line.split(|c: char| !c.is_alphanumeric() && c != '_');
+            if let (Some(typ), Some(name)) = (words.next(), words.next()) {
+                if line.ends_with('{') && line.contains('(') && !line.starts_with(' ') && typ != "struct" {
+                    assert!(functions.iter().any(|f| f == name), "`{}` has no CPU implementation", name);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let statements = crate::shaders::generated::parser::test::scene(&std::fs::read(&path).unwrap()).unwrap();
+            let desc = SceneDesc::from_statements(statements).unwrap();
+
+            for &t in &[0.0, 1.0, 20.0] {
+                let d = desc.cpu.distance([0.5, 1.0, -2.0], t);
+                assert!(d.is_ok(), "{}: {:?}", path.display(), d);
+            }
+        }
+    }
+}
@@ -0,0 +1,220 @@
+//! GLSL operators and built-in functions
+
+use super::{EvalError, Value};
+use crate::shaders::generated::expr::BinaryOp;
+
+/// Applies a function to each component, `float` arguments being used for every component
+pub fn componentwise(name: &str, args: &[Value], f: impl Fn(&[f64]) -> f64) -> Result<Value, EvalError> {
+    let mut len = None;
+    for arg in args {
+        match (arg, len) {
+            (Value::Float(_), _) => {}
+            (Value::Vec(n, _), None) => len = Some(*n),
+            (Value::Vec(n, _), Some(len)) if *n == len => {}
+            _ => return Err(EvalError::Arguments(name.to_owned())),
+        }
+    }
+
+    let component = |idx: usize| {
+        let mut xs = [0.0; 4];
+        for (x, arg) in xs.iter_mut().zip(args) {
+            *x = match arg {
+                Value::Vec(_, c) => c[idx],
+                Value::Float(x) => *x,
+                _ => unreachable!(),
+            };
+        }
+        f(&xs[..args.len()])
+    };
+
+    Ok(match len {
+        None => Value::Float(component(0)),
+        Some(n) => {
+            let mut out = [0.0; 4];
+            for (idx, x) in out.iter_mut().enumerate().take(n) {
+                *x = component(idx);
+            }
+            Value::Vec(n, out)
+        }
+    })
+}
+
+/// GLSL `mod`, which follows the sign of `y`
+pub fn glsl_mod(x: f64, y: f64) -> f64 {
+    // a zero period repeats nothing, which is also what GPUs end up doing
+    if y == 0.0 {
+        x
+    } else {
+        x - y * (x / y).floor()
+    }
+}
+
+pub fn binary(op: BinaryOp, lhs: &Value, rhs: &Value) -> Result<Value, EvalError> {
+    let args = [lhs.clone(), rhs.clone()];
+    let compare = |f: fn(f64, f64) -> bool| Ok(Value::Bool(f(lhs.as_float()?, rhs.as_float()?)));
+
+    match op {
+        BinaryOp::Add => componentwise("+", &args, |x| x[0] + x[1]),
+        BinaryOp::Sub => componentwise("-", &args, |x| x[0] - x[1]),
+        BinaryOp::Mul => componentwise("*", &args, |x| x[0] * x[1]),
+        BinaryOp::Div => componentwise("/", &args, |x| x[0] / x[1]),
+        BinaryOp::Lt => compare(|a, b| a < b),
+        BinaryOp::Gt => compare(|a, b| a > b),
+        BinaryOp::Le => compare(|a, b| a <= b),
+        BinaryOp::Ge => compare(|a, b| a >= b),
+        BinaryOp::Eq => Ok(Value::Bool(lhs == rhs)),
+        BinaryOp::Ne => Ok(Value::Bool(lhs != rhs)),
+        BinaryOp::And => Ok(Value::Bool(lhs.as_bool()? && rhs.as_bool()?)),
+        BinaryOp::Or => Ok(Value::Bool(lhs.as_bool()? || rhs.as_bool()?)),
+        BinaryOp::Xor => Ok(Value::Bool(lhs.as_bool()? != rhs.as_bool()?)),
+        _ => Err(EvalError::Unsupported(op.symbol().to_owned())),
+    }
+}
+
+fn vector(name: &str, n: usize, args: &[Value]) -> Result<Value, EvalError> {
+    let mut out = [0.0; 4];
+
+    match args {
+        [Value::Float(x)] => out[..n].iter_mut().for_each(|c| *c = *x),
+        _ => {
+            let mut len = 0;
+            for arg in args {
+                let components = match arg {
+                    Value::Float(x) => vec![*x],
+                    Value::Vec(m, c) => c[..*m].to_vec(),
+                    _ => return Err(EvalError::Arguments(name.to_owned())),
+                };
+                for x in components {
+                    if len == n {
+                        return Err(EvalError::Arguments(name.to_owned()));
+                    }
+                    out[len] = x;
+                    len += 1;
+                }
+            }
+
+            if len != n {
+                return Err(EvalError::Arguments(name.to_owned()));
+            }
+        }
+    }
+
+    Ok(Value::Vec(n, out))
+}
+
+fn components(value: &Value) -> &[f64] {
+    match value {
+        Value::Vec(n, c) => &c[..*n],
+        Value::Float(x) => std::slice::from_ref(x),
+        _ => &[],
+    }
+}
+
+fn dot(a: &Value, b: &Value) -> f64 {
+    components(a).iter().zip(components(b)).map(|(a, b)| a * b).sum()
+}
+
+fn smoothstep(edge0: f64, edge1: f64, x: f64) -> f64 {
+    let t = ((x - edge0) / (edge1 - edge0)).clamp(0.0, 1.0);
+    t * t * (3.0 - 2.0 * t)
+}
+
+/// Built-in function, `None` if there's none with that name
+pub fn call(name: &str, args: &[Value]) -> Option<Result<Value, EvalError>> {
+    let unary = |f: fn(f64) -> f64| match args {
+        [_] => componentwise(name, args, |x| f(x[0])),
+        _ => Err(EvalError::Arguments(name.to_owned())),
+    };
+    let binary = |f: fn(f64, f64) -> f64| match args {
+        [_, _] => componentwise(name, args, |x| f(x[0], x[1])),
+        _ => Err(EvalError::Arguments(name.to_owned())),
+    };
+    let ternary = |f: fn(f64, f64, f64) -> f64| match args {
+        [_, _, _] => componentwise(name, args, |x| f(x[0], x[1], x[2])),
+        _ => Err(EvalError::Arguments(name.to_owned())),
+    };
+
+    let result = match name {
+        "vec2" => vector(name, 2, args),
+        "vec3" => vector(name, 3, args),
+        "vec4" => vector(name, 4, args),
+        "float" => match args {
+            [Value::Float(x)] => Ok(Value::Float(*x)),
+            [Value::Bool(x)] => Ok(Value::Float(if *x { 1.0 } else { 0.0 })),
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+
+        "radians" => unary(f64::to_radians),
+        "degrees" => unary(f64::to_degrees),
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "asin" => unary(f64::asin),
+        "acos" => unary(f64::acos),
+        "atan" if args.len() == 2 => binary(f64::atan2),
+        "atan" => unary(f64::atan),
+        "sinh" => unary(f64::sinh),
+        "cosh" => unary(f64::cosh),
+        "tanh" => unary(f64::tanh),
+        "pow" => binary(f64::powf),
+        "exp" => unary(f64::exp),
+        "log" => unary(f64::ln),
+        "exp2" => unary(f64::exp2),
+        "log2" => unary(f64::log2),
+        "sqrt" => unary(f64::sqrt),
+        "inversesqrt" => unary(|x| 1.0 / x.sqrt()),
+        "abs" => unary(f64::abs),
+        "sign" => unary(|x| if x == 0.0 { 0.0 } else { x.signum() }),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "round" => unary(f64::round),
+        "trunc" => unary(f64::trunc),
+        "fract" => unary(|x| x - x.floor()),
+        "mod" => binary(glsl_mod),
+        "min" => binary(f64::min),
+        "max" => binary(f64::max),
+        "clamp" => ternary(|x, lo, hi| x.max(lo).min(hi)),
+        "mix" => ternary(|a, b, t| a * (1.0 - t) + b * t),
+        "step" => binary(|edge, x| if x < edge { 0.0 } else { 1.0 }),
+        "smoothstep" => ternary(smoothstep),
+
+        "length" => match args {
+            [a] => Ok(Value::Float(dot(a, a).sqrt())),
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+        "distance" => match args {
+            [a, b] => self::binary(BinaryOp::Sub, a, b).map(|d| Value::Float(dot(&d, &d).sqrt())),
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+        "dot" => match args {
+            [a, b] if a.type_name() == b.type_name() => Ok(Value::Float(dot(a, b))),
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+        "cross" => match args {
+            [Value::Vec(3, a), Value::Vec(3, b)] => Ok(Value::vec3([
+                a[1] * b[2] - a[2] * b[1],
+                a[2] * b[0] - a[0] * b[2],
+                a[0] * b[1] - a[1] * b[0],
+            ])),
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+        "reflect" => match args {
+            [i, n] => {
+                let k = 2.0 * dot(n, i);
+                componentwise(name, args, |x| x[0] - k * x[1])
+            }
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+        "normalize" => match args {
+            [a] => {
+                let length = dot(a, a).sqrt();
+                componentwise(name, args, |x| x[0] / length)
+            }
+            _ => Err(EvalError::Arguments(name.to_owned())),
+        },
+
+        _ => return None,
+    };
+
+    Some(result)
+}
@@ -0,0 +1,454 @@
+//! Functions of `library.glsl`
+
+use super::builtins::{componentwise, glsl_mod};
+use super::{EvalError, Value};
+
+/// Names of the `library.glsl` functions implemented here
+pub const NAMES: &[&str] = &[
+    "diffuse_material",
+    "mix_material",
+    "map_opaque",
+    "vmax",
+    "vmin",
+    "vat",
+    "at",
+    "vscale",
+    "scale",
+    "uscale",
+    "vrepeat",
+    "repeat",
+    "rotate",
+    "mirror",
+    "repeat_polar",
+    "repeat_limited",
+    "elongate",
+    "revolve",
+    "shear_lipschitz",
+    "twist",
+    "twist_lipschitz",
+    "bend",
+    "bend_lipschitz",
+    "at_t",
+    "start_at_t",
+    "end_at_t",
+    "repeat_t",
+    "map_t",
+    "sd_union",
+    "sd_isect",
+    "sd_diff",
+    "sd_onionize",
+    "sd_round",
+    "sd_displace",
+    "sd_smooth_union_factor",
+    "sd_smooth_union",
+    "csd_union",
+    "csd_isect",
+    "csd_diff",
+    "csd_smooth_union",
+    "tsd_union",
+    "tsd_isect",
+    "tsd_diff",
+    "tsd_smooth_union",
+    "sd_smooth_isect",
+    "sd_smooth_diff",
+    "sd_chamfer_union",
+    "sd_round_union",
+    "sd_round_isect",
+    "sd_round_diff",
+    "sd_stairs_union",
+    "sd_columns_union",
+    "sd_pipe",
+    "sd_groove",
+    "csd_smooth_isect",
+    "csd_smooth_diff",
+    "csd_chamfer_union",
+    "csd_round_union",
+    "csd_round_isect",
+    "csd_round_diff",
+    "csd_stairs_union",
+    "csd_columns_union",
+    "csd_pipe",
+    "csd_groove",
+    "tsd_smooth_isect",
+    "tsd_smooth_diff",
+    "tsd_chamfer_union",
+    "tsd_round_union",
+    "tsd_round_isect",
+    "tsd_round_diff",
+    "tsd_stairs_union",
+    "tsd_columns_union",
+    "tsd_pipe",
+    "tsd_groove",
+    "polygon_edge",
+    "polygon_distance",
+];
+
+pub fn diffuse_material(albedo: [f64; 3]) -> Value {
+    Value::new_struct(
+        "Material",
+        vec![Value::vec3(albedo), Value::Float(1.0), Value::Float(0.0), Value::vec3([0.0; 3])],
+    )
+    .unwrap()
+}
+
+fn vmax([x, y, z]: [f64; 3]) -> f64 {
+    x.max(y.max(z))
+}
+
+fn vmin([x, y, z]: [f64; 3]) -> f64 {
+    x.min(y.min(z))
+}
+
+fn map3(a: [f64; 3], f: impl Fn(usize, f64) -> f64) -> [f64; 3] {
+    [f(0, a[0]), f(1, a[1]), f(2, a[2])]
+}
+
+fn dot(a: [f64; 3], b: [f64; 3]) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+/// Copy of an `Arg` with its point changed
+fn with_p(arg: &Value, f: impl FnOnce([f64; 3]) -> [f64; 3]) -> Result<Value, EvalError> {
+    let mut arg = arg.clone();
+    let p = arg.field("p")?.as_vec3()?;
+    arg.set_field("p", Value::vec3(f(p)))?;
+    Ok(arg)
+}
+
+/// Copy of an `Arg` with its time changed
+fn with_t(arg: &Value, f: impl FnOnce(f64) -> f64) -> Result<Value, EvalError> {
+    let mut arg = arg.clone();
+    let t = arg.field("t")?.as_float()?;
+    arg.set_field("t", Value::Float(f(t)))?;
+    Ok(arg)
+}
+
+fn vat(shift: [f64; 3], arg: &Value) -> Result<Value, EvalError> {
+    with_p(arg, |p| map3(p, |i, x| x - shift[i]))
+}
+
+fn vscale(sc: [f64; 3], arg: &Value) -> Result<Value, EvalError> {
+    with_p(arg, |p| map3(p, |i, x| x / sc[i]))
+}
+
+fn vrepeat(size: [f64; 3], arg: &Value) -> Result<Value, EvalError> {
+    with_p(arg, |p| map3(p, |i, x| glsl_mod(x + size[i] / 2.0, size[i]) - size[i] / 2.0))
+}
+
+fn rotate(axis: [f64; 3], angle: f64, arg: &Value) -> Result<Value, EvalError> {
+    let length = dot(axis, axis).sqrt();
+    let [x, y, z] = map3(axis, |_, c| c / length);
+    let (s, c) = angle.sin_cos();
+    let oc = 1.0 - c;
+
+    // columns of the matrix built by `rotate` in `library.glsl`
+    let columns = [
+        [oc * x * x + c, oc * x * y - z * s, oc * z * x + y * s],
+        [oc * x * y + z * s, oc * y * y + c, oc * y * z - x * s],
+        [oc * z * x - y * s, oc * y * z + x * s, oc * z * z + c],
+    ];
+
+    with_p(arg, |p| map3(p, |i, _| (0..3).map(|col| columns[col][i] * p[col]).sum()))
+}
+
+fn mirror(axis: [f64; 3], arg: &Value) -> Result<Value, EvalError> {
+    let length = dot(axis, axis).sqrt();
+    let axis = map3(axis, |_, c| c / length);
+    with_p(arg, |p| {
+        let k = 2.0 * dot(p, axis).min(0.0);
+        map3(p, |i, x| x - k * axis[i])
+    })
+}
+
+fn repeat_polar(n: f64, arg: &Value) -> Result<Value, EvalError> {
+    let angle = std::f64::consts::TAU / n;
+    with_p(arg, |[x, y, z]| {
+        let a = glsl_mod(z.atan2(x) + angle / 2.0, angle) - angle / 2.0;
+        let r = x.hypot(z);
+        [r * a.cos(), y, r * a.sin()]
+    })
+}
+
+/// Point turned by `angle` in the plane of the components `i` and `j`
+fn turned(p: [f64; 3], i: usize, j: usize, angle: f64) -> [f64; 3] {
+    let (s, c) = angle.sin_cos();
+    let mut out = p;
+    out[i] = c * p[i] - s * p[j];
+    out[j] = s * p[i] + c * p[j];
+    out
+}
+
+/// Largest stretch of a shear
+fn shear_lipschitz(shear: f64) -> f64 {
+    (shear + (shear * shear + 4.0).sqrt()) / 2.0
+}
+
+fn sd_smooth_union_factor(a: f64, b: f64, k: f64) -> f64 {
+    (0.5 + 0.5 * (b - a) / k).clamp(0.0, 1.0)
+}
+
+fn sd_smooth_union(a: f64, b: f64, k: f64) -> f64 {
+    let h = sd_smooth_union_factor(a, b, k);
+    b * (1.0 - h) + a * h - k * h * (1.0 - h)
+}
+
+/// GLSL `mix` of two values of the same type, applied field by field to structs
+fn mix(a: &Value, b: &Value, h: f64) -> Result<Value, EvalError> {
+    match (a, b) {
+        (Value::Struct(name, xs), Value::Struct(other, ys)) if name == other => {
+            let fields = xs.iter().zip(ys).map(|(x, y)| mix(x, y, h)).collect::<Result<_, _>>()?;
+            Ok(Value::Struct(name, fields))
+        }
+        _ if a.type_name() == b.type_name() => componentwise("mix", &[a.clone(), b.clone()], |x| x[0] * (1.0 - h) + x[1] * h),
+        _ => Err(EvalError::Arguments(String::from("mix"))),
+    }
+}
+
+/// hg_sdf operators, with `sd_`, `csd_` and `tsd_` functions for each
+const OPERATORS: &[&str] = &[
+    "smooth_isect",
+    "smooth_diff",
+    "chamfer_union",
+    "round_union",
+    "round_isect",
+    "round_diff",
+    "stairs_union",
+    "columns_union",
+    "pipe",
+    "groove",
+];
+
+/// Operator a function is a version of, given the prefixes of the versions
+fn operator_name<'a>(name: &'a str, prefixes: &[&str]) -> Option<&'a str> {
+    prefixes
+        .iter()
+        .find_map(|prefix| name.strip_prefix(prefix))
+        .filter(|op| OPERATORS.contains(op))
+}
+
+/// Distance of an hg_sdf operator, `None` if the arguments don't fit
+fn operator(name: &str, a: f64, b: f64, params: &[f64]) -> Option<f64> {
+    let sqrt_half = 0.5f64.sqrt();
+
+    Some(match (name, params) {
+        ("smooth_isect", [k]) => -sd_smooth_union(-a, -b, *k),
+        ("smooth_diff", [k]) => -sd_smooth_union(-a, b, *k),
+        ("chamfer_union", [r]) => a.min(b).min((a - r + b) * sqrt_half),
+        ("round_union", [r]) => r.max(a.min(b)) - (r - a).max(0.0).hypot((r - b).max(0.0)),
+        ("round_isect", [r]) => (-r).min(a.max(b)) + (r + a).max(0.0).hypot((r + b).max(0.0)),
+        ("round_diff", [r]) => (-r).min(a.max(-b)) + (r + a).max(0.0).hypot((r - b).max(0.0)),
+        ("stairs_union", [r, n]) => {
+            let s = r / n;
+            let u = b - r;
+            a.min(b).min(0.5 * (u + a + (glsl_mod(u - a + s, 2.0 * s) - s).abs()))
+        }
+        ("columns_union", [r, n]) => {
+            if a >= *r || b >= *r {
+                return Some(a.min(b));
+            }
+
+            let column = r * 2f64.sqrt() / ((n - 1.0) * 2.0 + 2f64.sqrt());
+            let x = (a + b) * sqrt_half + column * 2f64.sqrt() - r * sqrt_half;
+            let mut y = (b - a) * sqrt_half;
+            if glsl_mod(*n, 2.0) == 1.0 {
+                y += column;
+            }
+            let y = glsl_mod(y + column, 2.0 * column) - column;
+
+            (x.hypot(y) - column).min(x).min(a.min(b))
+        }
+        ("pipe", [r]) => a.hypot(b) - r,
+        ("groove", [r, d]) => a.max((a + d).min(r - b.abs())),
+        _ => return None,
+    })
+}
+
+/// Colored version of an hg_sdf operator, named like in `library.glsl`, each part of the surface taking the color
+/// of the object that makes it
+fn colored_operator(name: &str, a: &Value, b: &Value, params: &[f64]) -> Result<Value, EvalError> {
+    let op = &name[4..];
+    let (da, db) = (a.distance()?, b.distance()?);
+    let d = operator(op, da, db, params).ok_or_else(|| EvalError::Arguments(name.to_owned()))?;
+
+    let value = match (op, params) {
+        ("smooth_isect", [k]) => mix(b, a, sd_smooth_union_factor(-da, -db, *k))?,
+        ("smooth_diff", [k]) => mix(b, a, sd_smooth_union_factor(-da, db, *k))?,
+        ("round_isect", _) => select(a, b, false)?,
+        ("round_diff", _) => if da > -db { a.clone() } else { b.clone() },
+        ("groove", [r, depth]) => if (da + depth).min(r - db.abs()) > da { b.clone() } else { a.clone() },
+        _ => select(a, b, true)?,
+    };
+    value.map_distance(|_| d)
+}
+
+/// Object of `a` or `b` with the smallest or largest distance, ties going to `b` like in `library.glsl`
+fn select(a: &Value, b: &Value, smallest: bool) -> Result<Value, EvalError> {
+    let (da, db) = (a.distance()?, b.distance()?);
+    let first = if smallest { da < db } else { da > db };
+    Ok(if first { a.clone() } else { b.clone() })
+}
+
+/// Intersection of `a` with `b`, or difference when `diff` is set, the faces cut by `b` taking its color if
+/// `cutter_color` is set
+fn cut(a: &Value, b: &Value, cutter_color: bool, diff: bool) -> Result<Value, EvalError> {
+    let (da, db) = (a.distance()?, b.distance()?);
+    let (db, cutter) = if diff { (-db, -db > da) } else { (db, da <= db) };
+
+    let value = if cutter_color && cutter { b.clone() } else { a.clone() };
+    value.map_distance(|_| da.max(db))
+}
+
+/// Smooth union of two objects, their colors blended like their distances
+fn smooth_union(a: &Value, b: &Value, k: f64) -> Result<Value, EvalError> {
+    let (da, db) = (a.distance()?, b.distance()?);
+    mix(b, a, sd_smooth_union_factor(da, db, k))?.map_distance(|_| sd_smooth_union(da, db, k))
+}
+
+/// Squared distance to the closest edge and sign, negative inside, after the edge from `a` to `b`
+fn polygon_edge(a: [f64; 2], b: [f64; 2], p: [f64; 2], d: f64, s: f64) -> (f64, f64) {
+    let e = [b[0] - a[0], b[1] - a[1]];
+    let w = [p[0] - a[0], p[1] - a[1]];
+    let h = ((w[0] * e[0] + w[1] * e[1]) / (e[0] * e[0] + e[1] * e[1])).clamp(0.0, 1.0);
+    let closest = [w[0] - e[0] * h, w[1] - e[1] * h];
+
+    // the edge crosses the line from the point along x
+    let crossing = [p[1] >= a[1], p[1] < b[1], e[0] * w[1] > e[1] * w[0]];
+    let s = if crossing.iter().all(|&c| c) || crossing.iter().all(|&c| !c) { -s } else { s };
+
+    (d.min(closest[0] * closest[0] + closest[1] * closest[1]), s)
+}
+
+/// `library.glsl` function, `None` if there's none with that name
+pub fn call(name: &str, args: &[Value]) -> Option<Result<Value, EvalError>> {
+    if !NAMES.contains(&name) {
+        return None;
+    }
+
+    let wrong = || Err(EvalError::Arguments(name.to_owned()));
+    let float = |x: f64| Ok(Value::Float(x));
+
+    let result = (|| match (name, args) {
+        ("diffuse_material", [albedo]) => Ok(diffuse_material(albedo.as_vec3()?)),
+        ("mix_material", [a, b, h]) => mix(a, b, h.as_float()?),
+        ("map_opaque", [albedo, d]) => Value::new_struct(
+            "MapOpaque",
+            vec![diffuse_material(albedo.as_vec3()?), Value::Float(d.as_float()?)],
+        ),
+        ("vmax", [a]) => float(vmax(a.as_vec3()?)),
+        ("vmin", [a]) => float(vmin(a.as_vec3()?)),
+
+        ("vat", [shift, arg]) => vat(shift.as_vec3()?, arg),
+        ("at", [x, y, z, arg]) => vat([x.as_float()?, y.as_float()?, z.as_float()?], arg),
+        ("vscale", [sc, arg]) => vscale(sc.as_vec3()?, arg),
+        ("scale", [x, y, z, arg]) => vscale([x.as_float()?, y.as_float()?, z.as_float()?], arg),
+        ("uscale", [sc, arg]) => vscale([sc.as_float()?; 3], arg),
+        ("vrepeat", [size, arg]) => vrepeat(size.as_vec3()?, arg),
+        ("repeat", [x, y, z, arg]) => vrepeat([x.as_float()?, y.as_float()?, z.as_float()?], arg),
+        ("rotate", [axis, angle, arg]) => rotate(axis.as_vec3()?, angle.as_float()?, arg),
+        ("mirror", [axis, arg]) => mirror(axis.as_vec3()?, arg),
+        ("repeat_polar", [n, arg]) => repeat_polar(n.as_float()?, arg),
+        ("repeat_limited", [spacing, count, arg]) => {
+            let (spacing, count) = (spacing.as_vec3()?, count.as_vec3()?);
+            with_p(arg, |p| map3(p, |i, x| x - spacing[i] * (x / spacing[i]).round().clamp(-count[i], count[i])))
+        }
+        ("elongate", [size, arg]) => {
+            let size = size.as_vec3()?;
+            with_p(arg, |p| map3(p, |i, x| x - x.clamp(-size[i], size[i])))
+        }
+        ("revolve", [offset, arg]) => {
+            let offset = offset.as_float()?;
+            with_p(arg, |[x, y, z]| [x.hypot(z) - offset, y, 0.0])
+        }
+        ("shear_lipschitz", [shear]) => float(shear_lipschitz(shear.as_float()?)),
+        ("twist", [rate, arg]) => {
+            let rate = rate.as_float()?;
+            with_p(arg, |p| turned(p, 0, 2, rate * p[1]))
+        }
+        ("twist_lipschitz", [rate, d, arg]) => {
+            let [x, _, z] = arg.field("p")?.as_vec3()?;
+            float(shear_lipschitz(rate.as_float()?.abs() * (x.hypot(z) + d.as_float()?.abs())))
+        }
+        ("bend", [rate, arg]) => {
+            let rate = rate.as_float()?;
+            with_p(arg, |p| turned(p, 0, 1, rate * p[0]))
+        }
+        ("bend_lipschitz", [rate, d, arg]) => {
+            let [x, y, _] = arg.field("p")?.as_vec3()?;
+            float(1.0 + rate.as_float()?.abs() * (x.hypot(y) + d.as_float()?.abs()))
+        }
+
+        ("at_t", [t, arg]) => {
+            let shift = t.as_float()?;
+            with_t(arg, |t| t - shift)
+        }
+        ("start_at_t", [min_t, arg]) => {
+            let min_t = min_t.as_float()?;
+            with_t(arg, |t| min_t.max(t))
+        }
+        ("end_at_t", [max_t, arg]) => {
+            let max_t = max_t.as_float()?;
+            with_t(arg, |t| max_t.min(t))
+        }
+        ("repeat_t", [interval, arg]) => {
+            let interval = interval.as_float()?;
+            with_t(arg, |t| glsl_mod(t, interval))
+        }
+        ("map_t", [start_in, end_in, start_out, end_out, arg]) => {
+            let (start_in, end_in) = (start_in.as_float()?, end_in.as_float()?);
+            let (start_out, end_out) = (start_out.as_float()?, end_out.as_float()?);
+            with_t(arg, |t| {
+                let k = (t - start_in) / (end_in - start_in);
+                start_out * (1.0 - k) + end_out * k
+            })
+        }
+
+        ("sd_union", [a, b]) => float(a.as_float()?.min(b.as_float()?)),
+        ("sd_isect", [a, b]) => float(a.as_float()?.max(b.as_float()?)),
+        ("sd_diff", [a, b]) => float(a.as_float()?.max(-b.as_float()?)),
+        ("sd_onionize", [thick, a]) => {
+            let a = a.as_float()?;
+            float(a.max(-(a + thick.as_float()?)))
+        }
+        ("sd_round", [r, a]) => float(a.as_float()? - r.as_float()?),
+        ("sd_displace", [amount, n, gradient, a]) => {
+            let (amount, gradient) = (amount.as_float()?, gradient.as_vec3()?);
+            let slope = gradient.iter().map(|x| x * x).sum::<f64>().sqrt();
+            float((a.as_float()? + amount * n.as_float()?) / (1.0 + amount.abs() * slope))
+        }
+        ("sd_smooth_union_factor", [a, b, k]) => {
+            float(sd_smooth_union_factor(a.as_float()?, b.as_float()?, k.as_float()?))
+        }
+        ("sd_smooth_union", [a, b, k]) => float(sd_smooth_union(a.as_float()?, b.as_float()?, k.as_float()?)),
+        ("csd_union", [a, b]) | ("tsd_union", [a, b]) => select(a, b, true),
+        ("csd_isect", [a, b]) | ("tsd_isect", [a, b]) => cut(a, b, true, false),
+        ("csd_isect", [a, b, cutter_color]) | ("tsd_isect", [a, b, cutter_color]) => {
+            cut(a, b, cutter_color.as_bool()?, false)
+        }
+        ("csd_diff", [a, b]) | ("tsd_diff", [a, b]) => cut(a, b, true, true),
+        ("csd_diff", [a, b, cutter_color]) | ("tsd_diff", [a, b, cutter_color]) => cut(a, b, cutter_color.as_bool()?, true),
+        ("csd_smooth_union", [a, b, k]) | ("tsd_smooth_union", [a, b, k]) => smooth_union(a, b, k.as_float()?),
+
+        ("polygon_edge", [a, b, acc, arg]) => {
+            let (a, b, acc) = (a.as_vec2()?, b.as_vec2()?, acc.as_vec2()?);
+            let [x, y, _] = arg.field("p")?.as_vec3()?;
+            let (d, s) = polygon_edge(a, b, [x, y], acc[0], acc[1]);
+            Ok(Value::Vec(2, [d, s, 0.0, 0.0]))
+        }
+        ("polygon_distance", [acc]) => {
+            let [d, s] = acc.as_vec2()?;
+            float(s * d.sqrt())
+        }
+
+        (_, [a, b, params @ ..]) if operator_name(name, &["sd_"]).is_some() => {
+            let params = params.iter().map(Value::as_float).collect::<Result<Vec<_>, _>>()?;
+            operator(&name[3..], a.as_float()?, b.as_float()?, &params).map_or_else(wrong, float)
+        }
+        (_, [a, b, params @ ..]) if operator_name(name, &["csd_", "tsd_"]).is_some() => {
+            let params = params.iter().map(Value::as_float).collect::<Result<Vec<_>, _>>()?;
+            colored_operator(name, a, b, &params)
+        }
+        _ => wrong(),
+    })();
+
+    Some(result)
+}
@@ -0,0 +1,195 @@
+//! Noise functions of `library.glsl`, computed in the same order so that the CPU finds the same values as the GPU
+//! up to rounding
+
+use super::{EvalError, Value};
+
+/// Names of the noise functions of `library.glsl`
+pub const NAMES: &[&str] = &[
+    "hash",
+    "noise_dims",
+    "noise_fade",
+    "value_noise_n",
+    "perlin_noise_n",
+    "simplex_noise_n",
+    "worley_noise_n",
+    "fbm_n",
+    "ridged_n",
+    "value_noise",
+    "perlin_noise",
+    "simplex_noise",
+    "worley_noise",
+    "fbm",
+    "ridged",
+];
+
+type Vec4 = [f64; 4];
+
+fn map4(f: impl Fn(usize) -> f64) -> Vec4 {
+    [f(0), f(1), f(2), f(3)]
+}
+
+fn dot(a: Vec4, b: Vec4) -> f64 {
+    a.iter().zip(&b).map(|(a, b)| a * b).sum()
+}
+
+/// Four random numbers from 0 to 1 for the cell of the integer lattice containing `p`, with PCG4D
+pub fn hash(p: Vec4) -> Vec4 {
+    let mut v = [0; 4];
+    for (v, x) in v.iter_mut().zip(&p) {
+        *v = (x.floor() as i32 as u32).wrapping_mul(1664525).wrapping_add(1013904223);
+    }
+
+    let mix = |v: &mut [u32; 4]| {
+        v[0] = v[0].wrapping_add(v[1].wrapping_mul(v[3]));
+        v[1] = v[1].wrapping_add(v[2].wrapping_mul(v[0]));
+        v[2] = v[2].wrapping_add(v[0].wrapping_mul(v[1]));
+        v[3] = v[3].wrapping_add(v[1].wrapping_mul(v[2]));
+    };
+    mix(&mut v);
+    for v in &mut v {
+        *v ^= *v >> 16;
+    }
+    mix(&mut v);
+
+    map4(|i| f64::from(v[i] >> 8) / 16_777_216.0)
+}
+
+fn dims(n: f64) -> Vec4 {
+    map4(|i| if n >= i as f64 + 0.5 { 1.0 } else { 0.0 })
+}
+
+fn fade(f: Vec4) -> Vec4 {
+    map4(|i| f[i] * f[i] * f[i] * (f[i] * (f[i] * 6.0 - 15.0) + 10.0))
+}
+
+/// Corners of a lattice cell in `n` dimensions, with the interpolation weight of each for the fractional position `f`
+fn corners(n: usize, f: Vec4) -> impl Iterator<Item = (Vec4, f64)> {
+    (0..1 << n).map(move |i: usize| {
+        let corner = map4(|k| ((i >> k) & 1) as f64);
+        let weight = (0..4).map(|k| if corner[k] == 1.0 { f[k] } else { 1.0 - f[k] }).product();
+        (corner, weight)
+    })
+}
+
+fn value_noise(p: Vec4, n: usize) -> f64 {
+    let cell = p.map(f64::floor);
+    let f = fade(map4(|i| p[i] - cell[i]));
+
+    corners(n, f)
+        .map(|(corner, w)| w * (hash(map4(|i| cell[i] + corner[i]))[0] * 2.0 - 1.0))
+        .sum()
+}
+
+fn perlin_noise(p: Vec4, n: usize) -> f64 {
+    let cell = p.map(f64::floor);
+    let f = map4(|i| p[i] - cell[i]);
+
+    corners(n, fade(f))
+        .map(|(corner, w)| {
+            let gradient = hash(map4(|i| cell[i] + corner[i])).map(|h| h * 2.0 - 1.0);
+            w * dot(gradient, map4(|i| f[i] - corner[i]))
+        })
+        .sum()
+}
+
+fn simplex_noise(p: Vec4, n: usize) -> f64 {
+    let nf = n as f64;
+    let dims = dims(nf);
+    let skew = ((nf + 1.0).sqrt() - 1.0) / nf;
+    let unskew = (1.0 - 1.0 / (nf + 1.0).sqrt()) / nf;
+    let s = p.iter().sum::<f64>() * skew;
+    let cell = map4(|i| (p[i] + s).floor() * dims[i]);
+    let t = cell.iter().sum::<f64>() * unskew;
+    let x0 = map4(|i| (p[i] - cell[i] + t) * dims[i]);
+
+    // the corners are visited along the components of `x0` from the largest to the smallest
+    let mut rank = [0.0; 4];
+    for i in 0..n {
+        for j in 0..i {
+            if x0[i] > x0[j] {
+                rank[i] += 1.0;
+            } else {
+                rank[j] += 1.0;
+            }
+        }
+    }
+
+    let r2 = if n > 2 { 0.6 } else { 0.5 };
+    let sum: f64 = (0..=n)
+        .map(|k| {
+            let k = k as f64;
+            let offset = map4(|i| if rank[i] >= nf - k - 0.5 { dims[i] } else { 0.0 });
+            let x = map4(|i| x0[i] - offset[i] + k * unskew * dims[i]);
+            let t = (r2 - dot(x, x)).max(0.0);
+            let h = hash(map4(|i| cell[i] + offset[i]));
+            let gradient = map4(|i| (h[i] * 2.0 - 1.0) * dims[i]);
+            t.powi(4) * dot(gradient, x)
+        })
+        .sum();
+
+    sum * [70.0, 32.0, 27.0][n.max(2) - 2]
+}
+
+fn worley_noise(p: Vec4, n: usize) -> f64 {
+    let dims = dims(n as f64);
+    let cell = p.map(f64::floor);
+
+    (0..3usize.pow(n as u32))
+        .map(|i| {
+            let offset = map4(|k| ((i / 3usize.pow(k as u32) % 3) as f64 - 1.0) * dims[k]);
+            let h = hash(map4(|k| cell[k] + offset[k]));
+            let point = map4(|k| cell[k] + offset[k] + h[k] * dims[k]);
+            let d = map4(|k| p[k] - point[k]);
+            dot(d, d).sqrt()
+        })
+        .fold(f64::INFINITY, f64::min)
+}
+
+/// Sum of `octaves` layers of Perlin noise shaped by `layer`, each twice as detailed and half as strong as the one
+/// before
+fn octaves(p: Vec4, n: usize, octaves: f64, layer: impl Fn(f64) -> f64) -> f64 {
+    (0..octaves.max(0.0) as i32)
+        .map(|i| {
+            let scale = 2f64.powi(i);
+            layer(perlin_noise(p.map(|x| x * scale), n)) / (2.0 * scale)
+        })
+        .sum()
+}
+
+fn noise(name: &str, p: Vec4, n: usize, args: &[Value]) -> Result<f64, EvalError> {
+    Ok(match (name, args) {
+        ("value_noise", []) => value_noise(p, n),
+        ("perlin_noise", []) => perlin_noise(p, n),
+        ("simplex_noise", []) => simplex_noise(p, n),
+        ("worley_noise", []) => worley_noise(p, n),
+        ("fbm", [o]) => octaves(p, n, o.as_float()?, |x| x),
+        ("ridged", [o]) => octaves(p, n, o.as_float()?, |x| (1.0 - x.abs().min(1.0)).powi(2)),
+        _ => return Err(EvalError::Arguments(name.to_owned())),
+    })
+}
+
+/// Noise function of `library.glsl`, `None` if there's none with that name
+pub fn call(name: &str, args: &[Value]) -> Option<Result<Value, EvalError>> {
+    if !NAMES.contains(&name) {
+        return None;
+    }
+
+    let wrong = || Err(EvalError::Arguments(name.to_owned()));
+
+    let result = (|| match (name, args) {
+        ("hash", [Value::Vec(2..=4, p)]) => Ok(Value::Vec(4, hash(*p))),
+        ("noise_dims", [n]) => Ok(Value::Vec(4, dims(n.as_float()?))),
+        ("noise_fade", [Value::Vec(4, f)]) => Ok(Value::Vec(4, fade(*f))),
+        (_, [Value::Vec(4, p), n, args @ ..]) if name.ends_with("_n") => {
+            let n = n.as_float()?;
+            if !(2.0..5.0).contains(&n) {
+                return wrong();
+            }
+            noise(&name[..name.len() - 2], *p, n as usize, args).map(Value::Float)
+        }
+        (_, [Value::Vec(n @ 2..=4, p), args @ ..]) => noise(name, *p, *n, args).map(Value::Float),
+        _ => wrong(),
+    })();
+
+    Some(result)
+}
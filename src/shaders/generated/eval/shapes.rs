@@ -0,0 +1,294 @@
+//! Shapes of `library.glsl`
+
+use super::{EvalError, Value};
+
+/// Names of the shapes of `library.glsl`
+pub const NAMES: &[&str] = &[
+    "sd_sphere",
+    "sd_box",
+    "sd_halfspace",
+    "sd_halfspace_aa",
+    "sd_column_aa",
+    "sd_torus",
+    "sd_capped_cylinder",
+    "sd_capsule",
+    "sd_capped_cone",
+    "sd_cone",
+    "sd_ellipsoid",
+    "sd_rounded_box",
+    "sd_box_frame",
+    "sd_hex_prism",
+    "sd_tri_prism",
+    "sd_octahedron",
+    "sd_link",
+    "sd_pyramid",
+    "sd_bezier",
+    "sd2_circle",
+    "sd2_rect",
+    "sd2_rounded_rect",
+    "sd2_arc",
+    "sd2_segment",
+    "sd_extrude",
+];
+
+type Vec3 = [f64; 3];
+
+fn map3(a: Vec3, f: impl Fn(usize, f64) -> f64) -> Vec3 {
+    [f(0, a[0]), f(1, a[1]), f(2, a[2])]
+}
+
+fn vmax([x, y, z]: Vec3) -> f64 {
+    x.max(y.max(z))
+}
+
+fn vmin([x, y, z]: Vec3) -> f64 {
+    x.min(y.min(z))
+}
+
+fn dot(a: Vec3, b: Vec3) -> f64 {
+    a[0] * b[0] + a[1] * b[1] + a[2] * b[2]
+}
+
+fn length(a: Vec3) -> f64 {
+    dot(a, a).sqrt()
+}
+
+/// `sign` of GLSL, which is zero for zero
+fn sign(x: f64) -> f64 {
+    if x == 0.0 {
+        0.0
+    } else {
+        x.signum()
+    }
+}
+
+fn sub(a: Vec3, b: Vec3) -> Vec3 {
+    map3(a, |i, x| x - b[i])
+}
+
+/// Along the axis of a shape of revolution around y, and away from it
+fn revolve([x, y, z]: Vec3) -> (f64, f64) {
+    (x.hypot(z), y)
+}
+
+/// Distance to a box, given how far the point is outside of it along each axis
+fn box_distance(q: Vec3) -> f64 {
+    length(q.map(|x| x.max(0.0))) + vmax(q).min(0.0)
+}
+
+/// Distance to a rectangle, given how far the point is outside of it along each axis
+fn rect_distance([x, y]: [f64; 2]) -> f64 {
+    x.max(y).min(0.0) + x.max(0.0).hypot(y.max(0.0))
+}
+
+fn capsule(a: Vec3, b: Vec3, r: f64, p: Vec3) -> f64 {
+    let (pa, ba) = (sub(p, a), sub(b, a));
+    let h = (dot(pa, ba) / dot(ba, ba)).clamp(0.0, 1.0);
+    length(sub(pa, ba.map(|x| x * h))) - r
+}
+
+fn segment(a: [f64; 2], b: [f64; 2], r: f64, [x, y]: [f64; 2]) -> f64 {
+    let (pa, ba) = ([x - a[0], y - a[1]], [b[0] - a[0], b[1] - a[1]]);
+    let h = ((pa[0] * ba[0] + pa[1] * ba[1]) / (ba[0] * ba[0] + ba[1] * ba[1])).clamp(0.0, 1.0);
+    (pa[0] - ba[0] * h).hypot(pa[1] - ba[1] * h) - r
+}
+
+fn capped_cone(r1: f64, r2: f64, h: f64, p: Vec3) -> f64 {
+    let q = revolve(p);
+    let (k1, k2) = ((r2, h), (r2 - r1, 2.0 * h));
+    let ca = (q.0 - q.0.min(if q.1 < 0.0 { r1 } else { r2 }), q.1.abs() - h);
+    let f = ((k1.0 - q.0) * k2.0 + (k1.1 - q.1) * k2.1) / (k2.0 * k2.0 + k2.1 * k2.1);
+    let f = f.clamp(0.0, 1.0);
+    let cb = (q.0 - k1.0 + k2.0 * f, q.1 - k1.1 + k2.1 * f);
+    let s = if cb.0 < 0.0 && ca.1 < 0.0 { -1.0 } else { 1.0 };
+    s * (ca.0 * ca.0 + ca.1 * ca.1).min(cb.0 * cb.0 + cb.1 * cb.1).sqrt()
+}
+
+fn box_frame(s: Vec3, e: f64, p: Vec3) -> f64 {
+    let p = map3(p, |i, x| x.abs() - s[i]);
+    let q = p.map(|x| (x + e / 2.0).abs() - e / 2.0);
+    let bar = |axis: usize| box_distance(map3(q, |i, x| if i == axis { p[i] } else { x }));
+    bar(0).min(bar(1)).min(bar(2))
+}
+
+fn hex_prism(r: f64, h: f64, p: Vec3) -> f64 {
+    let k = [-0.866_025_4, 0.5, 0.57735];
+    let (mut x, mut y, z) = (p[0].abs(), p[2].abs(), p[1].abs());
+    let fold = 2.0 * (k[0] * x + k[1] * y).min(0.0);
+    x -= fold * k[0];
+    y -= fold * k[1];
+
+    let edge = (x - x.clamp(-k[2] * r, k[2] * r)).hypot(y - r) * sign(y - r);
+    let d = (edge, z - h);
+    d.0.max(d.1).min(0.0) + d.0.max(0.0).hypot(d.1.max(0.0))
+}
+
+fn octahedron(s: f64, p: Vec3) -> f64 {
+    let p = p.map(f64::abs);
+    let m = p[0] + p[1] + p[2] - s;
+    let q = if 3.0 * p[0] < m {
+        p
+    } else if 3.0 * p[1] < m {
+        [p[1], p[2], p[0]]
+    } else if 3.0 * p[2] < m {
+        [p[2], p[0], p[1]]
+    } else {
+        return m * 0.577_350_27;
+    };
+
+    let k = (0.5 * (q[2] - q[1] + s)).clamp(0.0, s);
+    length([q[0], q[1] - s + k, q[2] - k])
+}
+
+fn pyramid(s: f64, h: f64, p: Vec3) -> f64 {
+    // the pyramid with a base of size 1, scaled
+    let p = p.map(|x| x / (2.0 * s));
+    let h = h / (2.0 * s);
+    let m2 = h * h + 0.25;
+    let (x, z) = (p[0].abs(), p[2].abs());
+    let (x, z) = if z > x { (z - 0.5, x - 0.5) } else { (x - 0.5, z - 0.5) };
+
+    let q = [z, h * p[1] - 0.5 * x, h * x + 0.5 * p[1]];
+    let e = (-q[0]).max(0.0);
+    let t = ((q[1] - 0.5 * z) / (m2 + 0.25)).clamp(0.0, 1.0);
+    let a = m2 * (q[0] + e) * (q[0] + e) + q[1] * q[1];
+    let b = m2 * (q[0] + 0.5 * t) * (q[0] + 0.5 * t) + (q[1] - m2 * t) * (q[1] - m2 * t);
+    let d2 = if q[1].min(-q[0] * m2 - q[1] * 0.5) > 0.0 { 0.0 } else { a.min(b) };
+    let base = length([x.max(0.0), p[1], z.max(0.0)]);
+    2.0 * s * ((d2 + q[2] * q[2]) / m2).sqrt().min(base) * sign(q[2].max(-p[1]))
+}
+
+fn bezier(a: Vec3, b: Vec3, c: Vec3, r: f64, p: Vec3) -> f64 {
+    let ab = sub(b, a);
+    let bend = map3(a, |i, x| x - 2.0 * b[i] + c[i]);
+    if dot(bend, bend) < 1e-8 {
+        return capsule(a, c, r, p);
+    }
+
+    // the closest point of the curve is at a root of a cubic
+    let d = sub(a, p);
+    let kk = 1.0 / dot(bend, bend);
+    let kx = kk * dot(ab, bend);
+    let ky = kk * (2.0 * dot(ab, ab) + dot(d, bend)) / 3.0;
+    let kz = kk * dot(d, ab);
+    let p = ky - kx * kx;
+    let q = kx * (2.0 * kx * kx - 3.0 * ky) + kz;
+    let h = q * q + 4.0 * p * p * p;
+    let at = |t: f64| {
+        let e = map3(d, |i, x| x + (2.0 * ab[i] + bend[i] * t) * t);
+        dot(e, e)
+    };
+
+    let res = if h >= 0.0 {
+        let h = h.sqrt();
+        let cbrt = |x: f64| x.signum() * x.abs().powf(1.0 / 3.0);
+        let t = (cbrt((h - q) / 2.0) + cbrt((-h - q) / 2.0) - kx).clamp(0.0, 1.0);
+        at(t)
+    } else {
+        // of the three roots, the one left out is never the closest
+        let z = (-p).sqrt();
+        let v = (q / (p * z * 2.0)).clamp(-1.0, 1.0).acos() / 3.0;
+        let (m, n) = (v.cos(), v.sin() * 1.732_050_808);
+        let t0 = ((m + m) * z - kx).clamp(0.0, 1.0);
+        let t1 = ((-n - m) * z - kx).clamp(0.0, 1.0);
+        at(t0).min(at(t1))
+    };
+
+    res.sqrt() - r
+}
+
+/// Distance to a shape of `library.glsl`, `None` if there's none with that name
+pub fn call(name: &str, args: &[Value]) -> Option<Result<Value, EvalError>> {
+    if !NAMES.contains(&name) {
+        return None;
+    }
+
+    let result = (|| {
+        let wrong = || EvalError::Arguments(name.to_owned());
+        let p = match args.last() {
+            Some(arg @ Value::Struct("Arg", _)) => arg.field("p")?.as_vec3()?,
+            _ => return Err(wrong()),
+        };
+        let args = &args[..args.len() - 1];
+        let f = |idx: usize| args[idx].as_float();
+        let v = |idx: usize| args[idx].as_vec3();
+        let w = |idx: usize| args[idx].as_vec2();
+
+        let d = match (name, args.len()) {
+            ("sd_sphere", 1) => length(p) - f(0)?,
+            ("sd_box", 1) => {
+                let s = v(0)?;
+                vmax(map3(p, |i, x| x.abs() - s[i]))
+            }
+            ("sd_halfspace", 1) => dot(p, v(0)?),
+            ("sd_halfspace_aa", 1) => {
+                let axis = v(0)?;
+                vmax(map3(p, |i, x| x * axis[i]))
+            }
+            ("sd_column_aa", 2) => {
+                let (axis, rad) = (v(0)?, f(1)?);
+                let across = axis.map(|x| 1.0 - x);
+                vmax(map3(p, |i, x| (x.abs() - rad) * across[i]))
+            }
+            ("sd_torus", 2) => {
+                let (x, y) = revolve(p);
+                (x - f(0)?).hypot(y) - f(1)?
+            }
+            ("sd_capped_cylinder", 2) => {
+                let (x, y) = revolve(p);
+                let d = (x.abs() - f(0)?, y.abs() - f(1)?);
+                d.0.max(d.1).min(0.0) + d.0.max(0.0).hypot(d.1.max(0.0))
+            }
+            ("sd_capsule", 3) => capsule(v(0)?, v(1)?, f(2)?, p),
+            ("sd_capped_cone", 3) => capped_cone(f(0)?, f(1)?, f(2)?, p),
+            ("sd_cone", 2) => {
+                let (r, h) = (f(0)?, f(1)?);
+                capped_cone(r, 0.0, h / 2.0, [p[0], p[1] - h / 2.0, p[2]])
+            }
+            ("sd_ellipsoid", 1) => {
+                let r = v(0)?;
+                (length(map3(p, |i, x| x / r[i])) - 1.0) * vmin(r)
+            }
+            ("sd_rounded_box", 2) => {
+                let (s, r) = (v(0)?, f(1)?);
+                box_distance(map3(p, |i, x| x.abs() - s[i] + r)) - r
+            }
+            ("sd_box_frame", 2) => box_frame(v(0)?, f(1)?, p),
+            ("sd_hex_prism", 2) => hex_prism(f(0)?, f(1)?, p),
+            ("sd_tri_prism", 2) => {
+                let (r, h) = (f(0)?, f(1)?);
+                (p[1].abs() - h).max((p[0].abs() * 0.866_025 + p[2] * 0.5).max(-p[2]) - r)
+            }
+            ("sd_octahedron", 1) => octahedron(f(0)?, p),
+            ("sd_link", 3) => {
+                let y = (p[1].abs() - f(0)?).max(0.0);
+                (p[0].hypot(y) - f(1)?).hypot(p[2]) - f(2)?
+            }
+            ("sd_pyramid", 2) => pyramid(f(0)?, f(1)?, p),
+            ("sd_bezier", 4) => bezier(v(0)?, v(1)?, v(2)?, f(3)?, p),
+            ("sd2_circle", 1) => p[0].hypot(p[1]) - f(0)?,
+            ("sd2_rect", 1) => {
+                let s = w(0)?;
+                rect_distance([p[0].abs() - s[0], p[1].abs() - s[1]])
+            }
+            ("sd2_rounded_rect", 2) => {
+                let (s, r) = (w(0)?, f(1)?);
+                rect_distance([p[0].abs() - s[0] + r, p[1].abs() - s[1] + r]) - r
+            }
+            ("sd2_arc", 3) => {
+                let (angle, big_r, r) = (f(0)?, f(1)?, f(2)?);
+                let (sin, cos) = angle.sin_cos();
+                let (x, y) = (p[0].abs(), p[1]);
+                let d = if cos * x > sin * y { (x - sin * big_r).hypot(y - cos * big_r) } else { (x.hypot(y) - big_r).abs() };
+                d - r
+            }
+            ("sd2_segment", 3) => segment(w(0)?, w(1)?, f(2)?, [p[0], p[1]]),
+            ("sd_extrude", 2) => rect_distance([f(1)?, p[2].abs() - f(0)?]),
+            _ => return Err(wrong()),
+        };
+
+        Ok(Value::Float(d))
+    })();
+
+    Some(result)
+}
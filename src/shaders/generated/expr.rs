@@ -0,0 +1,300 @@
+use std::fmt;
+
+use super::source::{Span, Spanned};
+
+/// Expression used as an argument of a statement
+#[derive(Debug, Clone, PartialEq)]
+pub enum Expr {
+    /// Number literal, kept as written
+    Number(String),
+    /// `"quoted"` string, without the quotes
+    String(String),
+    Ident(String),
+    /// `$name`, a field of the `Arg` passed to every object, like `$p` or `$t`
+    Arg(String),
+    Unary(UnaryOp, Box<Spanned<Expr>>),
+    Binary(BinaryOp, Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    /// `cond ? then : otherwise`
+    Ternary(Box<Spanned<Expr>>, Box<Spanned<Expr>>, Box<Spanned<Expr>>),
+    Call(String, Vec<Spanned<Expr>>),
+    /// Struct field or swizzle, like `$p.xz`
+    Field(Box<Spanned<Expr>>, String),
+    /// `<type> <name>`, only meaningful as a parameter of `define_*` statements
+    Declaration(String, String),
+    /// A lone operator used as a flag, like the `+` in `keyframe(1, +)`
+    Symbol(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnaryOp {
+    Neg,
+    Plus,
+    Not,
+    BitNot,
+}
+
+impl UnaryOp {
+    pub fn from_char(c: u8) -> Option<Self> {
+        match c {
+            b'-' => Some(UnaryOp::Neg),
+            b'+' => Some(UnaryOp::Plus),
+            b'!' => Some(UnaryOp::Not),
+            b'~' => Some(UnaryOp::BitNot),
+            _ => None,
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            UnaryOp::Neg => "-",
+            UnaryOp::Plus => "+",
+            UnaryOp::Not => "!",
+            UnaryOp::BitNot => "~",
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BinaryOp {
+    Or,
+    Xor,
+    And,
+    BitOr,
+    BitXor,
+    BitAnd,
+    Eq,
+    Ne,
+    Lt,
+    Gt,
+    Le,
+    Ge,
+    Shl,
+    Shr,
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+impl BinaryOp {
+    /// Longer symbols come first, so that the first symbol an input starts with is the right one
+    pub const ALL: [BinaryOp; 19] = [
+        BinaryOp::Or,
+        BinaryOp::Xor,
+        BinaryOp::And,
+        BinaryOp::Eq,
+        BinaryOp::Ne,
+        BinaryOp::Le,
+        BinaryOp::Ge,
+        BinaryOp::Shl,
+        BinaryOp::Shr,
+        BinaryOp::BitOr,
+        BinaryOp::BitXor,
+        BinaryOp::BitAnd,
+        BinaryOp::Lt,
+        BinaryOp::Gt,
+        BinaryOp::Add,
+        BinaryOp::Sub,
+        BinaryOp::Mul,
+        BinaryOp::Div,
+        BinaryOp::Rem,
+    ];
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            BinaryOp::Or => "||",
+            BinaryOp::Xor => "^^",
+            BinaryOp::And => "&&",
+            BinaryOp::BitOr => "|",
+            BinaryOp::BitXor => "^",
+            BinaryOp::BitAnd => "&",
+            BinaryOp::Eq => "==",
+            BinaryOp::Ne => "!=",
+            BinaryOp::Lt => "<",
+            BinaryOp::Gt => ">",
+            BinaryOp::Le => "<=",
+            BinaryOp::Ge => ">=",
+            BinaryOp::Shl => "<<",
+            BinaryOp::Shr => ">>",
+            BinaryOp::Add => "+",
+            BinaryOp::Sub => "-",
+            BinaryOp::Mul => "*",
+            BinaryOp::Div => "/",
+            BinaryOp::Rem => "%",
+        }
+    }
+
+    /// Same precedence levels as GLSL, higher binds tighter
+    pub fn precedence(self) -> u8 {
+        match self {
+            BinaryOp::Or => 2,
+            BinaryOp::Xor => 3,
+            BinaryOp::And => 4,
+            BinaryOp::BitOr => 5,
+            BinaryOp::BitXor => 6,
+            BinaryOp::BitAnd => 7,
+            BinaryOp::Eq | BinaryOp::Ne => 8,
+            BinaryOp::Lt | BinaryOp::Gt | BinaryOp::Le | BinaryOp::Ge => 9,
+            BinaryOp::Shl | BinaryOp::Shr => 10,
+            BinaryOp::Add | BinaryOp::Sub => 11,
+            BinaryOp::Mul | BinaryOp::Div | BinaryOp::Rem => 12,
+        }
+    }
+}
+
+const TERNARY_PRECEDENCE: u8 = 1;
+const UNARY_PRECEDENCE: u8 = 13;
+const POSTFIX_PRECEDENCE: u8 = 14;
+
+impl Expr {
+    pub fn number(x: impl ToString) -> Self {
+        Expr::Number(x.to_string())
+    }
+
+    /// Builds an expression that doesn't come from the source
+    pub fn binary(op: BinaryOp, lhs: Expr, rhs: Expr) -> Self {
+        Expr::Binary(
+            op,
+            Box::new(Spanned::new(lhs, Span::default())),
+            Box::new(Spanned::new(rhs, Span::default())),
+        )
+    }
+
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Expr::Number(x) => x.parse::<f64>() == Ok(0.0),
+            _ => false,
+        }
+    }
+
+    /// Value of an arithmetic expression made only of number literals
+    pub fn constant(&self) -> Option<f64> {
+        match self {
+            Expr::Number(x) => x.parse().ok(),
+            Expr::Unary(UnaryOp::Neg, operand) => Some(-operand.constant()?),
+            Expr::Unary(UnaryOp::Plus, operand) => operand.constant(),
+            Expr::Binary(op, lhs, rhs) => {
+                let (lhs, rhs) = (lhs.constant()?, rhs.constant()?);
+                match op {
+                    BinaryOp::Add => Some(lhs + rhs),
+                    BinaryOp::Sub => Some(lhs - rhs),
+                    BinaryOp::Mul => Some(lhs * rhs),
+                    BinaryOp::Div => Some(lhs / rhs).filter(|x| x.is_finite()),
+                    _ => None,
+                }
+            }
+            _ => None,
+        }
+    }
+
+    /// Prints the expression as GLSL, with `$name` referring to a field of the `arg` variable
+    pub fn glsl<'a>(&'a self, arg: &'a str) -> impl fmt::Display + 'a {
+        self.glsl_scoped(arg, &[], true)
+    }
+
+    /// Prints the expression as GLSL, with the names bound in `names`, innermost last, replaced by their variable.
+    /// Integer literals get a `.0` when `float_literals` is set, as GLSL ES doesn't turn an `int` into a `float`
+    pub fn glsl_scoped<'a>(
+        &'a self,
+        arg: &'a str,
+        names: &'a [(String, String)],
+        float_literals: bool,
+    ) -> impl fmt::Display + 'a {
+        Printer { expr: self, arg: Some(arg), names, float_literals }
+    }
+
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Ternary(..) => TERNARY_PRECEDENCE,
+            Expr::Binary(op, ..) => op.precedence(),
+            Expr::Unary(..) => UNARY_PRECEDENCE,
+            _ => POSTFIX_PRECEDENCE,
+        }
+    }
+}
+
+/// Prints an expression with only the parentheses its precedence needs
+struct Printer<'a> {
+    expr: &'a Expr,
+    /// Name of the `Arg` variable when printing GLSL, `None` to print the scene syntax back
+    arg: Option<&'a str>,
+    /// Scene names and the GLSL variables they stand for
+    names: &'a [(String, String)],
+    /// Whether number literals are printed as `float`s
+    float_literals: bool,
+}
+
+impl<'a> Printer<'a> {
+    fn child(&self, f: &mut fmt::Formatter, expr: &Expr, min_precedence: u8) -> fmt::Result {
+        let printer = Printer { expr, ..*self };
+
+        if expr.precedence() < min_precedence {
+            write!(f, "({})", printer)
+        } else {
+            write!(f, "{}", printer)
+        }
+    }
+}
+
+impl<'a> fmt::Display for Printer<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.expr {
+            Expr::Ident(x) => match self.names.iter().rev().find(|(name, _)| name == x) {
+                Some((_, ident)) => write!(f, "{}", ident),
+                None => write!(f, "{}", x),
+            },
+            Expr::Number(x) if self.float_literals && x.bytes().all(|b| b.is_ascii_digit()) => {
+                write!(f, "{}.0", x)
+            }
+            Expr::Number(x) | Expr::Symbol(x) => write!(f, "{}", x),
+            Expr::String(s) => write!(f, "\"{}\"", s),
+            Expr::Arg(name) => match self.arg {
+                Some(arg) => write!(f, "{}.{}", arg, name),
+                None => write!(f, "${}", name),
+            },
+            Expr::Unary(op, operand) => {
+                write!(f, "{}", op.symbol())?;
+                // anything but a primary expression gets parentheses, so `-(-x)` doesn't become `--x`
+                self.child(f, operand, POSTFIX_PRECEDENCE)
+            }
+            Expr::Binary(op, lhs, rhs) => {
+                self.child(f, lhs, op.precedence())?;
+                write!(f, " {} ", op.symbol())?;
+                self.child(f, rhs, op.precedence() + 1)
+            }
+            Expr::Ternary(cond, then, otherwise) => {
+                self.child(f, cond, TERNARY_PRECEDENCE + 1)?;
+                write!(f, " ? ")?;
+                self.child(f, then, TERNARY_PRECEDENCE + 1)?;
+                write!(f, " : ")?;
+                self.child(f, otherwise, TERNARY_PRECEDENCE)
+            }
+            Expr::Call(name, args) => {
+                write!(f, "{}(", name)?;
+                for (idx, arg) in args.iter().enumerate() {
+                    if idx > 0 {
+                        write!(f, ", ")?;
+                    }
+                    self.child(f, arg, 0)?;
+                }
+                write!(f, ")")
+            }
+            // `1.x` would read as the number `1.` followed by `x`
+            Expr::Field(base, field) if matches!(base.node, Expr::Number(_)) => {
+                write!(f, "({}).{}", Printer { expr: base, ..*self }, field)
+            }
+            Expr::Field(base, field) => {
+                self.child(f, base, POSTFIX_PRECEDENCE)?;
+                write!(f, ".{}", field)
+            }
+            Expr::Declaration(typ, name) => write!(f, "{} {}", typ, name),
+        }
+    }
+}
+
+impl fmt::Display for Expr {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        Printer { expr: self, arg: None, names: &[], float_literals: false }.fmt(f)
+    }
+}
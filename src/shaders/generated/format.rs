@@ -0,0 +1,299 @@
+use std::path::Path;
+
+use anyhow::Context as _;
+
+use super::desc::{SceneFile, Statement};
+use super::parser;
+use super::source::SourceFile;
+
+const INDENT: &str = "    ";
+
+/// Reads a scene file and prints it in the canonical style, without resolving includes
+pub fn format_scene_file(path: &Path) -> anyhow::Result<String> {
+    let source = std::fs::read(path).with_context(|| format!("Could not read `{}`", path.display()))?;
+
+    let file = parser::scene_file(&source)
+        .map_err(|e| SourceFile::new(path, &source).diagnostic(e.span, &e))?;
+
+    Ok(format(&file))
+}
+
+/// Prints a scene in the canonical style:
+///
+/// - a statement with a single nested statement is followed by it on the next line,
+///   with the same indentation
+/// - several nested statements, or a single one with a trailing comment, go into a `{ }` block,
+///   indented by four spaces
+/// - statements without a body end with `;`, blocks don't
+/// - statements spanning several lines are separated from their neighbours by a blank line
+///
+/// Parsing the output gives back the same statements, comments included.
+pub fn format(file: &SceneFile) -> String {
+    let mut out = statements(&file.statements, 0);
+
+    if !file.comments.is_empty() {
+        if !out.is_empty() {
+            out.push('\n');
+        }
+
+        for comment in &file.comments {
+            out.push_str(comment);
+            out.push('\n');
+        }
+    }
+
+    out
+}
+
+/// Prints statements one after another, each ending with a newline
+fn statements(stmts: &[Statement], depth: usize) -> String {
+    let mut out = String::new();
+    let mut previous_multiline = false;
+
+    for (idx, stmt) in stmts.iter().enumerate() {
+        let mut printed = statement(stmt, depth);
+        if let Some(comment) = &stmt.trailing_comment {
+            printed.push(' ');
+            printed.push_str(comment);
+        }
+
+        let multiline = printed.contains('\n');
+        if idx > 0 && (multiline || previous_multiline) {
+            out.push('\n');
+        }
+
+        out.push_str(&printed);
+        out.push('\n');
+        previous_multiline = multiline;
+    }
+
+    out
+}
+
+/// Prints a statement without its trailing comment or the final newline
+fn statement(stmt: &Statement, depth: usize) -> String {
+    let indent = INDENT.repeat(depth);
+    let mut out = String::new();
+
+    for comment in &stmt.comments {
+        out.push_str(&indent);
+        out.push_str(comment);
+        out.push('\n');
+    }
+
+    // a trailing comment after a chain would belong to its first statement
+    let block = stmt.body.len() > 1
+        || !stmt.closing_comments.is_empty()
+        || matches!(&stmt.body[..], [inner] if inner.trailing_comment.is_some());
+
+    out.push_str(&indent);
+    out.push_str(&stmt.name);
+    if !stmt.args.is_empty() || stmt.body.is_empty() {
+        let args = stmt.args.iter().map(|arg| arg.to_string()).collect::<Vec<_>>();
+        out.push_str(&format!("({})", args.join(", ")));
+    }
+
+    if block {
+        out.push_str(" {\n");
+        out.push_str(&statements(&stmt.body, depth + 1));
+
+        if !stmt.closing_comments.is_empty() {
+            if !stmt.body.is_empty() {
+                out.push('\n');
+            }
+
+            for comment in &stmt.closing_comments {
+                out.push_str(&indent);
+                out.push_str(INDENT);
+                out.push_str(comment);
+                out.push('\n');
+            }
+        }
+
+        out.push_str(&indent);
+        out.push('}');
+    } else if let [inner] = &stmt.body[..] {
+        out.push('\n');
+        out.push_str(&statement(inner, depth));
+    } else {
+        out.push(';');
+    }
+
+    out
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::expr::{BinaryOp, Expr, UnaryOp};
+    use crate::shaders::generated::parser::scene_file;
+    use crate::shaders::generated::source::{Span, Spanned};
+
+    use proptest::prelude::*;
+
+    fn reformat(source: &str) -> String {
+        format(&scene_file(source.as_bytes()).unwrap())
+    }
+
+    #[test]
+    fn test_format() {
+        assert_eq!(
+            reformat("at(0,0,5) opaque(1,1,1) difference {sd_box(vec3(1,1,1));sd_sphere(1.3);};"),
+            "at(0, 0, 5)\nopaque(1, 1, 1)\ndifference {\n    sd_box(vec3(1, 1, 1));\n    sd_sphere(1.3);\n}\n"
+        );
+        assert_eq!(
+            reformat("union { cross(); }\nscale(2*(1+$t)){ thing(float  x); }"),
+            "union\ncross();\n\nscale(2 * (1 + $t))\nthing(float x);\n"
+        );
+        assert_eq!(reformat("a(); b;\n\n\nc ( ) ;"), "a();\nb();\nc();\n");
+        assert_eq!(reformat(""), "");
+    }
+
+    #[test]
+    fn test_format_comments() {
+        let source = "// leading\nat(1, 2, 3) // trailing at\n// inner\nsd_sphere(1);\n\nunion { a(); b(); // trailing b\n // closing\n} // trailing union\n/* end */";
+        assert_eq!(
+            reformat(source),
+            "// leading\nat(1, 2, 3)\n// trailing at\n// inner\nsd_sphere(1);\n\nunion {\n    a();\n    b(); // trailing b\n\n    // closing\n} // trailing union\n\n/* end */\n"
+        );
+
+        // the comment would move to `union` after a chain
+        assert_eq!(reformat("union { a(); // x\n}\nb();"), "union {\n    a(); // x\n}\n\nb();\n");
+
+        // nothing in the output could hold these
+        assert!(scene_file(b"at(1, /* x */ 2, 3) sd_sphere(1);").is_err());
+        assert!(scene_file(b"sd_sphere(2 * // radius\n 1);").is_err());
+    }
+
+    #[test]
+    fn test_format_examples() {
+        for entry in std::fs::read_dir("examples").unwrap() {
+            let path = entry.unwrap().path();
+            let file = scene_file(&std::fs::read(&path).unwrap()).unwrap();
+
+            let formatted = format(&file);
+            assert_eq!(scene_file(formatted.as_bytes()).unwrap(), file, "{}", path.display());
+            assert_eq!(reformat(&formatted), formatted, "{}", path.display());
+        }
+    }
+
+    fn spanned(expr: Expr) -> Spanned<Expr> {
+        Spanned::new(expr, Span::default())
+    }
+
+    fn ident() -> impl Strategy<Value = String> {
+        "[a-z][a-z0-9_]{0,5}"
+    }
+
+    fn number() -> impl Strategy<Value = String> {
+        prop_oneof!["[0-9]{1,3}", "[0-9]{1,2}\\.[0-9]{0,2}", "\\.[0-9]{1,2}", "[0-9]\\.?[0-9]?e-?[0-9]"]
+    }
+
+    fn expr() -> impl Strategy<Value = Expr> {
+        let leaf = prop_oneof![
+            number().prop_map(Expr::Number),
+            "[a-z ]{0,6}".prop_map(Expr::String),
+            ident().prop_map(Expr::Ident),
+            ident().prop_map(Expr::Arg),
+        ];
+
+        leaf.prop_recursive(4, 24, 3, |inner| {
+            let boxed = inner.clone().prop_map(|e| Box::new(spanned(e)));
+            let unary = prop_oneof![Just(UnaryOp::Neg), Just(UnaryOp::Plus), Just(UnaryOp::Not), Just(UnaryOp::BitNot)];
+
+            prop_oneof![
+                (unary, boxed.clone()).prop_map(|(op, e)| Expr::Unary(op, e)),
+                (prop::sample::select(&BinaryOp::ALL[..]), boxed.clone(), boxed.clone())
+                    .prop_map(|(op, l, r)| Expr::Binary(op, l, r)),
+                (boxed.clone(), boxed.clone(), boxed.clone()).prop_map(|(c, t, o)| Expr::Ternary(c, t, o)),
+                (ident(), prop::collection::vec(inner.prop_map(spanned), 0..3))
+                    .prop_map(|(name, args)| Expr::Call(name, args)),
+                (boxed, "[xyzw]{1,4}").prop_map(|(base, field)| Expr::Field(base, field)),
+            ]
+        })
+    }
+
+    fn arg() -> impl Strategy<Value = Spanned<Expr>> {
+        prop_oneof![
+            8 => expr(),
+            1 => (ident(), ident()).prop_map(|(typ, name)| Expr::Declaration(typ, name)),
+            1 => prop_oneof![Just("+"), Just("-")].prop_map(|s| Expr::Symbol(s.to_owned())),
+        ]
+        .prop_map(spanned)
+    }
+
+    fn comment() -> impl Strategy<Value = String> {
+        prop_oneof![
+            "[a-z ]{0,8}".prop_map(|text| format!("//{}", text)),
+            "[a-z \n]{0,8}".prop_map(|text| format!("/*{}*/", text)),
+        ]
+    }
+
+    fn comments() -> impl Strategy<Value = Vec<String>> {
+        prop::collection::vec(comment(), 0..2)
+    }
+
+    /// Statements as the parser produces them
+    fn statement() -> impl Strategy<Value = Statement> {
+        let leaf = (ident(), prop::collection::vec(arg(), 0..3), comments()).prop_map(|(name, args, comments)| Statement {
+            name,
+            args,
+            body: Vec::new(),
+            span: Span::default(),
+            comments,
+            trailing_comment: None,
+            closing_comments: Vec::new(),
+        });
+
+        leaf.prop_recursive(4, 32, 4, |inner| {
+            let child = (inner, prop::option::of(comment())).prop_map(|(mut stmt, trailing)| {
+                stmt.trailing_comment = trailing;
+                stmt
+            });
+
+            (
+                ident(),
+                prop::collection::vec(arg(), 0..3),
+                comments(),
+                prop::collection::vec(child, 0..4),
+                comments(),
+            )
+                .prop_map(|(name, args, comments, body, closing_comments)| Statement {
+                    name,
+                    args,
+                    body,
+                    span: Span::default(),
+                    comments,
+                    trailing_comment: None,
+                    closing_comments,
+                })
+        })
+    }
+
+    fn scene() -> impl Strategy<Value = SceneFile> {
+        let top_level = (statement(), prop::option::of(comment())).prop_map(|(mut stmt, trailing)| {
+            stmt.trailing_comment = trailing;
+            stmt
+        });
+
+        (prop::collection::vec(top_level, 0..4), comments())
+            .prop_map(|(statements, comments)| SceneFile { statements, comments })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_round_trip(file in scene()) {
+            let formatted = format(&file);
+            let parsed = scene_file(formatted.as_bytes());
+
+            prop_assert!(parsed.is_ok(), "{:?} in\n{}", parsed, formatted);
+            prop_assert_eq!(parsed.unwrap(), file, "{}", formatted);
+        }
+
+        #[test]
+        fn prop_idempotent(file in scene()) {
+            let formatted = format(&file);
+            prop_assert_eq!(reformat(&formatted), formatted);
+        }
+    }
+}
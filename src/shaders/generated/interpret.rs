@@ -0,0 +1,787 @@
+//! Interpreter of the GLSL of `library.glsl` for the tests, so that its functions can be compared with their
+//! port in `eval` without a GPU
+//!
+//! Numbers are `f64`, and what the library has that the shapes don't use, like matrices and unsigned integers,
+//! is parsed but panics when it is run.
+
+use std::collections::HashMap;
+
+use super::validate::{tokenize, Token};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    Bool(bool),
+    Int(i64),
+    Float(f64),
+    Vec(Vec<f64>),
+    BVec(Vec<bool>),
+    /// Name of the struct and its fields
+    Struct(String, Vec<(String, Value)>),
+}
+
+impl Value {
+    fn type_name(&self) -> String {
+        match self {
+            Value::Bool(_) => String::from("bool"),
+            Value::Int(_) => String::from("int"),
+            Value::Float(_) => String::from("float"),
+            Value::Vec(v) => format!("vec{}", v.len()),
+            Value::BVec(v) => format!("bvec{}", v.len()),
+            Value::Struct(name, _) => name.clone(),
+        }
+    }
+
+    pub fn float(&self) -> f64 {
+        match self {
+            Value::Int(x) => *x as f64,
+            Value::Float(x) => *x,
+            _ => panic!("expected a number, found {:?}", self),
+        }
+    }
+
+    fn bool(&self) -> bool {
+        match self {
+            Value::Bool(b) => *b,
+            _ => panic!("expected a bool, found {:?}", self),
+        }
+    }
+
+    /// Component `idx` of a vector, or the number itself
+    fn component(&self, idx: usize) -> f64 {
+        match self {
+            Value::Vec(v) => v[idx],
+            _ => self.float(),
+        }
+    }
+
+    /// Components a constructor takes from the value
+    fn components(&self) -> Vec<f64> {
+        match self {
+            Value::Bool(b) => vec![f64::from(u8::from(*b))],
+            Value::Vec(v) => v.clone(),
+            Value::BVec(v) => v.iter().map(|b| f64::from(u8::from(*b))).collect(),
+            _ => vec![self.float()],
+        }
+    }
+
+    /// The value in a variable of type `typ`, ints being converted to floats like GLSL does implicitly
+    fn convert(self, typ: &str) -> Value {
+        match (typ, self) {
+            ("float", Value::Int(x)) => Value::Float(x as f64),
+            (_, value) => value,
+        }
+    }
+}
+
+/// Applies `f` to the components of the arguments, numbers going with every component
+fn map_components(args: &[Value], f: impl Fn(&[f64]) -> f64) -> Value {
+    let size = args
+        .iter()
+        .filter_map(|arg| match arg {
+            Value::Vec(v) => Some(v.len()),
+            _ => None,
+        })
+        .max();
+
+    match size {
+        Some(size) => Value::Vec(
+            (0..size)
+                .map(|idx| f(&args.iter().map(|arg| arg.component(idx)).collect::<Vec<_>>()))
+                .collect(),
+        ),
+        None => Value::Float(f(&args.iter().map(Value::float).collect::<Vec<_>>())),
+    }
+}
+
+/// Indices of the components named by a swizzle like `xzy`
+fn swizzle(name: &str) -> Vec<usize> {
+    name.chars()
+        .map(|c| match c {
+            'x' | 'r' | 's' => 0,
+            'y' | 'g' | 't' => 1,
+            'z' | 'b' | 'p' => 2,
+            'w' | 'a' | 'q' => 3,
+            _ => panic!("`{}` isn't a swizzle", name),
+        })
+        .collect()
+}
+
+/// Value of a variable that is declared without one
+fn default_value(library: &Library, typ: &str) -> Value {
+    match typ {
+        "bool" => Value::Bool(false),
+        "int" => Value::Int(0),
+        "float" => Value::Float(0.0),
+        "vec2" | "vec3" | "vec4" => Value::Vec(vec![0.0; usize::from(typ.as_bytes()[3] - b'0')]),
+        "bvec2" | "bvec3" | "bvec4" => Value::BVec(vec![false; usize::from(typ.as_bytes()[4] - b'0')]),
+        _ => {
+            let fields = library.structs.get(typ).unwrap_or_else(|| panic!("`{}` isn't supported", typ));
+            Value::Struct(
+                typ.to_owned(),
+                fields.iter().map(|(typ, name)| (name.clone(), default_value(library, typ))).collect(),
+            )
+        }
+    }
+}
+
+const ASSIGNMENTS: &[&str] = &["=", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<=", ">>="];
+
+/// Tokens of the source, without their lines
+fn tokens(source: &str) -> Vec<Token<'_>> {
+    tokenize(source).into_iter().map(|(token, _)| token).collect()
+}
+
+/// Value of a number literal, which is a `float` if it has a point or an exponent
+fn number(literal: &str) -> Value {
+    let digits = literal.trim_end_matches(['u', 'U', 'f', 'F']);
+    if digits.contains(['.', 'e', 'E']) {
+        Value::Float(digits.parse().unwrap())
+    } else {
+        Value::Int(digits.parse().unwrap())
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    Literal(Value),
+    Var(String),
+    Call(String, Vec<Expr>),
+    Field(Box<Expr>, String),
+    Index(Box<Expr>, Box<Expr>),
+    Unary(&'static str, Box<Expr>),
+    Binary(&'static str, Box<Expr>, Box<Expr>),
+    Ternary(Box<Expr>, Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+enum Statement {
+    /// Type and the declared names, with their values if they have one
+    Declare(String, Vec<(String, Option<Expr>)>),
+    /// Assigned expression, operator (`=` or one like `+=`) and value
+    Assign(Expr, &'static str, Expr),
+    Expr(Expr),
+    If(Expr, Box<Statement>, Option<Box<Statement>>),
+    For(Box<Statement>, Expr, Box<Statement>, Box<Statement>),
+    Return(Option<Expr>),
+    Block(Vec<Statement>),
+}
+
+#[derive(Debug)]
+struct Function {
+    params: Vec<(String, String)>,
+    body: Vec<Statement>,
+}
+
+/// Precedence of binary operators, the higher binding tighter
+fn precedence(op: &str) -> Option<u8> {
+    Some(match op {
+        "||" => 1,
+        "^^" => 2,
+        "&&" => 3,
+        "|" => 4,
+        "^" => 5,
+        "&" => 6,
+        "==" | "!=" => 7,
+        "<" | ">" | "<=" | ">=" => 8,
+        "<<" | ">>" => 9,
+        "+" | "-" => 10,
+        "*" | "/" | "%" => 11,
+        _ => return None,
+    })
+}
+
+struct Parser<'a> {
+    tokens: Vec<Token<'a>>,
+    pos: usize,
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&Token<'a>> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Token<'a> {
+        let token = self.tokens.get(self.pos).copied().expect("unexpected end of the source");
+        self.pos += 1;
+        token
+    }
+
+    fn is_punct(&self, punct: &str) -> bool {
+        matches!(self.peek(), Some(Token::Punct(p)) if *p == punct)
+    }
+
+    fn eat(&mut self, punct: &str) -> bool {
+        let found = self.is_punct(punct);
+        if found {
+            self.pos += 1;
+        }
+        found
+    }
+
+    fn expect(&mut self, punct: &str) {
+        if !self.eat(punct) {
+            panic!("expected `{}`, found {:?}", punct, self.peek());
+        }
+    }
+
+    fn ident(&mut self) -> String {
+        match self.next() {
+            Token::Ident(name) => name.to_owned(),
+            token => panic!("expected a name, found {:?}", token),
+        }
+    }
+
+    fn is_ident(&self, name: &str) -> bool {
+        matches!(self.peek(), Some(Token::Ident(n)) if *n == name)
+    }
+
+    fn library(&mut self) -> Library {
+        let mut library = Library::default();
+
+        while self.peek().is_some() {
+            if self.is_ident("struct") {
+                self.pos += 1;
+                let name = self.ident();
+                let mut fields = Vec::new();
+                self.expect("{");
+                while !self.eat("}") {
+                    let typ = self.ident();
+                    fields.push((typ, self.ident()));
+                    self.expect(";");
+                }
+                self.expect(";");
+                library.structs.insert(name, fields);
+                continue;
+            }
+
+            self.ident();
+            let name = self.ident();
+            let mut params = Vec::new();
+            self.expect("(");
+            while !self.eat(")") {
+                while self.is_ident("in") || self.is_ident("const") {
+                    self.pos += 1;
+                }
+                let typ = self.ident();
+                if typ != "void" {
+                    params.push((typ, self.ident()));
+                }
+                self.eat(",");
+            }
+
+            // prototypes only matter to the GLSL compiler
+            if self.eat(";") {
+                continue;
+            }
+            self.expect("{");
+            let body = self.block();
+            library.functions.entry(name).or_default().push(Function { params, body });
+        }
+
+        library
+    }
+
+    /// Statements up to the `}` closing a block
+    fn block(&mut self) -> Vec<Statement> {
+        let mut statements = Vec::new();
+        while !self.eat("}") {
+            statements.push(self.statement());
+        }
+        statements
+    }
+
+    fn statement(&mut self) -> Statement {
+        if self.eat("{") {
+            return Statement::Block(self.block());
+        }
+
+        if self.is_ident("if") {
+            self.pos += 1;
+            self.expect("(");
+            let cond = self.expr();
+            self.expect(")");
+            let then = Box::new(self.statement());
+            let otherwise = if self.is_ident("else") {
+                self.pos += 1;
+                Some(Box::new(self.statement()))
+            } else {
+                None
+            };
+            return Statement::If(cond, then, otherwise);
+        }
+
+        if self.is_ident("for") {
+            self.pos += 1;
+            self.expect("(");
+            let init = Box::new(self.simple_statement());
+            self.expect(";");
+            let cond = self.expr();
+            self.expect(";");
+            let step = Box::new(self.simple_statement());
+            self.expect(")");
+            return Statement::For(init, cond, step, Box::new(self.statement()));
+        }
+
+        if self.is_ident("return") {
+            self.pos += 1;
+            if self.eat(";") {
+                return Statement::Return(None);
+            }
+            let value = self.expr();
+            self.expect(";");
+            return Statement::Return(Some(value));
+        }
+
+        let statement = self.simple_statement();
+        self.expect(";");
+        statement
+    }
+
+    /// Declaration, assignment or expression, without the `;`
+    fn simple_statement(&mut self) -> Statement {
+        if let (Some(Token::Ident(_)), Some(Token::Ident(_))) = (self.peek(), self.tokens.get(self.pos + 1)) {
+            let typ = self.ident();
+            let mut names = Vec::new();
+            loop {
+                let name = self.ident();
+                let value = if self.eat("=") { Some(self.expr()) } else { None };
+                names.push((name, value));
+                if !self.eat(",") {
+                    break;
+                }
+            }
+            return Statement::Declare(typ, names);
+        }
+
+        let target = self.expr();
+        if let Some(&op) = ASSIGNMENTS.iter().find(|op| self.is_punct(op)) {
+            self.pos += 1;
+            Statement::Assign(target, op, self.expr())
+        } else if self.eat("++") {
+            Statement::Assign(target, "+=", Expr::Literal(Value::Int(1)))
+        } else if self.eat("--") {
+            Statement::Assign(target, "-=", Expr::Literal(Value::Int(1)))
+        } else {
+            Statement::Expr(target)
+        }
+    }
+
+    fn expr(&mut self) -> Expr {
+        let cond = self.binary(0);
+        if !self.eat("?") {
+            return cond;
+        }
+
+        let then = self.expr();
+        self.expect(":");
+        Expr::Ternary(Box::new(cond), Box::new(then), Box::new(self.expr()))
+    }
+
+    /// Binary operations whose operators have at least the precedence `min`
+    fn binary(&mut self, min: u8) -> Expr {
+        let mut lhs = self.unary();
+
+        while let Some(Token::Punct(op)) = self.peek() {
+            let op = *op;
+            match precedence(op) {
+                Some(prec) if prec >= min => {
+                    self.pos += 1;
+                    let rhs = self.binary(prec + 1);
+                    lhs = Expr::Binary(op, Box::new(lhs), Box::new(rhs));
+                }
+                _ => break,
+            }
+        }
+
+        lhs
+    }
+
+    fn unary(&mut self) -> Expr {
+        for &op in &["-", "+", "!", "~"] {
+            if self.eat(op) {
+                return Expr::Unary(op, Box::new(self.unary()));
+            }
+        }
+
+        let mut expr = match self.next() {
+            Token::Number(literal) => Expr::Literal(number(literal)),
+            Token::Ident(name) if name == "true" || name == "false" => Expr::Literal(Value::Bool(name == "true")),
+            Token::Ident(name) if self.eat("(") => {
+                let mut args = Vec::new();
+                while !self.eat(")") {
+                    args.push(self.expr());
+                    self.eat(",");
+                }
+                Expr::Call(name.to_owned(), args)
+            }
+            Token::Ident(name) => Expr::Var(name.to_owned()),
+            Token::Punct("(") => {
+                let expr = self.expr();
+                self.expect(")");
+                expr
+            }
+            token => panic!("unexpected {:?}", token),
+        };
+
+        loop {
+            if self.eat(".") {
+                expr = Expr::Field(Box::new(expr), self.ident());
+            } else if self.eat("[") {
+                let idx = self.expr();
+                self.expect("]");
+                expr = Expr::Index(Box::new(expr), Box::new(idx));
+            } else {
+                return expr;
+            }
+        }
+    }
+}
+
+/// Structs and functions of a GLSL source
+#[derive(Debug, Default)]
+pub struct Library {
+    /// Names of the structs with the types and names of their fields
+    structs: HashMap<String, Vec<(String, String)>>,
+    /// Overloads of each function
+    functions: HashMap<String, Vec<Function>>,
+}
+
+impl Library {
+    pub fn parse(source: &str) -> Self {
+        Parser { tokens: tokens(source), pos: 0 }.library()
+    }
+
+    /// Value of an expression calling functions of the library, like `sd_sphere(1.0, Arg(vec3(2, 0, 0), 0.0))`
+    pub fn eval(&self, source: &str) -> Value {
+        let mut parser = Parser { tokens: tokens(source), pos: 0 };
+        let expr = parser.expr();
+        assert_eq!(parser.peek(), None, "unexpected tokens after the expression");
+
+        Env { library: self, scopes: vec![HashMap::new()] }.eval(&expr)
+    }
+
+    fn call(&self, name: &str, args: Vec<Value>) -> Value {
+        if let Some(fields) = self.structs.get(name) {
+            let values = fields.iter().zip(args).map(|((typ, name), arg)| (name.clone(), arg.convert(typ)));
+            return Value::Struct(name.to_owned(), values.collect());
+        }
+
+        // an overload taking the exact types goes before one the ints have to be converted for
+        let overloads = self.functions.get(name).map_or(&[][..], Vec::as_slice);
+        let matches = |convert: bool| {
+            overloads.iter().find(|func| {
+                func.params.len() == args.len()
+                    && func.params.iter().zip(&args).all(|((typ, _), arg)| {
+                        *typ == arg.type_name() || (convert && typ == "float" && arg.type_name() == "int")
+                    })
+            })
+        };
+        let overload = matches(false).or_else(|| matches(true));
+        let func = match overload {
+            Some(func) => func,
+            None => return builtin(name, &args),
+        };
+
+        let params = func.params.iter().zip(args).map(|((typ, name), arg)| (name.clone(), arg.convert(typ)));
+        let mut env = Env { library: self, scopes: vec![params.collect()] };
+        func.body
+            .iter()
+            .find_map(|statement| env.run(statement))
+            .unwrap_or_else(|| panic!("`{}` doesn't return a value", name))
+    }
+}
+
+/// Functions and constructors of GLSL
+fn builtin(name: &str, args: &[Value]) -> Value {
+    let unary = |f: fn(f64) -> f64| map_components(args, |c| f(c[0]));
+
+    match name {
+        "bool" => Value::Bool(args[0].float() != 0.0),
+        "int" => Value::Int(args[0].float() as i64),
+        "float" => Value::Float(args[0].float()),
+        "vec2" | "vec3" | "vec4" => {
+            let size = usize::from(name.as_bytes()[3] - b'0');
+            let components = args.iter().flat_map(Value::components).collect::<Vec<_>>();
+            if components.len() == 1 {
+                Value::Vec(vec![components[0]; size])
+            } else {
+                Value::Vec(components[..size].to_vec())
+            }
+        }
+        "bvec2" | "bvec3" | "bvec4" => Value::BVec(args.iter().map(Value::bool).collect()),
+        "radians" => unary(f64::to_radians),
+        "degrees" => unary(f64::to_degrees),
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "asin" => unary(f64::asin),
+        "acos" => unary(f64::acos),
+        "atan" if args.len() == 2 => map_components(args, |c| c[0].atan2(c[1])),
+        "atan" => unary(f64::atan),
+        "pow" => map_components(args, |c| c[0].powf(c[1])),
+        "exp" => unary(f64::exp),
+        "log" => unary(f64::ln),
+        "sqrt" => unary(f64::sqrt),
+        "inversesqrt" => unary(|x| 1.0 / x.sqrt()),
+        "abs" => unary(f64::abs),
+        "sign" => unary(|x| if x == 0.0 { 0.0 } else { x.signum() }),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "fract" => unary(f64::fract),
+        "round" => unary(f64::round),
+        "mod" => map_components(args, |c| c[0] - c[1] * (c[0] / c[1]).floor()),
+        "min" => map_components(args, |c| c[0].min(c[1])),
+        "max" => map_components(args, |c| c[0].max(c[1])),
+        "clamp" => map_components(args, |c| c[0].max(c[1]).min(c[2])),
+        "mix" => map_components(args, |c| c[0] * (1.0 - c[2]) + c[1] * c[2]),
+        "step" => map_components(args, |c| if c[1] < c[0] { 0.0 } else { 1.0 }),
+        "length" => Value::Float(args[0].components().iter().map(|x| x * x).sum::<f64>().sqrt()),
+        "distance" => builtin("length", &[arithmetic("-", args[0].clone(), args[1].clone())]),
+        "dot" => Value::Float(args[0].components().iter().zip(args[1].components()).map(|(a, b)| a * b).sum()),
+        "normalize" => {
+            let length = builtin("length", args);
+            arithmetic("/", args[0].clone(), length)
+        }
+        "all" | "any" | "not" => {
+            let v = match &args[0] {
+                Value::BVec(v) => v,
+                arg => panic!("expected a bvec, found {:?}", arg),
+            };
+            match name {
+                "all" => Value::Bool(v.iter().all(|b| *b)),
+                "any" => Value::Bool(v.iter().any(|b| *b)),
+                _ => Value::BVec(v.iter().map(|b| !b).collect()),
+            }
+        }
+        _ => panic!("`{}` isn't supported", name),
+    }
+}
+
+fn arithmetic(op: &str, a: Value, b: Value) -> Value {
+    if let (Value::Int(x), Value::Int(y)) = (&a, &b) {
+        return Value::Int(match op {
+            "+" => x + y,
+            "-" => x - y,
+            "*" => x * y,
+            "/" => x / y,
+            "%" => x % y,
+            "&" => x & y,
+            "|" => x | y,
+            "^" => x ^ y,
+            "<<" => x << y,
+            ">>" => x >> y,
+            _ => panic!("`{}` isn't supported on ints", op),
+        });
+    }
+
+    map_components(&[a, b], |c| match op {
+        "+" => c[0] + c[1],
+        "-" => c[0] - c[1],
+        "*" => c[0] * c[1],
+        "/" => c[0] / c[1],
+        _ => panic!("`{}` isn't supported on floats", op),
+    })
+}
+
+/// Part of a variable that is assigned
+enum Access {
+    Field(String),
+    Index(usize),
+}
+
+fn store(target: &mut Value, path: &[Access], value: Value) {
+    match (path, target) {
+        ([], target) => *target = value,
+        ([Access::Field(name), rest @ ..], Value::Struct(_, fields)) => {
+            let field = fields.iter_mut().find(|(field, _)| field == name).expect("unknown field");
+            store(&mut field.1, rest, value);
+        }
+        ([Access::Field(name)], Value::Vec(v)) => {
+            for (idx, component) in swizzle(name).into_iter().enumerate() {
+                v[component] = value.component(idx);
+            }
+        }
+        ([Access::Index(idx)], Value::Vec(v)) => v[*idx] = value.float(),
+        (_, target) => panic!("can't assign to a part of {:?}", target),
+    }
+}
+
+/// Variables of a function call, in nested scopes
+struct Env<'a> {
+    library: &'a Library,
+    scopes: Vec<HashMap<String, Value>>,
+}
+
+impl Env<'_> {
+    fn var(&mut self, name: &str) -> &mut Value {
+        self.scopes
+            .iter_mut()
+            .rev()
+            .find_map(|scope| scope.get_mut(name))
+            .unwrap_or_else(|| panic!("`{}` is not defined", name))
+    }
+
+    /// Variable an expression assigns to, and the part of it
+    fn place(&mut self, expr: &Expr) -> (String, Vec<Access>) {
+        match expr {
+            Expr::Var(name) => (name.clone(), Vec::new()),
+            Expr::Field(base, name) => {
+                let (var, mut path) = self.place(base);
+                path.push(Access::Field(name.clone()));
+                (var, path)
+            }
+            Expr::Index(base, idx) => {
+                let idx = self.eval(idx).float() as usize;
+                let (var, mut path) = self.place(base);
+                path.push(Access::Index(idx));
+                (var, path)
+            }
+            _ => panic!("can't assign to {:?}", expr),
+        }
+    }
+
+    /// Runs a statement, giving the value it returns, if it does
+    fn run(&mut self, statement: &Statement) -> Option<Value> {
+        match statement {
+            Statement::Declare(typ, names) => {
+                for (name, value) in names {
+                    let value = match value {
+                        Some(value) => self.eval(value).convert(typ),
+                        None => default_value(self.library, typ),
+                    };
+                    self.scopes.last_mut().unwrap().insert(name.clone(), value);
+                }
+                None
+            }
+            Statement::Assign(target, op, value) => {
+                let mut value = self.eval(value);
+                if *op != "=" {
+                    value = arithmetic(&op[..op.len() - 1], self.eval(target), value);
+                }
+                let (var, path) = self.place(target);
+                store(self.var(&var), &path, value);
+                None
+            }
+            Statement::Expr(expr) => {
+                self.eval(expr);
+                None
+            }
+            Statement::If(cond, then, otherwise) => {
+                if self.eval(cond).bool() {
+                    self.run(then)
+                } else {
+                    otherwise.as_ref().and_then(|otherwise| self.run(otherwise))
+                }
+            }
+            Statement::For(init, cond, step, body) => {
+                self.scopes.push(HashMap::new());
+                self.run(init);
+                let mut returned = None;
+                while returned.is_none() && self.eval(cond).bool() {
+                    returned = self.run(body);
+                    self.run(step);
+                }
+                self.scopes.pop();
+                returned
+            }
+            Statement::Return(value) => Some(value.as_ref().map_or(Value::Bool(false), |value| self.eval(value))),
+            Statement::Block(statements) => {
+                self.scopes.push(HashMap::new());
+                let returned = statements.iter().find_map(|statement| self.run(statement));
+                self.scopes.pop();
+                returned
+            }
+        }
+    }
+
+    fn eval(&mut self, expr: &Expr) -> Value {
+        match expr {
+            Expr::Literal(value) => value.clone(),
+            Expr::Var(name) => self.var(name).clone(),
+            Expr::Call(name, args) => {
+                let args = args.iter().map(|arg| self.eval(arg)).collect();
+                self.library.call(name, args)
+            }
+            Expr::Field(base, name) => match self.eval(base) {
+                Value::Struct(_, fields) => {
+                    fields.into_iter().find(|(field, _)| field == name).expect("unknown field").1
+                }
+                Value::Vec(v) => match &swizzle(name)[..] {
+                    [idx] => Value::Float(v[*idx]),
+                    indices => Value::Vec(indices.iter().map(|idx| v[*idx]).collect()),
+                },
+                Value::BVec(v) => match &swizzle(name)[..] {
+                    [idx] => Value::Bool(v[*idx]),
+                    indices => Value::BVec(indices.iter().map(|idx| v[*idx]).collect()),
+                },
+                value => panic!("{:?} has no field `{}`", value, name),
+            },
+            Expr::Index(base, idx) => {
+                let idx = self.eval(idx).float() as usize;
+                match self.eval(base) {
+                    Value::Vec(v) => Value::Float(v[idx]),
+                    Value::BVec(v) => Value::Bool(v[idx]),
+                    value => panic!("{:?} can't be indexed", value),
+                }
+            }
+            Expr::Unary(op, operand) => match (*op, self.eval(operand)) {
+                ("-", Value::Int(x)) => Value::Int(-x),
+                ("-", value) => map_components(&[value], |c| -c[0]),
+                ("+", value) => value,
+                ("!", value) => Value::Bool(!value.bool()),
+                (op, value) => panic!("`{}` isn't supported on {:?}", op, value),
+            },
+            Expr::Binary("&&", a, b) => Value::Bool(self.eval(a).bool() && self.eval(b).bool()),
+            Expr::Binary("||", a, b) => Value::Bool(self.eval(a).bool() || self.eval(b).bool()),
+            Expr::Binary(op, a, b) => {
+                let (a, b) = (self.eval(a), self.eval(b));
+                match *op {
+                    "<" => Value::Bool(a.float() < b.float()),
+                    ">" => Value::Bool(a.float() > b.float()),
+                    "<=" => Value::Bool(a.float() <= b.float()),
+                    ">=" => Value::Bool(a.float() >= b.float()),
+                    "==" | "!=" => {
+                        let equal = match (&a, &b) {
+                            (Value::Int(_), Value::Float(_)) | (Value::Float(_), Value::Int(_)) => a.float() == b.float(),
+                            _ => a == b,
+                        };
+                        Value::Bool(equal == (*op == "=="))
+                    }
+                    "^^" => Value::Bool(a.bool() != b.bool()),
+                    op => arithmetic(op, a, b),
+                }
+            }
+            Expr::Ternary(cond, then, otherwise) => {
+                if self.eval(cond).bool() {
+                    self.eval(then)
+                } else {
+                    self.eval(otherwise)
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_interpret() {
+        let library = Library::parse(
+            "struct S {\n    vec3 p;\n    float t;\n};\n\
+             float f(float x) {\n    return x / 2;\n}\n\
+             int f(int x) {\n    return x / 2;\n}\n\
+             S g(S s) {\n    s.p.zx -= vec2(1, 2);\n    for (int i = 0; i < 3; i++) {\n        s.t += 1.0;\n    }\n    return s;\n}\n",
+        );
+
+        assert_eq!(library.eval("f(3.0)"), Value::Float(1.5));
+        assert_eq!(library.eval("f(3)"), Value::Int(1));
+        assert_eq!(library.eval("g(S(vec3(0), 1)).p"), Value::Vec(vec![-2.0, 0.0, -1.0]));
+        assert_eq!(library.eval("g(S(vec3(0), 1)).t"), Value::Float(4.0));
+        assert_eq!(library.eval("true ? 1e-1 : 2.0"), Value::Float(0.1));
+        assert_eq!(library.eval("sign(vec2(-2, 0)) + mod(-1.0, 3.0)"), Value::Vec(vec![1.0, 2.0]));
+
+        Library::parse(include_str!("../../glsl/library.glsl"));
+    }
+}
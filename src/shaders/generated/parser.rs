@@ -1,70 +1,375 @@
 use super::desc::*;
+use super::expr::{BinaryOp, Expr, UnaryOp};
+use super::source::{FileId, Span, Spanned};
 
 use nom::{
     branch::alt,
     bytes::complete as bytes,
     character::{complete as character, is_alphabetic, is_alphanumeric},
     combinator::*,
-    multi::{many0, many1, separated_list},
-    sequence::{delimited, tuple, terminated},
-    IResult,
+    error::{context, ErrorKind, ParseError as _, VerboseError, VerboseErrorKind},
+    multi::{many0, separated_list},
+    sequence::{preceded, terminated, tuple},
+    IResult, Offset,
 };
 
-pub fn scene(i: &[u8]) -> IResult<&[u8], Vec<Statement>> {
-    all_consuming(many0(statement))(i)
+type PResult<'a, O> = IResult<&'a [u8], O, VerboseError<&'a [u8]>>;
+
+#[derive(Debug, Clone, thiserror::Error)]
+#[error("{}", .message)]
+pub struct ParseError {
+    pub message: String,
+    pub span: Span,
 }
 
-fn statement(i: &[u8]) -> IResult<&[u8], Statement> {
-    
-    map(
-        tuple((
+/// Parses a whole file on its own, without resolving includes, keeping the comments that are not attached to any
+/// statement
+pub fn scene_file(src: &[u8]) -> Result<SceneFile, ParseError> {
+    scene_file_in(src, 0)
+}
+
+/// Same as [`scene_file`], but spans point into the given file of a [`SourceMap`](super::source::SourceMap)
+pub fn scene_file_in(src: &[u8], file: FileId) -> Result<SceneFile, ParseError> {
+    let parser = Parser { src, file };
+
+    let result = all_consuming(tuple((|i| parser.statements(i), trivia)))(src);
+
+    match result {
+        Ok((_, (statements, comments))) => Ok(SceneFile { statements, comments }),
+        Err(nom::Err::Error(e)) | Err(nom::Err::Failure(e)) => Err(parser.error(e)),
+        Err(nom::Err::Incomplete(_)) => Err(ParseError {
+            message: String::from("unexpected end of file"),
+            span: Span::point(src.len()).in_file(file),
+        }),
+    }
+}
+
+/// Holds on to the whole source so that parsers can turn their input into byte offsets
+struct Parser<'a> {
+    src: &'a [u8],
+    file: FileId,
+}
+
+impl<'a> Parser<'a> {
+    fn span(&self, from: &[u8], to: &[u8]) -> Span {
+        Span::new(self.src.offset(from), self.src.offset(to)).in_file(self.file)
+    }
+
+    /// Span of the character at the start of `at`, or an empty span at the end of file
+    fn token_span(&self, at: &[u8]) -> Span {
+        let start = self.src.offset(at);
+        Span::new(start, start + at.len().min(1)).in_file(self.file)
+    }
+
+    fn error(&self, e: VerboseError<&'a [u8]>) -> ParseError {
+        let expected = e.errors.iter().find_map(|(input, kind)| match kind {
+            VerboseErrorKind::Char(c) => Some((*input, format!("expected `{}`", c))),
+            VerboseErrorKind::Context(ctx) => Some((*input, ctx.to_string())),
+            _ => None,
+        });
+
+        match expected {
+            Some((input, message)) => ParseError {
+                message,
+                span: self.token_span(input),
+            },
+            None => ParseError {
+                message: String::from("expected a statement"),
+                span: e
+                    .errors
+                    .first()
+                    .map(|(input, _)| self.token_span(input))
+                    .unwrap_or_else(|| Span::point(self.src.len()).in_file(self.file)),
+            },
+        }
+    }
+
+    /// A sequence of statements together with the comments around them
+    fn statements(&self, i: &'a [u8]) -> PResult<'a, Vec<Statement>> {
+        many0(map(
+            tuple((trivia, |i| self.statement(i), opt(trailing_comment))),
+            |(mut comments, mut stmt, trailing)| {
+                comments.append(&mut stmt.comments);
+                stmt.comments = comments;
+                stmt.trailing_comment = trailing;
+                stmt
+            },
+        ))(i)
+    }
+
+    fn statement(&self, i: &'a [u8]) -> PResult<'a, Statement> {
+        let (rest, (name, args, body)) = tuple((
             ident,
-            opt(args),
-            ws(opt(alt((
-                map(character::char(';'), |_| Vec::new()),
-                map(statement, |s| vec![s]),
-                terminated(block_body, opt(ws(character::char(';')))),
-            )))),
-        )),
-        |(name, args, body)| Statement {
+            opt(|i| self.args(i)),
+            opt(tuple((
+                trivia,
+                alt((
+                    map(character::char(';'), |_| Body::Empty),
+                    map(|i| self.statement(i), Body::Single),
+                    terminated(
+                        map(|i| self.block_body(i), |(body, closing)| Body::Block(body, closing)),
+                        opt(preceded(ws0, character::char(';'))),
+                    ),
+                )),
+            ))),
+        ))(i)?;
+
+        let mut stmt = Statement {
             name,
             args: args.unwrap_or_default(),
-            body: body.unwrap_or_default(),
-        },
-    )(i)
+            body: Vec::new(),
+            span: self.span(i, rest),
+            comments: Vec::new(),
+            trailing_comment: None,
+            closing_comments: Vec::new(),
+        };
+
+        match body {
+            // comments in front of a nested statement belong to it,
+            // the rest are moved in front of the statement itself
+            Some((mut comments, Body::Single(mut inner))) => {
+                comments.append(&mut inner.comments);
+                inner.comments = comments;
+                stmt.body.push(inner);
+            }
+            Some((comments, Body::Block(body, closing))) => {
+                stmt.comments = comments;
+                stmt.body = body;
+                stmt.closing_comments = closing;
+            }
+            Some((comments, Body::Empty)) => stmt.comments = comments,
+            None => {}
+        }
+
+        Ok((rest, stmt))
+    }
+
+    fn block_body(&self, i: &'a [u8]) -> PResult<'a, (Vec<Statement>, Vec<String>)> {
+        preceded(
+            character::char('{'),
+            cut(terminated(
+                tuple((|i| self.statements(i), trivia)),
+                character::char('}'),
+            )),
+        )(i)
+    }
+
+    fn args(&self, i: &'a [u8]) -> PResult<'a, Vec<Spanned<Expr>>> {
+        preceded(
+            preceded(ws0, character::char('(')),
+            cut(terminated(
+                separated_list(preceded(arg_ws, character::char(',')), |i| self.arg(i)),
+                preceded(arg_ws, character::char(')')),
+            )),
+        )(i)
+    }
+
+    fn arg(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        preceded(
+            arg_ws,
+            alt((
+                |i| self.symbol(i),
+                |i| self.declaration(i),
+                |i| self.expr(i),
+            )),
+        )(i)
+    }
+
+    fn spanned(&self, node: Expr, from: &[u8], to: &[u8]) -> Spanned<Expr> {
+        Spanned::new(node, self.span(from, to))
+    }
+
+    /// Only valid as a whole argument, so it has to be followed by `,` or `)`
+    fn symbol(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        let (rest, symbol) = terminated(
+            recognize(character::one_of("+-")),
+            peek(preceded(arg_ws, character::one_of(",)"))),
+        )(i)?;
+
+        let symbol = String::from_utf8_lossy(symbol).into_owned();
+        Ok((rest, self.spanned(Expr::Symbol(symbol), i, rest)))
+    }
+
+    /// `<type> <name>`, only valid as a whole argument
+    fn declaration(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        let (rest, (typ, _, name)) = terminated(
+            tuple((ident, bytes::take_while1(is_ws), ident)),
+            peek(preceded(arg_ws, character::one_of(",)"))),
+        )(i)?;
+
+        Ok((rest, self.spanned(Expr::Declaration(typ, name), i, rest)))
+    }
+
+    /// Full expression, starting with the ternary operator which has the lowest precedence
+    fn expr(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        let (rest, cond) = self.binary(i, 0)?;
+
+        let rest = match preceded(arg_ws, character::char('?'))(rest) {
+            Ok((rest, _)) => rest,
+            Err(nom::Err::Error(_)) => return Ok((rest, cond)),
+            Err(e) => return Err(e),
+        };
+
+        let (rest, (then, _, otherwise)) = cut(tuple((
+            |i| self.operand(i, |i| self.expr(i)),
+            preceded(arg_ws, character::char(':')),
+            |i| self.operand(i, |i| self.expr(i)),
+        )))(rest)?;
+
+        let ternary = Expr::Ternary(Box::new(cond), Box::new(then), Box::new(otherwise));
+        Ok((rest, self.spanned(ternary, i, rest)))
+    }
+
+    /// Binary operators binding at least as tight as `min_precedence`
+    fn binary(&self, i: &'a [u8], min_precedence: u8) -> PResult<'a, Spanned<Expr>> {
+        let (mut rest, mut lhs) = self.unary(i)?;
+
+        loop {
+            let (after_op, op) = match preceded(arg_ws, binary_op)(rest) {
+                Ok((after_op, op)) if op.precedence() >= min_precedence => (after_op, op),
+                Ok(_) | Err(nom::Err::Error(_)) => return Ok((rest, lhs)),
+                Err(e) => return Err(e),
+            };
+
+            let (after_rhs, rhs) = self.operand(after_op, |i| self.binary(i, op.precedence() + 1))?;
+
+            lhs = self.spanned(Expr::Binary(op, Box::new(lhs), Box::new(rhs)), i, after_rhs);
+            rest = after_rhs;
+        }
+    }
+
+    /// Expression that has to follow an operator
+    fn operand(
+        &self,
+        i: &'a [u8],
+        parser: impl Fn(&'a [u8]) -> PResult<'a, Spanned<Expr>>,
+    ) -> PResult<'a, Spanned<Expr>> {
+        cut(preceded(arg_ws, parser))(i)
+    }
+
+    fn unary(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        match i.first().and_then(|&c| UnaryOp::from_char(c)) {
+            Some(op) => {
+                let (rest, operand) = self.operand(&i[1..], |i| self.unary(i))?;
+                Ok((rest, self.spanned(Expr::Unary(op, Box::new(operand)), i, rest)))
+            }
+            None => self.postfix(i),
+        }
+    }
+
+    /// Field accesses and swizzles
+    fn postfix(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        let (mut rest, mut expr) = self.primary(i)?;
+
+        while let Ok((after, field)) = preceded(character::char('.'), ident)(rest) {
+            expr = self.spanned(Expr::Field(Box::new(expr), field), i, after);
+            rest = after;
+        }
+
+        Ok((rest, expr))
+    }
+
+    fn primary(&self, i: &'a [u8]) -> PResult<'a, Spanned<Expr>> {
+        let (rest, expr) = alt((
+            map(number, Expr::Number),
+            map(string, Expr::String),
+            map(preceded(character::char('$'), cut(ident)), Expr::Arg),
+            |i| self.call_or_ident(i),
+            map(
+                preceded(
+                    character::char('('),
+                    cut(terminated(
+                        |i| self.operand(i, |i| self.expr(i)),
+                        preceded(arg_ws, character::char(')')),
+                    )),
+                ),
+                |inner| inner.node,
+            ),
+            expected_expression,
+        ))(i)?;
+
+        Ok((rest, self.spanned(expr, i, rest)))
+    }
+
+    fn call_or_ident(&self, i: &'a [u8]) -> PResult<'a, Expr> {
+        let (rest, name) = ident(i)?;
+
+        let (rest, args) = opt(preceded(
+            preceded(arg_ws, character::char('(')),
+            cut(terminated(
+                separated_list(
+                    preceded(arg_ws, character::char(',')),
+                    preceded(arg_ws, |i| self.expr(i)),
+                ),
+                preceded(arg_ws, character::char(')')),
+            )),
+        ))(rest)?;
+
+        match args {
+            Some(args) => Ok((rest, Expr::Call(name, args))),
+            None => Ok((rest, Expr::Ident(name))),
+        }
+    }
 }
 
-fn block_body(i: &[u8]) -> IResult<&[u8], Vec<Statement>> {
-    delimited(
-        ws(character::char('{')),
-        many0(statement),
-        ws(character::char('}')),
-    )(i)
+enum Body {
+    Empty,
+    Single(Statement),
+    Block(Vec<Statement>, Vec<String>),
+}
+
+fn binary_op(i: &[u8]) -> PResult<'_, BinaryOp> {
+    for &op in BinaryOp::ALL.iter() {
+        if let Ok((rest, _)) = bytes::tag::<_, _, ()>(op.symbol())(i) {
+            return Ok((rest, op));
+        }
+    }
+
+    Err(nom::Err::Error(VerboseError::from_error_kind(i, ErrorKind::Tag)))
+}
+
+fn expected_expression(i: &[u8]) -> PResult<'_, Expr> {
+    Err(nom::Err::Error(VerboseError {
+        errors: vec![(i, VerboseErrorKind::Context("expected an expression"))],
+    }))
 }
 
-fn complex_value(i: &[u8]) -> IResult<&[u8], String> {
+/// `1`, `1.`, `1.5`, `.5`, `1e-3`
+fn number(i: &[u8]) -> PResult<'_, String> {
     map(
-        many1(ws(alt((
-            simple_value,
-            map(bytes::is_a("+-*/%<>=!&|"), |b: &[u8]| {
-                String::from_utf8(b.to_owned()).unwrap()
-            }),
-            map(args, |args| format!("({})", args.join(", "))),
-        )))),
-        |parts| parts.join(""),
+        recognize(tuple((
+            alt((
+                recognize(tuple((
+                    character::digit1,
+                    opt(tuple((character::char('.'), character::digit0))),
+                ))),
+                recognize(tuple((character::char('.'), character::digit1))),
+            )),
+            opt(tuple((
+                character::one_of("eE"),
+                opt(character::one_of("+-")),
+                character::digit1,
+            ))),
+        ))),
+        |b: &[u8]| String::from_utf8_lossy(b).into_owned(),
     )(i)
 }
 
-fn simple_value(i: &[u8]) -> IResult<&[u8], String> {
+/// A `"quoted"` string, returned without the quotes
+fn string(i: &[u8]) -> PResult<'_, String> {
     map(
-        bytes::take_while1(|b| {
-            is_alphanumeric(b) || b == b'$' || b == b'.' || b == b'_' || b == b' '
-        }),
-        |b: &[u8]| std::str::from_utf8(b).unwrap().trim().to_owned(),
+        preceded(
+            character::char('"'),
+            cut(terminated(
+                bytes::take_till(|b| b == b'"' || b == b'\n'),
+                character::char('"'),
+            )),
+        ),
+        |b: &[u8]| String::from_utf8_lossy(b).into_owned(),
     )(i)
 }
 
-fn ident(i: &[u8]) -> IResult<&[u8], String> {
+fn ident(i: &[u8]) -> PResult<'_, String> {
     map(
         tuple((
             peek(verify(bytes::take(1usize), |b: &[u8]| is_alphabetic(b[0]))),
@@ -74,30 +379,85 @@ fn ident(i: &[u8]) -> IResult<&[u8], String> {
     )(i)
 }
 
-fn args(i: &[u8]) -> IResult<&[u8], Vec<String>> {
-    delimited(
-        ws(character::char('(')),
-        separated_list(ws(character::char(',')), complex_value),
-        ws(character::char(')')),
+/// `// line` or `/* block */` comment, returned as written
+fn comment(i: &[u8]) -> PResult<'_, String> {
+    map(
+        alt((
+            recognize(preceded(
+                bytes::tag("//"),
+                bytes::take_while(|b| b != b'\n'),
+            )),
+            recognize(preceded(
+                bytes::tag("/*"),
+                context(
+                    "unterminated block comment",
+                    cut(terminated(bytes::take_until("*/"), bytes::tag("*/"))),
+                ),
+            )),
+        )),
+        |b: &[u8]| String::from_utf8_lossy(b).trim_end_matches('\r').to_owned(),
     )(i)
 }
 
-fn ws<'a, O, E, P>(parser: P) -> impl Fn(&'a [u8]) -> IResult<&'a [u8], O, E>
-where
-    E: nom::error::ParseError<&'a [u8]>,
-    P: Fn(&'a [u8]) -> IResult<&'a [u8], O, E>,
-{
-    delimited(
-        bytes::take_while(|b| b == b' ' || b == b'\t' || b == b'\n'),
-        parser,
-        bytes::take_while(|b| b == b' ' || b == b'\t' || b == b'\n'),
-    )
+/// A comment on the same line as the end of the preceding statement
+fn trailing_comment(i: &[u8]) -> PResult<'_, String> {
+    preceded(bytes::take_while(|b| b == b' ' || b == b'\t'), comment)(i)
+}
+
+/// Whitespace and comments, collecting the comments
+fn trivia(i: &[u8]) -> PResult<'_, Vec<String>> {
+    terminated(
+        many0(preceded(bytes::take_while(is_ws), comment)),
+        bytes::take_while(is_ws),
+    )(i)
+}
+
+fn is_ws(b: u8) -> bool {
+    b == b' ' || b == b'\t' || b == b'\n' || b == b'\r'
+}
+
+/// Skips whitespace and comments
+fn ws0(i: &[u8]) -> PResult<'_, ()> {
+    map(trivia, |_| ())(i)
+}
+
+/// Skips whitespace inside arguments, which are printed from the parsed expression, so comments there would be lost
+fn arg_ws(i: &[u8]) -> PResult<'_, ()> {
+    let (rest, _) = bytes::take_while(is_ws)(i)?;
+
+    match comment(rest) {
+        Err(nom::Err::Error(_)) => Ok((rest, ())),
+        _ => Err(nom::Err::Failure(VerboseError {
+            errors: vec![(rest, VerboseErrorKind::Context("comments are not allowed inside arguments"))],
+        })),
+    }
 }
 
 #[cfg(test)]
-mod test {
+pub(crate) mod test {
     use super::*;
 
+    /// Statements of a scene that has no includes
+    pub(crate) fn scene(src: &[u8]) -> Result<Vec<Statement>, ParseError> {
+        scene_file(src).map(|file| file.statements)
+    }
+
+    fn statement(i: &[u8]) -> PResult<'_, Statement> {
+        Parser { src: i, file: 0 }.statement(i)
+    }
+
+    fn block_body(i: &[u8]) -> PResult<'_, Vec<Statement>> {
+        map(|i| Parser { src: i, file: 0 }.block_body(i), |(body, _)| body)(i)
+    }
+
+    fn args(i: &[u8]) -> PResult<'_, Vec<Spanned<Expr>>> {
+        Parser { src: i, file: 0 }.args(i)
+    }
+
+    fn expr(i: &[u8]) -> PResult<'_, Spanned<Expr>> {
+        Parser { src: i, file: 0 }.expr(i)
+    }
+
     #[test]
     fn test_ident() {
         assert_eq!(ident(b"abcde").unwrap().1, "abcde");
@@ -107,27 +467,35 @@ mod test {
     }
 
     #[test]
-    fn test_simple_value() {
-        assert_eq!(simple_value(b"1.0").unwrap().1, "1.0");
-        assert_eq!(simple_value(b"hello").unwrap().1, "hello");
-        assert!(simple_value(b"()").is_err());
-    }
+    fn test_expr() {
+        assert_eq!(expr(b"1.0").unwrap().1, "1.0");
+        assert_eq!(expr(b"hello").unwrap().1.node, Expr::Ident(String::from("hello")));
+        assert!(expr(b"()").is_err());
 
-    #[test]
-    fn test_complex_value() {
-        assert_eq!(complex_value(b"vec3(1,2,3)").unwrap().1, "vec3(1, 2, 3)");
-        assert_eq!(
-            complex_value(b"vec3(1,2,vec2(1))").unwrap().1,
-            "vec3(1, 2, vec2(1))"
-        );
-        assert_eq!(
-            complex_value(b"vec3(1, 2, vec2(1))").unwrap().1,
-            "vec3(1, 2, vec2(1))"
-        );
-        assert_eq!(
-            complex_value(b"vec3(1 , 2 , vec2(1))").unwrap().1,
-            "vec3(1, 2, vec2(1))"
-        );
+        assert_eq!(expr(b"vec3(1,2,3)").unwrap().1, "vec3(1, 2, 3)");
+        assert_eq!(expr(b"vec3(1 , 2 , vec2(1))").unwrap().1, "vec3(1, 2, vec2(1))");
+
+        assert_eq!(expr(b"1+2*3").unwrap().1, "1 + 2 * 3");
+        assert_eq!(expr(b"(1+2)*3").unwrap().1, "(1 + 2) * 3");
+        assert_eq!(expr(b"(a - b) - c").unwrap().1, "a - b - c");
+        assert_eq!(expr(b"a - (b - c)").unwrap().1, "a - (b - c)");
+        assert_eq!(expr(b"$t > 0 && $t <= 5 || !x").unwrap().1, "$t > 0 && $t <= 5 || !x");
+        assert_eq!(expr(b"- -x").unwrap().1, "-(-x)");
+        assert_eq!(expr(b"-(a + b).x").unwrap().1, "-(a + b).x");
+
+        let (_, e) = expr(b"-$t/3 + length($p.xz) * 1e-3").unwrap();
+        assert_eq!(e.glsl("arg").to_string(), "-arg.t / 3.0 + length(arg.p.xz) * 1e-3");
+
+        match &expr(b"1 + 2 * 3").unwrap().1.node {
+            Expr::Binary(BinaryOp::Add, lhs, rhs) => {
+                assert_eq!(lhs.span, Span::new(0, 1));
+                assert_eq!(rhs.span, Span::new(4, 9));
+            }
+            e => panic!("unexpected expression: {:?}", e),
+        }
+
+        assert_eq!(expr(b"$t < 1 ? a : b ? c : d").unwrap().1, "$t < 1 ? a : b ? c : d");
+        assert_eq!(expr(b"(a ? b : c) ? d : e").unwrap().1, "(a ? b : c) ? d : e");
     }
 
     #[test]
@@ -140,6 +508,11 @@ mod test {
             args(b"(1, hello, vec3(5))").unwrap().1,
             &["1", "hello", "vec3(5)"]
         );
+
+        let parsed = args(b"(vec3 size, +, \"lib.scene\")").unwrap().1;
+        assert_eq!(parsed[0].node, Expr::Declaration(String::from("vec3"), String::from("size")));
+        assert_eq!(parsed[1].node, Expr::Symbol(String::from("+")));
+        assert_eq!(parsed[2].node, Expr::String(String::from("lib.scene")));
     }
 
     #[test]
@@ -205,4 +578,90 @@ mod test {
         let body = all_consuming(block_body)(b"{ hello{}; world{}; }").unwrap().1;
         assert_eq!(body.len(), 2);
     }
+
+    #[test]
+    fn test_spans() {
+        let src = b"at(1, 2,3) {\n    cube(vec3(1) );\n}\n";
+        let stmts = scene(src).unwrap();
+        assert_eq!(stmts[0].span, Span::new(0, src.len() - 1));
+        assert_eq!(stmts[0].args[0].span, Span::new(3, 4));
+        assert_eq!(stmts[0].args[1].span, Span::new(6, 7));
+        assert_eq!(stmts[0].args[2].span, Span::new(8, 9));
+
+        let cube = &stmts[0].body[0];
+        assert_eq!(cube.span, Span::new(17, 32));
+        assert_eq!(cube.args[0].span, Span::new(22, 29));
+    }
+
+    #[test]
+    fn test_comments() {
+        let src = b"// scene\r\nunion { // shapes\r\n    cube(); /* trailing */\r\n    /* before */ sphere(1, 2) // after\r\n    // end\r\n}\r\n// eof\r\n";
+        let file = scene_file(src).unwrap();
+        assert_eq!(file.comments, &["// eof"]);
+
+        let union = &file.statements[0];
+        assert_eq!(union.comments, &["// scene"]);
+        assert_eq!(union.closing_comments, &["// end"]);
+        assert_eq!(union.body[0].comments, &["// shapes"]);
+        assert_eq!(union.body[0].trailing_comment.as_deref(), Some("/* trailing */"));
+        assert_eq!(union.body[1].comments, &["/* before */"]);
+        assert_eq!(union.body[1].args, &["1", "2"]);
+        assert_eq!(union.body[1].trailing_comment.as_deref(), Some("// after"));
+
+        let stmt = all_consuming(statement)(b"at(1, 2, 3) /* a */ // b\n cube()").unwrap().1;
+        assert_eq!(stmt.to_string(), "at(1, 2, 3){\n/* a */\n\n// b\ncube(){}}");
+        assert_eq!(stmt.body[0].comments, &["/* a */", "// b"]);
+
+        assert_eq!(expr(b"\"a // b.scene\"").unwrap().1, "\"a // b.scene\"");
+        assert_eq!(expr(b"$p.x/2").unwrap().1, "$p.x / 2");
+
+        let stmts = scene(b"camera {\n    // start\n    keyframe(0) { pos(0, 0, 0) }\n}").unwrap();
+        assert_eq!(stmts[0].body[0].comments, &["// start"]);
+    }
+
+    #[test]
+    fn test_print_comments() {
+        let src = "// scene\nunion { // shapes\n    cube(); /* trailing */\n    /* before */ sphere(1, 2) // after\n    // end\n}\n// eof\n";
+        let file = scene_file(src.as_bytes()).unwrap();
+        let printed = file.to_string();
+        assert_eq!(scene_file(printed.as_bytes()).unwrap(), file);
+        assert_eq!(printed.matches("//").count(), 5);
+        assert_eq!(printed.matches("/*").count(), 2);
+    }
+
+    #[test]
+    fn test_errors() {
+        let err = scene(b"at(1, 2 { cube() }").unwrap_err();
+        assert_eq!(err.message, "expected `)`");
+        assert_eq!(err.span, Span::new(8, 9));
+
+        let err = scene(b"union {\n    cube();\n").unwrap_err();
+        assert_eq!(err.message, "expected `}`");
+        assert_eq!(err.span, Span::point(20));
+
+        let err = scene(b"cube(); )").unwrap_err();
+        assert_eq!(err.message, "expected a statement");
+        assert_eq!(err.span, Span::new(8, 9));
+
+        let err = scene(b"at(1 +, 2, 3);").unwrap_err();
+        assert_eq!(err.message, "expected an expression");
+        assert_eq!(err.span, Span::new(6, 7));
+
+        let err = scene(b"at(1, (2, 3);").unwrap_err();
+        assert_eq!(err.message, "expected `)`");
+        assert_eq!(err.span, Span::new(8, 9));
+
+        let err = scene(b"at(1, /* x */ 2, 3);").unwrap_err();
+        assert_eq!(err.message, "comments are not allowed inside arguments");
+        assert_eq!(err.span, Span::new(6, 7));
+
+        let err = scene(b"sphere(1 // radius\n);").unwrap_err();
+        assert_eq!(err.message, "comments are not allowed inside arguments");
+        assert_eq!(err.span, Span::new(9, 10));
+        assert_eq!(scene(b"at($p.x/*x*/*2);").unwrap_err().span, Span::new(7, 8));
+
+        let err = scene(b"cube(); /* open").unwrap_err();
+        assert_eq!(err.message, "unterminated block comment");
+        assert_eq!(err.span, Span::new(10, 11));
+    }
 }
@@ -0,0 +1,268 @@
+use std::fmt;
+use std::ops::Deref;
+use std::path::PathBuf;
+
+/// Index of a file in a [`SourceMap`]
+pub type FileId = usize;
+
+/// Byte range inside a scene file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+    pub file: FileId,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Self {
+        Span { start, end, file: 0 }
+    }
+
+    pub fn point(at: usize) -> Self {
+        Span::new(at, at)
+    }
+
+    pub fn in_file(self, file: FileId) -> Self {
+        Span { file, ..self }
+    }
+}
+
+/// A value together with the place in the source it came from.
+///
+/// Comparison only looks at the value, so trees that differ only in layout are equal.
+#[derive(Debug, Clone, Default)]
+pub struct Spanned<T> {
+    pub node: T,
+    pub span: Span,
+}
+
+impl<T> Spanned<T> {
+    pub fn new(node: T, span: Span) -> Self {
+        Spanned { node, span }
+    }
+}
+
+impl<T> Deref for Spanned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.node
+    }
+}
+
+impl<T: PartialEq> PartialEq for Spanned<T> {
+    fn eq(&self, other: &Self) -> bool {
+        self.node == other.node
+    }
+}
+
+/// Compares the printed value
+impl<T: fmt::Display> PartialEq<str> for Spanned<T> {
+    fn eq(&self, other: &str) -> bool {
+        self.node.to_string() == other
+    }
+}
+
+impl<'a, T: fmt::Display> PartialEq<&'a str> for Spanned<T> {
+    fn eq(&self, other: &&'a str) -> bool {
+        self.node.to_string() == *other
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for Spanned<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.node.fmt(f)
+    }
+}
+
+#[derive(Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub text: String,
+}
+
+impl SourceFile {
+    pub fn new(path: impl Into<PathBuf>, text: &[u8]) -> Self {
+        SourceFile {
+            path: path.into(),
+            text: String::from_utf8_lossy(text).into_owned(),
+        }
+    }
+
+    /// One-based line and column of a byte offset
+    pub fn line_col(&self, offset: usize) -> (usize, usize) {
+        let offset = offset.min(self.text.len());
+        let before = &self.text.as_bytes()[..offset];
+
+        let line = before.iter().filter(|&&b| b == b'\n').count() + 1;
+        let line_start = before
+            .iter()
+            .rposition(|&b| b == b'\n')
+            .map(|x| x + 1)
+            .unwrap_or(0);
+        let col = String::from_utf8_lossy(&before[line_start..]).chars().count() + 1;
+
+        (line, col)
+    }
+
+    pub fn diagnostic(&self, span: Span, message: impl ToString) -> Diagnostic {
+        let (line, col) = self.line_col(span.start);
+
+        let line_text = self
+            .text
+            .lines()
+            .nth(line - 1)
+            .unwrap_or("")
+            .trim_end_matches('\r')
+            .to_owned();
+
+        // only the first line of a multiline span gets underlined
+        let first_line_rest = line_text.chars().count().saturating_sub(col - 1);
+        let width = self
+            .text
+            .get(span.start..span.end.min(self.text.len()))
+            .unwrap_or("")
+            .chars()
+            .take_while(|&c| c != '\n')
+            .count()
+            .min(first_line_rest)
+            .max(1);
+
+        Diagnostic {
+            path: self.path.clone(),
+            line,
+            col,
+            width,
+            line_text,
+            message: message.to_string(),
+            notes: Vec::new(),
+        }
+    }
+}
+
+/// All files that make up a scene, remembering which `include` pulled each of them in
+#[derive(Debug, Default)]
+pub struct SourceMap {
+    files: Vec<(SourceFile, Option<Span>)>,
+}
+
+impl SourceMap {
+    pub fn add(&mut self, file: SourceFile, included_from: Option<Span>) -> FileId {
+        self.files.push((file, included_from));
+        self.files.len() - 1
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id].0
+    }
+
+    /// The `include` statements that lead to a file, starting with the one in the scene file itself
+    pub fn include_chain(&self, file: FileId) -> Vec<Span> {
+        let mut chain = Vec::new();
+
+        let mut included_from = self.files[file].1;
+        while let Some(span) = included_from {
+            chain.insert(0, span);
+            included_from = self.files[span.file].1;
+        }
+
+        chain
+    }
+
+    pub fn diagnostic(&self, span: Span, message: impl ToString) -> Diagnostic {
+        let mut diagnostic = self.get(span.file).diagnostic(span, message);
+
+        let mut included_from = self.files[span.file].1;
+        while let Some(span) = included_from {
+            let (line, col) = self.get(span.file).line_col(span.start);
+            diagnostic.notes.push(format!(
+                "included from {}:{}:{}",
+                self.get(span.file).path.display(),
+                line,
+                col
+            ));
+            included_from = self.files[span.file].1;
+        }
+
+        diagnostic
+    }
+}
+
+/// An error message attached to a `file:line:col` location
+#[derive(Debug, Clone)]
+pub struct Diagnostic {
+    pub path: PathBuf,
+    pub line: usize,
+    pub col: usize,
+    pub width: usize,
+    pub line_text: String,
+    pub message: String,
+    pub notes: Vec<String>,
+}
+
+impl fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let gutter = self.line.to_string().len();
+        let indent = self
+            .line_text
+            .chars()
+            .take(self.col - 1)
+            .map(|c| if c == '\t' { '\t' } else { ' ' })
+            .collect::<String>();
+
+        writeln!(f, "error: {}", self.message)?;
+        writeln!(f, "{:gutter$}--> {}:{}:{}", "", self.path.display(), self.line, self.col, gutter = gutter)?;
+        writeln!(f, "{:gutter$} |", "", gutter = gutter)?;
+        writeln!(f, "{} | {}", self.line, self.line_text)?;
+        write!(f, "{:gutter$} | {}{}", "", indent, "^".repeat(self.width), gutter = gutter)?;
+
+        for note in &self.notes {
+            write!(f, "\n{:gutter$} = note: {}", "", note, gutter = gutter)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_line_col() {
+        let file = SourceFile::new("test.scene", b"a\nbc\n\td");
+        assert_eq!(file.line_col(0), (1, 1));
+        assert_eq!(file.line_col(3), (2, 2));
+        assert_eq!(file.line_col(6), (3, 2));
+        assert_eq!(file.line_col(100), (3, 3));
+    }
+
+    #[test]
+    fn test_diagnostic() {
+        let file = SourceFile::new("test.scene", b"union {\n    at(1, 2 { cube() }\n}");
+        let diagnostic = file.diagnostic(Span::new(16, 17), "expected `)`");
+
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: expected `)`\n --> test.scene:2:9\n  |\n2 |     at(1, 2 { cube() }\n  |         ^"
+        );
+    }
+
+    #[test]
+    fn test_included_diagnostic() {
+        let mut sources = SourceMap::default();
+        let root = sources.add(SourceFile::new("root.scene", b"\ninclude(\"lib.scene\")"), None);
+        let lib = sources.add(
+            SourceFile::new("lib.scene", b"cube(;"),
+            Some(Span::new(9, 20).in_file(root)),
+        );
+        let diagnostic = sources.diagnostic(Span::new(5, 6).in_file(lib), "expected `)`");
+
+        assert_eq!(
+            diagnostic.to_string(),
+            "error: expected `)`\n --> lib.scene:1:6\n  |\n1 | cube(;\n  |      ^\n  = note: included from root.scene:2:9"
+        );
+    }
+}
@@ -1,40 +1,114 @@
 use std::fmt::Debug;
 
+use super::check::Type;
 use super::codegen as glsl;
+use super::eval::{library, Env, Eval, EvalError, Value};
+use super::expr::{BinaryOp, Expr};
 
+pub mod bound;
+pub mod branch;
 pub mod fold;
 pub mod geometry;
+pub mod geometry2d;
+pub mod located;
 pub mod opaque;
 pub mod transparent;
 pub mod traits;
 pub mod transform;
 
+pub use bound::*;
+pub use branch::*;
 pub use fold::*;
 pub use geometry::*;
+pub use geometry2d::*;
+pub use located::*;
 pub use opaque::*;
 pub use traits::*;
 pub use transform::*;
 pub use transparent::*;
 
+#[derive(Clone)]
 pub struct Context {
     arg: String,
+    /// `let` bindings visible here, innermost last, with the variable each one got in the generated code
+    names: Vec<(String, String)>,
+    /// How far the operations around the object can still move its surface, in the units of its own distance, `None`
+    /// when that isn't known and nothing in it can be culled
+    offset: Option<f64>,
 }
 
 impl Context {
     pub fn new() -> Self {
         Context {
             arg: String::from("arg"),
+            names: Vec::new(),
+            offset: Some(0.0),
         }
     }
 
-    fn with_arg(arg: String) -> Self {
-        Context { arg }
+    /// Context of a `define_*` body, which may be called under anything, so none of it is culled
+    pub fn definition() -> Self {
+        Context {
+            offset: None,
+            ..Context::new()
+        }
+    }
+
+    fn with_arg(&self, arg: String) -> Self {
+        Context { arg, ..self.clone() }
+    }
+
+    fn with_name(&self, name: String, ident: String) -> Self {
+        let mut ctx = self.clone();
+        ctx.names.push((name, ident));
+
+        ctx
+    }
+
+    /// Context of an object whose surface is then moved by up to `by`
+    fn offset_by(&self, by: Option<f64>) -> Self {
+        Context {
+            offset: self.offset.and_then(|offset| Some(offset + by?.abs())),
+            ..self.clone()
+        }
+    }
+
+    /// Context of an object whose distance is then multiplied by `factor`
+    fn scaled_by(&self, factor: Option<f64>) -> Self {
+        Context {
+            offset: self.offset.and_then(|offset| Some(offset / factor?.abs())),
+            ..self.clone()
+        }
+    }
+
+    /// Context of an object whose distance is then divided by something unknown, only an exact sign still holds
+    fn distorted(&self) -> Self {
+        Context {
+            offset: self.offset.filter(|&offset| offset == 0.0),
+            ..self.clone()
+        }
+    }
+
+    /// Scene expression as GLSL, at this point of the tree
+    fn expr(&self, expr: &Expr) -> glsl::ArgExpr {
+        glsl::ArgExpr::scoped(expr, &self.arg, &self.names, true)
+    }
+
+    /// Scene expression giving a value of the given type, which only has `float` literals if the type is one the
+    /// checker knows
+    fn value(&self, expr: &Expr, typ: &Type) -> glsl::ArgExpr {
+        glsl::ArgExpr::scoped(expr, &self.arg, &self.names, !typ.is_other())
+    }
+
+    fn glsl<'a>(&'a self, expr: &'a Expr) -> impl std::fmt::Display + 'a {
+        expr.glsl_scoped(&self.arg, &self.names, true)
     }
 }
 
 #[derive(Debug, Clone, Copy)]
 pub enum TypeMarker {
     Geometry(GeometryMarker),
+    Geometry2d(Geometry2dMarker),
     Opaque(OpaqueMarker),
     Transparent(TransparentMarker),
 }
@@ -45,10 +119,42 @@ impl TypeMarker {
     pub fn typ(&self) -> &'static str {
         match self {
             TypeMarker::Geometry(_) => "float",
-            TypeMarker::Opaque(_) => "vec4",
+            TypeMarker::Geometry2d(_) => "float2d",
+            TypeMarker::Opaque(_) => "MapOpaque",
             TypeMarker::Transparent(_) => "MapTransparent",
         }
     }
+
+    /// Object at the given distance, with no color of its own
+    pub fn with_distance(&self, d: &str) -> String {
+        match self {
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => d.to_owned(),
+            TypeMarker::Opaque(_) => format!("map_opaque(vec3(0), {})", d),
+            TypeMarker::Transparent(_) => format!("MapTransparent(vec4(0), {})", d),
+        }
+    }
+
+    /// Value of `with_distance` on the CPU
+    pub fn value_with_distance(&self, d: f64) -> Value {
+        match self {
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => Value::Float(d),
+            TypeMarker::Opaque(_) => {
+                Value::new_struct("MapOpaque", vec![library::diffuse_material([0.0; 3]), Value::Float(d)]).unwrap()
+            }
+            TypeMarker::Transparent(_) => {
+                Value::new_struct("MapTransparent", vec![Value::Vec(4, [0.0; 4]), Value::Float(d)]).unwrap()
+            }
+        }
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TypeMarker::Geometry(_) => "geometry",
+            TypeMarker::Geometry2d(_) => "2D geometry",
+            TypeMarker::Opaque(_) => "opaque",
+            TypeMarker::Transparent(_) => "transparent",
+        }
+    }
 }
 
 impl From<GeometryMarker> for TypeMarker {
@@ -57,6 +163,12 @@ impl From<GeometryMarker> for TypeMarker {
     }
 }
 
+impl From<Geometry2dMarker> for TypeMarker {
+    fn from(m: Geometry2dMarker) -> Self {
+        TypeMarker::Geometry2d(m)
+    }
+}
+
 impl From<OpaqueMarker> for TypeMarker {
     fn from(m: OpaqueMarker) -> Self {
         TypeMarker::Opaque(m)
@@ -71,82 +183,268 @@ impl From<TransparentMarker> for TypeMarker {
 
 #[derive(Debug, Clone, Copy)]
 pub struct Union;
-#[derive(Debug, Clone, Copy)]
-pub struct Isect;
-#[derive(Debug, Clone, Copy)]
-pub struct Diff;
+/// Intersection, `args` optionally holding whether cut faces take the color of the cutter
+#[derive(Debug, Clone)]
+pub struct Isect {
+    pub args: Vec<Expr>
+}
+/// Difference, `args` optionally holding whether cut faces take the color of the cutter
+#[derive(Debug, Clone)]
+pub struct Diff {
+    pub args: Vec<Expr>
+}
 #[derive(Debug, Clone)]
 pub struct SmoothUnion {
-    pub args: Vec<String>
+    pub args: Vec<Expr>
 }
 
 impl IFunc for Union {
     fn name(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "sd_union",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "sd_union",
             TypeMarker::Opaque(_) => "csd_union",
             TypeMarker::Transparent(_) => "tsd_union",
         }
     }
     fn id(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "1.0/0.0",
-            TypeMarker::Opaque(_) => "vec4(0,0,0, 1.0/0.0)",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "1.0/0.0",
+            TypeMarker::Opaque(_) => "map_opaque(vec3(0), 1.0/0.0)",
             TypeMarker::Transparent(_) => "MapTransparent(vec4(0), 1.0/0.0)",
         }
     }
+    fn bound(&self, items: &[Option<Bound>]) -> Option<Bound> {
+        let (first, rest) = items.split_first()?;
+        rest.iter().try_fold((*first)?, |acc, item| Some(acc.union((*item)?)))
+    }
+    fn culls_items(&self) -> bool {
+        true
+    }
 }
 
 impl IFunc for Isect {
     fn name(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "sd_isect",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "sd_isect",
             TypeMarker::Opaque(_) => "csd_isect",
             TypeMarker::Transparent(_) => "tsd_isect",
         }
     }
     fn id(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "0.0",
-            TypeMarker::Opaque(_) => "vec4(0,0,0, 0.0)",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "0.0",
+            TypeMarker::Opaque(_) => "map_opaque(vec3(0), 0.0)",
             TypeMarker::Transparent(_) => "MapTransparent(vec4(0), 0.0)",
         }
     }
+    fn extra_args(&self, typ: TypeMarker) -> &[Expr] {
+        match typ {
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => &[],
+            _ => &self.args,
+        }
+    }
+    fn id_distance(&self) -> f64 {
+        0.0
+    }
+    /// The intersection is inside each of the objects, so the smallest known bound will do
+    fn bound(&self, items: &[Option<Bound>]) -> Option<Bound> {
+        items
+            .iter()
+            .flatten()
+            .min_by(|a, b| a.radius.partial_cmp(&b.radius).unwrap())
+            .map(|bound| bound.compound())
+    }
 }
 
 impl IFunc for Diff {
     fn name(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "sd_diff",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "sd_diff",
             TypeMarker::Opaque(_) => "csd_diff",
             TypeMarker::Transparent(_) => "tsd_diff",
         }
     }
     fn id(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "1.0/0.0",
-            TypeMarker::Opaque(_) => "vec4(0,0,0, 1.0/0.0)",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "1.0/0.0",
+            TypeMarker::Opaque(_) => "map_opaque(vec3(0), 1.0/0.0)",
             TypeMarker::Transparent(_) => "MapTransparent(vec4(0), 1.0/0.0)",
         }
     }
+    fn extra_args(&self, typ: TypeMarker) -> &[Expr] {
+        match typ {
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => &[],
+            _ => &self.args,
+        }
+    }
+    fn bound(&self, items: &[Option<Bound>]) -> Option<Bound> {
+        items.first().copied().flatten().map(|bound| bound.compound())
+    }
 }
 
 impl IFunc for SmoothUnion {
     fn name(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "sd_smooth_union",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "sd_smooth_union",
             TypeMarker::Opaque(_) => "csd_smooth_union",
             TypeMarker::Transparent(_) => "tsd_smooth_union",
         }
     }
     fn id(&self, typ: TypeMarker) -> &'static str {
         match typ {
-            TypeMarker::Geometry(_) => "1.0/0.0",
-            TypeMarker::Opaque(_) => "vec4(0,0,0, 1.0/0.0)",
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => "1.0/0.0",
+            TypeMarker::Opaque(_) => "map_opaque(vec3(0), 1.0/0.0)",
             TypeMarker::Transparent(_) => "MapTransparent(vec4(0), 1.0/0.0)",
         }
     }
-    fn extra_args(&self) -> &[String] {
+    fn extra_args(&self, _typ: TypeMarker) -> &[Expr] {
         &self.args
     }
+    /// Blending moves the surface by at most a quarter of `k`
+    fn bound(&self, items: &[Option<Bound>]) -> Option<Bound> {
+        let k = self.args[0].constant()?;
+        Union.bound(items).map(|bound| bound.grown(k / 4.0))
+    }
+    fn offset(&self) -> Option<f64> {
+        Some(self.args[0].constant()? / 4.0)
+    }
+}
+
+/// How an operator combines the objects, which gives its identity and its bound
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Combination {
+    /// Union that can add material up to its first argument away from the objects
+    Union,
+    /// Only keeps material of the intersection
+    Isect,
+    /// Only keeps material of the first object
+    Diff,
+}
+
+/// Operator from hg_sdf, implemented for each type by a function of `library.glsl`
+#[derive(Debug)]
+pub struct Operator {
+    pub statement: &'static str,
+    pub arity: usize,
+    /// Functions for geometry, which 2D shapes use too, opaque and transparent objects
+    pub functions: [&'static str; 3],
+    pub combination: Combination,
+}
+
+pub const OPERATORS: &[Operator] = &[
+    Operator {
+        statement: "smooth_intersection",
+        arity: 1,
+        functions: ["sd_smooth_isect", "csd_smooth_isect", "tsd_smooth_isect"],
+        combination: Combination::Isect,
+    },
+    Operator {
+        statement: "smooth_difference",
+        arity: 1,
+        functions: ["sd_smooth_diff", "csd_smooth_diff", "tsd_smooth_diff"],
+        combination: Combination::Diff,
+    },
+    Operator {
+        statement: "chamfer_union",
+        arity: 1,
+        functions: ["sd_chamfer_union", "csd_chamfer_union", "tsd_chamfer_union"],
+        combination: Combination::Union,
+    },
+    Operator {
+        statement: "round_union",
+        arity: 1,
+        functions: ["sd_round_union", "csd_round_union", "tsd_round_union"],
+        combination: Combination::Union,
+    },
+    Operator {
+        statement: "round_intersection",
+        arity: 1,
+        functions: ["sd_round_isect", "csd_round_isect", "tsd_round_isect"],
+        combination: Combination::Isect,
+    },
+    Operator {
+        statement: "round_difference",
+        arity: 1,
+        functions: ["sd_round_diff", "csd_round_diff", "tsd_round_diff"],
+        combination: Combination::Diff,
+    },
+    Operator {
+        statement: "stairs_union",
+        arity: 2,
+        functions: ["sd_stairs_union", "csd_stairs_union", "tsd_stairs_union"],
+        combination: Combination::Union,
+    },
+    Operator {
+        statement: "columns_union",
+        arity: 2,
+        functions: ["sd_columns_union", "csd_columns_union", "tsd_columns_union"],
+        combination: Combination::Union,
+    },
+    Operator {
+        statement: "pipe",
+        arity: 1,
+        functions: ["sd_pipe", "csd_pipe", "tsd_pipe"],
+        combination: Combination::Union,
+    },
+    Operator {
+        statement: "groove",
+        arity: 2,
+        functions: ["sd_groove", "csd_groove", "tsd_groove"],
+        combination: Combination::Diff,
+    },
+];
+
+impl Operator {
+    pub fn find(statement: &str) -> Option<&'static Operator> {
+        OPERATORS.iter().find(|op| op.statement == statement)
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct OperatorFunc {
+    pub op: &'static Operator,
+    pub args: Vec<Expr>,
+}
+
+impl IFunc for OperatorFunc {
+    fn name(&self, typ: TypeMarker) -> &'static str {
+        match typ {
+            TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => self.op.functions[0],
+            TypeMarker::Opaque(_) => self.op.functions[1],
+            TypeMarker::Transparent(_) => self.op.functions[2],
+        }
+    }
+    fn id(&self, typ: TypeMarker) -> &'static str {
+        match self.op.combination {
+            Combination::Isect => Isect { args: Vec::new() }.id(typ),
+            Combination::Union | Combination::Diff => Union.id(typ),
+        }
+    }
+    fn extra_args(&self, _typ: TypeMarker) -> &[Expr] {
+        &self.args
+    }
+    fn id_distance(&self) -> f64 {
+        match self.op.combination {
+            Combination::Isect => Isect { args: Vec::new() }.id_distance(),
+            Combination::Union | Combination::Diff => Union.id_distance(),
+        }
+    }
+    /// Joints are no further than the first argument from the objects
+    fn bound(&self, items: &[Option<Bound>]) -> Option<Bound> {
+        match self.op.combination {
+            Combination::Union => {
+                let r = self.args[0].constant()?;
+                Union.bound(items).map(|bound| bound.grown(r))
+            }
+            Combination::Isect => Isect { args: Vec::new() }.bound(items),
+            Combination::Diff => Diff { args: Vec::new() }.bound(items),
+        }
+    }
+    /// Cuts reach as far as their largest argument, other joints as far as the first one
+    fn offset(&self) -> Option<f64> {
+        match self.op.combination {
+            Combination::Diff => self.args.iter().try_fold(0.0, |acc: f64, arg| Some(acc.max(arg.constant()?.abs()))),
+            Combination::Union | Combination::Isect => self.args[0].constant(),
+        }
+    }
 }
@@ -0,0 +1,328 @@
+use super::*;
+
+/// Distance to its bound past which an object isn't evaluated at all
+const CULL_MARGIN: f64 = 1.0;
+
+/// Sphere containing every point where the distance to an object is negative,
+/// in the space of the `Arg` the object gets
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Bound {
+    pub center: [f64; 3],
+    pub radius: f64,
+    /// Whether the object is a single primitive, which costs about as much as its bound
+    pub leaf: bool,
+}
+
+impl Bound {
+    pub fn sphere(center: [f64; 3], radius: f64) -> Self {
+        Bound {
+            center,
+            radius: radius.abs(),
+            leaf: true,
+        }
+    }
+
+    /// Bound of an object that is more than a single primitive
+    pub fn compound(self) -> Self {
+        Bound { leaf: false, ..self }
+    }
+
+    pub fn shifted(self, by: [f64; 3]) -> Self {
+        let [x, y, z] = self.center;
+        Bound {
+            center: [x + by[0], y + by[1], z + by[2]],
+            ..self
+        }
+    }
+
+    pub fn scaled(self, factor: f64) -> Self {
+        let [x, y, z] = self.center;
+        Bound {
+            center: [x * factor, y * factor, z * factor],
+            radius: self.radius * factor.abs(),
+            ..self
+        }
+    }
+
+    pub fn grown(self, by: f64) -> Self {
+        Bound {
+            radius: self.radius + by.abs(),
+            ..self
+        }
+    }
+
+    /// Bound of the object rotated in any way around the origin
+    pub fn rotated(self) -> Self {
+        Bound {
+            center: [0.0; 3],
+            radius: length(self.center) + self.radius,
+            ..self
+        }
+    }
+
+    /// Smallest sphere containing both bounds
+    pub fn union(self, other: Bound) -> Self {
+        let offset = sub(other.center, self.center);
+        let dist = length(offset);
+
+        if dist + other.radius <= self.radius {
+            self.compound()
+        } else if dist + self.radius <= other.radius {
+            other.compound()
+        } else {
+            let radius = (dist + self.radius + other.radius) / 2.0;
+            let along = (radius - self.radius) / dist;
+
+            Bound {
+                center: [
+                    self.center[0] + offset[0] * along,
+                    self.center[1] + offset[1] * along,
+                    self.center[2] + offset[2] * along,
+                ],
+                radius,
+                leaf: false,
+            }
+        }
+    }
+
+    /// GLSL distance from the point of the `Arg` variable to the bound
+    pub fn distance(&self, arg: &str) -> String {
+        match self.center {
+            [x, y, z] if x != 0.0 || y != 0.0 || z != 0.0 => {
+                format!("length({}.p - vec3({:?}, {:?}, {:?})) - {:?}", arg, x, y, z, self.radius)
+            }
+            _ => format!("length({}.p) - {:?}", arg, self.radius),
+        }
+    }
+
+    /// Builds the object in a block that only runs when the point is close to the bound,
+    /// far away the object is replaced by its bound. The margin grows with how much the operations around the object
+    /// move its surface, as the distance to the bound is smaller than the real one
+    pub fn cull(
+        &self,
+        ctx: &Context,
+        func: &mut glsl::Function,
+        inside: &impl MakeExpr,
+        typ: TypeMarker,
+    ) -> glsl::Expr {
+        let offset = match ctx.offset {
+            Some(offset) => offset,
+            None => return inside.make_expr(ctx, func),
+        };
+
+        let distance = func.gen_definition("float", self.distance(&ctx.arg));
+        let far = typ.with_distance(&distance);
+        let condition = format!("{} > {:?}", distance, CULL_MARGIN + offset);
+
+        let ident = func.gen_conditional(typ.typ(), condition, far, |func| inside.make_expr(ctx, func));
+        glsl::RawString::new(ident).into()
+    }
+}
+
+fn sub(a: [f64; 3], b: [f64; 3]) -> [f64; 3] {
+    [a[0] - b[0], a[1] - b[1], a[2] - b[2]]
+}
+
+pub fn length(a: [f64; 3]) -> f64 {
+    (a[0] * a[0] + a[1] * a[1] + a[2] * a[2]).sqrt()
+}
+
+/// Value of `vec3(x)` or `vec3(x, y, z)` made of constants
+pub fn constant_vec3(expr: &Expr) -> Option<[f64; 3]> {
+    match expr {
+        Expr::Call(name, args) if name == "vec3" => match &args[..] {
+            [x] => {
+                let x = x.constant()?;
+                Some([x; 3])
+            }
+            [x, y, z] => Some([x.constant()?, y.constant()?, z.constant()?]),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// Bound of a library primitive
+pub fn primitive_bound(name: &str, args: &[Expr]) -> Option<Bound> {
+    match (name, args) {
+        ("sd_sphere", [r]) => Some(Bound::sphere([0.0; 3], r.constant()?)),
+        ("sd_box", [size]) | ("sd_rounded_box", [size, _]) | ("sd_box_frame", [size, _]) => {
+            Some(Bound::sphere([0.0; 3], length(constant_vec3(size)?)))
+        }
+        ("sd_torus", [big_r, r]) => Some(Bound::sphere([0.0; 3], big_r.constant()? + r.constant()?)),
+        ("sd_capped_cylinder", [r, h]) => Some(Bound::sphere([0.0; 3], r.constant()?.hypot(h.constant()?))),
+        ("sd_capsule", [a, b, r]) => {
+            let (a, b) = (constant_vec3(a)?, constant_vec3(b)?);
+            let center = [(a[0] + b[0]) / 2.0, (a[1] + b[1]) / 2.0, (a[2] + b[2]) / 2.0];
+            let half = [b[0] - center[0], b[1] - center[1], b[2] - center[2]];
+            Some(Bound::sphere(center, length(half) + r.constant()?))
+        }
+        ("sd_capped_cone", [r1, r2, h]) => {
+            let r = r1.constant()?.max(r2.constant()?);
+            Some(Bound::sphere([0.0; 3], r.hypot(h.constant()?)))
+        }
+        ("sd_cone", [r, h]) => {
+            let h = h.constant()?;
+            Some(Bound::sphere([0.0, h / 2.0, 0.0], r.constant()?.hypot(h / 2.0)))
+        }
+        ("sd_ellipsoid", [r]) => {
+            let [x, y, z] = constant_vec3(r)?;
+            Some(Bound::sphere([0.0; 3], x.max(y).max(z)))
+        }
+        // the corners of the sections are `2 / sqrt(3)` and 2 times as far as their sides
+        ("sd_hex_prism", [r, h]) => Some(Bound::sphere([0.0; 3], (r.constant()? / 0.866_025).hypot(h.constant()?))),
+        ("sd_tri_prism", [r, h]) => Some(Bound::sphere([0.0; 3], (2.0 * r.constant()?).hypot(h.constant()?))),
+        ("sd_octahedron", [s]) => Some(Bound::sphere([0.0; 3], s.constant()?)),
+        ("sd_link", [l, big_r, r]) => Some(Bound::sphere([0.0; 3], l.constant()? + big_r.constant()? + r.constant()?)),
+        ("sd_pyramid", [s, h]) => Some(Bound::sphere([0.0; 3], (s.constant()? * 2f64.sqrt()).max(h.constant()?))),
+        // the curve stays within the triangle of its control points
+        ("sd_bezier", [a, b, c, r]) => {
+            let (a, b, c) = (constant_vec3(a)?, constant_vec3(b)?, constant_vec3(c)?);
+            let far = |p: [f64; 3]| length([p[0] - a[0], p[1] - a[1], p[2] - a[2]]);
+            Some(Bound::sphere(a, far(b).max(far(c)) + r.constant()?))
+        }
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::desc::{GeometryVisitor, OpaqueVisitor, StatementVisitor, test::parse_scene};
+    use crate::shaders::generated::parser::test::scene;
+
+    fn bound(source: &str) -> Option<Bound> {
+        let stmts = scene(source.as_bytes()).unwrap();
+        GeometryVisitor.visit_statements(&stmts).unwrap()[0].bound()
+    }
+
+    #[test]
+    fn test_bounds() {
+        assert_eq!(bound("sd_sphere(2);"), Some(Bound::sphere([0.0; 3], 2.0)));
+        assert_eq!(bound("sd_torus(2, 0.5);"), Some(Bound::sphere([0.0; 3], 2.5)));
+        assert_eq!(bound("sd_cone(3, 8);"), Some(Bound::sphere([0.0, 4.0, 0.0], 5.0)));
+        assert_eq!(
+            bound("sd_capsule(vec3(0), vec3(0, 2, 0), 0.5);"),
+            Some(Bound::sphere([0.0, 1.0, 0.0], 1.5))
+        );
+        assert_eq!(bound("at(1, -2, 3) sd_sphere(2);"), Some(Bound::sphere([1.0, -2.0, 3.0], 2.0)));
+        assert_eq!(bound("scale(2) at(1, 0, 0) sd_box(vec3(3, 0, 4));"), Some(Bound::sphere([2.0, 0.0, 0.0], 10.0)));
+        assert_eq!(bound("rotate(vec3(0, 1, 0), $t) at(3, 0, 4) sd_sphere(1);"), Some(Bound::sphere([0.0; 3], 6.0)));
+        assert_eq!(bound("at_t(1) onionize(0.1) sd_sphere(1);"), Some(Bound::sphere([0.0; 3], 1.0)));
+        assert_eq!(bound("let(float, r, 2) sd_sphere(1);"), Some(Bound::sphere([0.0; 3], 1.0)));
+
+        assert_eq!(
+            bound("union { at(-1, 0, 0) sd_sphere(1); at(2, 0, 0) sd_sphere(2); }"),
+            Some(Bound::sphere([1.0, 0.0, 0.0], 3.0).compound())
+        );
+        assert_eq!(
+            bound("smooth_union(0.4) { sd_sphere(2); sd_sphere(1); }"),
+            Some(Bound::sphere([0.0; 3], 2.1).compound())
+        );
+        assert_eq!(
+            bound("intersection { sd_halfspace(vec3(0, 1, 0)); sd_sphere(2); sd_sphere(3); }"),
+            Some(Bound::sphere([0.0; 3], 2.0).compound())
+        );
+
+        assert_eq!(bound("sd_sphere($t);"), None);
+        assert_eq!(bound("raw(length($p) - 1);"), None);
+        assert_eq!(bound("repeat(5, 0, 5) sd_sphere(1);"), None);
+        assert_eq!(bound("union { sd_sphere(1); raw(1.0); }"), None);
+        assert_eq!(bound("let(float, r, 2) sd_sphere(r);"), None);
+
+        let stmts = scene(b"opaque(1, 0, 0) at(0, 0, 5) sd_sphere(1);").unwrap();
+        let opaque = OpaqueVisitor::default().visit_statements(&stmts).unwrap();
+        assert_eq!(opaque[0].bound(), Some(Bound::sphere([0.0, 0.0, 5.0], 1.0)));
+    }
+
+    #[test]
+    fn test_culling() {
+        let desc = parse_scene(
+            b"opaque(1, 1, 1) sd_halfspace(vec3(0, 1, 0));\nopaque(1, 0, 0) at(0, 0, 10) union { sd_sphere(1); at(2, 0, 0) sd_box(vec3(1)); }\nopaque(0, 1, 0) sd_sphere(1);",
+        )
+        .unwrap();
+
+        let start = desc.fragment.find("MapOpaque map_impl").unwrap();
+        let end = desc.fragment.find("MapTransparent map_transparent_impl").unwrap();
+        let map = &desc.fragment[start..end];
+
+        // only the union is worth culling, the primitives cost as much as their bounds
+        assert_eq!(map.matches("if (").count(), 1, "{}", map);
+        assert!(map.contains("length(arg.p - vec3(1.36602540378443"), "{}", map);
+        assert!(map.contains("= map_opaque(vec3(0), def_"), "{}", map);
+    }
+
+    /// Object put in place of `INNER` in the templates, it's culled when under a union
+    const INNER: &str = "union { at(10, 0, 0) sd_sphere(1); at(12, 0, 0) sd_sphere(1); }";
+
+    /// Margin of the culling of `INNER`, if it's culled
+    fn culling_margin(template: &str) -> Option<f64> {
+        let desc = parse_scene(template.replace("INNER", INNER).as_bytes()).unwrap();
+
+        desc.fragment.lines().find_map(|line| {
+            let condition = line.trim().strip_prefix("if (def_")?.strip_suffix(") {")?;
+            condition.split(" > ").nth(1)?.parse().ok()
+        })
+    }
+
+    /// The distance to the bound is only replaced by the real one within the margin, which has to cover how far the
+    /// operations around the object move its surface
+    #[test]
+    fn test_culling_margins() {
+        assert_eq!(culling_margin("opaque(1, 1, 1) union { sd_sphere(1); INNER }"), Some(CULL_MARGIN));
+        assert_eq!(culling_margin("opaque(1, 1, 1) onionize(3) union { sd_sphere(1); INNER }"), Some(CULL_MARGIN + 3.0));
+        assert_eq!(culling_margin("opaque(1, 1, 1) smooth_union(12) { sd_sphere(1); union { sd_sphere(1); INNER } }"), Some(CULL_MARGIN + 3.0));
+        assert_eq!(culling_margin("opaque(1, 1, 1) scale(2) onionize(3) union { sd_sphere(1); INNER }"), Some(CULL_MARGIN + 3.0));
+        assert_eq!(culling_margin("opaque(1, 1, 1) onionize(3) scale(2) union { sd_sphere(1); INNER }"), Some(CULL_MARGIN + 1.5));
+        assert_eq!(culling_margin("opaque(1, 1, 1) onionize($t) union { sd_sphere(1); INNER }"), None);
+        assert_eq!(
+            culling_margin("opaque(1, 1, 1) round_union(3) { sd_sphere(1); union { sd_sphere(1); INNER } }"),
+            Some(CULL_MARGIN + 3.0)
+        );
+        assert_eq!(culling_margin("opaque(1, 1, 1) twist(1) union { sd_sphere(1); INNER }"), Some(CULL_MARGIN));
+        assert_eq!(culling_margin("opaque(1, 1, 1) round(1) twist(1) union { sd_sphere(1); INNER }"), None);
+
+        // definitions can be called under anything
+        assert_eq!(culling_margin("define_geometry(thing) union { sd_sphere(1); INNER }\nopaque(1, 1, 1) thing();"), None);
+    }
+
+    /// Exact distance to the scene, and the one the GPU gets, where `INNER` is replaced by the distance to its bound
+    /// when it's culled
+    fn culled_distance(template: &str, p: [f64; 3]) -> (f64, f64) {
+        let desc = parse_scene(template.replace("INNER", INNER).as_bytes()).unwrap();
+        let exact = desc.cpu.distance(p, 0.0).unwrap();
+
+        let bound = Bound::sphere([11.0, 0.0, 0.0], 2.0);
+        let bound_distance = length(sub(p, bound.center)) - bound.radius;
+
+        match culling_margin(template) {
+            Some(margin) if bound_distance > margin => {
+                let far = template.replace("INNER", "raw(length($p - vec3(11, 0, 0)) - 2)");
+                let culled = parse_scene(far.as_bytes()).unwrap();
+                (exact, culled.cpu.distance(p, 0.0).unwrap())
+            }
+            _ => (exact, exact),
+        }
+    }
+
+    /// Culling may get the distance wrong, but never which side of the surface a point is on
+    #[test]
+    fn test_culling_under_offsets() {
+        let templates = [
+            "opaque(1, 1, 1) onionize(3) union { sd_sphere(1); INNER }",
+            "opaque(1, 1, 1) smooth_union(12) { sd_sphere(1); union { sd_sphere(1); INNER } }",
+            "opaque(1, 1, 1) round_union(3) { sd_sphere(1); union { sd_sphere(1); INNER } }",
+            "opaque(1, 1, 1) round(3) union { sd_sphere(1); INNER }",
+            "opaque(1, 1, 1) round(2) smooth_union(4) { sd_sphere(1); union { sd_sphere(1); INNER } }",
+            "opaque(1, 1, 1) displace(3, sin($p.x)) union { sd_sphere(1); INNER }",
+        ];
+
+        for template in &templates {
+            for step in 0..40 {
+                let p = [11.0, f64::from(step) * 0.25, 0.0];
+                let (exact, culled) = culled_distance(template, p);
+                assert_eq!(exact > 0.0, culled > 0.0, "{} at {:?}: {} culled to {}", template, p, exact, culled);
+            }
+        }
+    }
+}
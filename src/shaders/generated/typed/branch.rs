@@ -0,0 +1,50 @@
+use super::*;
+
+/// One of the `items`, the first whose condition holds, or the last one when none does
+#[derive(Debug)]
+pub struct Branch<T, M> {
+    pub conditions: Vec<Expr>,
+    pub items: Vec<T>,
+    pub marker: M,
+}
+
+impl<T: IGeometry> IGeometry for Branch<T, GeometryMarker> {}
+impl<T: IGeometry2d> IGeometry2d for Branch<T, Geometry2dMarker> {}
+impl<T: IOpaqueShape> IOpaqueShape for Branch<T, OpaqueMarker> {}
+impl<T: ITransparentShape> ITransparentShape for Branch<T, TransparentMarker> {}
+
+impl<T: MakeExpr, M: ITypeMarker> MakeExpr for Branch<T, M> {
+    fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        let mut items = self.items.iter().map(|item| item.make_expr(ctx, func).to_string()).collect::<Vec<_>>();
+        let otherwise = items.pop().unwrap_or_else(|| Union.id(self.marker.into()).to_owned());
+
+        let expr = self.conditions.iter().zip(items).rev().fold(otherwise, |otherwise, (cond, item)| {
+            format!("(({}) ? ({}) : ({}))", ctx.glsl(cond), item, otherwise)
+        });
+
+        glsl::RawString::new(expr).into()
+    }
+
+    /// Any of the items can be shown
+    fn bound(&self) -> Option<Bound> {
+        Union.bound(&self.items.iter().map(|item| item.bound()).collect::<Vec<_>>())
+    }
+}
+
+impl<T: Eval, M: ITypeMarker> Eval for Branch<T, M> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut chosen = self.conditions.len();
+        for (idx, cond) in self.conditions.iter().enumerate() {
+            if env.expr(cond)?.as_bool()? {
+                chosen = idx;
+                break;
+            }
+        }
+
+        let typ: TypeMarker = self.marker.into();
+        match self.items.get(chosen) {
+            Some(item) => item.eval(env),
+            None => Ok(typ.value_with_distance(Union.id_distance())),
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use super::*;
-use glsl::{RawString, ArgString};
+use glsl::RawString;
 
 #[derive(Debug)]
 pub struct Fold<F, T, M> {
@@ -9,6 +9,7 @@ pub struct Fold<F, T, M> {
 }
 
 impl<F: IFunc, T: IGeometry> IGeometry for Fold<F, T, GeometryMarker> {}
+impl<F: IFunc, T: IGeometry2d> IGeometry2d for Fold<F, T, Geometry2dMarker> {}
 impl<F: IFunc, T: IOpaqueShape> IOpaqueShape for Fold<F, T, OpaqueMarker> {}
 impl<F: IFunc, T: ITransparentShape> ITransparentShape for Fold<F, T, TransparentMarker> {}
 
@@ -19,20 +20,64 @@ impl<F: IFunc, T: MakeExpr, M: ITypeMarker> MakeExpr for Fold<F, T, M> {
             1 => self.items[0].make_expr(ctx, func),
             _ => {
                 let func_name = self.func.name(self.marker.into());
+                let inner = ctx.offset_by(self.func.offset());
 
-                let mut expr = self.items[0].make_expr(ctx, func);
+                let mut expr = self.item_expr(&self.items[0], &inner, func);
                 for item in self.items.iter().skip(1) {
                     let mut next_expr = glsl::FunctionCall::new(func_name);
                     next_expr.push_arg(expr);
-                    next_expr.push_arg(item.make_expr(ctx, func));
-                    for extra_arg in self.func.extra_args() {
-                        next_expr.push_arg(ArgString::new(extra_arg, &ctx.arg));
+                    next_expr.push_arg(self.item_expr(item, &inner, func));
+                    for extra_arg in self.func.extra_args(self.marker.into()) {
+                        next_expr.push_arg(ctx.expr(extra_arg));
                     }
                     expr = next_expr.into()
                 }
 
-                expr.into()
+                expr
             }
         }
     }
+
+    fn bound(&self) -> Option<Bound> {
+        match self.items.len() {
+            0 => None,
+            1 => self.items[0].bound(),
+            _ => {
+                let bounds = self.items.iter().map(|item| item.bound()).collect::<Vec<_>>();
+                self.func.bound(&bounds)
+            }
+        }
+    }
+}
+
+impl<F: IFunc, T: Eval, M: ITypeMarker> Eval for Fold<F, T, M> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let typ = self.marker.into();
+        let (first, rest) = match self.items.split_first() {
+            Some(items) => items,
+            None => return Ok(typ.value_with_distance(self.func.id_distance())),
+        };
+
+        let func_name = self.func.name(typ);
+        let mut value = first.eval(env)?;
+        for item in rest {
+            let mut args = vec![value, item.eval(env)?];
+            for extra_arg in self.func.extra_args(typ) {
+                args.push(env.expr(extra_arg)?);
+            }
+            value = env.call(func_name, args)?;
+        }
+
+        Ok(value)
+    }
+}
+
+impl<F: IFunc, T: MakeExpr, M: ITypeMarker> Fold<F, T, M> {
+    /// Items that are more than a single primitive are only evaluated close to their bound, if they have one
+    fn item_expr(&self, item: &T, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        match item.bound().filter(|bound| !bound.leaf && self.func.culls_items()) {
+            Some(bound) => bound.cull(ctx, func, item, self.marker.into()),
+            None => item.make_expr(ctx, func),
+        }
+    }
 }
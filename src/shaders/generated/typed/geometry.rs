@@ -1,5 +1,5 @@
 use super::*;
-use glsl::{ArgString, RawString};
+use glsl::RawString;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct GeometryMarker;
@@ -9,7 +9,7 @@ impl ITypeMarker for GeometryMarker {}
 #[derive(Debug)]
 pub struct NamedGeometry {
     pub name: String,
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl IGeometry for NamedGeometry {}
@@ -18,24 +18,43 @@ impl MakeExpr for NamedGeometry {
     fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
         let mut func = glsl::FunctionCall::new(&self.name);
         for arg in &self.args {
-            func.push_arg(ArgString::new(arg, &ctx.arg));
+            func.push_arg(ctx.expr(arg));
         }
 
         func.push_arg(RawString::new(&ctx.arg));
 
         func.into()
     }
+
+    fn bound(&self) -> Option<Bound> {
+        primitive_bound(&self.name, &self.args)
+    }
+}
+
+impl Eval for NamedGeometry {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut args = self.args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        args.push(env.arg().clone());
+
+        env.call(&self.name, args)
+    }
 }
 
 #[derive(Debug)]
 pub struct RawGeometry {
-    pub expr: String,
+    pub expr: Expr,
 }
 
 impl IGeometry for RawGeometry {}
 
 impl MakeExpr for RawGeometry {
     fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
-        ArgString::new(&self.expr, &ctx.arg).into()
+        ctx.expr(&self.expr).into()
+    }
+}
+
+impl Eval for RawGeometry {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        env.expr(&self.expr)
     }
 }
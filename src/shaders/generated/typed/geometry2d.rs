@@ -0,0 +1,161 @@
+use super::*;
+use glsl::RawString;
+
+/// 2D shapes in the xy plane, which are only geometry once extruded or revolved. They have no bounds, as bounds are
+/// spheres around 3D objects
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Geometry2dMarker;
+
+impl ITypeMarker for Geometry2dMarker {}
+
+#[derive(Debug)]
+pub struct NamedGeometry2d {
+    pub name: String,
+    pub args: Vec<Expr>,
+}
+
+impl IGeometry2d for NamedGeometry2d {}
+
+impl MakeExpr for NamedGeometry2d {
+    fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
+        let mut func = glsl::FunctionCall::new(&self.name);
+        for arg in &self.args {
+            func.push_arg(ctx.expr(arg));
+        }
+
+        func.push_arg(RawString::new(&ctx.arg));
+
+        func.into()
+    }
+}
+
+impl Eval for NamedGeometry2d {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut args = self.args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        args.push(env.arg().clone());
+
+        env.call(&self.name, args)
+    }
+}
+
+#[derive(Debug)]
+pub struct RawGeometry2d {
+    pub expr: Expr,
+}
+
+impl IGeometry2d for RawGeometry2d {}
+
+impl MakeExpr for RawGeometry2d {
+    fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
+        ctx.expr(&self.expr).into()
+    }
+}
+
+impl Eval for RawGeometry2d {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        env.expr(&self.expr)
+    }
+}
+
+/// Polygon through `vertices`, each a `vec2`, with an edge from the last one back to the first
+#[derive(Debug)]
+pub struct Polygon {
+    pub vertices: Vec<Expr>,
+}
+
+impl Polygon {
+    fn edges(&self) -> impl Iterator<Item = (&Expr, &Expr)> {
+        self.vertices.iter().zip(self.vertices.iter().cycle().skip(1))
+    }
+}
+
+impl IGeometry2d for Polygon {}
+
+impl MakeExpr for Polygon {
+    fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        let mut acc = String::from("vec2(1.0/0.0, 1.0)");
+        for (a, b) in self.edges() {
+            let mut edge = glsl::FunctionCall::new("polygon_edge");
+            edge.push_arg(ctx.expr(a));
+            edge.push_arg(ctx.expr(b));
+            edge.push_arg(RawString::new(acc));
+            edge.push_arg(RawString::new(&ctx.arg));
+            acc = func.gen_definition("vec2", edge);
+        }
+
+        let mut distance = glsl::FunctionCall::new("polygon_distance");
+        distance.push_arg(RawString::new(acc));
+
+        distance.into()
+    }
+}
+
+impl Eval for Polygon {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut acc = Value::Vec(2, [f64::INFINITY, 1.0, 0.0, 0.0]);
+        for (a, b) in self.edges() {
+            let args = vec![env.expr(a)?, env.expr(b)?, acc, env.arg().clone()];
+            acc = env.call("polygon_edge", args)?;
+        }
+
+        env.call("polygon_distance", vec![acc])
+    }
+}
+
+/// 2D shape stretched along z, `args` holding how far it goes each way
+#[derive(Debug)]
+pub struct Extrude<G: IGeometry2d> {
+    pub args: Vec<Expr>,
+    pub profile: G,
+}
+
+impl<G: IGeometry2d> IGeometry for Extrude<G> {}
+
+impl<G: IGeometry2d> MakeExpr for Extrude<G> {
+    fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        let mut extrude = glsl::FunctionCall::new("sd_extrude");
+        extrude.push_arg(ctx.expr(&self.args[0]));
+        extrude.push_arg(self.profile.make_expr(ctx, func));
+        extrude.push_arg(RawString::new(&ctx.arg));
+
+        extrude.into()
+    }
+}
+
+impl<G: IGeometry2d> Eval for Extrude<G> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let depth = env.expr(&self.args[0])?;
+        let d = self.profile.eval(env)?;
+
+        env.call("sd_extrude", vec![depth, d, env.arg().clone()])
+    }
+}
+
+/// 2D shape turned around the y axis, `args` holding how far from the axis its own y axis is
+#[derive(Debug)]
+pub struct Revolve<G: IGeometry2d> {
+    pub args: Vec<Expr>,
+    pub profile: G,
+}
+
+impl<G: IGeometry2d> IGeometry for Revolve<G> {}
+
+impl<G: IGeometry2d> MakeExpr for Revolve<G> {
+    fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        let mut revolve = glsl::FunctionCall::new("revolve");
+        revolve.push_arg(ctx.expr(&self.args[0]));
+        revolve.push_arg(RawString::new(&ctx.arg));
+
+        let ident = func.gen_definition("Arg", revolve);
+        self.profile.make_expr(&ctx.with_arg(ident), func)
+    }
+}
+
+impl<G: IGeometry2d> Eval for Revolve<G> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let offset = env.expr(&self.args[0])?;
+        let arg = env.call("revolve", vec![offset, env.arg().clone()])?;
+
+        env.with_arg(arg, |env| self.profile.eval(env))
+    }
+}
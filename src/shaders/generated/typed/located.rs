@@ -0,0 +1,34 @@
+use super::*;
+use crate::shaders::generated::source::Span;
+
+/// Object built by a statement, so the GLSL generated for it can be traced back to the statement
+#[derive(Debug)]
+pub struct Located<T> {
+    pub span: Span,
+    pub item: T,
+}
+
+impl<T: IGeometry> IGeometry for Located<T> {}
+impl<T: IGeometry2d> IGeometry2d for Located<T> {}
+impl<T: IOpaqueShape> IOpaqueShape for Located<T> {}
+impl<T: ITransparentShape> ITransparentShape for Located<T> {}
+
+impl<T: MakeExpr> MakeExpr for Located<T> {
+    fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        func.enter(self.span);
+        let expr = self.item.make_expr(ctx, func);
+        func.leave();
+
+        expr
+    }
+
+    fn bound(&self) -> Option<Bound> {
+        self.item.bound()
+    }
+}
+
+impl<T: Eval> Eval for Located<T> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        self.item.eval(env)
+    }
+}
@@ -1,14 +1,23 @@
 use super::*;
-use glsl::{ArgString, RawString};
+use glsl::RawString;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct OpaqueMarker;
 
 impl ITypeMarker for OpaqueMarker {}
 
+/// What the surface of an opaque object is made of
+#[derive(Debug, Clone)]
+pub enum Paint {
+    /// Components of a color, with the default material
+    Color(Vec<Expr>),
+    /// Function of a `material` statement
+    Material(String),
+}
+
 #[derive(Debug)]
 pub struct OpaqueShape<G: IGeometry> {
-    pub color: Vec<String>,
+    pub paint: Paint,
     pub geometry: G,
 }
 
@@ -16,21 +25,62 @@ impl<G: IGeometry> IOpaqueShape for OpaqueShape<G> {}
 
 impl<G: IGeometry> MakeExpr for OpaqueShape<G> {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
-        let mut vec4 = glsl::FunctionCall::new("vec4");
-        for c in &self.color {
-            vec4.push_arg(ArgString::new(c, &ctx.arg));
-        }
+        let mut opaque = match &self.paint {
+            Paint::Color(color) => {
+                let mut vec3 = glsl::FunctionCall::new("vec3");
+                for c in color {
+                    vec3.push_arg(ctx.expr(c));
+                }
+
+                let mut opaque = glsl::FunctionCall::new("map_opaque");
+                opaque.push_arg(vec3);
+                opaque
+            }
+            Paint::Material(name) => {
+                let mut material = glsl::FunctionCall::new(name);
+                material.push_arg(RawString::new(&ctx.arg));
+
+                let mut opaque = glsl::FunctionCall::new("MapOpaque");
+                opaque.push_arg(material);
+                opaque
+            }
+        };
 
-        vec4.push_arg(self.geometry.make_expr(ctx, func));
+        opaque.push_arg(self.geometry.make_expr(ctx, func));
 
-        vec4.into()
+        opaque.into()
+    }
+
+    fn bound(&self) -> Option<Bound> {
+        self.geometry.bound()
+    }
+}
+
+impl<G: IGeometry> Eval for OpaqueShape<G> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        match &self.paint {
+            Paint::Color(color) => {
+                let color = color.iter().map(|c| env.expr(c)).collect::<Result<Vec<_>, _>>()?;
+                let color = env.call("vec3", color)?;
+                let d = self.geometry.eval(env)?;
+
+                env.call("map_opaque", vec![color, d])
+            }
+            Paint::Material(name) => {
+                let arg = env.arg().clone();
+                let material = env.call(name, vec![arg])?;
+                let d = self.geometry.eval(env)?;
+
+                Value::new_struct("MapOpaque", vec![material, d])
+            }
+        }
     }
 }
 
 #[derive(Debug, Clone)]
 pub struct NamedOpaqueShape {
     pub name: String,
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl IOpaqueShape for NamedOpaqueShape {}
@@ -39,7 +89,7 @@ impl MakeExpr for NamedOpaqueShape {
     fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
         let mut func = glsl::FunctionCall::new(&self.name);
         for arg in &self.args {
-            func.push_arg(ArgString::new(arg, &ctx.arg));
+            func.push_arg(ctx.expr(arg));
         }
 
         func.push_arg(RawString::new(&ctx.arg));
@@ -48,15 +98,30 @@ impl MakeExpr for NamedOpaqueShape {
     }
 }
 
+impl Eval for NamedOpaqueShape {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut args = self.args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        args.push(env.arg().clone());
+
+        env.call(&self.name, args)
+    }
+}
+
 #[derive(Debug)]
 pub struct RawOpaque {
-    pub expr: String,
+    pub expr: Expr,
 }
 
 impl IOpaqueShape for RawOpaque {}
 
 impl MakeExpr for RawOpaque {
     fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
-        ArgString::new(&self.expr, &ctx.arg).into()
+        ctx.expr(&self.expr).into()
+    }
+}
+
+impl Eval for RawOpaque {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        env.expr(&self.expr)
     }
 }
@@ -2,44 +2,75 @@ use super::*;
 
 pub trait MakeExpr: Debug {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr;
+
+    /// Conservative bound of the object, `None` when it can't be known
+    fn bound(&self) -> Option<Bound> {
+        None
+    }
 }
 
 impl MakeExpr for Box<dyn MakeExpr> {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
         MakeExpr::make_expr(&**self, ctx, func)
     }
+    fn bound(&self) -> Option<Bound> {
+        MakeExpr::bound(&**self)
+    }
 }
-impl<'a, T: MakeExpr> MakeExpr for &'a T {
+impl<T: MakeExpr> MakeExpr for &T {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
         MakeExpr::make_expr(&**self, ctx, func)
     }
+    fn bound(&self) -> Option<Bound> {
+        MakeExpr::bound(&**self)
+    }
 }
 
-pub trait IGeometry: MakeExpr + 'static {}
+pub trait IGeometry: MakeExpr + Eval + 'static {}
 impl IGeometry for Box<dyn IGeometry> {}
 impl MakeExpr for Box<dyn IGeometry> {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
         MakeExpr::make_expr(&**self, ctx, func)
     }
+    fn bound(&self) -> Option<Bound> {
+        MakeExpr::bound(&**self)
+    }
+}
+
+pub trait IGeometry2d: MakeExpr + Eval + 'static {}
+impl IGeometry2d for Box<dyn IGeometry2d> {}
+impl MakeExpr for Box<dyn IGeometry2d> {
+    fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
+        MakeExpr::make_expr(&**self, ctx, func)
+    }
+    fn bound(&self) -> Option<Bound> {
+        MakeExpr::bound(&**self)
+    }
 }
 
-pub trait IOpaqueShape: MakeExpr + 'static {}
+pub trait IOpaqueShape: MakeExpr + Eval + 'static {}
 impl IOpaqueShape for Box<dyn IOpaqueShape> {}
 impl MakeExpr for Box<dyn IOpaqueShape> {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
         MakeExpr::make_expr(&**self, ctx, func)
     }
+    fn bound(&self) -> Option<Bound> {
+        MakeExpr::bound(&**self)
+    }
 }
 
-pub trait ITransparentShape: MakeExpr + 'static {}
+pub trait ITransparentShape: MakeExpr + Eval + 'static {}
 impl ITransparentShape for Box<dyn ITransparentShape> {}
 impl MakeExpr for Box<dyn ITransparentShape> {
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
         MakeExpr::make_expr(&**self, ctx, func)
     }
+    fn bound(&self) -> Option<Bound> {
+        MakeExpr::bound(&**self)
+    }
 }
 
-pub trait ITransform: Debug + 'static {
+pub trait ITransform: Debug + Send + Sync + 'static {
     fn wrap(
         &self,
         ctx: &Context,
@@ -47,14 +78,39 @@ pub trait ITransform: Debug + 'static {
         inside: &impl MakeExpr,
         typ: TypeMarker,
     ) -> glsl::Expr;
+
+    /// Value of the transformed object on the CPU, the counterpart of `wrap`
+    fn eval(&self, env: &mut Env, inside: &impl Eval, typ: TypeMarker) -> Result<Value, EvalError>;
+
+    /// Bound of the transformed object, given the bound of the object inside
+    fn bound(&self, _inside: Option<Bound>) -> Option<Bound> {
+        None
+    }
 }
 
-pub trait IFunc: Debug + 'static {
+pub trait IFunc: Debug + Send + Sync + 'static {
     fn name(&self, typ: TypeMarker) -> &'static str;
     fn id(&self, typ: TypeMarker) -> &'static str;
-    fn extra_args(&self) -> &[String] {
+    fn extra_args(&self, _typ: TypeMarker) -> &[Expr] {
         &[]
     }
+    /// Distance of the object returned by `id`
+    fn id_distance(&self) -> f64 {
+        f64::INFINITY
+    }
+
+    /// Bound of the combination of several objects, given their bounds
+    fn bound(&self, _items: &[Option<Bound>]) -> Option<Bound> {
+        None
+    }
+    /// Whether an object far away from the others can be left out of the combination
+    fn culls_items(&self) -> bool {
+        false
+    }
+    /// How far the combination can move the surface of the objects, `None` when that isn't known
+    fn offset(&self) -> Option<f64> {
+        Some(0.0)
+    }
 }
 
-pub trait ITypeMarker: Into<TypeMarker> + Debug + Copy + 'static {}
+pub trait ITypeMarker: Into<TypeMarker> + Debug + Copy + Send + Sync + 'static {}
@@ -1,5 +1,5 @@
 use super::*;
-use glsl::{ArgString, RawString};
+use glsl::RawString;
 
 #[derive(Debug)]
 pub struct Transform<F, T, M> {
@@ -9,6 +9,7 @@ pub struct Transform<F, T, M> {
 }
 
 impl<F: ITransform, T: IGeometry> IGeometry for Transform<F, T, GeometryMarker> {}
+impl<F: ITransform, T: IGeometry2d> IGeometry2d for Transform<F, T, Geometry2dMarker> {}
 impl<F: ITransform, T: IOpaqueShape> IOpaqueShape for Transform<F, T, OpaqueMarker> {}
 impl<F: ITransform, T: ITransparentShape> ITransparentShape for Transform<F, T, TransparentMarker> {}
 
@@ -16,30 +17,65 @@ impl<F: ITransform, T: MakeExpr, M: ITypeMarker> MakeExpr for Transform<F, T, M>
     fn make_expr(&self, ctx: &Context, func: &mut glsl::Function) -> glsl::Expr {
         self.tf.wrap(ctx, func, &self.item, self.marker.into())
     }
+
+    fn bound(&self) -> Option<Bound> {
+        self.tf.bound(self.item.bound())
+    }
+}
+
+impl<F: ITransform, T: Eval, M: ITypeMarker> Eval for Transform<F, T, M> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        self.tf.eval(env, &self.item, self.marker.into())
+    }
 }
 
 #[derive(Debug)]
 pub struct FunctionTf {
     pub func: String,
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl ITransform for FunctionTf {
     fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, _typ: TypeMarker) -> glsl::Expr {
         let mut tf = glsl::FunctionCall::new(&self.func);
         for arg in &self.args {
-            tf.push_arg(ArgString::new(arg, &ctx.arg));
+            tf.push_arg(ctx.expr(arg));
         }
         tf.push_arg(RawString::new(&ctx.arg));
 
         let ident = func.gen_definition("Arg", tf);
-        inside.make_expr(&Context::with_arg(ident), func)
+        inside.make_expr(&ctx.with_arg(ident), func)
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _typ: TypeMarker) -> Result<Value, EvalError> {
+        let mut args = self.args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        args.push(env.arg().clone());
+
+        let arg = env.call(&self.func, args)?;
+        env.with_arg(arg, |env| inside.eval(env))
+    }
+
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        let inside = inside?;
+        match (self.func.as_str(), &self.args[..]) {
+            ("at", [x, y, z]) => Some(inside.shifted([x.constant()?, y.constant()?, z.constant()?])),
+            ("vat", [shift]) => Some(inside.shifted(constant_vec3(shift)?)),
+            ("rotate", _) | ("mirror", _) | ("repeat_polar", _) => Some(inside.rotated()),
+            ("repeat_limited", [spacing, count]) => {
+                let [x, y, z] = constant_vec3(spacing)?;
+                let [i, j, k] = constant_vec3(count)?;
+                Some(inside.grown(length([x * i, y * j, z * k])))
+            }
+            ("elongate", [size]) => Some(inside.grown(length(constant_vec3(size)?))),
+            ("at_t", _) | ("start_at_t", _) | ("end_at_t", _) | ("repeat_t", _) | ("map_t", _) => Some(inside),
+            _ => None,
+        }
     }
 }
 
 #[derive(Debug)]
 pub struct Onionize {
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl ITransform for Onionize {
@@ -50,21 +86,50 @@ impl ITransform for Onionize {
         inside: &impl MakeExpr,
         _: TypeMarker,
     ) -> glsl::Expr {
-        let expr = inside.make_expr(ctx, func);
+        let expr = inside.make_expr(&ctx.offset_by(self.args[0].constant()), func);
         let expr_ident = func.gen_definition("float", expr);
 
         let mut onionize = glsl::FunctionCall::new("sd_onionize");
         assert_eq!(self.args.len(), 1);
-        onionize.push_arg(ArgString::new(&self.args[0], &ctx.arg));
+        onionize.push_arg(ctx.expr(&self.args[0]));
         onionize.push_arg(RawString::new(expr_ident));
 
         onionize.into()
     }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _: TypeMarker) -> Result<Value, EvalError> {
+        let d = inside.eval(env)?;
+        let thick = env.expr(&self.args[0])?;
+
+        env.call("sd_onionize", vec![thick, d])
+    }
+
+    /// The shell is inside the object
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        inside
+    }
+}
+
+/// Object with its distance changed to `f` of the distance, whatever its type
+fn map_distance(func: &mut glsl::Function, typ: TypeMarker, expr: glsl::Expr, f: impl FnOnce(&str) -> String) -> glsl::Expr {
+    let s = match typ {
+        TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) => f(&expr.to_string()),
+        TypeMarker::Opaque(_) => {
+            let expr = func.gen_definition(typ.typ(), expr);
+            format!("MapOpaque({}.material, {})", expr, f(&format!("{}.d", expr)))
+        }
+        TypeMarker::Transparent(_) => {
+            let expr = func.gen_definition(typ.typ(), expr);
+            format!("MapTransparent({}.color, {})", expr, f(&format!("{}.d", expr)))
+        }
+    };
+
+    RawString::new(s).into()
 }
 
 #[derive(Debug)]
 pub struct Scale {
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl ITransform for Scale {
@@ -76,43 +141,169 @@ impl ITransform for Scale {
         typ: TypeMarker,
     ) -> glsl::Expr {
         let mut scale = glsl::FunctionCall::new("uscale");
-        scale.push_arg(ArgString::new(&self.args[0], &ctx.arg));
+        scale.push_arg(ctx.expr(&self.args[0]));
         scale.push_arg(RawString::new(&ctx.arg));
         let ident = func.gen_definition("Arg", scale);
 
-        let expr = inside.make_expr(&Context::with_arg(ident), func);
-
-        let s = match typ {
-            TypeMarker::Geometry(_) => format!("(({}) * ({}))", expr.to_string(), self.args[0]),
-            TypeMarker::Opaque(_) => {
-                let expr = func.gen_definition(typ.typ(), expr);
-                format!(
-                    "vec4({expr}.xyz, {expr}.w * ({scale}))",
-                    expr = expr,
-                    scale = self.args[0]
-                )
-            }
-            TypeMarker::Transparent(_) => {
-                let expr = func.gen_definition(typ.typ(), expr);
-                format!(
-                    "MapTransparent({expr}.color, {expr}.d * ({scale}))",
-                    expr = expr,
-                    scale = self.args[0]
-                )
-            }
-        };
+        let expr = inside.make_expr(&ctx.with_arg(ident).scaled_by(self.args[0].constant()), func);
+        let factor = ctx.glsl(&self.args[0]);
 
-        ArgString::new(s, &ctx.arg).into()
+        map_distance(func, typ, expr, |d| format!("(({}) * ({}))", d, factor))
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _: TypeMarker) -> Result<Value, EvalError> {
+        let factor = env.expr(&self.args[0])?;
+        let arg = env.call("uscale", vec![factor.clone(), env.arg().clone()])?;
+        let factor = factor.as_float()?;
+
+        env.with_arg(arg, |env| inside.eval(env))?.map_distance(|d| d * factor)
+    }
+
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        Some(inside?.scaled(self.args[0].constant()?))
     }
 }
 
+/// Objects rounded by moving their surface out, `args` holding the radius
 #[derive(Debug)]
-pub struct AdvancedRepeat {
-    pub args: Vec<String>,
+pub struct Round {
+    pub args: Vec<Expr>,
 }
 
-impl ITransform for AdvancedRepeat {
+impl ITransform for Round {
+    fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, typ: TypeMarker) -> glsl::Expr {
+        let expr = inside.make_expr(&ctx.offset_by(self.args[0].constant()), func);
+        let r = ctx.glsl(&self.args[0]);
+
+        map_distance(func, typ, expr, |d| format!("sd_round({}, {})", r, d))
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _: TypeMarker) -> Result<Value, EvalError> {
+        let r = env.expr(&self.args[0])?.as_float()?;
+        inside.eval(env)?.map_distance(|d| d - r)
+    }
+
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        Some(inside?.grown(self.args[0].constant()?))
+    }
+}
+
+/// Step used to estimate how fast the noise of `displace` changes
+const DISPLACE_STEP: f64 = 0.001;
+
+/// Adds noise to the distance, which is divided by how fast the noise changes at the point so that marching by it
+/// stays close to the surface
+#[derive(Debug)]
+pub struct Displace {
+    pub args: Vec<Expr>,
+}
+
+impl ITransform for Displace {
+    fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, typ: TypeMarker) -> glsl::Expr {
+        // the noise can be anything, so the surface may move any distance
+        let expr = inside.make_expr(&ctx.offset_by(None), func);
+        let noise = func.gen_definition("float", ctx.glsl(&self.args[1]));
+
+        let mut differences = Vec::new();
+        for shift in &["vec3(1, 0, 0)", "vec3(0, 1, 0)", "vec3(0, 0, 1)"] {
+            let arg = format!("vat({} * {:?}, {})", shift, DISPLACE_STEP, ctx.arg);
+            let arg = func.gen_definition("Arg", arg);
+            let shifted = func.gen_definition("float", ctx.with_arg(arg).glsl(&self.args[1]));
+            differences.push(format!("{} - {}", noise, shifted));
+        }
+
+        let amount = ctx.glsl(&self.args[0]);
+        map_distance(func, typ, expr, |d| {
+            format!(
+                "sd_displace({}, {}, vec3({}) / {:?}, {})",
+                amount,
+                noise,
+                differences.join(", "),
+                DISPLACE_STEP,
+                d
+            )
+        })
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _: TypeMarker) -> Result<Value, EvalError> {
+        let amount = env.expr(&self.args[0])?.as_float()?;
+        let noise = env.expr(&self.args[1])?.as_float()?;
+        let outer = env.arg().clone();
+
+        let mut gradient = [0.0; 3];
+        for (axis, slope) in gradient.iter_mut().enumerate() {
+            let mut shift = [0.0; 3];
+            shift[axis] = DISPLACE_STEP;
+            let arg = env.call("vat", vec![Value::vec3(shift), outer.clone()])?;
+            let shifted = env.with_arg(arg, |env| env.expr(&self.args[1]))?.as_float()?;
+            *slope = (noise - shifted) / DISPLACE_STEP;
+        }
+
+        let value = inside.eval(env)?;
+        let args = vec![Value::Float(amount), Value::Float(noise), Value::vec3(gradient), Value::Float(value.distance()?)];
+        let d = env.call("sd_displace", args)?.as_float()?;
+        value.map_distance(|_| d)
+    }
+}
+
+/// Transform stretching space with a `library.glsl` function, the distance being divided by what the function with
+/// the `_lipschitz` suffix gives, so that marching by it never overshoots
+#[derive(Debug)]
+pub struct Distortion {
+    pub func: String,
+    pub args: Vec<Expr>,
+}
+
+impl ITransform for Distortion {
     fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, typ: TypeMarker) -> glsl::Expr {
+        let mut tf = glsl::FunctionCall::new(&self.func);
+        for arg in &self.args {
+            tf.push_arg(ctx.expr(arg));
+        }
+        tf.push_arg(RawString::new(&ctx.arg));
+
+        let ident = func.gen_definition("Arg", tf);
+        let mut expr = inside.make_expr(&ctx.with_arg(ident).distorted(), func);
+        if let TypeMarker::Geometry(_) | TypeMarker::Geometry2d(_) = typ {
+            // the distance is used twice
+            expr = RawString::new(func.gen_definition("float", expr)).into();
+        }
+
+        let args = self.args.iter().map(|arg| ctx.glsl(arg).to_string()).collect::<Vec<_>>().join(", ");
+        map_distance(func, typ, expr, |d| {
+            format!("(({d}) / {func}_lipschitz({args}, {d}, {arg}))", d = d, func = self.func, args = args, arg = ctx.arg)
+        })
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _: TypeMarker) -> Result<Value, EvalError> {
+        let mut args = self.args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        let outer = env.arg().clone();
+
+        let arg = env.call(&self.func, args.iter().cloned().chain(Some(outer.clone())).collect())?;
+        let value = env.with_arg(arg, |env| inside.eval(env))?;
+        let d = value.distance()?;
+
+        args.extend(vec![Value::Float(d), outer]);
+        let lipschitz = env.call(&format!("{}_lipschitz", self.func), args)?.as_float()?;
+        value.map_distance(|d| d / lipschitz)
+    }
+
+    /// Both distortions turn points around an axis through the origin
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        Some(inside?.rotated())
+    }
+}
+
+#[derive(Debug)]
+pub struct AdvancedRepeat {
+    pub args: Vec<Expr>,
+}
+
+/// Repetition that also looks at the neighbouring cells, for objects that stick out of their own
+type Repeated<'a, T> = Transform<FunctionTf, Fold<Union, Transform<FunctionTf, &'a T, TypeMarker>, TypeMarker>, TypeMarker>;
+
+impl AdvancedRepeat {
+    fn repeated<'a, T>(&self, inside: &'a T, typ: TypeMarker) -> Repeated<'a, T> {
         let mut fold = Fold {
             func: Union,
             items: Vec::new(),
@@ -120,22 +311,22 @@ impl ITransform for AdvancedRepeat {
         };
 
         for x in -1..=1 {
-            if x != 0 && self.args[0] == "0" {
+            if x != 0 && self.args[0].is_zero() {
                 continue;
             }
 
             for y in -1..=1 {
-                if y != 0 && self.args[1] == "0" {
+                if y != 0 && self.args[1].is_zero() {
                     continue;
                 }
 
                 for z in -1..=1 {
-                    if z != 0 && self.args[2] == "0" {
+                    if z != 0 && self.args[2].is_zero() {
                         continue;
                     }
 
                     let offset = self.args.iter().zip(&[x, y, z])
-                        .map(|(repeat_offset, mult)| format!("(({}) * ({}))", repeat_offset, mult))
+                        .map(|(repeat_offset, &mult)| Expr::binary(BinaryOp::Mul, repeat_offset.clone(), Expr::number(mult)))
                         .collect::<Vec<_>>();
 
                     let item = Transform {
@@ -149,42 +340,72 @@ impl ITransform for AdvancedRepeat {
             }
         }
 
-        let repeat = Transform {
+        Transform {
             tf: FunctionTf { func: String::from("repeat"), args: self.args.clone() },
             item: fold,
             marker: typ,
-        };
+        }
+    }
+}
 
-        repeat.make_expr(ctx, func)
+impl ITransform for AdvancedRepeat {
+    fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, typ: TypeMarker) -> glsl::Expr {
+        self.repeated(inside, typ).make_expr(ctx, func)
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, typ: TypeMarker) -> Result<Value, EvalError> {
+        self.repeated(inside, typ).eval(env)
     }
 }
 
 #[derive(Debug)]
 pub struct Cond {
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl ITransform for Cond {
-    fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, _typ: TypeMarker) -> glsl::Expr {
+    /// Nothing is shown while the condition doesn't hold, which is the identity of the union for any type
+    fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, typ: TypeMarker) -> glsl::Expr {
         let inside = inside.make_expr(ctx, func).to_string();
+        let cond = ctx.glsl(&self.args[0]);
+
+        RawString::new(format!("(({}) ? ({}) : ({}))", cond, inside, Union.id(typ))).into()
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, typ: TypeMarker) -> Result<Value, EvalError> {
+        if env.expr(&self.args[0])?.as_bool()? {
+            inside.eval(env)
+        } else {
+            Ok(typ.value_with_distance(Union.id_distance()))
+        }
+    }
 
-        ArgString::new(format!("(({cond}) ? ({obj}) : (1.0/0.0))", cond = self.args[0], obj = inside), &ctx.arg).into()
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        inside
     }
 }
 
 #[derive(Debug)]
 pub struct Let {
-    pub args: Vec<String>
+    pub args: Vec<Expr>
 }
 
 impl ITransform for Let {
     fn wrap(&self, ctx: &Context, func: &mut glsl::Function, inside: &impl MakeExpr, _typ: TypeMarker) -> glsl::Expr {
-        let typ = &self.args[0];
-        let name = &self.args[1];
-        let value = ArgString::new(&self.args[2], &ctx.arg);
+        let value = ctx.value(&self.args[2], &Type::parse(&self.args[0].to_string()));
+
+        // a fresh variable, so sibling bindings and GLSL built-ins of the same name don't clash
+        let ident = func.gen_definition(&self.args[0], value.as_ref());
+        inside.make_expr(&ctx.with_name(self.args[1].to_string(), ident), func)
+    }
+
+    fn eval(&self, env: &mut Env, inside: &impl Eval, _typ: TypeMarker) -> Result<Value, EvalError> {
+        let value = env.expr(&self.args[2])?;
 
-        func.add_definition(typ, name, value.as_ref());
+        env.with_variable(&self.args[1].to_string(), value, |env| inside.eval(env))
+    }
 
-        inside.make_expr(ctx, func)
+    fn bound(&self, inside: Option<Bound>) -> Option<Bound> {
+        inside
     }
 }
@@ -1,5 +1,5 @@
 use super::*;
-use glsl::{ArgString, RawString};
+use glsl::RawString;
 
 #[derive(Debug, Clone, Copy, Default)]
 pub struct TransparentMarker;
@@ -8,7 +8,7 @@ impl ITypeMarker for TransparentMarker {}
 
 #[derive(Debug)]
 pub struct TransparentShape<G: IGeometry> {
-    pub color: Vec<String>,
+    pub color: Vec<Expr>,
     pub geometry: G
 }
 
@@ -20,7 +20,7 @@ impl<G: IGeometry> MakeExpr for TransparentShape<G> {
 
         let mut color = glsl::FunctionCall::new("vec4");
         for c in &self.color {
-            color.push_arg(ArgString::new(c, &ctx.arg));
+            color.push_arg(ctx.expr(c));
         }
 
         transparent.push_arg(color);
@@ -28,12 +28,26 @@ impl<G: IGeometry> MakeExpr for TransparentShape<G> {
 
         transparent.into()
     }
+
+    fn bound(&self) -> Option<Bound> {
+        self.geometry.bound()
+    }
+}
+
+impl<G: IGeometry> Eval for TransparentShape<G> {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let color = self.color.iter().map(|c| env.expr(c)).collect::<Result<Vec<_>, _>>()?;
+        let color = env.call("vec4", color)?;
+        let d = self.geometry.eval(env)?;
+
+        Value::new_struct("MapTransparent", vec![color, d])
+    }
 }
 
 #[derive(Debug, Clone)]
 pub struct NamedTransparentShape {
     pub name: String,
-    pub args: Vec<String>,
+    pub args: Vec<Expr>,
 }
 
 impl ITransparentShape for NamedTransparentShape {}
@@ -42,7 +56,7 @@ impl MakeExpr for NamedTransparentShape {
     fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
         let mut func = glsl::FunctionCall::new(&self.name);
         for arg in &self.args {
-            func.push_arg(ArgString::new(arg, &ctx.arg));
+            func.push_arg(ctx.expr(arg));
         }
 
         func.push_arg(RawString::new(&ctx.arg));
@@ -51,15 +65,30 @@ impl MakeExpr for NamedTransparentShape {
     }
 }
 
+impl Eval for NamedTransparentShape {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        let mut args = self.args.iter().map(|arg| env.expr(arg)).collect::<Result<Vec<_>, _>>()?;
+        args.push(env.arg().clone());
+
+        env.call(&self.name, args)
+    }
+}
+
 #[derive(Debug)]
 pub struct RawTransparent {
-    pub expr: String,
+    pub expr: Expr,
 }
 
 impl ITransparentShape for RawTransparent {}
 
 impl MakeExpr for RawTransparent {
     fn make_expr(&self, ctx: &Context, _: &mut glsl::Function) -> glsl::Expr {
-        ArgString::new(&self.expr, &ctx.arg).into()
+        ctx.expr(&self.expr).into()
+    }
+}
+
+impl Eval for RawTransparent {
+    fn eval(&self, env: &mut Env) -> Result<Value, EvalError> {
+        env.expr(&self.expr)
     }
 }
@@ -0,0 +1,416 @@
+//! Checks of complete shaders without a GPU, so that generated code calling functions that don't
+//! exist shows up in the tests rather than when the driver compiles it
+
+use std::collections::HashSet;
+
+use super::check::BUILTINS;
+
+/// Keywords that can be followed by a parenthesis
+const KEYWORDS: &[&str] = &["if", "for", "while", "switch", "return", "layout"];
+
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum GlslError {
+    #[error("line {}: `{}` is not defined", .line, .name)]
+    Undefined { name: String, line: usize },
+    #[error("line {}: `{}` is used before it is declared", .line, .name)]
+    UsedBeforeDeclaration { name: String, line: usize },
+    #[error("line {}: unexpected `{}`", .line, .found)]
+    Unexpected { found: String, line: usize },
+    #[error("line {}: `{}` is never closed", .line, .open)]
+    Unclosed { open: &'static str, line: usize },
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Token<'a> {
+    Ident(&'a str),
+    /// Number as it's written, with its exponent and suffix
+    Number(&'a str),
+    Punct(&'static str),
+    /// Character that doesn't start any GLSL token
+    Invalid(char),
+}
+
+/// Operators and punctuation, longest first so that `<<=` isn't read as `<` and `<=`
+const PUNCTS: &[&str] = &[
+    "<<=", ">>=", "++", "--", "+=", "-=", "*=", "/=", "%=", "&=", "|=", "^=", "<<", ">>", "<=", ">=", "==", "!=",
+    "&&", "||", "^^", "(", ")", "[", "]", "{", "}", ".", ",", ";", "?", ":", "=", "+", "-", "*", "/", "%", "<", ">",
+    "!", "~", "&", "|", "^",
+];
+
+/// Tokens with their line numbers, leaving out comments and preprocessor directives
+pub fn tokenize(source: &str) -> Vec<(Token<'_>, usize)> {
+    let mut tokens = Vec::new();
+    let mut in_comment = false;
+
+    for (idx, mut line) in source.lines().enumerate() {
+        let line_number = idx + 1;
+
+        if !in_comment && line.trim_start().starts_with('#') {
+            continue;
+        }
+
+        while !line.is_empty() {
+            if in_comment {
+                match line.find("*/") {
+                    Some(end) => {
+                        in_comment = false;
+                        line = &line[end + 2..];
+                    }
+                    None => break,
+                }
+                continue;
+            }
+
+            let c = line.chars().next().unwrap();
+            if line.starts_with("//") {
+                break;
+            } else if line.starts_with("/*") {
+                in_comment = true;
+                line = &line[2..];
+            } else if c.is_alphabetic() || c == '_' {
+                let end = line.find(|c: char| !c.is_alphanumeric() && c != '_').unwrap_or(line.len());
+                tokens.push((Token::Ident(&line[..end]), line_number));
+                line = &line[end..];
+            } else if c.is_ascii_digit() {
+                let mut end = line.find(|c: char| !c.is_ascii_digit() && c != '.').unwrap_or(line.len());
+                // an exponent like `e-8`
+                if let Some(exponent) = line[end..].strip_prefix(['e', 'E']) {
+                    let digits = exponent.trim_start_matches(['+', '-']);
+                    if digits.starts_with(|c: char| c.is_ascii_digit()) {
+                        let len = digits.find(|c: char| !c.is_ascii_digit()).unwrap_or(digits.len());
+                        end = line.len() - (digits.len() - len);
+                    }
+                }
+                end = line.len() - line[end..].trim_start_matches(['u', 'U', 'f', 'F']).len();
+                tokens.push((Token::Number(&line[..end]), line_number));
+                line = &line[end..];
+            } else if c.is_whitespace() {
+                line = &line[c.len_utf8()..];
+            } else {
+                match PUNCTS.iter().find(|punct| line.starts_with(*punct)) {
+                    Some(punct) => {
+                        tokens.push((Token::Punct(punct), line_number));
+                        line = &line[punct.len()..];
+                    }
+                    None => {
+                        tokens.push((Token::Invalid(c), line_number));
+                        line = &line[c.len_utf8()..];
+                    }
+                }
+            }
+        }
+    }
+
+    tokens
+}
+
+/// Names a function or struct declaration starts at the given token, if any
+fn declared_name<'a>(tokens: &[(Token<'a>, usize)], idx: usize, depth: usize) -> Option<&'a str> {
+    match (idx.checked_sub(1).map(|prev| tokens[prev].0), tokens[idx].0, tokens.get(idx + 1).map(|t| t.0)) {
+        (Some(Token::Ident("struct")), Token::Ident(name), _) => Some(name),
+        (Some(Token::Ident(_)), Token::Ident(name), Some(Token::Punct("("))) if depth == 0 => Some(name),
+        _ => None,
+    }
+}
+
+/// Checks that the brackets match and that every called function is declared before it is called
+pub fn validate(source: &str) -> Result<(), Vec<GlslError>> {
+    let tokens = tokenize(source);
+    let mut errors = Vec::new();
+
+    // nesting depth before each token, declarations being the ones outside of any bracket
+    let depths = tokens
+        .iter()
+        .scan(0usize, |depth, (token, _)| {
+            let before = *depth;
+            match token {
+                Token::Punct("(" | "[" | "{") => *depth += 1,
+                Token::Punct(")" | "]" | "}") => *depth = depth.saturating_sub(1),
+                _ => {}
+            }
+            Some(before)
+        })
+        .collect::<Vec<_>>();
+    let everywhere = (0..tokens.len())
+        .filter_map(|idx| declared_name(&tokens, idx, depths[idx]))
+        .collect::<HashSet<_>>();
+    let mut declared = BUILTINS.iter().copied().collect::<HashSet<_>>();
+    let mut open = Vec::new();
+
+    for (idx, &(token, line)) in tokens.iter().enumerate() {
+        match token {
+            Token::Punct(punct @ ("(" | "[" | "{")) => open.push((punct, line)),
+            Token::Punct(punct @ (")" | "]" | "}")) => {
+                let expected = match punct {
+                    ")" => "(",
+                    "]" => "[",
+                    _ => "{",
+                };
+                match open.last() {
+                    Some(&(o, _)) if o == expected => {
+                        open.pop();
+                    }
+                    _ => errors.push(GlslError::Unexpected { found: punct.to_owned(), line }),
+                }
+            }
+            Token::Invalid(c) => errors.push(GlslError::Unexpected { found: c.to_string(), line }),
+            Token::Ident(name) => {
+                if let Some(name) = declared_name(&tokens, idx, open.len()) {
+                    declared.insert(name);
+                    continue;
+                }
+
+                let called = tokens.get(idx + 1).map(|t| t.0) == Some(Token::Punct("("));
+                let method = idx > 0 && tokens[idx - 1].0 == Token::Punct(".");
+                if !called || method || KEYWORDS.contains(&name) || declared.contains(name) {
+                    continue;
+                }
+
+                let name = name.to_owned();
+                if everywhere.contains(name.as_str()) {
+                    errors.push(GlslError::UsedBeforeDeclaration { name, line });
+                } else {
+                    errors.push(GlslError::Undefined { name, line });
+                }
+            }
+            _ => {}
+        }
+    }
+
+    errors.extend(open.into_iter().map(|(open, line)| GlslError::Unclosed { open, line }));
+
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(errors)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::shaders::generated::desc::{SceneDesc, test::parse_scene};
+    use crate::shaders::generated::parser;
+    use std::path::Path;
+
+    fn errors(source: &str) -> Vec<String> {
+        match validate(source) {
+            Ok(()) => Vec::new(),
+            Err(errors) => errors.iter().map(|e| e.to_string()).collect(),
+        }
+    }
+
+    fn assert_valid(scene: &str) -> SceneDesc {
+        let desc = parse_scene(scene.as_bytes()).unwrap();
+        assert_eq!(validate(&desc.fragment), Ok(()), "{}", scene);
+        desc
+    }
+
+    #[test]
+    fn test_validate() {
+        let source = "#version 330 core\nstruct Arg {\n    vec3 p;\n};\n// unknown(1)\nfloat f(Arg arg) {\n    return length(arg.p.xyz) + float(arg.p.length());\n}\n/* g(2) */\nvoid main() {\n    f(Arg(vec3(0)));\n}\n";
+        assert_eq!(errors(source), Vec::<String>::new());
+
+        assert_eq!(errors("float f() {\n    return g(1.0);\n}"), vec!["line 2: `g` is not defined"]);
+        assert_eq!(
+            errors("float f() {\n    return g(1.0);\n}\nfloat g(float x) {\n    return x;\n}"),
+            vec!["line 2: `g` is used before it is declared"]
+        );
+        assert_eq!(
+            errors("float g(float x);\nfloat f() {\n    return g(1.0);\n}\nfloat g(float x) {\n    return x;\n}"),
+            Vec::<String>::new()
+        );
+        assert_eq!(errors("void main() {\n    vec2(1, 2));\n}"), vec!["line 2: unexpected `)`"]);
+        assert_eq!(errors("void main() {\n    if (true) {\n}"), vec!["line 1: `{` is never closed"]);
+    }
+
+    #[test]
+    fn test_shared_code() {
+        let desc = assert_valid("");
+        assert_eq!(validate(&desc.shadertoy()), Ok(()));
+    }
+
+    /// Objects of every kind combined in every way, so that each fold has to exist in `library.glsl`
+    #[test]
+    fn test_folds() {
+        let folds = [
+            "union",
+            "intersection",
+            "intersection(false)",
+            "difference",
+            "difference(false)",
+            "smooth_union(0.5)",
+            "smooth_intersection(0.5)",
+            "smooth_difference(0.5)",
+            "chamfer_union(0.5)",
+            "round_union(0.5)",
+            "round_intersection(0.5)",
+            "round_difference(0.5)",
+            "stairs_union(0.5, 3)",
+            "columns_union(0.5, 3)",
+            "pipe(0.1)",
+            "groove(0.1, 0.2)",
+        ];
+
+        for fold in &folds {
+            let items = "sd_sphere(1); at(1, 0, 0) sd_sphere(1);";
+            let scenes = [
+                format!("opaque(1, 0, 0) {} {{ {} }}", fold, items),
+                format!("{} {{ opaque(1, 0, 0) sd_sphere(1); opaque(0, 1, 0) at(1, 0, 0) sd_sphere(1); }}", fold),
+                format!("{} {{ transparent(1, 0, 0, 0.5) sd_sphere(1); transparent(0, 1, 0, 0.5) at(1, 0, 0) sd_sphere(1); }}", fold),
+            ];
+
+            for scene in &scenes {
+                assert_valid(scene);
+            }
+        }
+    }
+
+    #[test]
+    fn test_transforms() {
+        let transforms = [
+            "mirror(vec3(1, 0, 0))",
+            "repeat_polar(6)",
+            "repeat_limited(vec3(2), vec3(1, 0, 1))",
+            "elongate(vec3(1, 0, 0))",
+            "round(0.1)",
+            "twist(0.5)",
+            "bend(0.5)",
+            "displace(0.2, fbm($p, 3) + ridged($p.xy, 2) + simplex_noise(vec4($p, $t)) + worley_noise($p) + hash($p).x)",
+        ];
+
+        for tf in &transforms {
+            let scenes = [
+                format!("opaque(1, 0, 0) {} sd_box(vec3(1));", tf),
+                format!("{} opaque(1, 0, 0) sd_box(vec3(1));", tf),
+                format!("{} transparent(1, 0, 0, 0.5) sd_box(vec3(1));", tf),
+            ];
+
+            for scene in &scenes {
+                assert_valid(scene);
+            }
+        }
+    }
+
+    #[test]
+    fn test_shapes() {
+        let shapes = [
+            "sd_torus(2, 0.5)",
+            "sd_capped_cylinder(1, 2)",
+            "sd_capsule(vec3(0), vec3(0, 2, 0), 0.5)",
+            "sd_capped_cone(1, 0.5, 1)",
+            "sd_cone(1, 2)",
+            "sd_ellipsoid(vec3(1, 2, 3))",
+            "sd_rounded_box(vec3(1), 0.2)",
+            "sd_box_frame(vec3(1), 0.1)",
+            "sd_hex_prism(1, 2)",
+            "sd_tri_prism(1, 2)",
+            "sd_octahedron(1)",
+            "sd_link(1, 1, 0.25)",
+            "sd_pyramid(1, 2)",
+            "sd_bezier(vec3(0), vec3(1, 2, 0), vec3(2, 0, 0), 0.1)",
+        ];
+
+        for shape in &shapes {
+            assert_valid(&format!("opaque(1, 0, 0) {};", shape));
+        }
+    }
+
+    /// Objects that aren't shown are the identity of the union for their type
+    #[test]
+    fn test_branches() {
+        let scene = "
+            cond($t > 1) opaque(1, 0, 0) sd_sphere(1);
+            cond($t > 1) transparent(1, 0, 0, 0.5) sd_sphere(1);
+            opaque(0, 1, 0) cond_else($p.x > 0) { sd_sphere(1); sd_box(vec3(1)); }
+            switch(1, 2) { transparent(0, 0, 1, 0.5) sd_sphere(1); union {} transparent(0, 1, 1, 0.5) sd_box(vec3(1)); }
+        ";
+        let desc = assert_valid(scene);
+        assert!(desc.generated.contains(": (map_opaque(vec3(0), 1.0/0.0))"), "{}", desc.generated);
+        assert!(desc.generated.contains(": (MapTransparent(vec4(0), 1.0/0.0))"), "{}", desc.generated);
+        assert!(!desc.generated.contains(": (1.0/0.0)"), "{}", desc.generated);
+    }
+
+    #[test]
+    fn test_let_scopes() {
+        let scene = "
+            opaque(1, 0, 0) union {
+                let(vec2, p, $p.xz) sd_sphere(length(p));
+                let(vec2, p, $p.xy) repeat(4, 0, 4) let(float, p, length(p)) sd_sphere(p);
+            }
+            opaque(0, 1, 0) let(float, length, 2) at(length, 0, 0) sd_sphere(length(vec2(length)));
+        ";
+        let desc = assert_valid(scene);
+        assert!(!desc.generated.contains(" p = "), "{}", desc.generated);
+        assert!(!desc.generated.contains(" length = "), "{}", desc.generated);
+        assert!(desc.generated.contains("sd_sphere(length(def_"), "{}", desc.generated);
+    }
+
+    #[test]
+    fn test_2d_shapes() {
+        let scene = "
+            define_geometry_2d(profile, float w) {
+                difference {
+                    sd2_rounded_rect(vec2(w, 1), 0.1);
+                    at(w, 1, 0) sd2_circle(0.5);
+                }
+                sd2_polygon(vec2(0), vec2(1, 0), vec2(0, 1));
+            }
+            opaque(1, 0, 0) {
+                extrude(2) profile(1);
+                revolve(3) smooth_union(0.2) {
+                    sd2_arc(1, 1, 0.1);
+                    sd2_segment(vec2(0), vec2(1), 0.1);
+                    sd2_rect(vec2(1, 2));
+                }
+            }
+        ";
+        assert_valid(scene);
+    }
+
+    /// Compares the code generated for each example with the snapshot in `tests/snapshots`.
+    /// A missing snapshot is an error, `UPDATE_SNAPSHOTS=1` writes all of them
+    #[test]
+    fn test_examples() {
+        let update = std::env::var_os("UPDATE_SNAPSHOTS").is_some();
+        let mut mismatches = Vec::new();
+
+        let mut paths = std::fs::read_dir("examples")
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect::<Vec<_>>();
+        paths.sort();
+
+        for path in paths {
+            let statements = parser::test::scene(&std::fs::read(&path).unwrap()).unwrap();
+            let desc = SceneDesc::from_statements(statements).unwrap();
+
+            if let Err(errors) = validate(&desc.fragment) {
+                let errors = errors.iter().map(|e| e.to_string()).collect::<Vec<_>>();
+                panic!("{}:\n{}", path.display(), errors.join("\n"));
+            }
+            assert_eq!(validate(&desc.shadertoy()), Ok(()), "{}", path.display());
+
+            let name = path.file_stem().unwrap().to_str().unwrap();
+            let snapshot = Path::new("tests/snapshots").join(format!("{}.glsl", name));
+
+            if update {
+                std::fs::create_dir_all("tests/snapshots").unwrap();
+                std::fs::write(&snapshot, &desc.generated).unwrap();
+                continue;
+            }
+
+            match std::fs::read_to_string(&snapshot) {
+                Ok(expected) if expected == desc.generated => {}
+                Ok(_) => mismatches.push(snapshot.display().to_string()),
+                Err(_) => mismatches.push(format!("{} (missing)", snapshot.display())),
+            }
+        }
+
+        assert!(
+            mismatches.is_empty(),
+            "generated code differs from {}, run with UPDATE_SNAPSHOTS=1 if the change is intended",
+            mismatches.join(", ")
+        );
+    }
+}
@@ -0,0 +1,49 @@
+float cross(Arg arg) {
+float def_1_1 = sd_union(sd_union(sd_box(vec3(3.0, 1.0, 1.0), arg), sd_box(vec3(1.0, 3.0, 1.0), arg)), sd_box(vec3(1.0, 1.0, 3.0), arg));
+Arg def_1_2 = at(0.0, -0.525, 0.0, arg);
+return sd_union(sd_diff(sd_diff(sd_diff(sd_onionize(0.1, def_1_1), sd_box(vec3(4.0, 0.5, 0.5), arg)), sd_box(vec3(0.5, 5.0, 0.5), arg)), sd_box(vec3(0.5, 0.5, 4.0), arg)), sd_diff(sd_union(sd_box(vec3(2.9, 0.025, 0.4), def_1_2), sd_box(vec3(0.4, 0.025, 2.9), def_1_2)), sd_box(vec3(0.8), arg)));
+}
+float ladder(Arg arg) {
+Arg def_2_1 = at(-0.2, 0.0, 0.0, arg);
+Arg def_2_2 = at(0.2, 0.0, 0.0, arg);
+Arg def_2_3 = repeat(0.0, 0.3, 0.0, arg);
+return sd_union(sd_union(sd_column_aa(vec3(0.0, 1.0, 0.0), 0.03, def_2_1), sd_column_aa(vec3(0.0, 1.0, 0.0), 0.03, def_2_2)), sd_box(vec3(0.2, 0.02, 0.02), def_2_3));
+}
+float pyramid(Arg arg) {
+Arg def_3_1 = rotate(vec3(1.0, 0.0, 1.0), 3.14 / 3.0, arg);
+Arg def_3_2 = at(0.0, 5.0 * sqrt(2.0), 0.0, arg);
+return sd_isect(sd_box(vec3(10.0), def_3_1), sd_halfspace_aa(vec3(0.0, -1.0, 0.0), def_3_2));
+}
+MapOpaque map_impl(Arg arg) {
+Arg def_4_1 = at(-160.0, 0.0, 5.0, arg);
+Arg def_4_2 = repeat(1000.0, 0.0, 1000.0, def_4_1);
+Arg def_4_3 = uscale(10.0, arg);
+Arg def_4_4 = at(0.0, 1.0, 0.0, def_4_3);
+Arg def_4_5 = at(-2.0, 0.0, -1.04, def_4_3);
+float def_4_6 = length(def_4_3.p - vec3(-1.0, 2.0, 0.0)) - 1.7320508075688772;
+float def_4_7;
+if (def_4_6 > 1.0) {
+    def_4_7 = def_4_6;
+} else {
+    Arg def_4_8 = at(-1.0, 2.0, 0.0, def_4_3);
+    Arg def_4_9 = at(-1.04, 0.0, 0.0, def_4_3);
+    Arg def_4_10 = rotate(vec3(0.0, 1.0, 0.0), 6.28 / 4.0, def_4_9);
+    def_4_7 = sd_isect(sd_box(vec3(1.0), def_4_8), ladder(def_4_10));
+}
+Arg def_4_11 = at(0.0, -def_4_3.t / 3.0 + 1.0, 0.0, def_4_3);
+Arg def_4_12 = repeat(0.0, 3.0, 0.0, def_4_11);
+MapOpaque def_4_13 = csd_union(map_opaque(vec3(0.9, 0.8, 1.0), sd_union(sd_union(cross(def_4_3), sd_isect(sd_halfspace_aa(vec3(0.0, 1.0, 0.0), def_4_4), ladder(def_4_5))), def_4_7)), map_opaque(vec3(vec3(min(-arg.p.y / 10.0 + 4.0, 1.0)) * min(1.0, (arg.p.y + 70.0) / 50.0)), sd_box(vec3(0.4 * min(-arg.p.y / 10.0 + 4.0, 1.0), 0.025, 0.4 * min(-arg.p.y / 10.0 + 4.0, 1.0)), def_4_12)));
+Arg def_4_14 = at(0.0, -10.0, 0.0, arg);
+Arg def_4_15 = at(-10.0, 0.0, 0.0, def_4_14);
+Arg def_4_16 = at(10.0, 0.0, 0.0, def_4_14);
+Arg def_4_17 = at(0.0, 0.0, 10.0, def_4_14);
+Arg def_4_18 = at(0.0, 0.0, -10.0, def_4_14);
+Arg def_4_19 = at(500.0, -500.0, 500.0, arg);
+Arg def_4_20 = uscale(50.0, def_4_19);
+MapOpaque def_4_21 = map_opaque(vec3(1.0, 1.0, 1.0), pyramid(def_4_20));
+return csd_union(csd_union(csd_union(map_opaque(vec3(1.0, 0.98, 0.75), sd_column_aa(vec3(0.0, 1.0, 0.0), 50.0, def_4_2)), MapOpaque(def_4_13.material, ((def_4_13.d) * (10.0)))), map_opaque(vec3(vec3(1.0, 0.9, 0.9) * min(1.0, (arg.p.y + 70.0) / 50.0)), sd_isect(def_4_14.p.y + sin(def_4_14.p.x / 10.0) + sin(def_4_14.p.z / 8.0) + 10.0 * sin(def_4_14.p.z / 42.0) + 15.0 * sin(def_4_14.p.x / 31.0) - 40.0 * pow(sin(def_4_14.p.x / 500.0), 2.0) - 20.0 * pow(sin(def_4_14.p.x / 1000.0), 2.0), sd_union(sd_union(sd_union(sd_halfspace_aa(vec3(1.0, 0.0, 0.0), def_4_15), sd_halfspace_aa(vec3(-1.0, 0.0, 0.0), def_4_16)), sd_halfspace_aa(vec3(0.0, 0.0, -1.0), def_4_17)), sd_halfspace_aa(vec3(0.0, 0.0, 1.0), def_4_18))))), MapOpaque(def_4_21.material, ((def_4_21.d) * (50.0))));
+}
+MapTransparent map_transparent_impl(Arg arg) {
+
+return MapTransparent(vec4(0), 1.0/0.0);
+}
\ No newline at end of file
@@ -0,0 +1,112 @@
+float platform(float ir, float or, Arg arg) {
+
+return sd_isect(sd_diff(sd_box(vec3(or, 0.5, or), arg), sd_sphere(ir, arg)), sd_sphere(or, arg));
+}
+float miniplatform(Arg arg) {
+
+return sd_isect(sd_box(vec3(5.0, 0.5, 5.0), arg), sd_sphere(5.0, arg));
+}
+float spheres(float rad, Arg arg) {
+Arg def_3_1 = rotate(vec3(0.0, 1.0, 0.0), arg.t, arg);
+Arg def_3_2 = at(0.0, -sin(def_3_1.t) * rad, rad, def_3_1);
+Arg def_3_3 = rotate(vec3(0.0, 1.0, 0.0), arg.t + 6.28 / 4.0, arg);
+Arg def_3_4 = at(0.0, -cos(def_3_3.t) * rad, rad, def_3_3);
+Arg def_3_5 = rotate(vec3(0.0, 1.0, 0.0), arg.t + 6.28 / 2.0, arg);
+Arg def_3_6 = at(0.0, sin(def_3_5.t) * rad, rad, def_3_5);
+Arg def_3_7 = rotate(vec3(0.0, 1.0, 0.0), arg.t + 6.28 / 4.0 * 3.0, arg);
+Arg def_3_8 = at(0.0, cos(def_3_7.t) * rad, rad, def_3_7);
+Arg def_3_9 = at_t(arg.t * 2.1, arg);
+Arg def_3_10 = rotate(vec3(0.0, 1.0, 0.0), 6.28 / 8.0, def_3_9);
+Arg def_3_11 = rotate(vec3(0.0, 1.0, 0.0), def_3_10.t, def_3_10);
+Arg def_3_12 = at(0.0, -sin(def_3_11.t) * rad, rad, def_3_11);
+Arg def_3_13 = rotate(vec3(0.0, 1.0, 0.0), def_3_10.t + 6.28 / 4.0, def_3_10);
+Arg def_3_14 = at(0.0, -cos(def_3_13.t) * rad, rad, def_3_13);
+Arg def_3_15 = rotate(vec3(0.0, 1.0, 0.0), def_3_10.t + 6.28 / 2.0, def_3_10);
+Arg def_3_16 = at(0.0, sin(def_3_15.t) * rad, rad, def_3_15);
+Arg def_3_17 = rotate(vec3(0.0, 1.0, 0.0), def_3_10.t + 6.28 / 4.0 * 3.0, def_3_10);
+Arg def_3_18 = at(0.0, cos(def_3_17.t) * rad, rad, def_3_17);
+Arg def_3_19 = rotate(vec3(0.0, 1.0, 0.0), 6.28 / 8.0, arg);
+Arg def_3_20 = rotate(vec3(0.0, 1.0, 0.0), def_3_19.t, def_3_19);
+Arg def_3_21 = at(0.0, sin(def_3_20.t) * rad, rad, def_3_20);
+Arg def_3_22 = rotate(vec3(0.0, 1.0, 0.0), def_3_19.t + 6.28 / 4.0, def_3_19);
+Arg def_3_23 = at(0.0, cos(def_3_22.t) * rad, rad, def_3_22);
+Arg def_3_24 = rotate(vec3(0.0, 1.0, 0.0), def_3_19.t + 6.28 / 2.0, def_3_19);
+Arg def_3_25 = at(0.0, -sin(def_3_24.t) * rad, rad, def_3_24);
+Arg def_3_26 = rotate(vec3(0.0, 1.0, 0.0), def_3_19.t + 6.28 / 4.0 * 3.0, def_3_19);
+Arg def_3_27 = at(0.0, -cos(def_3_26.t) * rad, rad, def_3_26);
+Arg def_3_28 = rotate(vec3(0.0, 1.0, 0.0), 6.28 / 4.0, def_3_9);
+Arg def_3_29 = rotate(vec3(0.0, 1.0, 0.0), def_3_28.t, def_3_28);
+Arg def_3_30 = at(0.0, sin(def_3_29.t) * rad, rad, def_3_29);
+Arg def_3_31 = rotate(vec3(0.0, 1.0, 0.0), def_3_28.t + 6.28 / 4.0, def_3_28);
+Arg def_3_32 = at(0.0, cos(def_3_31.t) * rad, rad, def_3_31);
+Arg def_3_33 = rotate(vec3(0.0, 1.0, 0.0), def_3_28.t + 6.28 / 2.0, def_3_28);
+Arg def_3_34 = at(0.0, -sin(def_3_33.t) * rad, rad, def_3_33);
+Arg def_3_35 = rotate(vec3(0.0, 1.0, 0.0), def_3_28.t + 6.28 / 4.0 * 3.0, def_3_28);
+Arg def_3_36 = at(0.0, -cos(def_3_35.t) * rad, rad, def_3_35);
+return sd_union(sd_union(sd_union(sd_union(sd_union(sd_union(sd_sphere(2.0, def_3_2), sd_sphere(2.0, def_3_4)), sd_sphere(2.0, def_3_6)), sd_sphere(2.0, def_3_8)), sd_union(sd_union(sd_union(sd_sphere(2.0, def_3_12), sd_sphere(2.0, def_3_14)), sd_sphere(2.0, def_3_16)), sd_sphere(2.0, def_3_18))), sd_union(sd_union(sd_union(sd_sphere(2.0, def_3_21), sd_sphere(2.0, def_3_23)), sd_sphere(2.0, def_3_25)), sd_sphere(2.0, def_3_27))), sd_union(sd_union(sd_union(sd_sphere(2.0, def_3_30), sd_sphere(2.0, def_3_32)), sd_sphere(2.0, def_3_34)), sd_sphere(2.0, def_3_36)));
+}
+MapOpaque map_impl(Arg arg) {
+float def_4_1 = 10.0;
+float def_4_2 = 16.0;
+float def_4_3 = def_4_2 + 3.0;
+float def_4_4 = def_4_3 + 2.5;
+float def_4_5 = def_4_4 + 3.0;
+float def_4_6 = def_4_5 + 4.0;
+Arg def_4_7 = at_t(23.0, arg);
+Arg def_4_8 = map_t(6.0, 10.0, 0.0, 1.0, def_4_7);
+Arg def_4_9 = start_at_t(0.0, def_4_8);
+Arg def_4_10 = end_at_t(1.0, def_4_9);
+Arg def_4_11 = repeat(0.0, 0.01, 0.0, def_4_10);
+Arg def_4_12 = at(0.0, sign(arg.p.y) * 2.0 * def_4_11.t, 0.0, def_4_11);
+Arg def_4_13 = uscale(max(0.0, abs(arg.p.y * arg.p.y / 400.0)), def_4_12);
+Arg def_4_14 = map_t(def_4_5, def_4_6, 0.0, 1.0, def_4_7);
+Arg def_4_15 = end_at_t(1.0, def_4_14);
+Arg def_4_16 = repeat(0.0, 0.01, 0.0, def_4_15);
+Arg def_4_17 = at(0.0, sign(arg.p.y) * 2.0 * (1.0 - def_4_16.t), 0.0, def_4_16);
+Arg def_4_18 = uscale(max(0.0, abs(arg.p.y * arg.p.y / 400.0)), def_4_17);
+Arg def_4_19 = map_t(4.0, 5.0, 0.0, 1.0, def_4_7);
+Arg def_4_20 = start_at_t(0.0, def_4_19);
+float def_4_21 = clamp(def_4_20.t * 10.0 - 8.0, 0.0, 10.0);
+Arg def_4_22 = map_t(def_4_2 - 1.0, def_4_2, 0.0, 1.0, def_4_7);
+Arg def_4_23 = start_at_t(0.0, def_4_22);
+Arg def_4_24 = end_at_t(1.0, def_4_23);
+Arg def_4_25 = at(0.0, 10.0 * def_4_24.t, 0.0, def_4_24);
+Arg def_4_26 = at(0.0, -10.0 * def_4_24.t, 0.0, def_4_24);
+float def_4_27 = 14.0;
+vec3 def_4_28 = vec3(13.0, 0.0, 13.0);
+vec3 def_4_29 = vec3(30.0, 0.0, 30.0);
+vec3 def_4_30 = vec3(40.0, 16.0, 0.0);
+Arg def_4_31 = map_t(def_4_27, def_4_27 + 2.0, 0.0, 1.0, arg);
+Arg def_4_32 = start_at_t(0.0, def_4_31);
+Arg def_4_33 = end_at_t(1.0, def_4_32);
+Arg def_4_34 = vat(mix(def_4_28, def_4_29, def_4_33.t), def_4_33);
+Arg def_4_35 = map_t(def_4_27 + 2.0, def_4_27 + 2.0 + 5.0, 0.0, 1.0, arg);
+Arg def_4_36 = start_at_t(0.0, def_4_35);
+Arg def_4_37 = end_at_t(1.0, def_4_36);
+Arg def_4_38 = vat(mix(def_4_29, def_4_30, def_4_37.t), def_4_37);
+return csd_union(csd_union(map_opaque(vec3(vec3(1.0)), sd_union(((def_4_7.t <= def_4_5) ? (((sd_sphere(1.0, def_4_13)) * (max(0.0, abs(arg.p.y * arg.p.y / 400.0))))) : (1.0/0.0)), ((def_4_7.t > def_4_5) ? (((sd_sphere(1.0, def_4_18)) * (max(0.0, abs(arg.p.y * arg.p.y / 400.0))))) : (1.0/0.0)))), map_opaque(vec3(vec3(1.0)), sd_union(((def_4_7.t <= def_4_2 - 1.0) ? (platform(10.0 + min(def_4_20.t * 8.0, 8.0) + def_4_21, 20.0 + def_4_21, def_4_20)) : (1.0/0.0)), ((def_4_7.t > def_4_2 - 1.0) ? (((def_4_22.t > 0.0) ? (sd_union(platform(28.0, 30.0, def_4_25), platform(28.0, 30.0, def_4_26))) : (1.0/0.0))) : (1.0/0.0))))), map_opaque(vec3(0.0, 0.0, 0.0), sd_union(((arg.t <= def_4_27 + 2.0) ? (miniplatform(def_4_34)) : (1.0/0.0)), ((arg.t > def_4_27 + 2.0) ? (miniplatform(def_4_38)) : (1.0/0.0)))));
+}
+MapTransparent map_transparent_impl(Arg arg) {
+float def_5_1 = 10.0;
+float def_5_2 = 16.0;
+float def_5_3 = def_5_2 + 3.0;
+float def_5_4 = def_5_3 + 2.5;
+float def_5_5 = def_5_4 + 3.0;
+float def_5_6 = def_5_5 + 4.0;
+Arg def_5_7 = at_t(23.0, arg);
+Arg def_5_8 = at_t(10.0, def_5_7);
+Arg def_5_9 = map_t(10.0, 15.0, 0.0, 5.0, def_5_7);
+Arg def_5_10 = start_at_t(0.0, def_5_9);
+Arg def_5_11 = end_at_t(5.0, def_5_10);
+Arg def_5_12 = map_t(def_5_2, def_5_2 + 3.0, 0.0, 1.0, def_5_7);
+Arg def_5_13 = start_at_t(0.0, def_5_12);
+Arg def_5_14 = end_at_t(1.0, def_5_13);
+Arg def_5_15 = map_t(def_5_2 + 0.3, def_5_3, 0.0, 5.0, def_5_7);
+Arg def_5_16 = start_at_t(0.0, def_5_15);
+Arg def_5_17 = end_at_t(5.1, def_5_16);
+float def_5_18 = 1.0 / (def_5_17.t * def_5_17.t + 1.0);
+Arg def_5_19 = at_t(def_5_3, def_5_7);
+Arg def_5_20 = at_t(def_5_4, def_5_7);
+Arg def_5_21 = at_t(def_5_6, def_5_7);
+return tsd_union(MapTransparent(vec4(1.0, 0.0, 0.0, 1.0), sd_union(((def_5_7.t < def_5_2) ? (sd_union(((def_5_7.t < def_5_1) ? (spheres(30.0, def_5_8)) : (1.0/0.0)), ((def_5_7.t >= def_5_1) ? (spheres(30.0 * (1.0 - def_5_11.t / 5.0), def_5_11)) : (1.0/0.0)))) : (1.0/0.0)), ((def_5_7.t >= def_5_2) ? (sd_sphere(2.0 * pow(1.0 - def_5_14.t, 2.0), def_5_14)) : (1.0/0.0)))), MapTransparent(vec4(0.0, 1.0, 1.0, 0.2), sd_union(((def_5_17.t > 0.0 && def_5_17.t <= 5.0) ? (sd_sphere(20.8 * (1.0 - def_5_18), def_5_17)) : (1.0/0.0)), ((def_5_7.t > def_5_3) ? (sd_union(sd_union(((def_5_7.t <= def_5_4) ? (sd_sphere(20.0 - def_5_19.t * 2.0, def_5_19)) : (1.0/0.0)), ((def_5_7.t > def_5_4 && def_5_7.t <= def_5_6) ? (sd_sphere(15.0 - sin(def_5_20.t * 2.0) * 0.5, def_5_20)) : (1.0/0.0))), ((def_5_7.t > def_5_6) ? (sd_sphere(15.0 + pow(def_5_21.t * 4.0, 2.0), def_5_21)) : (1.0/0.0)))) : (1.0/0.0)))));
+}
\ No newline at end of file
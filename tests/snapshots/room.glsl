@@ -0,0 +1,18 @@
+float room(vec3 size, vec2 door, float thick, Arg arg) {
+Arg def_1_1 = at(0.0, size.y, 0.0, arg);
+float def_1_2 = sd_box(size, def_1_1);
+Arg def_1_3 = at(0.0, -door.y / 2.0, -size.z + thick, def_1_1);
+Arg def_1_4 = at(0.0, 0.0, -5.0, def_1_1);
+Arg def_1_5 = at(0.0, 0.0, 5.0, def_1_1);
+return sd_diff(sd_diff(sd_diff(sd_onionize(thick, def_1_2), sd_box(vec3(door, thick * 2.0), def_1_3)), sd_box(vec3(10.0, 3.0, 3.0), def_1_4)), sd_box(vec3(10.0, 3.0, 3.0), def_1_5));
+}
+MapOpaque map_impl(Arg arg) {
+Arg def_2_1 = at(10.0, -3.0, 10.0, arg);
+Arg def_2_2 = at(-160.0, 0.0, 5.0, arg);
+Arg def_2_3 = repeat(1000.0, 0.0, 1000.0, def_2_2);
+return csd_union(map_opaque(vec3(0.9, 0.8, 1.0), sd_union(room(vec3(5.0, 5.0, 10.0), vec2(1.5, 3.0), 0.5, def_2_1), sd_isect(sd_column_aa(vec3(0.0, 1.0, 0.0), 3.0, def_2_1), sd_halfspace_aa(vec3(0.0, 1.0, 0.0), def_2_1)))), map_opaque(vec3(1.0, 0.98, 0.75), sd_column_aa(vec3(0.0, 1.0, 0.0), 50.0, def_2_3)));
+}
+MapTransparent map_transparent_impl(Arg arg) {
+
+return MapTransparent(vec4(0), 1.0/0.0);
+}
\ No newline at end of file
@@ -0,0 +1,8 @@
+MapOpaque map_impl(Arg arg) {
+Arg def_1_1 = at(0.0, 0.0, 5.0, arg);
+return map_opaque(vec3(1.0, 1.0, 1.0), sd_diff(sd_box(vec3(1.0, 1.0, 1.0), def_1_1), sd_sphere(1.3, def_1_1)));
+}
+MapTransparent map_transparent_impl(Arg arg) {
+
+return MapTransparent(vec4(0), 1.0/0.0);
+}
\ No newline at end of file
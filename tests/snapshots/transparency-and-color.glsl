@@ -0,0 +1,49 @@
+Material material_copper(Arg arg) {
+
+return Material(vec3(0.95, 0.64, 0.54), 0.3, 1.0, vec3(0));
+}
+float cylinder(float r, Arg arg) {
+
+return length(arg.p.xz) - r;
+}
+MapOpaque map_impl(Arg arg) {
+float def_3_1 = length(arg.p - vec3(20.0, 0.0, 0.0)) - 52.36626924634505;
+MapOpaque def_3_2;
+if (def_3_1 > 1.0) {
+    def_3_2 = map_opaque(vec3(0), def_3_1);
+} else {
+    Arg def_3_3 = at(20.0, 0.0, 0.0, arg);
+    Arg def_3_4 = rotate(vec3(-1.0, 0.0, 1.0), 6.28 / 6.0, def_3_3);
+    float def_3_5 = length(def_3_4.p) - 34.64101615137755;
+    float def_3_6;
+    if (def_3_5 > 1.0) {
+        def_3_6 = def_3_5;
+    } else {
+        def_3_6 = sd_isect(sd_box(vec3(20.0), def_3_4), sd_diff(cylinder(10.0 + def_3_4.p.y / 10.0, def_3_4), cylinder(8.0 + def_3_4.p.y / 8.0, def_3_4)));
+    }
+    Arg def_3_7 = at(0.0, -30.0, 0.0, def_3_4);
+    def_3_2 = map_opaque(vec3(vec3(1.0)), sd_union(def_3_6, sd_box(vec3(10.0, 0.5, 20.0), def_3_7)));
+}
+vec4 def_3_8 = hash(arg.p.xz / 10.0);
+Arg def_3_9 = at(0.0, -20.0, 0.0, arg);
+Arg def_3_10 = at(60.0, 0.0, 0.0, arg);
+Arg def_3_11 = rotate(vec3(0.0, 0.0, 1.0), def_3_10.p.x / 20.0, def_3_10);
+return csd_union(csd_union(def_3_2, map_opaque(vec3(def_3_8.x, def_3_8.y, def_3_8.z), sd_halfspace_aa(vec3(0.0, 1.0, 0.0), def_3_9))), MapOpaque(material_copper(def_3_10), sd_torus(5.0, 1.0, def_3_11)));
+}
+MapTransparent map_transparent_impl(Arg arg) {
+float def_4_1 = length(arg.p - vec3(20.0, 0.0, 0.0)) - 34.64101615137755;
+MapTransparent def_4_2;
+if (def_4_1 > 1.0) {
+    def_4_2 = MapTransparent(vec4(0), def_4_1);
+} else {
+    Arg def_4_3 = at(20.0, 0.0, 0.0, arg);
+    Arg def_4_4 = rotate(vec3(-1.0, 0.0, 1.0), 6.28 / 6.0, def_4_3);
+    def_4_2 = MapTransparent(vec4(0.0, 1.0, 1.0, 0.05), sd_isect(sd_box(vec3(20.0), def_4_4), cylinder(8.0 + def_4_4.p.y / 8.0, def_4_4)));
+}
+Arg def_4_5 = at(0.0, 20.0, 40.0, arg);
+Arg def_4_6 = at(def_4_5.t * 10.0, 0.0, 0.0, def_4_5);
+Arg def_4_7 = at(0.0, 20.0 + 5.0 * sin(def_4_6.p.x / 5.0 + 2.0), 0.0, def_4_6);
+Arg def_4_8 = rotate(vec3(0.0, 0.0, 1.0), 6.28 / 4.0, def_4_7);
+Arg def_4_9 = rotate(vec3(0.0, 0.0, 1.0), 6.28 / 4.0, def_4_5);
+return tsd_union(def_4_2, MapTransparent(vec4(1.0, 0.0, 1.0, 1.0), sd_union(cylinder(1.0, def_4_8), cylinder(1.0, def_4_9))));
+}
\ No newline at end of file